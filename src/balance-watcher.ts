@@ -0,0 +1,59 @@
+export interface BalanceWatcherOptions {
+  fetchBalance: () => Promise<unknown>;
+  intervalMs: number;
+}
+
+export type BalanceListener = () => void;
+
+// Polls the balance only while someone is subscribed and reports changes; the first poll sets the baseline.
+export class BalanceWatcher {
+  private readonly options: BalanceWatcherOptions;
+  private readonly listeners = new Set<BalanceListener>();
+  private timer: NodeJS.Timeout | undefined;
+  private last: string | undefined;
+
+  constructor(options: BalanceWatcherOptions) {
+    this.options = options;
+  }
+
+  get subscriberCount(): number {
+    return this.listeners.size;
+  }
+
+  subscribe(listener: BalanceListener): () => void {
+    this.listeners.add(listener);
+    if (!this.timer) {
+      void this.poll();
+      this.timer = setInterval(() => void this.poll(), this.options.intervalMs);
+      this.timer.unref();
+    }
+
+    return () => {
+      this.listeners.delete(listener);
+      if (this.listeners.size === 0) {
+        clearInterval(this.timer);
+        this.timer = undefined;
+        this.last = undefined;
+      }
+    };
+  }
+
+  async poll(): Promise<boolean> {
+    let current: string;
+    try {
+      current = JSON.stringify(await this.options.fetchBalance());
+    } catch {
+      // Keep the last known balance; a transient failure is not a change.
+      return false;
+    }
+
+    const changed = this.last !== undefined && this.last !== current;
+    this.last = current;
+    if (changed) {
+      for (const listener of this.listeners) {
+        listener();
+      }
+    }
+    return changed;
+  }
+}
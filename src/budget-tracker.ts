@@ -0,0 +1,124 @@
+import { mkdir, readFile, writeFile } from "node:fs/promises";
+import { dirname } from "node:path";
+
+export interface BudgetStatus {
+  month: string;
+  budget_usd: number;
+  spent_usd: number;
+  remaining_usd: number;
+  exceeded: boolean;
+}
+
+export interface BudgetTrackerOptions {
+  monthlyBudgetUsd: number;
+  filePath?: string;
+  now?: () => Date;
+}
+
+export class BudgetExceededError extends Error {
+  public readonly status: BudgetStatus;
+
+  constructor(message: string, status: BudgetStatus) {
+    super(message);
+    this.name = "BudgetExceededError";
+    this.status = status;
+  }
+}
+
+export class BudgetTracker {
+  private readonly monthlyBudgetUsd: number;
+  private readonly filePath?: string;
+  private readonly now: () => Date;
+  private state?: { month: string; spentUsd: number };
+
+  constructor(options: BudgetTrackerOptions) {
+    this.monthlyBudgetUsd = options.monthlyBudgetUsd;
+    this.filePath = options.filePath;
+    this.now = options.now ?? (() => new Date());
+  }
+
+  async getStatus(): Promise<BudgetStatus> {
+    const state = await this.load();
+    return this.toStatus(state.month, state.spentUsd);
+  }
+
+  async assertAvailable(): Promise<void> {
+    const status = await this.getStatus();
+    if (status.exceeded) {
+      throw new BudgetExceededError(
+        `Monthly budget of $${status.budget_usd.toFixed(2)} exhausted for ${status.month} (spent $${status.spent_usd.toFixed(4)})`,
+        status,
+      );
+    }
+  }
+
+  async record(costUsd: number): Promise<BudgetStatus> {
+    const state = await this.load();
+    state.spentUsd += Math.max(costUsd, 0);
+    await this.persist(state);
+
+    const status = this.toStatus(state.month, state.spentUsd);
+    if (status.spent_usd > status.budget_usd) {
+      throw new BudgetExceededError(
+        `Request cost $${costUsd.toFixed(4)} pushed ${status.month} spend to $${status.spent_usd.toFixed(4)}, over the $${status.budget_usd.toFixed(2)} monthly budget`,
+        status,
+      );
+    }
+
+    return status;
+  }
+
+  private async load(): Promise<{ month: string; spentUsd: number }> {
+    const month = currentMonth(this.now());
+
+    if (!this.state) {
+      this.state = (await this.readFile()) ?? { month, spentUsd: 0 };
+    }
+
+    if (this.state.month !== month) {
+      this.state = { month, spentUsd: 0 };
+    }
+
+    return this.state;
+  }
+
+  private async readFile(): Promise<{ month: string; spentUsd: number } | undefined> {
+    if (!this.filePath) {
+      return undefined;
+    }
+
+    try {
+      const parsed = JSON.parse(await readFile(this.filePath, "utf8")) as Record<string, unknown>;
+      if (typeof parsed.month !== "string" || typeof parsed.spent_usd !== "number") {
+        return undefined;
+      }
+
+      return { month: parsed.month, spentUsd: parsed.spent_usd };
+    } catch {
+      return undefined;
+    }
+  }
+
+  private async persist(state: { month: string; spentUsd: number }): Promise<void> {
+    if (!this.filePath) {
+      return;
+    }
+
+    await mkdir(dirname(this.filePath), { recursive: true });
+    await writeFile(this.filePath, JSON.stringify({ month: state.month, spent_usd: state.spentUsd }, null, 2), "utf8");
+  }
+
+  private toStatus(month: string, spentUsd: number): BudgetStatus {
+    return {
+      month,
+      budget_usd: this.monthlyBudgetUsd,
+      spent_usd: spentUsd,
+      remaining_usd: Math.max(this.monthlyBudgetUsd - spentUsd, 0),
+      exceeded: spentUsd >= this.monthlyBudgetUsd,
+    };
+  }
+}
+
+function currentMonth(date: Date): string {
+  return date.toISOString().slice(0, 7);
+}
@@ -0,0 +1,19 @@
+import { RequestHandlerExtra } from "@modelcontextprotocol/sdk/shared/protocol.js";
+import { ServerNotification, ServerRequest } from "@modelcontextprotocol/sdk/types.js";
+
+// Tool results' structuredContent was introduced in this protocol revision.
+export const STRUCTURED_CONTENT_PROTOCOL_VERSION = "2025-06-18";
+
+// HTTP clients repeat the negotiated version in this header on every request after initialize. It is read per
+// request because the SDK keeps no per-session record of it and one stateless server answers every client.
+export function clientProtocolVersion(
+  extra: RequestHandlerExtra<ServerRequest, ServerNotification>,
+): string | undefined {
+  const header = extra.requestInfo?.headers["mcp-protocol-version"];
+  return Array.isArray(header) ? header[0] : header;
+}
+
+// Unknown versions (stdio and the other stream transports) keep structured results.
+export function supportsStructuredContent(protocolVersion: string | undefined): boolean {
+  return protocolVersion === undefined || protocolVersion >= STRUCTURED_CONTENT_PROTOCOL_VERSION;
+}
@@ -1,44 +1,286 @@
-export type McpTransportMode = "stdio" | "streamable-http";
+export type McpTransportMode = "stdio" | "streamable-http" | "sse" | "websocket" | "tcp" | "pipe";
 
 export interface RuntimeConfig {
   deepseekApiKey: string;
+  offline: boolean;
+  samplingFallback: boolean;
   deepseekBaseUrl: string;
+  deepseekBaseUrls: string[];
+  baseUrlCooldownMs: number;
+  extraHeaders: Record<string, string>;
   deepseekRequestTimeoutMs: number;
   defaultModel: string;
   enableReasonerFallback: boolean;
   fallbackModel: string;
-  transport: McpTransportMode;
+  transports: McpTransportMode[];
   httpHost: string;
   httpPort: number;
+  ssePort: number;
+  webSocketPort: number;
+  basePath: string;
   httpPath: string;
   httpStatefulSession: boolean;
+  healthProbeIntervalMs: number;
+  keepAliveIntervalMs?: number;
+  idleTimeoutMs?: number;
+  ssePath: string;
+  sseMessagesPath: string;
+  webSocketPath: string;
+  authTokens: string[];
+  sessionIsolation: boolean;
+  clientRateLimitRpm?: number;
+  clientMaxConcurrent?: number;
+  tlsCertFile?: string;
+  tlsKeyFile?: string;
+  oauthResource?: string;
+  oauthAuthorizationServers: string[];
+  oauthIntrospectionUrl?: string;
+  oauthClientId?: string;
+  oauthClientSecret?: string;
+  oauthScopes: string[];
+  tcpHost: string;
+  tcpPort: number;
+  tcpAllowRemote: boolean;
+  pipePath?: string;
   conversationMaxMessages: number;
+  conversationMaxSessions: number;
   experimentalV4Enabled: boolean;
+  modelsCacheFile?: string;
+  disabledTools: string[];
+  enabledTools: string[];
+  enableToolAdmin: boolean;
+  signingSecret?: string;
+  poolMaxConnectionsPerHost?: number;
+  poolIdleTimeoutMs: number;
+  tcpKeepAlive: boolean;
+  maxResponseBytes: number;
+  retryOnDecodeError: boolean;
+  monthlyBudgetUsd?: number;
+  budgetFile?: string;
+  summaryMaxChars?: number;
+  imageDir?: string;
+  attachDirs: string[];
+  attachMaxFileBytes: number;
+  attachMaxTotalBytes: number;
+  maxMessages?: number;
+  modelWeights?: Record<string, number>;
+  recordDir?: string;
+  replayDir?: string;
+  chatToCompletionFallback: boolean;
+  retryStatuses: number[];
+  errorFormat: "text" | "json";
+  tokenParam: "max_tokens" | "max_completion_tokens";
+  timing: boolean;
+  mergeConsecutiveMessages: boolean;
+  enableRawRequests: boolean;
+  enableBatch: boolean;
+  batchConcurrency: number;
+  allowClientApiKeys: boolean;
+  moderationUrl?: string;
+  moderationFailMode: "open" | "closed";
+  compressRequests: boolean;
+  autoContinue: boolean;
+  autoContinueMax: number;
+  rateLimitRpm?: number;
+  rateLimitBurst?: number;
+  rateLimitNotifyMs: number;
+  progressIntervalMs: number;
+  lowBalanceThreshold: number;
+  balancePollIntervalMs: number;
+  confirmAboveTokens?: number;
+  confirmAboveUsd?: number;
+  trimOutput: boolean;
+  stripCodeFences: boolean;
+  sanitizeExtraBody: boolean;
+  compressionThresholdBytes: number;
+  chatPath?: string;
+  completionsPath?: string;
+  modelsPath?: string;
+  rateLimitRetries: number;
+  rateLimitBackoffMs: number;
+  rateLimitMaxBackoffMs: number;
+  rateLimitJitter: number;
+  rateLimitSeed?: number;
+  gitCommit?: string;
+  loopGuardEnabled: boolean;
+  loopGuardNgramSize: number;
+  loopGuardMaxRepeats: number;
+  streamSoftMaxTokens?: number;
+  shutdownTimeoutMs: number;
 }
 
-export function loadRuntimeConfig(env: NodeJS.ProcessEnv = process.env): RuntimeConfig {
-  const deepseekApiKey = env.DEEPSEEK_API_KEY;
-  if (!deepseekApiKey) {
-    throw new Error("DEEPSEEK_API_KEY environment variable is required");
+export function loadRuntimeConfig(env: NodeJS.ProcessEnv = process.env, argv: string[] = []): RuntimeConfig {
+  const offline = parseBoolean(env.DEEPSEEK_OFFLINE, false);
+  const samplingFallback = parseBoolean(env.DEEPSEEK_SAMPLING_FALLBACK, false);
+  const deepseekApiKey = env.DEEPSEEK_API_KEY ?? "";
+  if (!deepseekApiKey && !offline && !samplingFallback) {
+    throw new Error(
+      "DEEPSEEK_API_KEY environment variable is required (or set DEEPSEEK_OFFLINE=1 for read-only mode, or DEEPSEEK_SAMPLING_FALLBACK=1 to answer chat via client sampling)",
+    );
   }
 
-  const transportRaw = (env.MCP_TRANSPORT ?? "stdio").trim().toLowerCase();
-  const transport = transportRaw === "streamable-http" ? "streamable-http" : "stdio";
+  // --listen on its own implies the TCP transport, --pipe the named-pipe/Unix-socket transport.
+  const listenArg = parseCliOption(argv, "listen");
+  const pipeArg = parseCliOption(argv, "pipe");
+  const transportRaw =
+    parseCliOption(argv, "transport") ??
+    (listenArg ? "tcp" : undefined) ??
+    (pipeArg ? "pipe" : undefined) ??
+    env.DEEPSEEK_TRANSPORT ??
+    env.MCP_TRANSPORT ??
+    "stdio";
+  const transports = [...new Set(parseList(transportRaw).map(parseTransportMode))];
+  if (Boolean(env.DEEPSEEK_TLS_CERT) !== Boolean(env.DEEPSEEK_TLS_KEY)) {
+    throw new Error("DEEPSEEK_TLS_CERT and DEEPSEEK_TLS_KEY must be set together");
+  }
+
+  // Prefixes every HTTP/SSE/WebSocket route, e.g. /mcp/deepseek behind a shared reverse proxy.
+  const basePath = normalizeBasePath(parseCliOption(argv, "base-path") ?? env.MCP_BASE_PATH);
+
+  const tcpListen = parseListenAddress(listenArg ?? env.MCP_TCP_LISTEN, "127.0.0.1", 7400);
+  // The TCP transport has no authentication, so anyone who can reach a non-loopback listener can spend credits.
+  const tcpAllowRemote = parseBoolean(env.MCP_TCP_ALLOW_REMOTE, false);
+  if (transports.includes("tcp") && !isLoopbackHost(tcpListen.host) && !tcpAllowRemote) {
+    throw new Error(
+      `The TCP transport is unauthenticated; refusing to listen on ${tcpListen.host}. Listen on 127.0.0.1, or set MCP_TCP_ALLOW_REMOTE=1 to expose it anyway`,
+    );
+  }
+
+  // Each HTTP-based transport runs its own listener, so two enabled ones can't share a port.
+  const httpPort = parsePort(env.MCP_HTTP_PORT, 3001);
+  const listenerPorts: Partial<Record<McpTransportMode, number>> = {
+    "streamable-http": httpPort,
+    sse: parsePort(env.MCP_SSE_PORT, httpPort),
+    websocket: parsePort(env.MCP_WS_PORT, httpPort),
+  };
+  const claimedPorts = new Map<number, McpTransportMode>();
+  for (const transport of transports) {
+    const port = listenerPorts[transport];
+    if (port === undefined) {
+      continue;
+    }
+
+    const owner = claimedPorts.get(port);
+    if (owner) {
+      throw new Error(
+        `${owner} and ${transport} would both listen on port ${port}; give each its own MCP_HTTP_PORT, MCP_SSE_PORT or MCP_WS_PORT`,
+      );
+    }
+    claimedPorts.set(port, transport);
+  }
 
   return {
     deepseekApiKey,
-    deepseekBaseUrl: env.DEEPSEEK_BASE_URL ?? "https://api.deepseek.com",
+    offline,
+    samplingFallback,
+    deepseekBaseUrl: interpolateEnv(env.DEEPSEEK_BASE_URL ?? "https://api.deepseek.com", env, "DEEPSEEK_BASE_URL"),
+    deepseekBaseUrls: parseList(env.DEEPSEEK_BASE_URLS).map((baseUrl) =>
+      interpolateEnv(baseUrl, env, "DEEPSEEK_BASE_URLS"),
+    ),
+    baseUrlCooldownMs: parsePositiveInt(env.DEEPSEEK_BASE_URL_COOLDOWN_MS, 30000),
+    extraHeaders: parseExtraHeaders(env.DEEPSEEK_EXTRA_HEADERS, env),
     deepseekRequestTimeoutMs: parsePositiveInt(env.DEEPSEEK_REQUEST_TIMEOUT_MS, 120000),
     defaultModel: env.DEEPSEEK_DEFAULT_MODEL ?? "deepseek-chat",
     enableReasonerFallback: parseBoolean(env.DEEPSEEK_ENABLE_REASONER_FALLBACK, true),
     fallbackModel: env.DEEPSEEK_FALLBACK_MODEL ?? "deepseek-chat",
-    transport,
+    transports: transports.length > 0 ? transports : ["stdio"],
     httpHost: env.MCP_HTTP_HOST ?? "127.0.0.1",
-    httpPort: parsePort(env.MCP_HTTP_PORT, 3001),
-    httpPath: normalizePath(env.MCP_HTTP_PATH ?? "/mcp"),
+    httpPort,
+    ssePort: listenerPorts.sse ?? httpPort,
+    webSocketPort: listenerPorts.websocket ?? httpPort,
+    basePath,
+    httpPath: basePath + normalizePath(env.MCP_HTTP_PATH ?? "/mcp"),
     httpStatefulSession: parseBoolean(env.MCP_HTTP_STATEFUL_SESSION, false),
+    healthProbeIntervalMs: parsePositiveInt(env.MCP_HEALTH_PROBE_INTERVAL_MS, 60000),
+    keepAliveIntervalMs: parseOptionalPositiveInt(env.MCP_KEEPALIVE_INTERVAL_MS),
+    idleTimeoutMs: parseOptionalPositiveInt(env.MCP_IDLE_TIMEOUT_MS),
+    ssePath: basePath + normalizePath(env.MCP_SSE_PATH ?? "/sse"),
+    sseMessagesPath: basePath + normalizePath(env.MCP_SSE_MESSAGES_PATH ?? "/messages"),
+    webSocketPath: basePath + normalizePath(env.MCP_WS_PATH ?? "/ws"),
+    authTokens: parseList(env.MCP_AUTH_TOKENS),
+    sessionIsolation: parseBoolean(env.MCP_SESSION_ISOLATION, true),
+    clientRateLimitRpm: parseOptionalPositiveInt(env.MCP_CLIENT_RATE_LIMIT_RPM),
+    clientMaxConcurrent: parseOptionalPositiveInt(env.MCP_CLIENT_MAX_CONCURRENT),
+    tlsCertFile: env.DEEPSEEK_TLS_CERT || undefined,
+    tlsKeyFile: env.DEEPSEEK_TLS_KEY || undefined,
+    oauthResource: env.MCP_OAUTH_RESOURCE,
+    oauthAuthorizationServers: parseList(env.MCP_OAUTH_AUTHORIZATION_SERVERS),
+    oauthIntrospectionUrl: env.MCP_OAUTH_INTROSPECTION_URL,
+    oauthClientId: env.MCP_OAUTH_CLIENT_ID,
+    oauthClientSecret: env.MCP_OAUTH_CLIENT_SECRET,
+    oauthScopes: parseList(env.MCP_OAUTH_SCOPES),
+    tcpHost: tcpListen.host,
+    tcpPort: tcpListen.port,
+    tcpAllowRemote,
+    pipePath: pipeArg ?? (env.MCP_PIPE_PATH || undefined),
     conversationMaxMessages: parsePositiveInt(env.CONVERSATION_MAX_MESSAGES, 200),
+    conversationMaxSessions: parsePositiveInt(env.CONVERSATION_MAX_SESSIONS, 1000),
     experimentalV4Enabled: parseBoolean(env.DEEPSEEK_EXPERIMENTAL_V4_ENABLED, false),
+    modelsCacheFile: env.DEEPSEEK_MODELS_CACHE_FILE?.trim() || undefined,
+    disabledTools: parseList(env.DEEPSEEK_DISABLED_TOOLS),
+    enabledTools: parseList(env.DEEPSEEK_ENABLED_TOOLS),
+    enableToolAdmin: parseBoolean(env.DEEPSEEK_ENABLE_TOOL_ADMIN, false),
+    signingSecret: env.DEEPSEEK_SIGNING_SECRET || undefined,
+    poolMaxConnectionsPerHost: parseOptionalPositiveInt(env.DEEPSEEK_POOL_MAX_CONNECTIONS_PER_HOST),
+    poolIdleTimeoutMs: parsePositiveInt(env.DEEPSEEK_POOL_IDLE_TIMEOUT_MS, 60000),
+    tcpKeepAlive: parseBoolean(env.DEEPSEEK_TCP_KEEPALIVE, true),
+    maxResponseBytes: parsePositiveInt(env.DEEPSEEK_MAX_RESPONSE_BYTES, 16 * 1024 * 1024),
+    retryOnDecodeError: parseBoolean(env.DEEPSEEK_RETRY_DECODE, true),
+    monthlyBudgetUsd: parsePositiveNumber(env.DEEPSEEK_MONTHLY_BUDGET_USD),
+    budgetFile: env.DEEPSEEK_BUDGET_FILE?.trim() || undefined,
+    summaryMaxChars: parseOptionalPositiveInt(env.DEEPSEEK_SUMMARY_MAX_CHARS),
+    imageDir: env.DEEPSEEK_IMAGE_DIR?.trim() || undefined,
+    attachDirs: parseList(env.DEEPSEEK_ATTACH_DIRS),
+    attachMaxFileBytes: parsePositiveInt(env.DEEPSEEK_ATTACH_MAX_FILE_BYTES, 102400),
+    attachMaxTotalBytes: parsePositiveInt(env.DEEPSEEK_ATTACH_MAX_TOTAL_BYTES, 409600),
+    maxMessages: parseOptionalPositiveInt(env.DEEPSEEK_MAX_MESSAGES),
+    modelWeights: parseModelWeights(env.DEEPSEEK_MODEL_WEIGHTS),
+    recordDir: env.DEEPSEEK_RECORD_DIR?.trim() || undefined,
+    replayDir: env.DEEPSEEK_REPLAY_DIR?.trim() || undefined,
+    chatToCompletionFallback: parseBoolean(env.DEEPSEEK_CHAT_TO_COMPLETION_FALLBACK, false),
+    errorFormat: (env.DEEPSEEK_ERROR_FORMAT ?? "").trim().toLowerCase() === "json" ? "json" : "text",
+    tokenParam:
+      (env.DEEPSEEK_TOKEN_PARAM ?? "").trim().toLowerCase() === "max_completion_tokens"
+        ? "max_completion_tokens"
+        : "max_tokens",
+    timing: parseBoolean(env.DEEPSEEK_TIMING, false),
+    mergeConsecutiveMessages: parseBoolean(env.DEEPSEEK_MERGE_CONSECUTIVE, false),
+    enableRawRequests: parseBoolean(env.DEEPSEEK_ENABLE_RAW, false),
+    enableBatch: parseBoolean(env.DEEPSEEK_ENABLE_BATCH, false),
+    batchConcurrency: parsePositiveInt(env.DEEPSEEK_BATCH_CONCURRENCY, 4),
+    allowClientApiKeys: parseBoolean(env.DEEPSEEK_ALLOW_CLIENT_KEYS, false),
+    moderationUrl: env.DEEPSEEK_MODERATION_URL?.trim() || undefined,
+    moderationFailMode: (env.DEEPSEEK_MODERATION_FAIL ?? "").trim().toLowerCase() === "open" ? "open" : "closed",
+    compressRequests: parseBoolean(env.DEEPSEEK_COMPRESS_REQUESTS, false),
+    autoContinue: parseBoolean(env.DEEPSEEK_AUTO_CONTINUE, false),
+    autoContinueMax: parsePositiveInt(env.DEEPSEEK_AUTO_CONTINUE_MAX, 3),
+    rateLimitRpm: parsePositiveNumber(env.DEEPSEEK_RATE_LIMIT_RPM),
+    rateLimitBurst: parseOptionalPositiveInt(env.DEEPSEEK_RATE_LIMIT_BURST),
+    rateLimitNotifyMs: parseNonNegativeInt(env.DEEPSEEK_RATE_LIMIT_NOTIFY_MS, 1000),
+    progressIntervalMs: parseNonNegativeInt(env.DEEPSEEK_PROGRESS_INTERVAL_MS, 5000),
+    lowBalanceThreshold: parseNonNegativeNumber(env.DEEPSEEK_LOW_BALANCE_THRESHOLD, 1),
+    balancePollIntervalMs: parsePositiveInt(env.DEEPSEEK_BALANCE_POLL_INTERVAL_MS, 60000),
+    confirmAboveTokens: parseOptionalPositiveInt(env.DEEPSEEK_CONFIRM_ABOVE_TOKENS),
+    confirmAboveUsd: parsePositiveNumber(env.DEEPSEEK_CONFIRM_ABOVE_USD),
+    trimOutput: parseBoolean(env.DEEPSEEK_TRIM_OUTPUT, false),
+    stripCodeFences: parseBoolean(env.DEEPSEEK_STRIP_CODE_FENCES, false),
+    sanitizeExtraBody: parseBoolean(env.DEEPSEEK_SANITIZE_EXTRA, true),
+    compressionThresholdBytes: parsePositiveInt(env.DEEPSEEK_COMPRESS_THRESHOLD_BYTES, 8192),
+    chatPath: env.DEEPSEEK_CHAT_PATH?.trim() || undefined,
+    completionsPath: env.DEEPSEEK_COMPLETIONS_PATH?.trim() || undefined,
+    modelsPath: env.DEEPSEEK_MODELS_PATH?.trim() || undefined,
+    rateLimitRetries: parseNonNegativeInt(env.DEEPSEEK_RATE_LIMIT_RETRIES, 2),
+    rateLimitBackoffMs: parseNonNegativeInt(env.DEEPSEEK_RATE_LIMIT_BACKOFF_MS, 1000),
+    rateLimitMaxBackoffMs: parsePositiveInt(env.DEEPSEEK_RATE_LIMIT_MAX_BACKOFF_MS, 30000),
+    rateLimitJitter: Math.min(1, parseNonNegativeNumber(env.DEEPSEEK_RATE_LIMIT_JITTER, 0.2)),
+    rateLimitSeed: env.DEEPSEEK_RATE_LIMIT_SEED ? parseNonNegativeInt(env.DEEPSEEK_RATE_LIMIT_SEED, 0) : undefined,
+    gitCommit: env.DEEPSEEK_MCP_GIT_COMMIT?.trim() || undefined,
+    loopGuardEnabled: parseBoolean(env.DEEPSEEK_LOOP_GUARD, false),
+    loopGuardNgramSize: parsePositiveInt(env.DEEPSEEK_LOOP_GUARD_NGRAM, 8),
+    loopGuardMaxRepeats: parsePositiveInt(env.DEEPSEEK_LOOP_GUARD_MAX_REPEATS, 4),
+    streamSoftMaxTokens: parseOptionalPositiveInt(env.DEEPSEEK_STREAM_SOFT_MAX_TOKENS),
+    shutdownTimeoutMs: parseNonNegativeInt(env.DEEPSEEK_SHUTDOWN_TIMEOUT_MS, 10000),
+    retryStatuses: parseStatusList(env.DEEPSEEK_RETRY_STATUSES, [408, 409, 429, 500, 502, 503, 504]),
   };
 }
 
@@ -72,6 +314,76 @@ function parsePositiveInt(value: string | undefined, fallback: number): number {
   return parsed;
 }
 
+function parseNonNegativeInt(value: string | undefined, fallback: number): number {
+  if (!value) {
+    return fallback;
+  }
+
+  const parsed = Number.parseInt(value, 10);
+  if (!Number.isFinite(parsed) || parsed < 0) {
+    return fallback;
+  }
+
+  return parsed;
+}
+
+function parseOptionalPositiveInt(value: string | undefined): number | undefined {
+  const parsed = parsePositiveInt(value, 0);
+  return parsed > 0 ? parsed : undefined;
+}
+
+function parsePositiveNumber(value: string | undefined): number | undefined {
+  if (!value) {
+    return undefined;
+  }
+
+  const parsed = Number(value);
+  if (!Number.isFinite(parsed) || parsed <= 0) {
+    return undefined;
+  }
+
+  return parsed;
+}
+
+function parseNonNegativeNumber(value: string | undefined, fallback: number): number {
+  if (!value) {
+    return fallback;
+  }
+
+  const parsed = Number(value);
+  if (!Number.isFinite(parsed) || parsed < 0) {
+    return fallback;
+  }
+
+  return parsed;
+}
+
+export function isLoopbackHost(host: string): boolean {
+  const normalized = host.toLowerCase().replace(/^\[|\]$/g, "");
+  return normalized === "localhost" || normalized === "::1" || /^127(\.\d{1,3}){3}$/.test(normalized);
+}
+
+function parseListenAddress(
+  value: string | undefined,
+  fallbackHost: string,
+  fallbackPort: number,
+): { host: string; port: number } {
+  const trimmed = value?.trim();
+  if (!trimmed) {
+    return { host: fallbackHost, port: fallbackPort };
+  }
+
+  const separator = trimmed.lastIndexOf(":");
+  if (separator === -1) {
+    return { host: fallbackHost, port: parsePort(trimmed, fallbackPort) };
+  }
+
+  return {
+    host: trimmed.slice(0, separator).replace(/^\[|\]$/g, "") || fallbackHost,
+    port: parsePort(trimmed.slice(separator + 1), fallbackPort),
+  };
+}
+
 function parsePort(value: string | undefined, fallback: number): number {
   if (!value) {
     return fallback;
@@ -85,6 +397,120 @@ function parsePort(value: string | undefined, fallback: number): number {
   return parsed;
 }
 
+function parseTransportMode(value: string): McpTransportMode {
+  const normalized = value.toLowerCase();
+  return normalized === "streamable-http" ||
+    normalized === "sse" ||
+    normalized === "websocket" ||
+    normalized === "tcp" ||
+    normalized === "pipe"
+    ? normalized
+    : "stdio";
+}
+
+export function parseCliOption(argv: string[], name: string): string | undefined {
+  const flag = `--${name}`;
+
+  for (let index = 0; index < argv.length; index += 1) {
+    const arg = argv[index];
+    if (arg === flag) {
+      return argv[index + 1];
+    }
+
+    if (arg.startsWith(`${flag}=`)) {
+      return arg.slice(flag.length + 1);
+    }
+  }
+
+  return undefined;
+}
+
+function parseList(value: string | undefined): string[] {
+  if (!value) {
+    return [];
+  }
+
+  return value
+    .split(",")
+    .map((item) => item.trim())
+    .filter((item) => item.length > 0);
+}
+
+function parseStatusList(value: string | undefined, fallback: number[]): number[] {
+  const statuses = parseList(value)
+    .map((item) => Number.parseInt(item, 10))
+    .filter((status) => Number.isInteger(status) && status >= 100 && status <= 599);
+
+  return statuses.length > 0 ? statuses : fallback;
+}
+
+function parseModelWeights(value: string | undefined): Record<string, number> | undefined {
+  if (!value?.trim()) {
+    return undefined;
+  }
+
+  let parsed: unknown;
+  try {
+    parsed = JSON.parse(value);
+  } catch {
+    throw new Error("DEEPSEEK_MODEL_WEIGHTS must be a JSON object of model IDs to weights");
+  }
+
+  if (!parsed || typeof parsed !== "object" || Array.isArray(parsed)) {
+    throw new Error("DEEPSEEK_MODEL_WEIGHTS must be a JSON object of model IDs to weights");
+  }
+
+  const weights: Record<string, number> = {};
+  for (const [model, weight] of Object.entries(parsed)) {
+    if (typeof weight !== "number" || !Number.isFinite(weight) || weight < 0) {
+      throw new Error(`DEEPSEEK_MODEL_WEIGHTS weight for ${model} must be a non-negative number`);
+    }
+
+    weights[model] = weight;
+  }
+
+  return weights;
+}
+
+function parseExtraHeaders(value: string | undefined, env: NodeJS.ProcessEnv): Record<string, string> {
+  if (!value?.trim()) {
+    return {};
+  }
+
+  let parsed: unknown;
+  try {
+    parsed = JSON.parse(value);
+  } catch {
+    throw new Error("DEEPSEEK_EXTRA_HEADERS must be a JSON object of header names to values");
+  }
+
+  if (!parsed || typeof parsed !== "object" || Array.isArray(parsed)) {
+    throw new Error("DEEPSEEK_EXTRA_HEADERS must be a JSON object of header names to values");
+  }
+
+  const headers: Record<string, string> = {};
+  for (const [name, headerValue] of Object.entries(parsed)) {
+    if (typeof headerValue !== "string") {
+      throw new Error(`DEEPSEEK_EXTRA_HEADERS value for ${name} must be a string`);
+    }
+
+    headers[name] = interpolateEnv(headerValue, env, `DEEPSEEK_EXTRA_HEADERS.${name}`);
+  }
+
+  return headers;
+}
+
+function interpolateEnv(value: string, env: NodeJS.ProcessEnv, source: string): string {
+  return value.replace(/\$\{([A-Za-z_][A-Za-z0-9_]*)\}/g, (_match, name: string) => {
+    const resolved = env[name];
+    if (resolved === undefined) {
+      throw new Error(`${source} references unset environment variable ${name}`);
+    }
+
+    return resolved;
+  });
+}
+
 function normalizePath(path: string): string {
   if (!path.startsWith("/")) {
     return `/${path}`;
@@ -92,3 +518,8 @@ function normalizePath(path: string): string {
 
   return path;
 }
+
+function normalizeBasePath(value: string | undefined): string {
+  const trimmed = value?.trim().replace(/\/+$/, "");
+  return trimmed ? normalizePath(trimmed) : "";
+}
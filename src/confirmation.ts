@@ -0,0 +1,61 @@
+import { getModelLimits } from "./deepseek/model-capabilities.js";
+import { estimateCostUsd } from "./deepseek/pricing.js";
+import { estimateMessagesTokens } from "./deepseek/token-estimator.js";
+import { DeepSeekChatCompletionRequest } from "./deepseek/types.js";
+
+export interface RequestCostEstimate {
+  promptTokens: number;
+  maxOutputTokens: number;
+  totalTokens: number;
+  costUsd: number;
+}
+
+export class ConfirmationDeclinedError extends Error {
+  constructor(message: string) {
+    super(message);
+    this.name = "ConfirmationDeclinedError";
+  }
+}
+
+// Upper-bound estimate: the prompt plus the full output budget (the model's maximum when the request sets none),
+// priced as cache misses.
+export function estimateRequestCost(request: DeepSeekChatCompletionRequest): RequestCostEstimate {
+  const promptTokens = estimateMessagesTokens(request.messages);
+  const maxOutputTokens =
+    request.max_tokens ?? request.max_completion_tokens ?? getModelLimits(String(request.model)).maxOutputTokens;
+  const totalTokens = promptTokens + maxOutputTokens;
+
+  return {
+    promptTokens,
+    maxOutputTokens,
+    totalTokens,
+    costUsd: estimateCostUsd(String(request.model), {
+      prompt_tokens: promptTokens,
+      completion_tokens: maxOutputTokens,
+      total_tokens: totalTokens,
+    }),
+  };
+}
+
+// Tools that send several requests for one call (compare_models, batch_chat_completion) confirm their total.
+export function estimateRequestsCost(requests: readonly DeepSeekChatCompletionRequest[]): RequestCostEstimate {
+  return requests.map(estimateRequestCost).reduce(
+    (total, estimate) => ({
+      promptTokens: total.promptTokens + estimate.promptTokens,
+      maxOutputTokens: total.maxOutputTokens + estimate.maxOutputTokens,
+      totalTokens: total.totalTokens + estimate.totalTokens,
+      costUsd: total.costUsd + estimate.costUsd,
+    }),
+    { promptTokens: 0, maxOutputTokens: 0, totalTokens: 0, costUsd: 0 },
+  );
+}
+
+export function exceedsConfirmationThreshold(
+  estimate: RequestCostEstimate,
+  thresholds: { tokens?: number; usd?: number },
+): boolean {
+  return (
+    (thresholds.tokens !== undefined && estimate.totalTokens > thresholds.tokens) ||
+    (thresholds.usd !== undefined && estimate.costUsd > thresholds.usd)
+  );
+}
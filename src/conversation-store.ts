@@ -1,33 +1,143 @@
-import { DeepSeekChatMessage } from "./deepseek/types.js";
+import { estimateMessagesTokens } from "./deepseek/token-estimator.js";
+import { DeepSeekChatMessage, DeepSeekUsage } from "./deepseek/types.js";
+
+export interface ConversationSessionInfo {
+  conversation_id: string;
+  message_count: number;
+  turn_count: number;
+  created_at: string;
+  last_used_at: string;
+  estimated_tokens: number;
+  prompt_tokens_total: number;
+  completion_tokens_total: number;
+}
+
+interface ConversationEntry {
+  messages: DeepSeekChatMessage[];
+  createdAt: Date;
+  lastUsedAt: Date;
+  promptTokensTotal: number;
+  completionTokensTotal: number;
+}
 
 export class ConversationStore {
-  private readonly store = new Map<string, DeepSeekChatMessage[]>();
+  private store = new Map<string, ConversationEntry>();
+  private prefix = "";
   private readonly maxMessagesPerConversation: number;
+  private readonly maxConversations?: number;
+  private readonly now: () => Date;
 
-  constructor(maxMessagesPerConversation = 200) {
+  constructor(maxMessagesPerConversation = 200, maxConversations?: number, now: () => Date = () => new Date()) {
     this.maxMessagesPerConversation = maxMessagesPerConversation;
+    this.maxConversations = maxConversations;
+    this.now = now;
+  }
+
+  // A view of this store whose conversation IDs live in their own namespace, for callers that share one server
+  // (stateless HTTP). The views share the underlying entries, so the session limit still covers all of them.
+  scoped(namespace: string): ConversationStore {
+    const view = new ConversationStore(this.maxMessagesPerConversation, this.maxConversations, this.now);
+    view.store = this.store;
+    view.prefix = `${this.prefix}${namespace}\u0000`;
+    return view;
+  }
+
+  has(conversationId: string): boolean {
+    return this.store.has(this.prefix + conversationId);
   }
 
   get(conversationId: string): DeepSeekChatMessage[] {
-    return [...(this.store.get(conversationId) ?? [])];
+    const key = this.prefix + conversationId;
+    const entry = this.store.get(key);
+    if (!entry) {
+      return [];
+    }
+
+    // A read counts as use, so move the entry to the most-recently-used end.
+    entry.lastUsedAt = this.now();
+    this.store.delete(key);
+    this.store.set(key, entry);
+    return [...entry.messages];
   }
 
   set(conversationId: string, messages: DeepSeekChatMessage[]): void {
-    const normalized = this.cap(messages);
-    this.store.set(conversationId, normalized);
+    this.save(conversationId, this.cap(messages));
   }
 
   append(conversationId: string, messages: DeepSeekChatMessage[]): void {
-    const current = this.store.get(conversationId) ?? [];
-    this.store.set(conversationId, this.cap([...current, ...messages]));
+    const current = this.store.get(this.prefix + conversationId)?.messages ?? [];
+    this.save(conversationId, this.cap([...current, ...messages]));
   }
 
   clear(conversationId: string): boolean {
-    return this.store.delete(conversationId);
+    return this.store.delete(this.prefix + conversationId);
+  }
+
+  recordUsage(conversationId: string, usage: DeepSeekUsage | undefined): void {
+    const entry = this.store.get(this.prefix + conversationId);
+    if (!entry || !usage) {
+      return;
+    }
+
+    entry.promptTokensTotal += typeof usage.prompt_tokens === "number" ? usage.prompt_tokens : 0;
+    entry.completionTokensTotal += typeof usage.completion_tokens === "number" ? usage.completion_tokens : 0;
   }
 
   listConversationIds(): string[] {
-    return [...this.store.keys()].sort();
+    return this.entries()
+      .map(([conversationId]) => conversationId)
+      .sort();
+  }
+
+  listSessions(): ConversationSessionInfo[] {
+    return this.entries()
+      .map(([conversationId, entry]) => describeSession(conversationId, entry))
+      .sort((left, right) => left.conversation_id.localeCompare(right.conversation_id));
+  }
+
+  getSession(conversationId: string): ConversationSessionInfo | undefined {
+    const entry = this.store.get(this.prefix + conversationId);
+    return entry ? describeSession(conversationId, entry) : undefined;
+  }
+
+  // This view's entries, keyed by their unprefixed conversation IDs.
+  private entries(): [string, ConversationEntry][] {
+    return [...this.store.entries()]
+      .filter(([key]) => key.startsWith(this.prefix))
+      .map(([key, entry]): [string, ConversationEntry] => [key.slice(this.prefix.length), entry]);
+  }
+
+  private save(conversationId: string, messages: DeepSeekChatMessage[]): void {
+    const now = this.now();
+    const key = this.prefix + conversationId;
+    const existing = this.store.get(key);
+
+    // Re-inserting keeps Map iteration order equal to least-recently-used first.
+    this.store.delete(key);
+    this.store.set(key, {
+      messages,
+      createdAt: existing?.createdAt ?? now,
+      lastUsedAt: now,
+      promptTokensTotal: existing?.promptTokensTotal ?? 0,
+      completionTokensTotal: existing?.completionTokensTotal ?? 0,
+    });
+
+    this.evict();
+  }
+
+  private evict(): void {
+    if (this.maxConversations === undefined) {
+      return;
+    }
+
+    while (this.store.size > this.maxConversations) {
+      const oldest = this.store.keys().next();
+      if (oldest.done) {
+        return;
+      }
+
+      this.store.delete(oldest.value);
+    }
   }
 
   private cap(messages: DeepSeekChatMessage[]): DeepSeekChatMessage[] {
@@ -38,3 +148,16 @@ export class ConversationStore {
     return messages.slice(messages.length - this.maxMessagesPerConversation);
   }
 }
+
+function describeSession(conversationId: string, entry: ConversationEntry): ConversationSessionInfo {
+  return {
+    conversation_id: conversationId,
+    message_count: entry.messages.length,
+    turn_count: entry.messages.filter((message) => message.role === "user").length,
+    created_at: entry.createdAt.toISOString(),
+    last_used_at: entry.lastUsedAt.toISOString(),
+    estimated_tokens: estimateMessagesTokens(entry.messages),
+    prompt_tokens_total: entry.promptTokensTotal,
+    completion_tokens_total: entry.completionTokensTotal,
+  };
+}
@@ -0,0 +1,200 @@
+import { execFileSync, spawn } from "node:child_process";
+import { closeSync, constants, lstatSync, mkdirSync, openSync, readFileSync, unlinkSync, writeSync } from "node:fs";
+import { tmpdir } from "node:os";
+import { join } from "node:path";
+
+import { parseCliOption } from "./config.js";
+
+export type DaemonCommand = "start" | "stop" | "status";
+
+export interface DaemonOptions {
+  command?: DaemonCommand;
+  pidFile: string;
+  logFile: string;
+  // Set when a default location is in use; startDaemon creates it 0700 and refuses it if someone else owns it.
+  runtimeDir?: string;
+}
+
+// Set on the detached child so it knows to own (and later remove) the PID file.
+export const DAEMON_CHILD_ENV = "DEEPSEEK_DAEMON_CHILD";
+
+export function parseDaemonOptions(env: NodeJS.ProcessEnv, argv: string[]): DaemonOptions {
+  const command = argv.includes("--stop")
+    ? "stop"
+    : argv.includes("--status")
+      ? "status"
+      : argv.includes("--daemon")
+        ? "start"
+        : undefined;
+
+  const pidFile = parseCliOption(argv, "pid-file") ?? env.DEEPSEEK_PID_FILE;
+  const logFile = parseCliOption(argv, "log-file") ?? env.DEEPSEEK_LOG_FILE;
+  const runtimeDir = env.XDG_RUNTIME_DIR || join(tmpdir(), `deepseek-mcp-server-${process.getuid?.() ?? "user"}`);
+  return {
+    command,
+    pidFile: pidFile ?? join(runtimeDir, "deepseek-mcp-server.pid"),
+    logFile: logFile ?? join(runtimeDir, "deepseek-mcp-server.log"),
+    runtimeDir: pidFile && logFile ? undefined : runtimeDir,
+  };
+}
+
+export function readPidFile(pidFile: string): number | undefined {
+  try {
+    const pid = Number.parseInt(readFileSync(pidFile, "utf8").trim(), 10);
+    return Number.isInteger(pid) && pid > 0 ? pid : undefined;
+  } catch {
+    return undefined;
+  }
+}
+
+export function isProcessRunning(pid: number): boolean {
+  try {
+    process.kill(pid, 0);
+    return true;
+  } catch (error) {
+    return (error as NodeJS.ErrnoException).code === "EPERM";
+  }
+}
+
+// A PID file can outlive its process and the PID be reused, so only signal a process whose command line names this
+// entrypoint. Linux reads /proc; elsewhere `ps` answers, and if neither can the process is treated as someone else's.
+export function isDaemonProcess(pid: number): boolean {
+  if (!isProcessRunning(pid)) {
+    return false;
+  }
+
+  let commandLine: string;
+  try {
+    commandLine = readFileSync(`/proc/${pid}/cmdline`, "utf8").split("\0").join(" ");
+  } catch {
+    try {
+      commandLine = execFileSync("ps", ["-p", String(pid), "-o", "command="], { encoding: "utf8" });
+    } catch {
+      return false;
+    }
+  }
+
+  return process.argv[1] !== undefined && commandLine.includes(process.argv[1]);
+}
+
+// Re-runs this entrypoint detached, without --daemon, with stdout/stderr appended to the log file.
+export function startDaemon(options: DaemonOptions, argv: string[]): number {
+  if (options.runtimeDir) {
+    preparePrivateDir(options.runtimeDir);
+  }
+
+  const pidFd = acquirePidFile(options.pidFile);
+  if (pidFd === undefined) {
+    const existing = readPidFile(options.pidFile);
+    console.error(
+      existing !== undefined
+        ? `deepseek-mcp-server is already running (pid ${existing}, ${options.pidFile})`
+        : `deepseek-mcp-server is already starting; remove ${options.pidFile} if no server is running`,
+    );
+    return 1;
+  }
+
+  try {
+    const log = openSync(
+      options.logFile,
+      constants.O_WRONLY | constants.O_APPEND | constants.O_CREAT | (constants.O_NOFOLLOW ?? 0),
+      0o600,
+    );
+    try {
+      const child = spawn(
+        process.execPath,
+        [...process.execArgv, process.argv[1], ...argv.filter((arg) => arg !== "--daemon")],
+        {
+          detached: true,
+          stdio: ["ignore", log, log],
+          env: { ...process.env, [DAEMON_CHILD_ENV]: "1" },
+        },
+      );
+      child.unref();
+      writeSync(pidFd, `${child.pid}\n`);
+      console.log(`deepseek-mcp-server started in the background (pid ${child.pid}, log ${options.logFile})`);
+    } finally {
+      closeSync(log);
+    }
+  } catch (error) {
+    removePidFile(options.pidFile);
+    throw error;
+  } finally {
+    closeSync(pidFd);
+  }
+
+  return 0;
+}
+
+export function stopDaemon(options: DaemonOptions): number {
+  const pid = readPidFile(options.pidFile);
+  if (pid === undefined || !isDaemonProcess(pid)) {
+    removePidFile(options.pidFile);
+    console.log("deepseek-mcp-server is not running");
+    return 1;
+  }
+
+  process.kill(pid, "SIGTERM");
+  console.log(`Sent SIGTERM to deepseek-mcp-server (pid ${pid})`);
+  return 0;
+}
+
+// Exit codes follow the LSB init-script convention: 0 running, 3 not running.
+export function daemonStatus(options: DaemonOptions): number {
+  const pid = readPidFile(options.pidFile);
+  if (pid === undefined || !isDaemonProcess(pid)) {
+    console.log("deepseek-mcp-server is not running");
+    return 3;
+  }
+
+  console.log(`deepseek-mcp-server is running (pid ${pid})`);
+  return 0;
+}
+
+// Called by the detached child, whose PID the parent already wrote: removes the file on exit if it still points at us.
+export function releasePidFileOnExit(pidFile: string): void {
+  process.on("exit", () => {
+    if (readPidFile(pidFile) === process.pid) {
+      removePidFile(pidFile);
+    }
+  });
+}
+
+// O_EXCL makes checking for a running server and claiming the PID file one step, so two concurrent starts can't both
+// spawn. A file left by a dead process is removed and the claim retried once; an empty one means a start in progress.
+function acquirePidFile(pidFile: string): number | undefined {
+  for (let attempt = 0; attempt < 2; attempt += 1) {
+    try {
+      return openSync(pidFile, "wx", 0o600);
+    } catch (error) {
+      if ((error as NodeJS.ErrnoException).code !== "EEXIST") {
+        throw error;
+      }
+
+      const existing = readPidFile(pidFile);
+      if (existing === undefined || isDaemonProcess(existing)) {
+        return undefined;
+      }
+      removePidFile(pidFile);
+    }
+  }
+
+  return undefined;
+}
+
+function preparePrivateDir(dir: string): void {
+  mkdirSync(dir, { recursive: true, mode: 0o700 });
+  const stats = lstatSync(dir);
+  const uid = process.getuid?.();
+  if (!stats.isDirectory() || (uid !== undefined && stats.uid !== uid) || (stats.mode & 0o077) !== 0) {
+    throw new Error(`${dir} must be a directory owned by the current user with mode 0700`);
+  }
+}
+
+function removePidFile(pidFile: string): void {
+  try {
+    unlinkSync(pidFile);
+  } catch {
+    // Already gone.
+  }
+}
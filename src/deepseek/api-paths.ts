@@ -0,0 +1,4 @@
+// Raw requests may only address paths under the configured base URL: no absolute or protocol-relative URLs.
+export function isRelativeApiPath(path: string): boolean {
+  return path.startsWith("/") && !path.startsWith("//") && !/^[a-z][a-z0-9+.-]*:/i.test(path);
+}
@@ -0,0 +1,18 @@
+// `jitter` is the extra fraction of the exponential delay to add (0 = none); the result is capped at `maxMs`.
+export function computeBackoffDelay(attempt: number, baseMs: number, jitter: number, maxMs = Infinity): number {
+  const exponential = baseMs * 2 ** Math.max(0, attempt);
+  return Math.min(maxMs, Math.round(exponential * (1 + Math.max(0, jitter))));
+}
+
+// mulberry32: small deterministic PRNG for reproducible jitter in tests.
+export function createSeededRandom(seed: number): () => number {
+  let state = seed >>> 0;
+
+  return () => {
+    state = (state + 0x6d2b79f5) >>> 0;
+    let value = state;
+    value = Math.imul(value ^ (value >>> 15), value | 1);
+    value ^= value + Math.imul(value ^ (value >>> 7), value | 61);
+    return ((value ^ (value >>> 14)) >>> 0) / 4294967296;
+  };
+}
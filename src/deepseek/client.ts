@@ -1,3 +1,6 @@
+import { createHmac } from "node:crypto";
+import { gzipSync } from "node:zlib";
+
 import {
   ChatCompletionExecutionResult,
   CompletionExecutionResult,
@@ -10,33 +13,103 @@ import {
   DeepSeekUsage,
   DeepSeekUserBalanceResponse,
   FallbackMetadata,
+  NonJsonResponseBody,
+  StreamCompletion,
 } from "./types.js";
+import {
+  ConnectionPoolOptions,
+  ResolvedConnectionPoolOptions,
+  createPooledFetch,
+  resolveConnectionPoolOptions,
+} from "./pooled-fetch.js";
+import { computeBackoffDelay } from "./backoff.js";
+import { isRelativeApiPath } from "./api-paths.js";
+import { LoopDetector, LoopGuardOptions } from "./loop-guard.js";
+import { createRecordingFetch, createReplayFetch } from "./recording-fetch.js";
+import { estimateTextTokens, textTokenWeight } from "./token-estimator.js";
 import { V4_ENDPOINTS, V4_ENDPOINT_CANDIDATES, buildTaskStatusPath } from "./v4-mapping.js";
 
 export interface DeepSeekApiClientOptions {
   apiKey: string;
   baseUrl?: string;
+  baseUrls?: string[];
+  baseUrlCooldownMs?: number;
+  extraHeaders?: Record<string, string>;
   timeoutMs?: number;
   userAgent?: string;
   fetchFn?: typeof fetch;
   enableReasonerFallback?: boolean;
   fallbackModel?: string;
+  signingSecret?: string;
+  connectionPool?: ConnectionPoolOptions;
+  maxResponseBytes?: number;
+  retryOnDecodeError?: boolean;
+  recordDir?: string;
+  replayDir?: string;
+  retryStatuses?: number[];
+  loopGuard?: LoopGuardOptions;
+  streamSoftMaxTokens?: number;
+  rateLimitRetries?: number;
+  rateLimitBackoffMs?: number;
+  rateLimitMaxBackoffMs?: number;
+  rateLimitJitter?: number;
+  random?: () => number;
+  sleepFn?: (ms: number) => Promise<void>;
+  now?: () => number;
+  endpointPaths?: Partial<DeepSeekEndpointPaths>;
+  compressRequests?: boolean;
+  compressionThresholdBytes?: number;
 }
 
+export interface DeepSeekEndpointPaths {
+  chat: string;
+  completions: string;
+  models: string;
+}
+
+export const DEFAULT_ENDPOINT_PATHS: DeepSeekEndpointPaths = {
+  chat: "/chat/completions",
+  completions: "/completions",
+  models: "/models",
+};
+
 const DEFAULT_BASE_URL = "https://api.deepseek.com";
 const DEFAULT_TIMEOUT_MS = 120000;
 const DEFAULT_USER_AGENT = "deepseek-mcp-server/0.3.0";
-const RETRIABLE_STATUS_CODES = new Set([408, 409, 429, 500, 502, 503, 504]);
+const DEFAULT_MAX_RESPONSE_BYTES = 16 * 1024 * 1024;
+export const DEFAULT_BASE_URL_COOLDOWN_MS = 30000;
+export const DEFAULT_RETRY_STATUS_CODES = [408, 409, 429, 500, 502, 503, 504];
+export const DEFAULT_RATE_LIMIT_RETRIES = 2;
+export const DEFAULT_RATE_LIMIT_BACKOFF_MS = 1000;
+export const DEFAULT_RATE_LIMIT_MAX_BACKOFF_MS = 30000;
+export const DEFAULT_RATE_LIMIT_JITTER = 0.2;
+export const DEFAULT_COMPRESSION_THRESHOLD_BYTES = 8 * 1024;
+
+export type DeepSeekErrorCategory =
+  | "auth"
+  | "rate_limit"
+  | "invalid_request"
+  | "server"
+  | "network"
+  | "timeout"
+  | "unknown";
 
 export class DeepSeekApiError extends Error {
   public readonly status?: number;
   public readonly payload?: unknown;
+  public readonly code?: string;
+  public readonly retryable?: boolean;
 
-  constructor(message: string, options?: { status?: number; payload?: unknown; cause?: unknown }) {
+  constructor(
+    message: string,
+    options?: { status?: number; payload?: unknown; cause?: unknown; code?: string; retryable?: boolean },
+  ) {
     super(message);
     this.name = "DeepSeekApiError";
     this.status = options?.status;
     this.payload = options?.payload;
+    this.code = options?.code;
+    this.retryable = options?.retryable;
 
     if (options?.cause !== undefined) {
       Object.defineProperty(this, "cause", {
@@ -47,6 +120,32 @@ export class DeepSeekApiError extends Error {
       });
     }
   }
+
+  category(): DeepSeekErrorCategory {
+    return classifyDeepSeekError(this.status, this.code ?? extractErrorType(this.payload));
+  }
+}
+
+export interface ChatCompletionCallOptions {
+  disableFallback?: boolean;
+  streamSoftMaxTokens?: number;
+  onStreamProgress?: StreamProgressCallback;
+  signal?: AbortSignal;
+  onRetry?: (retry: { attempt: number; delayMs: number; status: number }) => void;
+}
+
+export interface CompletionCallOptions {
+  onStreamProgress?: StreamProgressCallback;
+  signal?: AbortSignal;
+}
+
+// Called per streamed chunk with the estimated number of tokens (content plus reasoning) received so far.
+export type StreamProgressCallback = (receivedTokens: number) => void;
+
+interface ChatStreamOptions {
+  softMaxTokens?: number;
+  onProgress?: StreamProgressCallback;
+  signal?: AbortSignal;
 }
 
 interface RequestOptions {
@@ -55,6 +154,9 @@ interface RequestOptions {
   body?: Record<string, unknown>;
   stream?: boolean;
   baseUrlOverride?: string;
+  onResponse?: () => void;
+  allowNonJson?: boolean;
+  signal?: AbortSignal;
 }
 
 interface CompletionDeltaToolCall {
@@ -68,29 +170,106 @@ interface CompletionDeltaToolCall {
 }
 
 export class DeepSeekApiClient {
+  private readonly options: DeepSeekApiClientOptions;
   private readonly apiKey: string;
   private readonly baseUrl: string;
+  private readonly baseUrls: string[];
+  private readonly baseUrlCooldownMs: number;
+  private readonly baseUrlUnhealthyUntil = new Map<string, number>();
+  private lastBaseUrl?: string;
+  private readonly extraHeaders: Record<string, string>;
   private readonly timeoutMs: number;
   private readonly userAgent: string;
   private readonly fetchFn: typeof fetch;
   private readonly enableReasonerFallback: boolean;
   private readonly fallbackModel: string;
+  private readonly signingSecret?: string;
+  public readonly connectionPool?: ResolvedConnectionPoolOptions;
+  private readonly maxResponseBytes: number;
+  private readonly retryOnDecodeError: boolean;
+  private readonly retryStatuses: Set<number>;
+  private readonly loopGuard?: LoopGuardOptions;
+  private readonly streamSoftMaxTokens?: number;
+  private readonly rateLimitRetries: number;
+  private readonly rateLimitBackoffMs: number;
+  private readonly rateLimitMaxBackoffMs: number;
+  private readonly rateLimitJitter: number;
+  private readonly random: () => number;
+  private readonly sleep: (ms: number) => Promise<void>;
+  private readonly now: () => number;
+  private readonly endpointPaths: DeepSeekEndpointPaths;
+  private readonly compressRequests: boolean;
+  private readonly compressionThresholdBytes: number;
 
   constructor(options: DeepSeekApiClientOptions) {
+    this.options = options;
     this.apiKey = options.apiKey;
-    this.baseUrl = normalizeBaseUrl(options.baseUrl ?? DEFAULT_BASE_URL);
+    this.baseUrls = (
+      options.baseUrls && options.baseUrls.length > 0 ? options.baseUrls : [options.baseUrl ?? DEFAULT_BASE_URL]
+    ).map(normalizeBaseUrl);
+    this.baseUrl = this.baseUrls[0];
+    this.baseUrlCooldownMs = Math.max(0, options.baseUrlCooldownMs ?? DEFAULT_BASE_URL_COOLDOWN_MS);
+    this.extraHeaders = options.extraHeaders ?? {};
     this.timeoutMs = options.timeoutMs ?? DEFAULT_TIMEOUT_MS;
     this.userAgent = options.userAgent ?? DEFAULT_USER_AGENT;
-    this.fetchFn = options.fetchFn ?? fetch;
+    this.connectionPool = options.connectionPool ? resolveConnectionPoolOptions(options.connectionPool) : undefined;
+    const baseFetch = options.fetchFn ?? (this.connectionPool ? createPooledFetch(this.connectionPool) : fetch);
+    this.fetchFn = options.replayDir
+      ? createReplayFetch(options.replayDir)
+      : options.recordDir
+        ? createRecordingFetch(baseFetch, options.recordDir)
+        : baseFetch;
     this.enableReasonerFallback = options.enableReasonerFallback ?? true;
     this.fallbackModel = options.fallbackModel ?? "deepseek-chat";
+    this.signingSecret = options.signingSecret || undefined;
+    this.maxResponseBytes = options.maxResponseBytes ?? DEFAULT_MAX_RESPONSE_BYTES;
+    this.retryOnDecodeError = options.retryOnDecodeError ?? true;
+    this.loopGuard = options.loopGuard;
+    this.streamSoftMaxTokens = options.streamSoftMaxTokens;
+    this.rateLimitRetries = Math.max(0, options.rateLimitRetries ?? DEFAULT_RATE_LIMIT_RETRIES);
+    this.rateLimitBackoffMs = Math.max(0, options.rateLimitBackoffMs ?? DEFAULT_RATE_LIMIT_BACKOFF_MS);
+    this.rateLimitMaxBackoffMs = options.rateLimitMaxBackoffMs ?? DEFAULT_RATE_LIMIT_MAX_BACKOFF_MS;
+    this.rateLimitJitter = Math.max(0, options.rateLimitJitter ?? DEFAULT_RATE_LIMIT_JITTER);
+    this.random = options.random ?? Math.random;
+    this.sleep = options.sleepFn ?? ((ms) => new Promise((resolve) => setTimeout(resolve, ms)));
+    this.now = options.now ?? Date.now;
+    this.compressRequests = options.compressRequests ?? false;
+    this.compressionThresholdBytes = options.compressionThresholdBytes ?? DEFAULT_COMPRESSION_THRESHOLD_BYTES;
+    this.endpointPaths = {
+      chat: options.endpointPaths?.chat || DEFAULT_ENDPOINT_PATHS.chat,
+      completions: options.endpointPaths?.completions || DEFAULT_ENDPOINT_PATHS.completions,
+      models: options.endpointPaths?.models || DEFAULT_ENDPOINT_PATHS.models,
+    };
+    this.retryStatuses = new Set(
+      options.retryStatuses && options.retryStatuses.length > 0 ? options.retryStatuses : DEFAULT_RETRY_STATUS_CODES,
+    );
   }
 
-  async createChatCompletion(request: DeepSeekChatCompletionRequest): Promise<ChatCompletionExecutionResult> {
+  // Same configuration and connection pool, different credentials (e.g. a client-supplied key for one session).
+  withApiKey(apiKey: string): DeepSeekApiClient {
+    return new DeepSeekApiClient({
+      ...this.options,
+      apiKey,
+      fetchFn: this.fetchFn,
+      recordDir: undefined,
+      replayDir: undefined,
+    });
+  }
+
+  async createChatCompletion(
+    request: DeepSeekChatCompletionRequest,
+    options: ChatCompletionCallOptions = {},
+  ): Promise<ChatCompletionExecutionResult> {
+    const streamOptions: ChatStreamOptions = {
+      softMaxTokens: options.streamSoftMaxTokens ?? this.streamSoftMaxTokens,
+      onProgress: options.onStreamProgress,
+      signal: options.signal,
+    };
+
     try {
-      return await this.createChatCompletionNoFallback(request);
+      return await this.createChatCompletionWithRateLimitRetry(request, streamOptions, options.onRetry);
     } catch (error) {
-      if (!this.shouldFallback(request, error)) {
+      if (options.disableFallback || options.signal?.aborted || !this.shouldFallback(request, error)) {
         throw error;
       }
 
@@ -98,7 +277,7 @@ export class DeepSeekApiClient {
         ...request,
         model: this.fallbackModel,
       };
-      const fallback = await this.createChatCompletionNoFallback(fallbackRequest);
+      const fallback = await this.createChatCompletionNoFallback(fallbackRequest, undefined, streamOptions);
 
       const fallbackMetadata: FallbackMetadata = {
         fromModel: String(request.model),
@@ -113,70 +292,226 @@ export class DeepSeekApiClient {
     }
   }
 
+  async *createChatCompletionStream(
+    request: DeepSeekChatCompletionRequest,
+  ): AsyncGenerator<Record<string, unknown>, void, undefined> {
+    const response = await this.send({
+      method: "POST",
+      path: this.endpointPaths.chat,
+      body: { ...request, stream: true },
+      stream: true,
+    });
+
+    if (!response.ok) {
+      throw await this.parseApiError(response);
+    }
+
+    yield* this.readSseEvents<Record<string, unknown>>(response);
+  }
+
+  async createChatPrefixCompletion(
+    request: DeepSeekChatCompletionRequest,
+    options: { signal?: AbortSignal } = {},
+  ): Promise<ChatCompletionExecutionResult> {
+    return this.createChatCompletionNoFallback(request, this.buildBetaBaseUrl(), { signal: options.signal });
+  }
+
+  // 429s back off and retry the same model first; other retryable errors go straight to the fallback model.
+  private async createChatCompletionWithRateLimitRetry(
+    request: DeepSeekChatCompletionRequest,
+    streamOptions: ChatStreamOptions,
+    onRetry?: ChatCompletionCallOptions["onRetry"],
+  ): Promise<ChatCompletionExecutionResult> {
+    for (let attempt = 0; ; attempt += 1) {
+      try {
+        return await this.createChatCompletionNoFallback(request, undefined, streamOptions);
+      } catch (error) {
+        if (
+          attempt >= this.rateLimitRetries ||
+          streamOptions.signal?.aborted ||
+          !(error instanceof DeepSeekApiError) ||
+          error.status !== 429
+        ) {
+          throw error;
+        }
+
+        const delayMs = computeBackoffDelay(
+          attempt,
+          this.rateLimitBackoffMs,
+          this.random() * this.rateLimitJitter,
+          this.rateLimitMaxBackoffMs,
+        );
+        onRetry?.({ attempt: attempt + 1, delayMs, status: error.status });
+        await this.sleep(delayMs);
+      }
+    }
+  }
+
   private async createChatCompletionNoFallback(
     request: DeepSeekChatCompletionRequest,
+    baseUrlOverride?: string,
+    streamOptions: ChatStreamOptions = {},
   ): Promise<ChatCompletionExecutionResult> {
+    const { softMaxTokens: streamSoftMaxTokens, onProgress, signal } = streamOptions;
+    const startedAt = performance.now();
+    const marks: { firstByteAt?: number; firstChunkAt?: number; lastChunkAt?: number } = {};
+    const onResponse = () => {
+      marks.firstByteAt = performance.now();
+    };
+
     if (request.stream) {
-      const chunks = await this.requestSseJson<unknown>({
-        method: "POST",
-        path: "/chat/completions",
-        body: request as Record<string, unknown>,
-        stream: true,
-      });
+      const loopDetector = this.loopGuard ? new LoopDetector(this.loopGuard) : undefined;
+      const loopState: { repeatedFragment?: string } = {};
+      let streamedTokens = 0;
+      let receivedTokens = 0;
+      let softCapped = false;
+
+      const { chunks, doneReceived } = await this.requestSseJson<unknown>(
+        {
+          method: "POST",
+          path: this.endpointPaths.chat,
+          body: request as Record<string, unknown>,
+          stream: true,
+          baseUrlOverride,
+          onResponse,
+          signal,
+        },
+        (chunk) => {
+          marks.lastChunkAt = performance.now();
+          if (marks.firstChunkAt === undefined) {
+            marks.firstChunkAt = marks.lastChunkAt;
+          }
+
+          const deltaContent = extractChatDeltaContent(chunk);
+          if (onProgress) {
+            receivedTokens += estimateTextTokens(deltaContent + extractChatDeltaReasoning(chunk));
+            onProgress(receivedTokens);
+          }
+
+          if (streamSoftMaxTokens !== undefined) {
+            streamedTokens += textTokenWeight(deltaContent);
+            if (Math.ceil(streamedTokens) > streamSoftMaxTokens) {
+              softCapped = true;
+              return true;
+            }
+          }
+
+          if (!loopDetector) {
+            return false;
+          }
+
+          loopState.repeatedFragment = loopDetector.push(deltaContent);
+          return loopState.repeatedFragment !== undefined;
+        },
+      );
+      const finishedAt = performance.now();
 
       return {
         response: aggregateChatCompletionChunks(chunks, String(request.model)),
         streamChunkCount: chunks.length,
+        ...(loopState.repeatedFragment !== undefined
+          ? { loopGuard: { repeatedFragment: loopState.repeatedFragment } }
+          : {}),
+        ...(softCapped ? { softCapped: true } : {}),
+        streamCompletion: describeStreamCompletion(
+          doneReceived,
+          softCapped
+            ? "stopped at soft token cap"
+            : loopState.repeatedFragment !== undefined
+              ? "aborted by loop guard"
+              : undefined,
+        ),
+        timing: {
+          timeToFirstByteMs: roundMs((marks.firstByteAt ?? finishedAt) - startedAt),
+          totalMs: roundMs(finishedAt - startedAt),
+          firstToLastChunkMs: roundMs((marks.lastChunkAt ?? finishedAt) - (marks.firstChunkAt ?? finishedAt)),
+        },
       };
     }
 
     const response = await this.requestJson<DeepSeekChatCompletionResponse>({
       method: "POST",
-      path: "/chat/completions",
+      path: this.endpointPaths.chat,
       body: request as Record<string, unknown>,
       stream: false,
+      baseUrlOverride,
+      onResponse,
+      signal,
     });
+    const finishedAt = performance.now();
 
-    return { response };
+    return {
+      response,
+      timing: {
+        timeToFirstByteMs: roundMs((marks.firstByteAt ?? finishedAt) - startedAt),
+        totalMs: roundMs(finishedAt - startedAt),
+      },
+    };
   }
 
-  async createCompletion(request: DeepSeekCompletionRequest): Promise<CompletionExecutionResult> {
+  async createCompletion(
+    request: DeepSeekCompletionRequest,
+    options: CompletionCallOptions = {},
+  ): Promise<CompletionExecutionResult> {
     try {
-      return await this.createCompletionInternal(request);
+      return await this.createCompletionInternal(request, undefined, options);
     } catch (error) {
-      if (!this.shouldRetryCompletionOnBeta(error)) {
+      if (options.signal?.aborted || !this.shouldRetryCompletionOnBeta(error)) {
         throw error;
       }
 
-      return this.createCompletionInternal(request, this.buildBetaBaseUrl());
+      return this.createCompletionInternal(request, this.buildBetaBaseUrl(), options);
     }
   }
 
+  // FIM always goes to the beta base URL rather than waiting for the "only available when using beta api" rejection.
+  async createFimCompletion(
+    request: DeepSeekCompletionRequest,
+    options: CompletionCallOptions = {},
+  ): Promise<CompletionExecutionResult> {
+    return this.createCompletionInternal(request, this.buildBetaBaseUrl(), options);
+  }
+
   private async createCompletionInternal(
     request: DeepSeekCompletionRequest,
-    baseUrlOverride?: string,
+    baseUrlOverride: string | undefined,
+    { onStreamProgress: onProgress, signal }: CompletionCallOptions,
   ): Promise<CompletionExecutionResult> {
     if (request.stream) {
-      const chunks = await this.requestSseJson<unknown>({
-        method: "POST",
-        path: "/completions",
-        body: request as Record<string, unknown>,
-        stream: true,
-        baseUrlOverride,
-      });
+      let receivedTokens = 0;
+      const { chunks, doneReceived } = await this.requestSseJson<unknown>(
+        {
+          method: "POST",
+          path: this.endpointPaths.completions,
+          body: request as Record<string, unknown>,
+          stream: true,
+          baseUrlOverride,
+          signal,
+        },
+        (chunk) => {
+          if (onProgress) {
+            receivedTokens += estimateTextTokens(extractCompletionDeltaText(chunk));
+            onProgress(receivedTokens);
+          }
+
+          return false;
+        },
+      );
 
       return {
         response: aggregateCompletionChunks(chunks, String(request.model)),
         streamChunkCount: chunks.length,
+        streamCompletion: describeStreamCompletion(doneReceived),
       };
     }
 
     const response = await this.requestJson<DeepSeekCompletionResponse>({
       method: "POST",
-      path: "/completions",
+      path: this.endpointPaths.completions,
       body: request as Record<string, unknown>,
       stream: false,
       baseUrlOverride,
+      signal,
     });
 
     return { response };
@@ -185,7 +520,7 @@ export class DeepSeekApiClient {
   async listModels(): Promise<DeepSeekListModelsResponse> {
     return this.requestJson<DeepSeekListModelsResponse>({
       method: "GET",
-      path: "/models",
+      path: this.endpointPaths.models,
       stream: false,
     });
   }
@@ -198,6 +533,22 @@ export class DeepSeekApiClient {
     });
   }
 
+  async rawRequest(
+    method: "GET" | "POST",
+    path: string,
+    body?: Record<string, unknown>,
+    options: { signal?: AbortSignal } = {},
+  ): Promise<unknown> {
+    if (!isRelativeApiPath(path)) {
+      throw new DeepSeekApiError(`Raw request path must be relative to the base URL, got: ${path}`, {
+        code: "invalid_path",
+        retryable: false,
+      });
+    }
+
+    return this.requestJson<unknown>({ method, path, body, stream: false, allowNonJson: true, signal: options.signal });
+  }
+
   async uploadVisionAsset(request: Record<string, unknown>): Promise<Record<string, unknown>> {
     return this.requestJsonWithFallback<Record<string, unknown>>({
       method: "POST",
@@ -261,18 +612,57 @@ export class DeepSeekApiClient {
       return true;
     }
 
-    return RETRIABLE_STATUS_CODES.has(error.status);
+    return this.retryStatuses.has(error.status);
   }
 
   private async requestJson<T>(options: RequestOptions): Promise<T> {
+    try {
+      return await this.requestJsonOnce<T>(options);
+    } catch (error) {
+      if (!this.retryOnDecodeError || !(error instanceof DeepSeekApiError) || error.code !== "decode_error") {
+        throw error;
+      }
+
+      return this.requestJsonOnce<T>(options);
+    }
+  }
+
+  private async requestJsonOnce<T>(options: RequestOptions): Promise<T> {
     const response = await this.send(options);
 
     if (!response.ok) {
       throw await this.parseApiError(response);
     }
 
-    const payload = await response.json();
-    return payload as T;
+    const contentType = response.headers.get("content-type") ?? "";
+    if (options.allowNonJson && contentType && !contentType.toLowerCase().includes("json")) {
+      const bytes = await this.readBodyBytes(response);
+      const wrapped: NonJsonResponseBody = isTextContentType(contentType)
+        ? { content_type: contentType, body_text: bytes.toString("utf8") }
+        : { content_type: contentType, body_base64: bytes.toString("base64") };
+      return wrapped as T;
+    }
+
+    const raw = await this.readBody(response);
+
+    try {
+      return JSON.parse(raw) as T;
+    } catch (error) {
+      if (!looksLikeJsonPayload(response, raw)) {
+        throw new DeepSeekApiError("DeepSeek API returned a non-JSON response", {
+          status: response.status,
+          payload: raw,
+          code: "non_json_response",
+        });
+      }
+
+      throw new DeepSeekApiError("Failed to decode DeepSeek API JSON response", {
+        status: response.status,
+        payload: raw,
+        cause: error,
+        code: "decode_error",
+      });
+    }
   }
 
   private async requestJsonWithFallback<T>(options: {
@@ -317,13 +707,33 @@ export class DeepSeekApiClient {
     throw new DeepSeekApiError("No endpoint path candidates configured");
   }
 
-  private async requestSseJson<T>(options: RequestOptions): Promise<T[]> {
+  private async requestSseJson<T>(
+    options: RequestOptions,
+    shouldStop?: (chunk: T) => boolean,
+  ): Promise<{ chunks: T[]; doneReceived: boolean }> {
     const response = await this.send(options);
 
     if (!response.ok) {
       throw await this.parseApiError(response);
     }
 
+    const streamState = { doneReceived: false };
+    const chunks: T[] = [];
+    for await (const chunk of this.readSseEvents<T>(response, streamState, options.signal)) {
+      chunks.push(chunk);
+      if (shouldStop?.(chunk)) {
+        break;
+      }
+    }
+
+    return { chunks, doneReceived: streamState.doneReceived };
+  }
+
+  private async *readSseEvents<T>(
+    response: Response,
+    streamState?: { doneReceived: boolean },
+    signal?: AbortSignal,
+  ): AsyncGenerator<T, void, undefined> {
     if (!response.body) {
       throw new DeepSeekApiError("DeepSeek API returned an empty stream response", {
         status: response.status,
@@ -334,71 +744,230 @@ export class DeepSeekApiClient {
     const decoder = new TextDecoder();
 
     let buffer = "";
-    const chunks: T[] = [];
+    let receivedBytes = 0;
+    let finished = false;
+    const cancel = () => void reader.cancel().catch(() => undefined);
+    signal?.addEventListener("abort", cancel, { once: true });
 
-    while (true) {
-      const { done, value } = await reader.read();
-      if (done) {
-        break;
-      }
+    try {
+      while (true) {
+        const { done, value } = await reader.read();
+        if (signal?.aborted) {
+          throw cancelledError();
+        }
+        if (done) {
+          break;
+        }
+
+        receivedBytes += value.byteLength;
+        if (receivedBytes > this.maxResponseBytes) {
+          throw this.responseTooLargeError(response.status);
+        }
 
-      buffer += decoder.decode(value, { stream: true }).replace(/\r\n/g, "\n");
+        buffer += decoder.decode(value, { stream: true }).replace(/\r\n/g, "\n");
 
-      let splitIndex = buffer.indexOf("\n\n");
-      while (splitIndex !== -1) {
-        const eventBlock = buffer.slice(0, splitIndex).trim();
-        buffer = buffer.slice(splitIndex + 2);
+        let splitIndex = buffer.indexOf("\n\n");
+        while (splitIndex !== -1) {
+          const eventBlock = buffer.slice(0, splitIndex).trim();
+          buffer = buffer.slice(splitIndex + 2);
 
-        const parsedChunk = parseSseEventBlock<T>(eventBlock);
-        if (parsedChunk !== undefined) {
-          chunks.push(parsedChunk);
+          const data = extractSseData(eventBlock);
+          if (data === "[DONE]") {
+            if (streamState) {
+              streamState.doneReceived = true;
+            }
+          } else if (data !== undefined) {
+            yield parseSseData<T>(data);
+          }
+
+          splitIndex = buffer.indexOf("\n\n");
         }
+      }
 
-        splitIndex = buffer.indexOf("\n\n");
+      const finalData = extractSseData(buffer.trim());
+      if (finalData === "[DONE]") {
+        if (streamState) {
+          streamState.doneReceived = true;
+        }
+      } else if (finalData !== undefined) {
+        yield parseSseData<T>(finalData);
+      }
+
+      finished = true;
+    } finally {
+      signal?.removeEventListener("abort", cancel);
+      if (!finished) {
+        await reader.cancel().catch(() => undefined);
       }
     }
+  }
+
+  get hasApiKey(): boolean {
+    return this.apiKey.length > 0;
+  }
+
+  get reasonerFallback(): { enabled: boolean; model: string } {
+    return { enabled: this.enableReasonerFallback, model: this.fallbackModel };
+  }
+
+  get activeBaseUrl(): string | undefined {
+    return this.lastBaseUrl;
+  }
 
-    const finalChunk = parseSseEventBlock<T>(buffer.trim());
-    if (finalChunk !== undefined) {
-      chunks.push(finalChunk);
+  // Reachability only: any non-5xx answer from the beta base counts, even a 404 for /models.
+  async probeBetaBaseUrl(
+    signal?: AbortSignal,
+  ): Promise<{ base_url: string; reachable: boolean; status: number | null; error?: string }> {
+    const baseUrl = this.buildBetaBaseUrl();
+    try {
+      const response = await this.sendTo({ method: "GET", path: this.endpointPaths.models, signal }, baseUrl);
+      await response.body?.cancel().catch(() => undefined);
+      return { base_url: baseUrl, reachable: response.status < 500, status: response.status };
+    } catch (error) {
+      return {
+        base_url: baseUrl,
+        reachable: false,
+        status: null,
+        error: error instanceof Error ? error.message : String(error),
+      };
     }
+  }
 
-    return chunks;
+  getBaseUrlHealth(): { base_url: string; healthy: boolean }[] {
+    return this.baseUrls.map((baseUrl) => ({ base_url: baseUrl, healthy: this.isBaseUrlHealthy(baseUrl) }));
+  }
+
+  // A failed base sits out for baseUrlCooldownMs; after that the next request re-probes it in priority order.
+  private isBaseUrlHealthy(baseUrl: string): boolean {
+    return (this.baseUrlUnhealthyUntil.get(baseUrl) ?? 0) <= this.now();
+  }
+
+  private markBaseUrlHealth(baseUrl: string, healthy: boolean): void {
+    if (healthy) {
+      this.baseUrlUnhealthyUntil.delete(baseUrl);
+    } else {
+      this.baseUrlUnhealthyUntil.set(baseUrl, this.now() + this.baseUrlCooldownMs);
+    }
   }
 
+  // Healthy bases keep their configured priority; bases still cooling down after a failure are tried after them.
   private async send(options: RequestOptions): Promise<Response> {
+    if (!this.apiKey) {
+      throw new DeepSeekApiError("No DeepSeek API key configured; network tools are unavailable in offline mode", {
+        code: "no_api_key",
+        retryable: false,
+      });
+    }
+
+    if (options.baseUrlOverride) {
+      return this.sendTo(options, options.baseUrlOverride);
+    }
+
+    const candidates = [
+      ...this.baseUrls.filter((baseUrl) => this.isBaseUrlHealthy(baseUrl)),
+      ...this.baseUrls.filter((baseUrl) => !this.isBaseUrlHealthy(baseUrl)),
+    ];
+
+    for (let index = 0; index < candidates.length; index += 1) {
+      const baseUrl = candidates[index];
+      const isLast = index === candidates.length - 1;
+
+      let response: Response;
+      try {
+        response = await this.sendTo(options, baseUrl);
+      } catch (error) {
+        if (options.signal?.aborted) {
+          throw error;
+        }
+
+        this.markBaseUrlHealth(baseUrl, false);
+        if (isLast) {
+          throw error;
+        }
+
+        continue;
+      }
+
+      const healthy = response.status < 500;
+      this.markBaseUrlHealth(baseUrl, healthy);
+      if (!healthy && !isLast) {
+        await response.body?.cancel().catch(() => undefined);
+        continue;
+      }
+
+      if (healthy) {
+        this.lastBaseUrl = baseUrl;
+      }
+
+      return response;
+    }
+
+    throw new DeepSeekApiError("No DeepSeek base URLs configured");
+  }
+
+  private async sendTo(options: RequestOptions, baseUrl: string): Promise<Response> {
+    if (options.signal?.aborted) {
+      throw cancelledError();
+    }
+
+    // The caller's listener is removed once the headers arrive; readSseEvents watches the signal while streaming.
     const controller = new AbortController();
     const timeoutId = setTimeout(() => controller.abort(), this.timeoutMs);
+    const abort = () => controller.abort();
+    options.signal?.addEventListener("abort", abort, { once: true });
+
+    const body = options.body ? JSON.stringify(options.body) : undefined;
+    const headers: Record<string, string> = {
+      ...this.extraHeaders,
+      Authorization: `Bearer ${this.apiKey}`,
+      "Content-Type": "application/json",
+      Accept: options.stream ? "text/event-stream" : "application/json",
+      "User-Agent": this.userAgent,
+    };
+
+    if (this.signingSecret) {
+      const timestamp = String(Math.floor(this.now() / 1000));
+      headers["X-Timestamp"] = timestamp;
+      headers["X-Signature"] = signRequestBody(this.signingSecret, timestamp, body ?? "");
+    }
+
+    // The signature always covers the uncompressed JSON, so the receiver must inflate the body before verifying it.
+    let requestBody: string | Uint8Array | undefined = body;
+    if (this.compressRequests && body !== undefined && Buffer.byteLength(body) > this.compressionThresholdBytes) {
+      requestBody = new Uint8Array(gzipSync(body));
+      headers["Content-Encoding"] = "gzip";
+    }
 
     try {
-      const response = await this.fetchFn(this.resolveUrl(options.path, options.baseUrlOverride), {
+      const response = await this.fetchFn(this.resolveUrl(options.path, baseUrl), {
         method: options.method,
-        headers: {
-          Authorization: `Bearer ${this.apiKey}`,
-          "Content-Type": "application/json",
-          Accept: options.stream ? "text/event-stream" : "application/json",
-          "User-Agent": this.userAgent,
-        },
-        body: options.body ? JSON.stringify(options.body) : undefined,
+        headers,
+        body: requestBody,
         signal: controller.signal,
       });
 
+      options.onResponse?.();
       return response;
     } catch (error) {
       if (error instanceof DeepSeekApiError) {
         throw error;
       }
 
+      if (options.signal?.aborted) {
+        throw cancelledError(error);
+      }
+
       if (error instanceof Error && error.name === "AbortError") {
         throw new DeepSeekApiError(
           `DeepSeek API request timed out after ${this.timeoutMs}ms`,
-          { cause: error },
+          { cause: error, code: "timeout" },
         );
       }
 
-      throw new DeepSeekApiError("Failed to call DeepSeek API", { cause: error });
+      throw new DeepSeekApiError("Failed to call DeepSeek API", { cause: error, code: "network_error" });
     } finally {
       clearTimeout(timeoutId);
+      options.signal?.removeEventListener("abort", abort);
     }
   }
 
@@ -406,13 +975,14 @@ export class DeepSeekApiClient {
     let payload: unknown;
 
     try {
-      payload = await response.json();
-    } catch {
+      const raw = await this.readBody(response);
       try {
-        payload = await response.text();
+        payload = JSON.parse(raw);
       } catch {
-        payload = undefined;
+        payload = raw;
       }
+    } catch {
+      payload = undefined;
     }
 
     const message = extractErrorMessage(payload) || `DeepSeek API request failed with status ${response.status}`;
@@ -420,12 +990,56 @@ export class DeepSeekApiClient {
     return new DeepSeekApiError(message, {
       status: response.status,
       payload,
+      retryable: this.retryStatuses.has(response.status),
     });
   }
 
-  private resolveUrl(path: string, baseUrlOverride?: string): string {
+  private async readBody(response: Response): Promise<string> {
+    return (await this.readBodyBytes(response)).toString("utf8");
+  }
+
+  private async readBodyBytes(response: Response): Promise<Buffer> {
+    const declaredLength = Number(response.headers.get("content-length"));
+    if (Number.isFinite(declaredLength) && declaredLength > this.maxResponseBytes) {
+      await response.body?.cancel().catch(() => undefined);
+      throw this.responseTooLargeError(response.status);
+    }
+
+    if (!response.body) {
+      return Buffer.alloc(0);
+    }
+
+    const reader = response.body.getReader();
+    const parts: Uint8Array[] = [];
+    let receivedBytes = 0;
+
+    while (true) {
+      const { done, value } = await reader.read();
+      if (done) {
+        break;
+      }
+
+      receivedBytes += value.byteLength;
+      if (receivedBytes > this.maxResponseBytes) {
+        await reader.cancel().catch(() => undefined);
+        throw this.responseTooLargeError(response.status);
+      }
+
+      parts.push(value);
+    }
+
+    return Buffer.concat(parts);
+  }
+
+  private responseTooLargeError(status: number): DeepSeekApiError {
+    return new DeepSeekApiError(
+      `DeepSeek API response exceeded the ${this.maxResponseBytes} byte limit (DEEPSEEK_MAX_RESPONSE_BYTES)`,
+      { status },
+    );
+  }
+
+  private resolveUrl(path: string, baseUrl: string): string {
     const normalizedPath = path.startsWith("/") ? path : `/${path}`;
-    const baseUrl = baseUrlOverride ?? this.baseUrl;
     return `${baseUrl}${normalizedPath}`;
   }
 
@@ -453,11 +1067,82 @@ export class DeepSeekApiClient {
   }
 }
 
+export function signRequestBody(secret: string, timestamp: string, body: string): string {
+  return createHmac("sha256", secret).update(`${timestamp}.${body}`).digest("hex");
+}
+
 function normalizeBaseUrl(input: string): string {
   return input.endsWith("/") ? input.slice(0, -1) : input;
 }
 
-function parseSseEventBlock<T>(block: string): T | undefined {
+function roundMs(value: number): number {
+  return Math.round(value * 100) / 100;
+}
+
+function extractChatDeltaContent(chunk: unknown): string {
+  if (!isObject(chunk) || !Array.isArray(chunk.choices)) {
+    return "";
+  }
+
+  const delta = isObject(chunk.choices[0]) ? chunk.choices[0].delta : undefined;
+  return isObject(delta) && typeof delta.content === "string" ? delta.content : "";
+}
+
+function cancelledError(cause?: unknown): DeepSeekApiError {
+  return new DeepSeekApiError("DeepSeek API request was cancelled by the client", {
+    cause,
+    code: "cancelled",
+    retryable: false,
+  });
+}
+
+function extractChatDeltaReasoning(chunk: unknown): string {
+  if (!isObject(chunk) || !Array.isArray(chunk.choices)) {
+    return "";
+  }
+
+  const delta = isObject(chunk.choices[0]) ? chunk.choices[0].delta : undefined;
+  return isObject(delta) && typeof delta.reasoning_content === "string" ? delta.reasoning_content : "";
+}
+
+function extractCompletionDeltaText(chunk: unknown): string {
+  if (!isObject(chunk) || !Array.isArray(chunk.choices)) {
+    return "";
+  }
+
+  const choice = chunk.choices[0];
+  return isObject(choice) && typeof choice.text === "string" ? choice.text : "";
+}
+
+function isTextContentType(contentType: string): boolean {
+  const normalized = contentType.toLowerCase();
+  return (
+    normalized.startsWith("text/") ||
+    normalized.includes("xml") ||
+    normalized.includes("javascript") ||
+    normalized.includes("x-www-form-urlencoded")
+  );
+}
+
+function looksLikeJsonPayload(response: Response, raw: string): boolean {
+  const contentType = (response.headers.get("content-type") ?? "").toLowerCase();
+  if (contentType.includes("json")) {
+    return true;
+  }
+
+  const trimmed = raw.trimStart();
+  return trimmed === "" || trimmed.startsWith("{") || trimmed.startsWith("[");
+}
+
+function describeStreamCompletion(doneReceived: boolean, stoppedReason?: string): StreamCompletion {
+  if (doneReceived) {
+    return { completed: true };
+  }
+
+  return { completed: false, reason: stoppedReason ?? "stream ended without [DONE]" };
+}
+
+function extractSseData(block: string): string | undefined {
   if (!block) {
     return undefined;
   }
@@ -471,11 +1156,10 @@ function parseSseEventBlock<T>(block: string): T | undefined {
     return undefined;
   }
 
-  const data = dataLines.join("\n").trim();
-  if (data === "[DONE]") {
-    return undefined;
-  }
+  return dataLines.join("\n").trim();
+}
 
+function parseSseData<T>(data: string): T {
   try {
     return JSON.parse(data) as T;
   } catch (error) {
@@ -486,15 +1170,22 @@ function parseSseEventBlock<T>(block: string): T | undefined {
   }
 }
 
+interface ChatChoiceAccumulator {
+  finishReason: string | null;
+  content: string;
+  reasoningContent: string;
+  toolCalls: DeepSeekToolCall[];
+  logprobs: unknown[];
+}
+
+// Rebuilds the non-streaming chat.completion shape so streamed and buffered results are interchangeable.
 function aggregateChatCompletionChunks(chunks: unknown[], requestedModel: string): DeepSeekChatCompletionResponse {
   let id = "";
   let model = requestedModel;
   let created = Math.floor(Date.now() / 1000);
-  let finishReason: string | null = null;
-  let content = "";
-  let reasoningContent = "";
+  let systemFingerprint: string | undefined;
   let usage: DeepSeekUsage | undefined;
-  const toolCalls: DeepSeekToolCall[] = [];
+  const choices = new Map<number, ChatChoiceAccumulator>();
 
   for (const chunk of chunks) {
     if (!isObject(chunk)) {
@@ -513,67 +1204,88 @@ function aggregateChatCompletionChunks(chunks: unknown[], requestedModel: string
       created = chunk.created;
     }
 
-    if (isObject(chunk.usage)) {
-      usage = chunk.usage as DeepSeekUsage;
+    if (typeof chunk.system_fingerprint === "string") {
+      systemFingerprint = chunk.system_fingerprint;
     }
 
-    const choices = Array.isArray(chunk.choices) ? chunk.choices : [];
-    const choice = choices[0];
-    if (!isObject(choice)) {
-      continue;
+    if (isObject(chunk.usage)) {
+      usage = { ...usage, ...chunk.usage } as DeepSeekUsage;
     }
 
-    if (typeof choice.finish_reason === "string") {
-      finishReason = choice.finish_reason;
-    }
+    for (const choice of Array.isArray(chunk.choices) ? chunk.choices : []) {
+      if (!isObject(choice)) {
+        continue;
+      }
 
-    const delta = isObject(choice.delta)
-      ? choice.delta
-      : isObject(choice.message)
-        ? choice.message
-        : undefined;
+      const index = typeof choice.index === "number" ? choice.index : 0;
+      let accumulator = choices.get(index);
+      if (!accumulator) {
+        accumulator = { finishReason: null, content: "", reasoningContent: "", toolCalls: [], logprobs: [] };
+        choices.set(index, accumulator);
+      }
 
-    if (!delta) {
-      continue;
-    }
+      if (typeof choice.finish_reason === "string") {
+        accumulator.finishReason = choice.finish_reason;
+      }
 
-    if (typeof delta.content === "string") {
-      content += delta.content;
-    }
+      if (isObject(choice.logprobs) && Array.isArray(choice.logprobs.content)) {
+        accumulator.logprobs.push(...choice.logprobs.content);
+      }
 
-    if (typeof delta.reasoning_content === "string") {
-      reasoningContent += delta.reasoning_content;
-    }
+      const delta = isObject(choice.delta)
+        ? choice.delta
+        : isObject(choice.message)
+          ? choice.message
+          : undefined;
+
+      if (!delta) {
+        continue;
+      }
 
-    const deltaToolCalls = Array.isArray(delta.tool_calls)
-      ? (delta.tool_calls as CompletionDeltaToolCall[])
-      : [];
+      if (typeof delta.content === "string") {
+        accumulator.content += delta.content;
+      }
 
-    mergeDeltaToolCalls(toolCalls, deltaToolCalls);
+      if (typeof delta.reasoning_content === "string") {
+        accumulator.reasoningContent += delta.reasoning_content;
+      }
+
+      const deltaToolCalls = Array.isArray(delta.tool_calls)
+        ? (delta.tool_calls as CompletionDeltaToolCall[])
+        : [];
+
+      mergeDeltaToolCalls(accumulator.toolCalls, deltaToolCalls);
+    }
   }
 
   if (!id) {
     id = `chatcmpl-${Date.now()}`;
   }
 
+  if (choices.size === 0) {
+    choices.set(0, { finishReason: null, content: "", reasoningContent: "", toolCalls: [], logprobs: [] });
+  }
+
   return {
     id,
     object: "chat.completion",
     created,
     model,
-    choices: [
-      {
-        index: 0,
-        finish_reason: finishReason,
+    choices: [...choices.entries()]
+      .sort(([left], [right]) => left - right)
+      .map(([index, accumulator]) => ({
+        index,
+        finish_reason: accumulator.finishReason,
         message: {
-          role: "assistant",
-          content: content || null,
-          ...(reasoningContent ? { reasoning_content: reasoningContent } : {}),
-          ...(toolCalls.length > 0 ? { tool_calls: toolCalls } : {}),
+          role: "assistant" as const,
+          content: accumulator.content || null,
+          ...(accumulator.reasoningContent ? { reasoning_content: accumulator.reasoningContent } : {}),
+          ...(accumulator.toolCalls.length > 0 ? { tool_calls: accumulator.toolCalls } : {}),
         },
-      },
-    ],
+        ...(accumulator.logprobs.length > 0 ? { logprobs: { content: accumulator.logprobs } } : {}),
+      })),
     ...(usage ? { usage } : {}),
+    ...(systemFingerprint ? { system_fingerprint: systemFingerprint } : {}),
   };
 }
 
@@ -676,6 +1388,53 @@ function isObject(value: unknown): value is Record<string, unknown> {
   return typeof value === "object" && value !== null;
 }
 
+export function classifyDeepSeekError(status: number | undefined, code?: string): DeepSeekErrorCategory {
+  const normalizedCode = (code ?? "").toLowerCase();
+
+  if (normalizedCode === "timeout" || status === 408 || status === 504) {
+    return "timeout";
+  }
+
+  if (normalizedCode.includes("auth") || normalizedCode === "no_api_key" || normalizedCode.includes("permission")) {
+    return "auth";
+  }
+
+  if (normalizedCode.includes("rate_limit") || status === 429) {
+    return "rate_limit";
+  }
+
+  if (normalizedCode === "network_error") {
+    return "network";
+  }
+
+  if (status === 401 || status === 402 || status === 403) {
+    return "auth";
+  }
+
+  if (normalizedCode.includes("invalid") || (status !== undefined && status >= 400 && status < 500)) {
+    return "invalid_request";
+  }
+
+  if (
+    (status !== undefined && status >= 500) ||
+    normalizedCode === "decode_error" ||
+    normalizedCode === "non_json_response" ||
+    normalizedCode.includes("server")
+  ) {
+    return "server";
+  }
+
+  return "unknown";
+}
+
+export function extractErrorType(payload: unknown): string | undefined {
+  if (!isObject(payload) || !isObject(payload.error)) {
+    return undefined;
+  }
+
+  return typeof payload.error.type === "string" ? payload.error.type : undefined;
+}
+
 function extractErrorMessage(payload: unknown): string {
   if (typeof payload === "string") {
     return payload;
@@ -0,0 +1,133 @@
+import { open, realpath } from "node:fs/promises";
+import { extname, isAbsolute, relative, resolve } from "node:path";
+
+import { DeepSeekChatMessage } from "./types.js";
+
+export const DEFAULT_ATTACH_MAX_FILE_BYTES = 100 * 1024;
+export const DEFAULT_ATTACH_MAX_TOTAL_BYTES = 400 * 1024;
+
+export interface AttachmentLimits {
+  maxFileBytes?: number;
+  maxTotalBytes?: number;
+}
+
+export interface AttachedFileInfo {
+  path: string;
+  bytes: number;
+  included_bytes: number;
+  truncated: boolean;
+}
+
+export interface FileAttachments {
+  text: string;
+  files: AttachedFileInfo[];
+}
+
+// Reads each file from inside one of the allowed roots and renders them as fenced blocks for a prompt.
+// Files over the per-file limit, or past the total budget, are cut and marked as truncated.
+export async function attachFiles(
+  paths: string[],
+  allowedRoots: string[],
+  limits: AttachmentLimits = {},
+): Promise<FileAttachments> {
+  if (allowedRoots.length === 0) {
+    throw new Error(
+      "attach_files has no readable roots; DEEPSEEK_ATTACH_DIRS is unset and client MCP roots are only used over stdio",
+    );
+  }
+
+  const maxFileBytes = limits.maxFileBytes ?? DEFAULT_ATTACH_MAX_FILE_BYTES;
+  let remaining = limits.maxTotalBytes ?? DEFAULT_ATTACH_MAX_TOTAL_BYTES;
+  const roots = await Promise.all(allowedRoots.map((root) => realpath(root).catch(() => undefined)));
+  const blocks: string[] = [];
+  const files: AttachedFileInfo[] = [];
+
+  for (const filePath of paths) {
+    const resolvedPath = await resolveInsideRoots(filePath, roots.filter((root): root is string => root !== undefined));
+    const { size, data } = await readHead(resolvedPath, filePath, Math.max(Math.min(maxFileBytes, remaining), 0));
+    if (data.includes(0)) {
+      throw new Error(`Attachment ${filePath} looks like a binary file; only text files can be attached`);
+    }
+
+    const includedBytes = data.length;
+    remaining -= includedBytes;
+    const truncated = includedBytes < size;
+    const content = data.toString("utf8");
+    const fence = "`".repeat(Math.max(3, longestBacktickRun(content) + 1));
+
+    blocks.push(
+      [
+        `Attached file: ${filePath}`,
+        `${fence}${extname(filePath).slice(1)}`,
+        content,
+        fence,
+        truncated ? `[truncated: included ${includedBytes} of ${size} bytes]` : undefined,
+      ]
+        .filter((line) => line !== undefined)
+        .join("\n"),
+    );
+    files.push({ path: filePath, bytes: size, included_bytes: includedBytes, truncated });
+  }
+
+  return { text: blocks.join("\n\n"), files };
+}
+
+// The attachments go in their own user turn just before the newest message, so the question stays last.
+export function insertAttachmentMessage(messages: DeepSeekChatMessage[], text: string): DeepSeekChatMessage[] {
+  const attachment: DeepSeekChatMessage = { role: "user", content: text };
+  return [...messages.slice(0, -1), attachment, ...messages.slice(-1)];
+}
+
+async function resolveInsideRoots(filePath: string, roots: string[]): Promise<string> {
+  for (const root of roots) {
+    const resolvedPath = await realpath(resolve(root, filePath)).catch(() => undefined);
+    if (!resolvedPath) {
+      continue;
+    }
+
+    const relativePath = relative(root, resolvedPath);
+    if (!relativePath.startsWith("..") && !isAbsolute(relativePath)) {
+      return resolvedPath;
+    }
+  }
+
+  throw new Error(`Attachment ${filePath} was not found inside the allowed roots`);
+}
+
+// Reads no more than `limit` bytes, so a huge file never lands in memory, and cuts a truncated read back to the
+// start of any UTF-8 character the limit would split. One extra byte is read to see whether the cut splits one.
+async function readHead(
+  resolvedPath: string,
+  filePath: string,
+  limit: number,
+): Promise<{ size: number; data: Buffer }> {
+  const handle = await open(resolvedPath, "r");
+  try {
+    const info = await handle.stat();
+    if (!info.isFile()) {
+      throw new Error(`Attachment ${filePath} is not a regular file`);
+    }
+
+    const buffer = Buffer.alloc(Math.min(info.size, limit + 1));
+    const { bytesRead } = await handle.read(buffer, 0, buffer.length, 0);
+    if (bytesRead <= limit) {
+      return { size: info.size, data: buffer.subarray(0, bytesRead) };
+    }
+
+    let end = limit;
+    while (end > 0 && limit - end < 3 && (buffer[end] & 0xc0) === 0x80) {
+      end -= 1;
+    }
+    return { size: info.size, data: buffer.subarray(0, end) };
+  } finally {
+    await handle.close();
+  }
+}
+
+function longestBacktickRun(text: string): number {
+  let longest = 0;
+  for (const match of text.match(/`+/g) ?? []) {
+    longest = Math.max(longest, match.length);
+  }
+  return longest;
+}
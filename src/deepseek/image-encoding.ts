@@ -0,0 +1,56 @@
+import { readFile, realpath, stat } from "node:fs/promises";
+import { extname, isAbsolute, relative, resolve } from "node:path";
+
+const IMAGE_MIME_TYPES: Record<string, string> = {
+  ".png": "image/png",
+  ".jpg": "image/jpeg",
+  ".jpeg": "image/jpeg",
+  ".gif": "image/gif",
+  ".webp": "image/webp",
+};
+
+export const DEFAULT_MAX_IMAGE_BYTES = 20 * 1024 * 1024;
+
+export interface EncodedImage {
+  path: string;
+  mime_type: string;
+  bytes: number;
+  data_url: string;
+}
+
+export async function encodeImageFile(
+  filePath: string,
+  allowedDir: string,
+  maxBytes = DEFAULT_MAX_IMAGE_BYTES,
+): Promise<EncodedImage> {
+  const mimeType = IMAGE_MIME_TYPES[extname(filePath).toLowerCase()];
+  if (!mimeType) {
+    throw new Error(
+      `Unsupported image extension for ${filePath}; expected one of ${Object.keys(IMAGE_MIME_TYPES).join(", ")}`,
+    );
+  }
+
+  const allowedRoot = await realpath(allowedDir);
+  const resolvedPath = await realpath(resolve(allowedRoot, filePath));
+  const relativePath = relative(allowedRoot, resolvedPath);
+  if (relativePath.startsWith("..") || isAbsolute(relativePath)) {
+    throw new Error(`Image path ${filePath} is outside the allowed directory`);
+  }
+
+  const info = await stat(resolvedPath);
+  if (!info.isFile()) {
+    throw new Error(`Image path ${filePath} is not a regular file`);
+  }
+
+  if (info.size > maxBytes) {
+    throw new Error(`Image ${filePath} is ${info.size} bytes, above the ${maxBytes} byte limit`);
+  }
+
+  const data = await readFile(resolvedPath);
+  return {
+    path: resolvedPath,
+    mime_type: mimeType,
+    bytes: data.length,
+    data_url: `data:${mimeType};base64,${data.toString("base64")}`,
+  };
+}
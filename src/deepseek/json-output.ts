@@ -0,0 +1,51 @@
+import { AjvJsonSchemaValidator } from "@modelcontextprotocol/sdk/validation/ajv-provider.js";
+import { JsonSchemaType } from "@modelcontextprotocol/sdk/validation/types.js";
+
+export type JsonOutputCheck = { valid: true; data: unknown } | { valid: false; error: string };
+
+export class JsonOutputValidationError extends Error {
+  readonly attempts: number;
+  readonly lastOutput: string;
+
+  constructor(message: string, attempts: number, lastOutput: string) {
+    super(message);
+    this.name = "JsonOutputValidationError";
+    this.attempts = attempts;
+    this.lastOutput = lastOutput;
+  }
+}
+
+const FENCED_JSON_PATTERN = /^```(?:json)?\s*\n([\s\S]*?)\n?```$/i;
+
+// Compiles the schema once; the returned checker parses a model reply (tolerating a ```json fence) and validates it.
+export function createJsonOutputChecker(schema: Record<string, unknown>): (text: string) => JsonOutputCheck {
+  const validate = new AjvJsonSchemaValidator().getValidator<unknown>(schema as JsonSchemaType);
+
+  return (text) => {
+    const trimmed = text.trim();
+    const fenced = FENCED_JSON_PATTERN.exec(trimmed);
+
+    let parsed: unknown;
+    try {
+      parsed = JSON.parse(fenced ? fenced[1] : trimmed);
+    } catch (error) {
+      return { valid: false, error: `reply is not valid JSON (${error instanceof Error ? error.message : error})` };
+    }
+
+    const result = validate(parsed);
+    return result.valid
+      ? { valid: true, data: parsed }
+      : { valid: false, error: `reply does not match the schema (${result.errorMessage})` };
+  };
+}
+
+export function jsonSchemaSystemPrompt(schema: Record<string, unknown>): string {
+  return [
+    "Reply with a single JSON object only, no prose or code fences. It must conform to this JSON Schema:",
+    JSON.stringify(schema),
+  ].join("\n");
+}
+
+export function jsonCorrectionPrompt(error: string): string {
+  return `Your previous reply was rejected: ${error}. Reply again with only a JSON object that conforms to the schema.`;
+}
@@ -0,0 +1,62 @@
+import { DeepSeekApiError } from "./client.js";
+import { DeepSeekUserBalanceResponse } from "./types.js";
+
+export type ApiKeyVerdict = "valid" | "invalid" | "expired" | "insufficient_balance" | "missing" | "unknown";
+
+export interface ApiKeyDiagnosis {
+  verdict: ApiKeyVerdict;
+  key_valid: boolean;
+  status: number | null;
+  message: string;
+  remediation: string;
+}
+
+const REMEDIATION: Record<ApiKeyVerdict, string> = {
+  valid: "No action needed.",
+  invalid:
+    "Check DEEPSEEK_API_KEY for typos or stray whitespace, or create a new key at https://platform.deepseek.com/api_keys.",
+  expired: "The key was revoked or has expired; create a new key at https://platform.deepseek.com/api_keys.",
+  insufficient_balance: "The key works but the account has no usable balance; top up at https://platform.deepseek.com/top_up.",
+  missing: "Set DEEPSEEK_API_KEY and restart the server without DEEPSEEK_OFFLINE.",
+  unknown: "The probe could not reach a verdict; run health_check and retry shortly.",
+};
+
+const EXPIRED_PATTERN = /expired|revoked|disabled|deactivated/i;
+
+// Classifies the outcome of probing GET /user/balance with the configured key.
+export function diagnoseApiKey(outcome: { balance: DeepSeekUserBalanceResponse } | { error: unknown }): ApiKeyDiagnosis {
+  if ("balance" in outcome) {
+    return outcome.balance.is_available
+      ? diagnosis("valid", 200, "The API key authenticated and the account has a usable balance.")
+      : diagnosis("insufficient_balance", 200, "The API key authenticated but the balance is not available.");
+  }
+
+  const error = outcome.error;
+  const message = error instanceof Error ? error.message : String(error);
+  if (!(error instanceof DeepSeekApiError)) {
+    return diagnosis("unknown", null, message);
+  }
+
+  const status = error.status ?? null;
+  if (error.code === "no_api_key") {
+    return diagnosis("missing", status, message);
+  }
+  if (status === 401 || status === 403) {
+    return diagnosis(EXPIRED_PATTERN.test(message) ? "expired" : "invalid", status, message);
+  }
+  if (status === 402) {
+    return diagnosis("insufficient_balance", status, message);
+  }
+
+  return diagnosis("unknown", status, message);
+}
+
+function diagnosis(verdict: ApiKeyVerdict, status: number | null, message: string): ApiKeyDiagnosis {
+  return {
+    verdict,
+    key_valid: verdict === "valid" || verdict === "insufficient_balance",
+    status,
+    message,
+    remediation: REMEDIATION[verdict],
+  };
+}
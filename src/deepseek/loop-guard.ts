@@ -0,0 +1,57 @@
+export interface LoopGuardOptions {
+  ngramSize: number;
+  maxRepeats: number;
+}
+
+export const DEFAULT_LOOP_GUARD_NGRAM_SIZE = 8;
+export const DEFAULT_LOOP_GUARD_MAX_REPEATS = 4;
+
+export class LoopDetector {
+  private readonly ngramSize: number;
+  private readonly maxRepeats: number;
+  private readonly windowSize: number;
+  private words: string[] = [];
+  private partialWord = "";
+
+  constructor(options: LoopGuardOptions) {
+    this.ngramSize = Math.max(1, options.ngramSize);
+    this.maxRepeats = Math.max(1, options.maxRepeats);
+    // Only the trailing words are scanned, so each delta costs the same however long the stream runs;
+    // loops with a period of up to four n-grams still fit maxRepeats + 1 times in the window.
+    this.windowSize = this.ngramSize * (this.maxRepeats + 1) * 4;
+  }
+
+  // Returns the repeated fragment once the trailing n-gram has occurred more than maxRepeats times.
+  push(delta: string): string | undefined {
+    if (!delta) {
+      return undefined;
+    }
+
+    const pieces = (this.partialWord + delta).split(/\s+/);
+    // The last piece is still being streamed unless the delta ended on whitespace (then it is "").
+    this.partialWord = pieces.pop() ?? "";
+    const completed = pieces.filter((word) => word.length > 0);
+    if (completed.length === 0) {
+      return undefined;
+    }
+
+    this.words.push(...completed);
+    if (this.words.length > this.windowSize) {
+      this.words = this.words.slice(-this.windowSize);
+    }
+
+    if (this.words.length < this.ngramSize * (this.maxRepeats + 1)) {
+      return undefined;
+    }
+
+    const tail = this.words.slice(-this.ngramSize).join(" ");
+    let occurrences = 0;
+    for (let index = 0; index + this.ngramSize <= this.words.length; index += 1) {
+      if (this.words.slice(index, index + this.ngramSize).join(" ") === tail) {
+        occurrences += 1;
+      }
+    }
+
+    return occurrences > this.maxRepeats ? tail : undefined;
+  }
+}
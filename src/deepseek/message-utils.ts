@@ -0,0 +1,107 @@
+import { ChatContentPart, DeepSeekChatMessage, DeepSeekToolCall } from "./types.js";
+
+export function messageContentToText(content: DeepSeekChatMessage["content"]): string {
+  if (typeof content === "string") {
+    return content;
+  }
+
+  if (!Array.isArray(content)) {
+    return "";
+  }
+
+  return content
+    .map((part) => {
+      const record = part as Record<string, unknown>;
+      if (typeof part === "object" && part !== null && record.type === "text" && typeof record.text === "string") {
+        return record.text;
+      }
+
+      return "";
+    })
+    .filter((text) => text.length > 0)
+    .join("\n");
+}
+
+export function renderTranscript(messages: DeepSeekChatMessage[]): string {
+  return messages
+    .map((message) => `${message.role}: ${messageContentToText(message.content)}`)
+    .join("\n\n");
+}
+
+export function flattenMessagesToPrompt(messages: DeepSeekChatMessage[]): string {
+  return `${renderTranscript(messages)}\n\nassistant:`;
+}
+
+export function renderToolCalls(toolCalls: DeepSeekToolCall[]): string {
+  return toolCalls
+    .map((toolCall) => `- ${toolCall.function?.name ?? "(unnamed)"}(${toolCall.function?.arguments ?? ""})`)
+    .join("\n");
+}
+
+export function truncateText(text: string, maxChars: number | undefined): string {
+  if (maxChars === undefined || text.length <= maxChars) {
+    return text;
+  }
+
+  return `${text.slice(0, maxChars)}... (truncated, ${text.length - maxChars} chars omitted)`;
+}
+
+export function normalizeOutputText(text: string, options: { trim?: boolean; stripCodeFences?: boolean }): string {
+  let normalized = options.trim ? text.trim() : text;
+
+  if (options.stripCodeFences) {
+    const fenced = /^\s*```[\w+-]*[ \t]*\r?\n([\s\S]*?)\r?\n?```\s*$/.exec(normalized);
+    if (fenced) {
+      normalized = options.trim ? fenced[1].trim() : fenced[1];
+    }
+  }
+
+  return normalized;
+}
+
+export function mergeConsecutiveMessages(messages: DeepSeekChatMessage[]): DeepSeekChatMessage[] {
+  const merged: DeepSeekChatMessage[] = [];
+
+  for (const message of messages) {
+    const previous = merged[merged.length - 1];
+    if (previous && isMergeable(previous, message)) {
+      merged[merged.length - 1] = { ...previous, content: mergeContent(previous.content, message.content) };
+      continue;
+    }
+
+    merged.push(message);
+  }
+
+  return merged;
+}
+
+function isMergeable(previous: DeepSeekChatMessage, next: DeepSeekChatMessage): boolean {
+  return (
+    previous.role === next.role &&
+    previous.role !== "tool" &&
+    previous.name === next.name &&
+    !previous.prefix &&
+    !next.prefix &&
+    (previous.tool_calls ?? []).length === 0 &&
+    (next.tool_calls ?? []).length === 0
+  );
+}
+
+function mergeContent(
+  left: DeepSeekChatMessage["content"],
+  right: DeepSeekChatMessage["content"],
+): DeepSeekChatMessage["content"] {
+  if (!Array.isArray(left) && !Array.isArray(right)) {
+    return [left, right].filter((text): text is string => typeof text === "string" && text.length > 0).join("\n\n");
+  }
+
+  return [...toContentParts(left), ...toContentParts(right)];
+}
+
+function toContentParts(content: DeepSeekChatMessage["content"]): ChatContentPart[] {
+  if (Array.isArray(content)) {
+    return content;
+  }
+
+  return content ? [{ type: "text", text: content }] : [];
+}
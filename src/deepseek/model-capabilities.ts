@@ -0,0 +1,123 @@
+import { DeepSeekChatCompletionRequest } from "./types.js";
+
+export interface ModelLimits {
+  contextWindow: number;
+  defaultMaxOutputTokens: number;
+  maxOutputTokens: number;
+}
+
+export interface ModelCapabilities extends ModelLimits {
+  supportsThinking: boolean;
+  supportsTools: boolean;
+  supportsLogprobs: boolean;
+  supportsSamplingParameters: boolean;
+}
+
+const GENERIC_CHAT_CAPABILITIES: ModelCapabilities = {
+  contextWindow: 128000,
+  defaultMaxOutputTokens: 4096,
+  maxOutputTokens: 8192,
+  supportsThinking: false,
+  supportsTools: true,
+  supportsLogprobs: true,
+  supportsSamplingParameters: true,
+};
+
+const REASONER_CAPABILITIES: ModelCapabilities = {
+  contextWindow: 128000,
+  defaultMaxOutputTokens: 32768,
+  maxOutputTokens: 65536,
+  supportsThinking: true,
+  supportsTools: true,
+  supportsLogprobs: false,
+  supportsSamplingParameters: false,
+};
+
+const STATIC_MODEL_CAPABILITIES: Record<string, ModelCapabilities> = {
+  "deepseek-chat": {
+    ...GENERIC_CHAT_CAPABILITIES,
+    supportsThinking: true,
+  },
+  "deepseek-reasoner": REASONER_CAPABILITIES,
+};
+
+const UNSUPPORTED_PARAMETERS: Array<{ capability: keyof ModelCapabilities; fields: string[] }> = [
+  { capability: "supportsThinking", fields: ["thinking"] },
+  { capability: "supportsTools", fields: ["tools", "tool_choice"] },
+  { capability: "supportsLogprobs", fields: ["logprobs", "top_logprobs"] },
+  { capability: "supportsSamplingParameters", fields: ["temperature", "top_p", "presence_penalty", "frequency_penalty"] },
+];
+
+export function inferModelCapabilities(model: string): ModelCapabilities {
+  const known = STATIC_MODEL_CAPABILITIES[model];
+  if (known) {
+    return known;
+  }
+
+  return /reason|(^|[-_])r1([-_]|$)/i.test(model) ? REASONER_CAPABILITIES : GENERIC_CHAT_CAPABILITIES;
+}
+
+export function getModelLimits(model: string): ModelLimits {
+  return inferModelCapabilities(model);
+}
+
+export class ModelCapabilityRegistry {
+  private readonly capabilities = new Map<string, ModelCapabilities>();
+
+  constructor() {
+    for (const [model, capabilities] of Object.entries(STATIC_MODEL_CAPABILITIES)) {
+      this.capabilities.set(model, capabilities);
+    }
+  }
+
+  registerModels(modelIds: string[]): void {
+    for (const modelId of modelIds) {
+      if (!this.capabilities.has(modelId)) {
+        this.capabilities.set(modelId, inferModelCapabilities(modelId));
+      }
+    }
+  }
+
+  // Drops the models learned from /models, keeping the built-in ones; returns how many were dropped.
+  clearRegisteredModels(): number {
+    let cleared = 0;
+    for (const model of this.capabilities.keys()) {
+      if (!(model in STATIC_MODEL_CAPABILITIES)) {
+        this.capabilities.delete(model);
+        cleared += 1;
+      }
+    }
+    return cleared;
+  }
+
+  get(model: string): ModelCapabilities {
+    return this.capabilities.get(model) ?? inferModelCapabilities(model);
+  }
+
+  listModelIds(): string[] {
+    return [...this.capabilities.keys()].sort();
+  }
+}
+
+export function stripUnsupportedParameters(
+  request: DeepSeekChatCompletionRequest,
+  capabilities: ModelCapabilities,
+): string[] {
+  const stripped: string[] = [];
+  const requestRecord = request as Record<string, unknown>;
+
+  for (const { capability, fields } of UNSUPPORTED_PARAMETERS) {
+    if (capabilities[capability]) {
+      continue;
+    }
+
+    for (const field of fields) {
+      if (requestRecord[field] !== undefined) {
+        delete requestRecord[field];
+        stripped.push(field);
+      }
+    }
+  }
+
+  return stripped;
+}
@@ -0,0 +1,33 @@
+export class WeightedModelSelector {
+  private readonly entries: Array<{ model: string; weight: number }>;
+  private readonly totalWeight: number;
+  private readonly random: () => number;
+
+  constructor(weights: Record<string, number>, random: () => number = Math.random) {
+    this.entries = Object.entries(weights)
+      .filter(([, weight]) => Number.isFinite(weight) && weight > 0)
+      .map(([model, weight]) => ({ model, weight }));
+    this.totalWeight = this.entries.reduce((sum, entry) => sum + entry.weight, 0);
+    this.random = random;
+
+    if (this.entries.length === 0) {
+      throw new Error("Model weights must include at least one model with a positive weight");
+    }
+  }
+
+  select(): string {
+    let remaining = this.random() * this.totalWeight;
+    for (const entry of this.entries) {
+      remaining -= entry.weight;
+      if (remaining < 0) {
+        return entry.model;
+      }
+    }
+
+    return this.entries[this.entries.length - 1].model;
+  }
+
+  weights(): Record<string, number> {
+    return Object.fromEntries(this.entries.map((entry) => [entry.model, entry.weight]));
+  }
+}
@@ -0,0 +1,57 @@
+import { mkdir, readFile, rm, writeFile } from "node:fs/promises";
+import { dirname } from "node:path";
+
+import { DeepSeekListModelsResponse } from "./types.js";
+
+export interface CachedModelsEntry {
+  cachedAt: string;
+  response: DeepSeekListModelsResponse;
+}
+
+export class ModelsCache {
+  private readonly filePath: string;
+
+  constructor(filePath: string) {
+    this.filePath = filePath;
+  }
+
+  async read(): Promise<CachedModelsEntry | undefined> {
+    let raw: string;
+
+    try {
+      raw = await readFile(this.filePath, "utf8");
+    } catch {
+      return undefined;
+    }
+
+    try {
+      const parsed = JSON.parse(raw) as Record<string, unknown>;
+      const response = parsed.response as DeepSeekListModelsResponse | undefined;
+      if (typeof parsed.cached_at !== "string" || !response || !Array.isArray(response.data)) {
+        return undefined;
+      }
+
+      return {
+        cachedAt: parsed.cached_at,
+        response,
+      };
+    } catch {
+      return undefined;
+    }
+  }
+
+  async write(response: DeepSeekListModelsResponse, now = new Date()): Promise<void> {
+    await mkdir(dirname(this.filePath), { recursive: true });
+    await writeFile(
+      this.filePath,
+      JSON.stringify({ cached_at: now.toISOString(), response }, null, 2),
+      "utf8",
+    );
+  }
+
+  async clear(): Promise<number> {
+    const cached = await this.read();
+    await rm(this.filePath, { force: true });
+    return cached?.response.data.length ?? 0;
+  }
+}
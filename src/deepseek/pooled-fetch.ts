@@ -0,0 +1,51 @@
+import { Agent } from "undici";
+
+export interface ConnectionPoolOptions {
+  maxConnectionsPerHost?: number;
+  idleTimeoutMs?: number;
+  keepAlive?: boolean;
+}
+
+export interface ResolvedConnectionPoolOptions {
+  // Caps concurrent requests per origin; further requests queue. Unset means unbounded.
+  maxConnectionsPerHost?: number;
+  idleTimeoutMs: number;
+  keepAlive: boolean;
+}
+
+export const DEFAULT_POOL_IDLE_TIMEOUT_MS = 60000;
+export const TCP_KEEPALIVE_INITIAL_DELAY_MS = 60000;
+
+export function resolveConnectionPoolOptions(options: ConnectionPoolOptions = {}): ResolvedConnectionPoolOptions {
+  return {
+    maxConnectionsPerHost: isPositiveInteger(options.maxConnectionsPerHost) ? options.maxConnectionsPerHost : undefined,
+    idleTimeoutMs: isPositiveInteger(options.idleTimeoutMs) ? options.idleTimeoutMs : DEFAULT_POOL_IDLE_TIMEOUT_MS,
+    keepAlive: options.keepAlive ?? true,
+  };
+}
+
+// Native fetch over an undici Agent, so redirects, decompression and every body type keep working while
+// connections are reused, each closed after idleTimeoutMs without a request. maxConnectionsPerHost, when set, is a
+// concurrency cap: undici opens no more connections than that per origin and queues the rest.
+export function createPooledFetch(options: ResolvedConnectionPoolOptions): typeof fetch {
+  const dispatcher = new Agent(connectionPoolAgentOptions(options));
+
+  const pooledFetch = (input: string | URL | Request, init?: RequestInit): Promise<Response> =>
+    fetch(input, { ...init, dispatcher } as RequestInit);
+
+  return pooledFetch as typeof fetch;
+}
+
+// HTTP connection reuse is always on; keepAlive only toggles TCP keep-alive probes on the pooled sockets.
+export function connectionPoolAgentOptions(options: ResolvedConnectionPoolOptions): Agent.Options {
+  return {
+    connections: options.maxConnectionsPerHost ?? null,
+    keepAliveTimeout: options.idleTimeoutMs,
+    keepAliveMaxTimeout: options.idleTimeoutMs,
+    connect: { keepAlive: options.keepAlive, keepAliveInitialDelay: TCP_KEEPALIVE_INITIAL_DELAY_MS },
+  };
+}
+
+function isPositiveInteger(value: number | undefined): value is number {
+  return typeof value === "number" && Number.isInteger(value) && value > 0;
+}
@@ -0,0 +1,41 @@
+import { DeepSeekUsage } from "./types.js";
+
+export interface ModelPricing {
+  inputCacheHitPerMillion: number;
+  inputCacheMissPerMillion: number;
+  outputPerMillion: number;
+}
+
+const DEFAULT_PRICING: ModelPricing = {
+  inputCacheHitPerMillion: 0.028,
+  inputCacheMissPerMillion: 0.28,
+  outputPerMillion: 0.42,
+};
+
+const MODEL_PRICING: Record<string, ModelPricing> = {
+  "deepseek-chat": DEFAULT_PRICING,
+  "deepseek-reasoner": DEFAULT_PRICING,
+};
+
+export function getModelPricing(model: string): ModelPricing {
+  return MODEL_PRICING[model] ?? DEFAULT_PRICING;
+}
+
+export function estimateCostUsd(model: string, usage: DeepSeekUsage | undefined): number {
+  if (!usage) {
+    return 0;
+  }
+
+  const pricing = getModelPricing(model);
+  const promptTokens = usage.prompt_tokens ?? 0;
+  const cacheHitTokens = usage.prompt_cache_hit_tokens ?? 0;
+  const cacheMissTokens = usage.prompt_cache_miss_tokens ?? Math.max(promptTokens - cacheHitTokens, 0);
+  const completionTokens = usage.completion_tokens ?? 0;
+
+  return (
+    (cacheHitTokens * pricing.inputCacheHitPerMillion +
+      cacheMissTokens * pricing.inputCacheMissPerMillion +
+      completionTokens * pricing.outputPerMillion) /
+    1_000_000
+  );
+}
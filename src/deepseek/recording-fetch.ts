@@ -0,0 +1,110 @@
+import { createHash } from "node:crypto";
+import { mkdir, readFile, writeFile } from "node:fs/promises";
+import { join } from "node:path";
+import { gunzipSync } from "node:zlib";
+
+export interface RecordedInteraction {
+  method: string;
+  path: string;
+  body_sha256: string;
+  request_body: string | null;
+  status: number;
+  headers: Record<string, string>;
+  response_body: string;
+}
+
+export function createRecordingFetch(baseFetch: typeof fetch, recordDir: string): typeof fetch {
+  const recordingFetch = async (input: string | URL | Request, init?: RequestInit): Promise<Response> => {
+    const key = describeRequest(input, init);
+    const response = await baseFetch(input, init);
+    const responseBody = await response.text();
+    const headers = Object.fromEntries(response.headers.entries());
+    delete headers["content-encoding"];
+    delete headers["content-length"];
+
+    const interaction: RecordedInteraction = {
+      method: key.method,
+      path: key.path,
+      body_sha256: key.bodyHash,
+      request_body: key.body,
+      status: response.status,
+      headers,
+      response_body: responseBody,
+    };
+
+    await mkdir(recordDir, { recursive: true });
+    await writeFile(join(recordDir, interactionFileName(key)), JSON.stringify(interaction, null, 2), "utf8");
+
+    return new Response(responseBody, {
+      status: response.status,
+      statusText: response.statusText,
+      headers,
+    });
+  };
+
+  return recordingFetch as typeof fetch;
+}
+
+export function createReplayFetch(replayDir: string): typeof fetch {
+  const replayFetch = async (input: string | URL | Request, init?: RequestInit): Promise<Response> => {
+    const key = describeRequest(input, init);
+    const filePath = join(replayDir, interactionFileName(key));
+
+    let raw: string;
+    try {
+      raw = await readFile(filePath, "utf8");
+    } catch {
+      throw new Error(`No recorded interaction for ${key.method} ${key.path} (body sha256 ${key.bodyHash}) in ${replayDir}`);
+    }
+
+    const interaction = JSON.parse(raw) as RecordedInteraction;
+    return new Response(interaction.status === 204 ? null : interaction.response_body, {
+      status: interaction.status,
+      headers: interaction.headers,
+    });
+  };
+
+  return replayFetch as typeof fetch;
+}
+
+interface RequestKey {
+  method: string;
+  path: string;
+  body: string | null;
+  bodyHash: string;
+}
+
+function describeRequest(input: string | URL | Request, init?: RequestInit): RequestKey {
+  const url = new URL(typeof input === "string" ? input : input instanceof URL ? input.href : input.url);
+  const method = (init?.method ?? "GET").toUpperCase();
+  const body = requestBodyText(init?.body);
+
+  return {
+    method,
+    path: url.pathname,
+    body,
+    bodyHash: createHash("sha256")
+      .update(body ?? "")
+      .digest("hex"),
+  };
+}
+
+// Compressed requests are keyed by their inflated JSON, so a recording matches whether or not compression was on.
+function requestBodyText(body: RequestInit["body"]): string | null {
+  if (typeof body === "string") {
+    return body;
+  }
+
+  if (!(body instanceof Uint8Array) && !(body instanceof ArrayBuffer)) {
+    return null;
+  }
+
+  const bytes = body instanceof ArrayBuffer ? new Uint8Array(body) : body;
+  const isGzip = bytes.length >= 2 && bytes[0] === 0x1f && bytes[1] === 0x8b;
+  return Buffer.from(isGzip ? gunzipSync(bytes) : bytes).toString("utf8");
+}
+
+function interactionFileName(key: RequestKey): string {
+  const slug = key.path.replace(/^\/+/, "").replace(/[^a-zA-Z0-9]+/g, "_") || "root";
+  return `${key.method}_${slug}_${key.bodyHash.slice(0, 16)}.json`;
+}
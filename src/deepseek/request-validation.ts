@@ -0,0 +1,158 @@
+import { getModelLimits } from "./model-capabilities.js";
+import { estimateMessagesTokens } from "./token-estimator.js";
+import { DeepSeekChatCompletionRequest } from "./types.js";
+
+export interface RequestValidationIssue {
+  severity: "error" | "warning";
+  field: string;
+  message: string;
+}
+
+export interface RequestValidationReport {
+  valid: boolean;
+  model: string;
+  message_count: number;
+  estimated_prompt_tokens: number;
+  requested_output_tokens: number;
+  context_window: number;
+  max_output_tokens: number;
+  fits_context_window: boolean;
+  issues: RequestValidationIssue[];
+}
+
+export function validateChatCompletionRequest(request: DeepSeekChatCompletionRequest): RequestValidationReport {
+  const model = String(request.model);
+  const limits = getModelLimits(model);
+  const issues: RequestValidationIssue[] = [];
+
+  validateRoles(request, issues);
+
+  if (
+    request.max_tokens !== undefined &&
+    request.max_completion_tokens !== undefined &&
+    request.max_tokens !== request.max_completion_tokens
+  ) {
+    issues.push({
+      severity: "error",
+      field: "max_tokens",
+      message: `max_tokens (${request.max_tokens}) conflicts with max_completion_tokens (${request.max_completion_tokens})`,
+    });
+  }
+
+  const requestedOutputTokens = request.max_completion_tokens ?? request.max_tokens ?? limits.defaultMaxOutputTokens;
+  if (requestedOutputTokens > limits.maxOutputTokens) {
+    issues.push({
+      severity: "error",
+      field: request.max_completion_tokens !== undefined ? "max_completion_tokens" : "max_tokens",
+      message: `${model} accepts at most ${limits.maxOutputTokens} output tokens; requested ${requestedOutputTokens}`,
+    });
+  }
+
+  if (request.top_logprobs !== undefined && !request.logprobs) {
+    issues.push({
+      severity: "error",
+      field: "top_logprobs",
+      message: "top_logprobs requires logprobs=true",
+    });
+  }
+
+  if (request.tool_choice !== undefined && (!request.tools || request.tools.length === 0)) {
+    issues.push({
+      severity: "error",
+      field: "tool_choice",
+      message: "tool_choice is set but no tools are defined",
+    });
+  }
+
+  const estimatedPromptTokens = estimateMessagesTokens(request.messages);
+  const fitsContextWindow = estimatedPromptTokens + requestedOutputTokens <= limits.contextWindow;
+  if (!fitsContextWindow) {
+    issues.push({
+      severity: "error",
+      field: "messages",
+      message: `Estimated ${estimatedPromptTokens} prompt tokens + ${requestedOutputTokens} output tokens exceeds the ${limits.contextWindow} token context window of ${model}`,
+    });
+  }
+
+  return {
+    valid: !issues.some((issue) => issue.severity === "error"),
+    model,
+    message_count: request.messages.length,
+    estimated_prompt_tokens: estimatedPromptTokens,
+    requested_output_tokens: requestedOutputTokens,
+    context_window: limits.contextWindow,
+    max_output_tokens: limits.maxOutputTokens,
+    fits_context_window: fitsContextWindow,
+    issues,
+  };
+}
+
+function validateRoles(request: DeepSeekChatCompletionRequest, issues: RequestValidationIssue[]): void {
+  const { messages } = request;
+
+  if (messages.length === 0) {
+    issues.push({ severity: "error", field: "messages", message: "At least one message is required" });
+    return;
+  }
+
+  let seenNonSystem = false;
+  const pendingToolCallIds = new Set<string>();
+
+  messages.forEach((message, index) => {
+    const field = `messages[${index}]`;
+
+    if (message.role === "system" && seenNonSystem) {
+      issues.push({
+        severity: "warning",
+        field,
+        message: "system message appears after the conversation started; DeepSeek applies it as a regular turn",
+      });
+    }
+
+    if (message.role !== "system") {
+      seenNonSystem = true;
+    }
+
+    if (message.role === "assistant") {
+      pendingToolCallIds.clear();
+      for (const toolCall of message.tool_calls ?? []) {
+        if (toolCall.id) {
+          pendingToolCallIds.add(toolCall.id);
+        }
+      }
+    }
+
+    if (message.role === "tool") {
+      if (!message.tool_call_id) {
+        issues.push({ severity: "error", field, message: "tool message requires tool_call_id" });
+      } else if (!pendingToolCallIds.has(message.tool_call_id)) {
+        issues.push({
+          severity: "error",
+          field,
+          message: `tool_call_id "${message.tool_call_id}" does not match a preceding assistant tool call`,
+        });
+      }
+    }
+
+    if (message.prefix && (message.role !== "assistant" || index !== messages.length - 1)) {
+      issues.push({
+        severity: "error",
+        field,
+        message: "prefix=true is only valid on the final assistant message",
+      });
+    }
+
+    if (message.role === "user" && (message.content === undefined || message.content === null || message.content === "")) {
+      issues.push({ severity: "error", field, message: "user message content must not be empty" });
+    }
+  });
+
+  const last = messages[messages.length - 1];
+  if (last.role === "assistant" && !last.prefix) {
+    issues.push({
+      severity: "warning",
+      field: `messages[${messages.length - 1}]`,
+      message: "conversation ends with an assistant message; use prefix=true (beta) to continue it",
+    });
+  }
+}
@@ -1,5 +1,7 @@
 import { z } from "zod";
 
+import { isRelativeApiPath } from "./api-paths.js";
+
 export const chatMessageSchema = z
   .object({
     role: z.enum(["system", "user", "assistant", "tool"]),
@@ -52,9 +54,25 @@ const toolChoiceSchema = z.union([
 
 const thinkingSchema = z
   .object({
-    type: z.enum(["enabled", "disabled"]).optional(),
+    type: z.enum(["enabled", "disabled"]).describe("Turn the model's reasoning phase on or off."),
+    budget_tokens: z
+      .number()
+      .int()
+      .positive()
+      .optional()
+      .describe('Upper bound on reasoning tokens; only valid with `type: "enabled"`.'),
   })
-  .passthrough();
+  .strict()
+  .superRefine((value, context) => {
+    if (value.type === "disabled" && value.budget_tokens !== undefined) {
+      context.addIssue({
+        code: z.ZodIssueCode.custom,
+        path: ["budget_tokens"],
+        message: '`thinking.budget_tokens` requires `thinking.type="enabled"`',
+      });
+    }
+  })
+  .describe("DeepSeek thinking mode configuration.");
 
 const audioSchema = z
   .object({
@@ -63,25 +81,52 @@ const audioSchema = z
   })
   .passthrough();
 
+const jsonSchemaFormatSchema = z
+  .object({
+    name: z
+      .string()
+      .min(1)
+      .max(64)
+      .regex(/^[a-zA-Z0-9_-]+$/)
+      .describe("Schema name reported back by the provider; letters, digits, `_` and `-` only."),
+    description: z.string().optional().describe("What the structured output represents."),
+    schema: z
+      .record(z.string(), z.unknown())
+      .refine((value) => !Array.isArray(value), { message: "`json_schema.schema` must be a JSON object" })
+      .describe("JSON Schema object the model output must conform to."),
+    strict: z.boolean().optional().describe("Ask the provider to enforce the schema strictly."),
+  })
+  .passthrough();
+
+const responseFormatSchema = z.union([
+  z
+    .object({
+      type: z.enum(["text", "json_object"]),
+    })
+    .passthrough(),
+  z
+    .object({
+      type: z.literal("json_schema"),
+      json_schema: jsonSchemaFormatSchema,
+    })
+    .passthrough()
+    .describe("Structured output: the model reply is constrained to `json_schema.schema`."),
+]);
+
 export const emptyToolInputSchema = z.object({});
 
 export const chatCompletionToolInputSchema = z
   .object({
     message: z.string().min(1).optional(),
     messages: z.array(chatMessageSchema).min(1).optional(),
-    model: z.string().default("deepseek-chat"),
+    model: z.string().optional(),
     conversation_id: z.string().min(1).optional(),
     clear_conversation: z.boolean().default(false),
     frequency_penalty: z.number().min(-2).max(2).optional(),
     max_tokens: z.number().int().positive().optional(),
     max_completion_tokens: z.number().int().positive().optional(),
     presence_penalty: z.number().min(-2).max(2).optional(),
-    response_format: z
-      .object({
-        type: z.enum(["text", "json_object"]),
-      })
-      .passthrough()
-      .optional(),
+    response_format: responseFormatSchema.optional(),
     stop: stopSchema.optional(),
     stream: z.boolean().default(false),
     stream_options: streamOptionsSchema.optional(),
@@ -94,8 +139,11 @@ export const chatCompletionToolInputSchema = z
     thinking: thinkingSchema.optional(),
     modalities: z.array(z.string().min(1)).optional(),
     audio: audioSchema.optional(),
+    disable_fallback: z.boolean().optional(),
+    stream_soft_max_tokens: z.number().int().positive().optional(),
     include_raw_response: z.boolean().default(false),
     extra_body: z.record(z.string(), z.unknown()).optional(),
+    attach_files: z.array(z.string().min(1)).max(20).optional(),
   })
   .superRefine((value, context) => {
     if (!value.message && !value.messages) {
@@ -113,6 +161,149 @@ export const chatCompletionToolInputSchema = z
     }
   });
 
+export const chatPrefixCompletionToolInputSchema = z
+  .object({
+    message: z.string().min(1).optional(),
+    messages: z.array(chatMessageSchema).min(1).optional(),
+    prefix: z.string().min(1),
+    include_prefix: z.boolean().default(false),
+    model: z.string().default("deepseek-chat"),
+    max_tokens: z.number().int().positive().optional(),
+    temperature: z.number().min(0).max(2).optional(),
+    top_p: z.number().min(0).max(1).optional(),
+    stop: stopSchema.optional(),
+    stream: z.boolean().default(false),
+    include_raw_response: z.boolean().default(false),
+    extra_body: z.record(z.string(), z.unknown()).optional(),
+  })
+  .superRefine((value, context) => {
+    if (!value.message && !value.messages) {
+      context.addIssue({
+        code: z.ZodIssueCode.custom,
+        message: "Either `message` or `messages` must be provided",
+      });
+    }
+
+    if (value.messages && value.messages[value.messages.length - 1]?.role === "assistant") {
+      context.addIssue({
+        code: z.ZodIssueCode.custom,
+        message: "The assistant turn is supplied via `prefix`; `messages` must not end with an assistant message",
+      });
+    }
+  });
+
+export const jsonChatToolInputSchema = z.object({
+  messages: z.array(chatMessageSchema).min(1),
+  schema: z.record(z.string(), z.unknown()),
+  model: z.string().default("deepseek-chat"),
+  max_retries: z.number().int().min(0).max(5).default(2),
+  max_tokens: z.number().int().positive().optional(),
+  temperature: z.number().min(0).max(2).optional(),
+  top_p: z.number().min(0).max(1).optional(),
+});
+
+export const functionCallingToolInputSchema = z
+  .object({
+    messages: z.array(chatMessageSchema).min(1).optional(),
+    tools: z.array(toolDefinitionSchema).min(1).max(128).optional(),
+    tool_choice: toolChoiceSchema.optional(),
+    model: z.string().default("deepseek-chat"),
+    max_depth: z.number().int().min(1).max(20).default(5),
+    max_tokens: z.number().int().positive().optional(),
+    temperature: z.number().min(0).max(2).optional(),
+    top_p: z.number().min(0).max(1).optional(),
+    run_id: z.string().min(1).optional(),
+    tool_results: z
+      .array(z.object({ tool_call_id: z.string().min(1), content: z.string() }))
+      .min(1)
+      .optional(),
+  })
+  .superRefine((value, context) => {
+    if (value.run_id) {
+      if (!value.tool_results) {
+        context.addIssue({
+          code: z.ZodIssueCode.custom,
+          message: "`tool_results` must be provided when resuming a run with `run_id`",
+        });
+      }
+    } else if (!value.messages || !value.tools) {
+      context.addIssue({
+        code: z.ZodIssueCode.custom,
+        message: "Start a run with `messages` and `tools`, or resume one with `run_id` and `tool_results`",
+      });
+    }
+  });
+
+export const countTokensToolInputSchema = z
+  .object({
+    text: z.string().optional(),
+    messages: z.array(chatMessageSchema).min(1).optional(),
+    model: z.string().optional(),
+  })
+  .superRefine((value, context) => {
+    if (value.text === undefined && !value.messages) {
+      context.addIssue({
+        code: z.ZodIssueCode.custom,
+        message: "Either `text` or `messages` must be provided",
+      });
+    }
+  });
+
+export const estimateCostToolInputSchema = z
+  .object({
+    model: z.string().optional(),
+    input_tokens: z.number().int().min(0).optional(),
+    messages: z.array(chatMessageSchema).min(1).optional(),
+    output_tokens: z.number().int().min(0).default(0),
+    cache_hit_ratio: z.number().min(0).max(1).default(0),
+  })
+  .superRefine((value, context) => {
+    if (value.input_tokens === undefined && !value.messages) {
+      context.addIssue({
+        code: z.ZodIssueCode.custom,
+        message: "Either `input_tokens` or `messages` must be provided",
+      });
+    }
+  });
+
+export const summarizeAndContinueToolInputSchema = z.object({
+  messages: z.array(chatMessageSchema).min(1),
+  keep_last: z.number().int().min(1).max(100).default(4),
+  model: z.string().default("deepseek-chat"),
+  summary_model: z.string().optional(),
+  summary_max_tokens: z.number().int().positive().optional(),
+  max_tokens: z.number().int().positive().optional(),
+  temperature: z.number().min(0).max(2).optional(),
+  top_p: z.number().min(0).max(1).optional(),
+  include_raw_response: z.boolean().default(false),
+});
+
+export const compareModelsToolInputSchema = z.object({
+  messages: z.array(chatMessageSchema).min(1),
+  model_a: z.string().min(1),
+  model_b: z.string().min(1),
+  include_diff: z.boolean().default(false),
+  max_tokens: z.number().int().positive().optional(),
+  temperature: z.number().min(0).max(2).optional(),
+  top_p: z.number().min(0).max(1).optional(),
+});
+
+export const batchChatCompletionToolInputSchema = z.object({
+  requests: z
+    .array(
+      z.object({
+        messages: z.array(chatMessageSchema).min(1),
+        model: z.string().min(1).optional(),
+        max_tokens: z.number().int().positive().optional(),
+        temperature: z.number().min(0).max(2).optional(),
+        top_p: z.number().min(0).max(1).optional(),
+      }),
+    )
+    .min(1)
+    .max(50),
+  concurrency: z.number().int().min(1).max(16).optional(),
+});
+
 export const completionToolInputSchema = z.object({
   model: z.string().default("deepseek-chat"),
   prompt: z.string().min(1),
@@ -132,6 +323,20 @@ export const completionToolInputSchema = z.object({
   extra_body: z.record(z.string(), z.unknown()).optional(),
 });
 
+export const fimCompletionToolInputSchema = z.object({
+  prefix: z.string().min(1),
+  suffix: z.string().default(""),
+  language: z.string().min(1).optional(),
+  model: z.string().default("deepseek-chat"),
+  max_tokens: z.number().int().positive().max(4096).optional(),
+  temperature: z.number().min(0).max(2).optional(),
+  top_p: z.number().min(0).max(1).optional(),
+  stop: stopSchema.optional(),
+  stream: z.boolean().default(false),
+  include_raw_response: z.boolean().default(false),
+  extra_body: z.record(z.string(), z.unknown()).optional(),
+});
+
 const uploadToolInputBaseSchema = z
   .object({
     file_url: z.string().url().optional(),
@@ -186,14 +391,157 @@ export const videoGenerationToolInputSchema = z.object({
   extra_body: z.record(z.string(), z.unknown()).optional(),
 });
 
+export const encodeImageToolInputSchema = z.object({
+  path: z.string().min(1),
+  detail: z.enum(["auto", "low", "high"]).optional(),
+});
+
+export const CACHE_NAMES = ["models", "models_file"] as const;
+
+export const clearCachesToolInputSchema = z.object({
+  name: z.enum(CACHE_NAMES).optional(),
+});
+
+export const usageSummaryToolInputSchema = z.object({
+  reset: z.boolean().default(false),
+});
+
+export const rawRequestToolInputSchema = z.object({
+  method: z.enum(["GET", "POST"]).default("GET"),
+  path: z
+    .string()
+    .min(1)
+    .refine(isRelativeApiPath, {
+      message: "`path` must be relative to the configured base URL (e.g. /user/balance), not an absolute URL",
+    }),
+  body: z.record(z.string(), z.unknown()).optional(),
+});
+
+export const authenticateToolInputSchema = z.object({
+  api_key: z.string().optional(),
+});
+
+export const setToolEnabledToolInputSchema = z.object({
+  name: z.string().min(1),
+  enabled: z.boolean(),
+});
+
 export const resetConversationToolInputSchema = z.object({
   conversation_id: z.string().min(1),
 });
 
+export const createConversationToolInputSchema = z.object({
+  conversation_id: z.string().min(1).optional(),
+  system_prompt: z.string().min(1).optional(),
+  messages: z.array(chatMessageSchema).optional(),
+});
+
+export const appendMessageToolInputSchema = z.object({
+  conversation_id: z.string().min(1),
+  role: z.enum(["system", "user", "assistant", "tool"]).default("user"),
+  content: z.string(),
+  name: z.string().optional(),
+  tool_call_id: z.string().optional(),
+});
+
+export const continueConversationToolInputSchema = z.object({
+  conversation_id: z.string().min(1),
+  message: z.string().min(1).optional(),
+  model: z.string().optional(),
+  max_tokens: z.number().int().positive().optional(),
+  temperature: z.number().min(0).max(2).optional(),
+  top_p: z.number().min(0).max(1).optional(),
+});
+
+const usageOutputSchema = z
+  .object({
+    prompt_tokens: z.number().optional(),
+    completion_tokens: z.number().optional(),
+    total_tokens: z.number().optional(),
+  })
+  .passthrough();
+
+// Success shapes describe the fields clients can rely on; passthrough keeps optional debug fields
+// (raw_response, timing) and future additions valid. Error results for these tools carry no structuredContent.
+export const chatCompletionToolOutputSchema = z
+  .object({
+    id: z.string(),
+    model: z.string(),
+    conversation_id: z.string().nullable(),
+    response_text: z.string(),
+    reasoning_content: z.string().nullable(),
+    tool_calls: z.array(z.record(z.string(), z.unknown())),
+    finish_reason: z.string().nullable(),
+    usage: usageOutputSchema.nullable(),
+    fallback: z
+      .object({ fromModel: z.string(), toModel: z.string(), reason: z.string() })
+      .passthrough()
+      .nullable(),
+    choices: z.array(
+      z
+        .object({
+          index: z.number(),
+          finish_reason: z.string().nullable(),
+          content: z.string().nullable(),
+          reasoning_content: z.string().nullable(),
+          tool_calls: z.array(z.record(z.string(), z.unknown())),
+        })
+        .passthrough(),
+    ),
+  })
+  .passthrough();
+
+export const listModelsToolOutputSchema = z
+  .object({
+    object: z.string(),
+    data: z.array(z.object({ id: z.string(), object: z.string(), owned_by: z.string().optional() }).passthrough()),
+    stale: z.boolean(),
+    cached_at: z.string().optional(),
+    live_error: z.string().optional(),
+  })
+  .passthrough();
+
+export const userBalanceToolOutputSchema = z
+  .object({
+    is_available: z.boolean(),
+    balance_infos: z.array(
+      z
+        .object({
+          currency: z.string(),
+          total_balance: z.string(),
+          granted_balance: z.string(),
+          topped_up_balance: z.string(),
+        })
+        .passthrough(),
+    ),
+  })
+  .passthrough();
+
 export type ChatCompletionToolInput = z.infer<typeof chatCompletionToolInputSchema>;
+export type ChatPrefixCompletionToolInput = z.infer<typeof chatPrefixCompletionToolInputSchema>;
+export type JsonChatToolInput = z.infer<typeof jsonChatToolInputSchema>;
+export type FunctionCallingToolInput = z.infer<typeof functionCallingToolInputSchema>;
+export type CountTokensToolInput = z.infer<typeof countTokensToolInputSchema>;
+export type EstimateCostToolInput = z.infer<typeof estimateCostToolInputSchema>;
+export type UsageSummaryToolInput = z.infer<typeof usageSummaryToolInputSchema>;
+export type CreateConversationToolInput = z.infer<typeof createConversationToolInputSchema>;
+export type AppendMessageToolInput = z.infer<typeof appendMessageToolInputSchema>;
+export type ContinueConversationToolInput = z.infer<typeof continueConversationToolInputSchema>;
+export type SummarizeAndContinueToolInput = z.infer<typeof summarizeAndContinueToolInputSchema>;
+export type CompareModelsToolInput = z.infer<typeof compareModelsToolInputSchema>;
+export type BatchChatCompletionToolInput = z.infer<typeof batchChatCompletionToolInputSchema>;
 export type CompletionToolInput = z.infer<typeof completionToolInputSchema>;
+export type FimCompletionToolInput = z.infer<typeof fimCompletionToolInputSchema>;
+export type ClearCachesToolInput = z.infer<typeof clearCachesToolInputSchema>;
+export type EncodeImageToolInput = z.infer<typeof encodeImageToolInputSchema>;
+export type RawRequestToolInput = z.infer<typeof rawRequestToolInputSchema>;
+export type AuthenticateToolInput = z.infer<typeof authenticateToolInputSchema>;
 export type ResetConversationToolInput = z.infer<typeof resetConversationToolInputSchema>;
+export type SetToolEnabledToolInput = z.infer<typeof setToolEnabledToolInputSchema>;
 export type VisionUploadToolInput = z.infer<typeof visionUploadToolInputSchema>;
 export type VideoUploadToolInput = z.infer<typeof videoUploadToolInputSchema>;
 export type ImageGenerationToolInput = z.infer<typeof imageGenerationToolInputSchema>;
 export type VideoGenerationToolInput = z.infer<typeof videoGenerationToolInputSchema>;
+export type ChatCompletionToolOutput = z.infer<typeof chatCompletionToolOutputSchema>;
+export type ListModelsToolOutput = z.infer<typeof listModelsToolOutputSchema>;
+export type UserBalanceToolOutput = z.infer<typeof userBalanceToolOutputSchema>;
@@ -0,0 +1,79 @@
+export interface LineDiff {
+  text: string;
+  added: number;
+  removed: number;
+  unchanged: number;
+  // Set when the inputs were too large for the LCS table and lines were compared by position instead.
+  approximate?: true;
+}
+
+// Two 1000-line answers still get an exact diff.
+const DEFAULT_MAX_LCS_CELLS = 1_000_000;
+
+// Line-level LCS diff: unchanged lines are prefixed with two spaces, removals with "- " and additions with "+ ".
+// The LCS table is O(n*m), so past maxCells the lines are compared side by side instead.
+export function diffLines(before: string, after: string, maxCells = DEFAULT_MAX_LCS_CELLS): LineDiff {
+  const left = before.split("\n");
+  const right = after.split("\n");
+  if ((left.length + 1) * (right.length + 1) > maxCells) {
+    return diffSideBySide(left, right);
+  }
+
+  const lengths: number[][] = Array.from({ length: left.length + 1 }, () =>
+    new Array<number>(right.length + 1).fill(0),
+  );
+
+  for (let i = left.length - 1; i >= 0; i -= 1) {
+    for (let j = right.length - 1; j >= 0; j -= 1) {
+      lengths[i][j] =
+        left[i] === right[j] ? lengths[i + 1][j + 1] + 1 : Math.max(lengths[i + 1][j], lengths[i][j + 1]);
+    }
+  }
+
+  const lines: string[] = [];
+  let added = 0;
+  let removed = 0;
+  let i = 0;
+  let j = 0;
+  while (i < left.length || j < right.length) {
+    if (i < left.length && j < right.length && left[i] === right[j]) {
+      lines.push(`  ${left[i]}`);
+      i += 1;
+      j += 1;
+    } else if (i < left.length && (j === right.length || lengths[i + 1][j] >= lengths[i][j + 1])) {
+      lines.push(`- ${left[i]}`);
+      removed += 1;
+      i += 1;
+    } else {
+      lines.push(`+ ${right[j]}`);
+      added += 1;
+      j += 1;
+    }
+  }
+
+  return { text: lines.join("\n"), added, removed, unchanged: lines.length - added - removed };
+}
+
+// Pairs line i of one text with line i of the other: equal pairs are unchanged, the rest become a removal and an
+// addition, and the longer text's extra lines are added or removed.
+function diffSideBySide(left: string[], right: string[]): LineDiff {
+  const lines: string[] = [];
+  let added = 0;
+  let removed = 0;
+  for (let index = 0; index < Math.max(left.length, right.length); index += 1) {
+    if (index < left.length && index < right.length && left[index] === right[index]) {
+      lines.push(`  ${left[index]}`);
+      continue;
+    }
+    if (index < left.length) {
+      lines.push(`- ${left[index]}`);
+      removed += 1;
+    }
+    if (index < right.length) {
+      lines.push(`+ ${right[index]}`);
+      added += 1;
+    }
+  }
+
+  return { text: lines.join("\n"), added, removed, unchanged: lines.length - added - removed, approximate: true };
+}
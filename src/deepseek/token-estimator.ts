@@ -0,0 +1,52 @@
+import { messageContentToText } from "./message-utils.js";
+import { DeepSeekChatMessage } from "./types.js";
+
+// DeepSeek's published rule of thumb: ~0.3 tokens per English character, ~0.6 per CJK character.
+const TOKENS_PER_LATIN_CHAR = 0.3;
+const TOKENS_PER_CJK_CHAR = 0.6;
+const MESSAGE_OVERHEAD_TOKENS = 4;
+
+export function estimateTextTokens(text: string): number {
+  return Math.ceil(textTokenWeight(text));
+}
+
+// Unrounded estimate, so streamed deltas can be summed into a running count without rounding up each one.
+export function textTokenWeight(text: string): number {
+  let tokens = 0;
+
+  for (const char of text) {
+    tokens += isCjkCharacter(char) ? TOKENS_PER_CJK_CHAR : TOKENS_PER_LATIN_CHAR;
+  }
+
+  return tokens;
+}
+
+export function estimateMessageTokens(message: DeepSeekChatMessage): number {
+  const parts = [messageContentToText(message.content)];
+
+  if (message.reasoning_content) {
+    parts.push(message.reasoning_content);
+  }
+
+  if (message.tool_calls && message.tool_calls.length > 0) {
+    parts.push(JSON.stringify(message.tool_calls));
+  }
+
+  return MESSAGE_OVERHEAD_TOKENS + estimateTextTokens(parts.join("\n"));
+}
+
+export function estimateMessagesTokens(messages: DeepSeekChatMessage[]): number {
+  return messages.reduce((total, message) => total + estimateMessageTokens(message), 0);
+}
+
+function isCjkCharacter(char: string): boolean {
+  const codePoint = char.codePointAt(0) ?? 0;
+  return (
+    (codePoint >= 0x3040 && codePoint <= 0x30ff) ||
+    (codePoint >= 0x3400 && codePoint <= 0x4dbf) ||
+    (codePoint >= 0x4e00 && codePoint <= 0x9fff) ||
+    (codePoint >= 0xac00 && codePoint <= 0xd7af) ||
+    (codePoint >= 0xf900 && codePoint <= 0xfaff) ||
+    (codePoint >= 0xff00 && codePoint <= 0xffef)
+  );
+}
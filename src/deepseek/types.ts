@@ -39,6 +39,8 @@ export interface DeepSeekChatMessage {
   tool_calls?: DeepSeekToolCall[];
 }
 
+export type DeepSeekThinkingConfig = { type: "enabled"; budget_tokens?: number } | { type: "disabled" };
+
 export interface DeepSeekToolDefinition {
   type: "function";
   function: {
@@ -60,6 +62,24 @@ export type DeepSeekToolChoice =
       };
     };
 
+export interface DeepSeekJsonSchemaFormat {
+  name: string;
+  description?: string;
+  schema: Record<string, unknown>;
+  strict?: boolean;
+}
+
+export type DeepSeekResponseFormat =
+  | {
+      type: "text" | "json_object";
+    }
+  | {
+      type: "json_schema";
+      json_schema: DeepSeekJsonSchemaFormat;
+    };
+
+export type DeepSeekTokenParam = "max_tokens" | "max_completion_tokens";
+
 export interface DeepSeekChatCompletionRequest {
   model: DeepSeekModelId;
   messages: DeepSeekChatMessage[];
@@ -67,9 +87,7 @@ export interface DeepSeekChatCompletionRequest {
   max_tokens?: number;
   max_completion_tokens?: number;
   presence_penalty?: number;
-  response_format?: {
-    type: "text" | "json_object";
-  };
+  response_format?: DeepSeekResponseFormat;
   stop?: string | string[];
   stream?: boolean;
   stream_options?: Record<string, unknown>;
@@ -79,7 +97,7 @@ export interface DeepSeekChatCompletionRequest {
   tool_choice?: DeepSeekToolChoice;
   logprobs?: boolean;
   top_logprobs?: number;
-  thinking?: Record<string, unknown>;
+  thinking?: DeepSeekThinkingConfig;
   modalities?: string[];
   audio?: Record<string, unknown>;
   [key: string]: unknown;
@@ -188,13 +206,37 @@ export interface FallbackMetadata {
   reason: string;
 }
 
+export interface LoopGuardMetadata {
+  repeatedFragment: string;
+}
+
+export type NonJsonResponseBody =
+  | { content_type: string; body_text: string }
+  | { content_type: string; body_base64: string };
+
+export interface RequestTiming {
+  timeToFirstByteMs: number;
+  totalMs: number;
+  firstToLastChunkMs?: number;
+}
+
+export interface StreamCompletion {
+  completed: boolean;
+  reason?: string;
+}
+
 export interface ChatCompletionExecutionResult {
   response: DeepSeekChatCompletionResponse;
   fallback?: FallbackMetadata;
   streamChunkCount?: number;
+  loopGuard?: LoopGuardMetadata;
+  softCapped?: boolean;
+  streamCompletion?: StreamCompletion;
+  timing?: RequestTiming;
 }
 
 export interface CompletionExecutionResult {
   response: DeepSeekCompletionResponse;
   streamChunkCount?: number;
+  streamCompletion?: StreamCompletion;
 }
@@ -0,0 +1,64 @@
+export interface HealthMonitorOptions {
+  apiKeyPresent: boolean;
+  probe: () => Promise<unknown>;
+  intervalMs: number;
+  now?: () => number;
+}
+
+export interface ReadinessReport {
+  ready: boolean;
+  api_key_present: boolean;
+  upstream: "ok" | "failed" | "unknown";
+  last_probe_at?: string;
+  last_probe_error?: string;
+}
+
+// Periodically probes the DeepSeek API so /readyz can answer from the last result without a round trip.
+export class HealthMonitor {
+  private readonly options: HealthMonitorOptions;
+  private readonly now: () => number;
+  private timer: NodeJS.Timeout | undefined;
+  private lastProbe: { at: number; ok: boolean; error?: string } | undefined;
+
+  constructor(options: HealthMonitorOptions) {
+    this.options = options;
+    this.now = options.now ?? Date.now;
+  }
+
+  start(): void {
+    if (this.timer) {
+      return;
+    }
+
+    void this.probe();
+    this.timer = setInterval(() => void this.probe(), this.options.intervalMs);
+    this.timer.unref();
+  }
+
+  stop(): void {
+    clearInterval(this.timer);
+    this.timer = undefined;
+  }
+
+  async probe(): Promise<boolean> {
+    try {
+      await this.options.probe();
+      this.lastProbe = { at: this.now(), ok: true };
+    } catch (error) {
+      this.lastProbe = { at: this.now(), ok: false, error: error instanceof Error ? error.message : String(error) };
+    }
+
+    return this.lastProbe.ok;
+  }
+
+  readiness(): ReadinessReport {
+    const upstream = this.lastProbe ? (this.lastProbe.ok ? "ok" : "failed") : "unknown";
+    return {
+      ready: this.options.apiKeyPresent && upstream === "ok",
+      api_key_present: this.options.apiKeyPresent,
+      upstream,
+      ...(this.lastProbe ? { last_probe_at: new Date(this.lastProbe.at).toISOString() } : {}),
+      ...(this.lastProbe?.error ? { last_probe_error: this.lastProbe.error } : {}),
+    };
+  }
+}
@@ -3,62 +3,343 @@ import dotenv from "dotenv";
 
 dotenv.config();
 
+import { readFileSync } from "node:fs";
+import { IncomingMessage } from "node:http";
+
+import { McpServer } from "@modelcontextprotocol/sdk/server/mcp.js";
 import { StdioServerTransport } from "@modelcontextprotocol/sdk/server/stdio.js";
 
+import { BalanceWatcher } from "./balance-watcher.js";
+import { BudgetTracker } from "./budget-tracker.js";
 import { ConversationStore } from "./conversation-store.js";
-import { loadRuntimeConfig } from "./config.js";
+import { isLoopbackHost, loadRuntimeConfig, McpTransportMode, RuntimeConfig } from "./config.js";
+import {
+  DAEMON_CHILD_ENV,
+  daemonStatus,
+  parseDaemonOptions,
+  releasePidFileOnExit,
+  startDaemon,
+  stopDaemon,
+} from "./daemon.js";
+import { createSeededRandom } from "./deepseek/backoff.js";
 import { DeepSeekApiClient } from "./deepseek/client.js";
-import { createDeepSeekMcpServer } from "./mcp-server.js";
+import { WeightedModelSelector } from "./deepseek/model-selection.js";
+import { ModelsCache } from "./deepseek/models-cache.js";
+import { HealthMonitor } from "./health.js";
+import { attachKeepAlive } from "./keepalive.js";
+import { createDeepSeekMcpServer, DeepSeekMcpServerOptions, describeConfiguration } from "./mcp-server.js";
+import { PromptModerator } from "./moderation.js";
+import { ClientRateLimiter, TokenBucketRateLimiter } from "./rate-limiter.js";
+import { ShutdownController } from "./shutdown.js";
+import { ToolToggles } from "./tool-toggles.js";
 import { startStreamableHttpServer } from "./transports/http.js";
+import { startSseServer } from "./transports/sse.js";
+import { OAuthResourceServer } from "./transports/oauth.js";
+import { TlsOptions } from "./transports/listener.js";
+import { DEFAULT_PIPE_PATH, startPipeServer } from "./transports/pipe.js";
+import { startTcpServer } from "./transports/tcp.js";
+import { startWebSocketServer } from "./transports/websocket.js";
 
 async function main(): Promise<void> {
-  const config = loadRuntimeConfig();
+  const argv = process.argv.slice(2);
+  const daemon = parseDaemonOptions(process.env, argv);
+  if (daemon.command === "stop") {
+    process.exit(stopDaemon(daemon));
+  }
+
+  if (daemon.command === "status") {
+    process.exit(daemonStatus(daemon));
+  }
+
+  const config = loadRuntimeConfig(process.env, argv);
+  if (daemon.command === "start") {
+    if (config.transports.includes("stdio")) {
+      throw new Error("--daemon needs a network transport (streamable-http, sse, websocket, tcp or pipe), not stdio");
+    }
+
+    process.exit(startDaemon(daemon, argv));
+  }
+
+  if (process.env[DAEMON_CHILD_ENV] === "1") {
+    releasePidFileOnExit(daemon.pidFile);
+  }
+  if (config.samplingFallback && !config.deepseekApiKey) {
+    console.error("No DEEPSEEK_API_KEY: chat_completion will use MCP sampling; other network tools will return errors");
+  } else if (config.offline && !config.deepseekApiKey) {
+    console.error("DEEPSEEK_OFFLINE=1 without DEEPSEEK_API_KEY: network tools will return errors");
+  }
 
   const client = new DeepSeekApiClient({
     apiKey: config.deepseekApiKey,
     baseUrl: config.deepseekBaseUrl,
+    baseUrls: config.deepseekBaseUrls,
+    baseUrlCooldownMs: config.baseUrlCooldownMs,
+    extraHeaders: config.extraHeaders,
     timeoutMs: config.deepseekRequestTimeoutMs,
     enableReasonerFallback: config.enableReasonerFallback,
     fallbackModel: config.fallbackModel,
+    signingSecret: config.signingSecret,
+    connectionPool: {
+      maxConnectionsPerHost: config.poolMaxConnectionsPerHost,
+      idleTimeoutMs: config.poolIdleTimeoutMs,
+      keepAlive: config.tcpKeepAlive,
+    },
+    maxResponseBytes: config.maxResponseBytes,
+    retryOnDecodeError: config.retryOnDecodeError,
+    recordDir: config.recordDir,
+    replayDir: config.replayDir,
+    retryStatuses: config.retryStatuses,
+    rateLimitRetries: config.rateLimitRetries,
+    rateLimitBackoffMs: config.rateLimitBackoffMs,
+    rateLimitMaxBackoffMs: config.rateLimitMaxBackoffMs,
+    rateLimitJitter: config.rateLimitJitter,
+    compressRequests: config.compressRequests,
+    compressionThresholdBytes: config.compressionThresholdBytes,
+    random: config.rateLimitSeed !== undefined ? createSeededRandom(config.rateLimitSeed) : undefined,
+    endpointPaths: {
+      chat: config.chatPath,
+      completions: config.completionsPath,
+      models: config.modelsPath,
+    },
+    loopGuard: config.loopGuardEnabled
+      ? { ngramSize: config.loopGuardNgramSize, maxRepeats: config.loopGuardMaxRepeats }
+      : undefined,
+    streamSoftMaxTokens: config.streamSoftMaxTokens,
   });
 
-  const conversations = new ConversationStore(config.conversationMaxMessages);
+  const conversations = new ConversationStore(config.conversationMaxMessages, config.conversationMaxSessions);
+  const shutdown = new ShutdownController();
 
-  const mcpServer = createDeepSeekMcpServer({
+  const serverOptions: DeepSeekMcpServerOptions = {
     client,
     conversations,
     defaultModel: config.defaultModel,
     experimentalV4Enabled: config.experimentalV4Enabled,
+    modelsCache: config.modelsCacheFile ? new ModelsCache(config.modelsCacheFile) : undefined,
+    disabledTools: config.disabledTools,
+    enabledTools: config.enabledTools,
+    toolToggles: new ToolToggles(),
+    enableToolAdmin: config.enableToolAdmin,
+    budget:
+      config.monthlyBudgetUsd !== undefined
+        ? new BudgetTracker({ monthlyBudgetUsd: config.monthlyBudgetUsd, filePath: config.budgetFile })
+        : undefined,
+    summaryMaxChars: config.summaryMaxChars,
+    imageDir: config.imageDir,
+    attachDirs: config.attachDirs,
+    attachMaxFileBytes: config.attachMaxFileBytes,
+    attachMaxTotalBytes: config.attachMaxTotalBytes,
+    maxMessages: config.maxMessages,
+    chatToCompletionFallback: config.chatToCompletionFallback,
+    errorFormat: config.errorFormat,
+    tokenParam: config.tokenParam,
+    timing: config.timing,
+    mergeConsecutiveMessages: config.mergeConsecutiveMessages,
+    enableRawRequests: config.enableRawRequests,
+    enableBatch: config.enableBatch,
+    batchConcurrency: config.batchConcurrency,
+    allowClientApiKeys: config.allowClientApiKeys,
+    autoContinueLimit: config.autoContinue ? config.autoContinueMax : undefined,
+    trimOutput: config.trimOutput,
+    stripCodeFences: config.stripCodeFences,
+    sanitizeExtraBody: config.sanitizeExtraBody,
+    shutdown,
+    retryStatuses: config.retryStatuses,
+    moderator: config.moderationUrl
+      ? new PromptModerator({ url: config.moderationUrl, failMode: config.moderationFailMode })
+      : undefined,
+    rateLimiter: config.rateLimitRpm
+      ? new TokenBucketRateLimiter({ requestsPerMinute: config.rateLimitRpm, burst: config.rateLimitBurst })
+      : undefined,
+    rateLimitNotifyThresholdMs: config.rateLimitNotifyMs,
+    progressIntervalMs: config.progressIntervalMs,
+    lowBalanceThreshold: config.lowBalanceThreshold,
+    balanceWatcher: new BalanceWatcher({
+      fetchBalance: () => client.getUserBalance(),
+      intervalMs: config.balancePollIntervalMs,
+    }),
+    confirmAboveTokens: config.confirmAboveTokens,
+    confirmAboveUsd: config.confirmAboveUsd,
+    gitCommit: config.gitCommit,
+    offline: config.offline,
+    samplingFallback: config.samplingFallback,
+    modelSelector: config.modelWeights ? new WeightedModelSelector(config.modelWeights) : undefined,
+  };
+  serverOptions.instructions = describeConfiguration(serverOptions);
+
+  // Each configured transport gets its own MCP server instances sharing the DeepSeek client and budget.
+  // Network sessions also get their own conversation store unless MCP_SESSION_ISOLATION=false.
+  // With DEEPSEEK_ALLOW_CLIENT_KEYS, an X-DeepSeek-Api-Key header on the session-opening request
+  // replaces the server key for that session; HTTP requests can also carry the header individually.
+  const clientRateLimiter =
+    config.clientRateLimitRpm || config.clientMaxConcurrent
+      ? new ClientRateLimiter({
+          requestsPerMinute: config.clientRateLimitRpm,
+          maxConcurrent: config.clientMaxConcurrent,
+        })
+      : undefined;
+  const createSessionServer = (req: IncomingMessage | undefined, sessionScoped: boolean) => {
+    const clientApiKey = config.allowClientApiKeys ? readClientApiKey(req) : undefined;
+    return createDeepSeekMcpServer({
+      ...serverOptions,
+      clientRateLimiter,
+      sessionScoped,
+      client: clientApiKey ? client.withApiKey(clientApiKey) : client,
+      conversations: config.sessionIsolation
+        ? new ConversationStore(config.conversationMaxMessages, config.conversationMaxSessions)
+        : conversations,
+    });
+  };
+  const tls =
+    config.tlsCertFile && config.tlsKeyFile
+      ? { cert: readFileSync(config.tlsCertFile), key: readFileSync(config.tlsKeyFile) }
+      : undefined;
+  const health = config.transports.includes("streamable-http")
+    ? new HealthMonitor({
+        apiKeyPresent: Boolean(config.deepseekApiKey),
+        probe: () => client.listModels(),
+        intervalMs: config.healthProbeIntervalMs,
+      })
+    : undefined;
+  health?.start();
+  const keepAlive = { pingIntervalMs: config.keepAliveIntervalMs, idleTimeoutMs: config.idleTimeoutMs };
+  const closers: Array<() => Promise<void>> = [];
+  for (const transport of config.transports) {
+    // Stateless HTTP serves every client from one server with no session to keep alive or expire.
+    const sessionScoped = transport !== "streamable-http" || config.httpStatefulSession;
+    const createTransportServer = (req?: IncomingMessage) => {
+      const mcpServer =
+        transport === "stdio"
+          ? createDeepSeekMcpServer({ ...serverOptions, trustedTransport: true })
+          : createSessionServer(req, sessionScoped);
+      return sessionScoped ? attachKeepAlive(mcpServer, keepAlive) : mcpServer;
+    };
+    closers.push(await startTransport(transport, config, createTransportServer, tls, health));
+  }
+
+  installShutdownHandlers(async () => {
+    const inFlight = shutdown.inFlight;
+    if (inFlight > 0) {
+      console.error(`Waiting up to ${config.shutdownTimeoutMs} ms for ${inFlight} in-flight tool call(s)...`);
+    }
+
+    if (!(await shutdown.drain(config.shutdownTimeoutMs))) {
+      console.error(`Shutdown timeout reached with ${shutdown.inFlight} tool call(s) still running`);
+    }
+
+    health?.stop();
+    await Promise.all(closers.map((close) => close()));
   });
+}
 
-  if (config.transport === "stdio") {
-    const transport = new StdioServerTransport();
-    await mcpServer.connect(transport);
+async function startTransport(
+  transport: McpTransportMode,
+  config: RuntimeConfig,
+  createMcpServer: (req?: IncomingMessage) => McpServer,
+  tls: TlsOptions | undefined,
+  health: HealthMonitor | undefined,
+): Promise<() => Promise<void>> {
+  const httpScheme = tls ? "https" : "http";
 
-    console.error("DeepSeek MCP server connected via stdio");
+  if (transport === "sse") {
+    const sseRuntime = await startSseServer(createMcpServer, {
+      host: config.httpHost,
+      port: config.ssePort,
+      ssePath: config.ssePath,
+      messagesPath: config.sseMessagesPath,
+      authTokens: config.authTokens,
+      tls,
+    });
+
+    console.error(
+      `DeepSeek MCP server running on SSE at ${httpScheme}://${config.httpHost}:${sseRuntime.port}${config.ssePath}`,
+    );
+
+    return sseRuntime.close;
+  }
+
+  if (transport === "websocket") {
+    const webSocketRuntime = await startWebSocketServer(createMcpServer, {
+      host: config.httpHost,
+      port: config.webSocketPort,
+      path: config.webSocketPath,
+      authTokens: config.authTokens,
+      tls,
+    });
 
-    installShutdownHandlers(async () => {
-      await mcpServer.close();
+    console.error(
+      `DeepSeek MCP server running on WebSocket at ${tls ? "wss" : "ws"}://${config.httpHost}:${webSocketRuntime.port}${config.webSocketPath}`,
+    );
+
+    return webSocketRuntime.close;
+  }
+
+  if (transport === "tcp") {
+    if (!isLoopbackHost(config.tcpHost)) {
+      console.error(
+        `WARNING: the TCP transport has no authentication and is listening on ${config.tcpHost}; anyone who can reach this port can spend your DeepSeek credits`,
+      );
+    }
+    const tcpRuntime = await startTcpServer(createMcpServer, {
+      host: config.tcpHost,
+      port: config.tcpPort,
     });
 
-    return;
+    console.error(`DeepSeek MCP server listening on TCP ${config.tcpHost}:${tcpRuntime.port}`);
+
+    return tcpRuntime.close;
+  }
+
+  if (transport === "pipe") {
+    const pipeRuntime = await startPipeServer(createMcpServer, { path: config.pipePath ?? DEFAULT_PIPE_PATH });
+
+    console.error(`DeepSeek MCP server listening on pipe ${pipeRuntime.path}`);
+
+    return pipeRuntime.close;
+  }
+
+  if (transport === "stdio") {
+    const mcpServer = createMcpServer();
+    await mcpServer.connect(new StdioServerTransport());
+
+    console.error("DeepSeek MCP server connected via stdio");
+
+    return () => mcpServer.close();
   }
 
-  const httpRuntime = await startStreamableHttpServer(mcpServer, {
+  const httpRuntime = await startStreamableHttpServer(createMcpServer, {
     host: config.httpHost,
     port: config.httpPort,
     path: config.httpPath,
+    basePath: config.basePath,
     statefulSession: config.httpStatefulSession,
+    authTokens: config.authTokens,
+    oauth:
+      config.oauthResource && config.oauthIntrospectionUrl
+        ? new OAuthResourceServer({
+            resource: config.oauthResource,
+            authorizationServers: config.oauthAuthorizationServers,
+            introspectionUrl: config.oauthIntrospectionUrl,
+            clientId: config.oauthClientId,
+            clientSecret: config.oauthClientSecret,
+            requiredScopes: config.oauthScopes,
+          })
+        : undefined,
+    tls,
+    health,
   });
 
   console.error(
-    `DeepSeek MCP server running on Streamable HTTP at http://${config.httpHost}:${config.httpPort}${config.httpPath}`,
+    `DeepSeek MCP server running on Streamable HTTP at ${httpScheme}://${config.httpHost}:${config.httpPort}${config.httpPath}`,
   );
 
-  installShutdownHandlers(async () => {
-    await httpRuntime.close();
-    await mcpServer.close();
-  });
+  return httpRuntime.close;
+}
+
+function readClientApiKey(req: IncomingMessage | undefined): string | undefined {
+  const header = req?.headers["x-deepseek-api-key"];
+  const value = Array.isArray(header) ? header[0] : header;
+  return value?.trim() || undefined;
 }
 
 function installShutdownHandlers(closeFn: () => Promise<void>): void {
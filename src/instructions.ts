@@ -0,0 +1,33 @@
+export interface ServerInstructionsInfo {
+  defaultModel: string;
+  baseUrls: readonly string[];
+  reasonerFallback: { enabled: boolean; model: string };
+  chatToCompletionFallback: boolean;
+  // Undefined when every tool is allowed.
+  enabledTools?: readonly string[];
+  disabledTools: readonly string[];
+  flags: Record<string, boolean>;
+}
+
+// Describes how this instance is actually configured, for the initialize response and get_version.
+export function buildServerInstructions(info: ServerInstructionsInfo): string {
+  const fallback = info.reasonerFallback.enabled
+    ? `retryable deepseek-reasoner failures fall back to ${info.reasonerFallback.model}`
+    : "no model fallback";
+  const enabledFlags = Object.keys(info.flags).filter((flag) => info.flags[flag]);
+  const enabledTools = info.enabledTools?.filter((tool) => !info.disabledTools.includes(tool));
+  const tools = enabledTools
+    ? enabledTools.join(", ") || "none"
+    : info.disabledTools.length > 0
+      ? `all except ${info.disabledTools.join(", ")}`
+      : "all";
+
+  return [
+    "DeepSeek MCP server: chat and FIM completions, model listing and account balance via the DeepSeek API.",
+    `Default model: ${info.defaultModel}.`,
+    `Fallback policy: ${fallback}${info.chatToCompletionFallback ? "; chat failures retry via /completions" : ""}.`,
+    `Base URL: ${info.baseUrls.length > 0 ? info.baseUrls.join(", ") : "none"}.`,
+    `Enabled tools: ${tools}.`,
+    `Enabled features: ${enabledFlags.length > 0 ? enabledFlags.join(", ") : "none"}.`,
+  ].join("\n");
+}
@@ -0,0 +1,75 @@
+import { McpServer } from "@modelcontextprotocol/sdk/server/mcp.js";
+
+export interface KeepAliveOptions {
+  pingIntervalMs?: number;
+  idleTimeoutMs?: number;
+  now?: () => number;
+}
+
+// Pings the client so intermediaries see traffic, and closes the session (freeing its per-session state)
+// once nothing, ping replies included, has arrived from the client for idleTimeoutMs.
+export function attachKeepAlive(mcpServer: McpServer, options: KeepAliveOptions): McpServer {
+  if (!options.pingIntervalMs && !options.idleTimeoutMs) {
+    return mcpServer;
+  }
+
+  const server = mcpServer.server;
+  const now = options.now ?? Date.now;
+  let lastActivity = now();
+  let timers: NodeJS.Timeout[] = [];
+
+  const stop = () => {
+    for (const timer of timers) {
+      clearInterval(timer);
+    }
+    timers = [];
+  };
+
+  const schedule = (callback: () => void, intervalMs: number) => {
+    const timer = setInterval(callback, intervalMs);
+    timer.unref();
+    timers.push(timer);
+  };
+
+  const previousInitialized = server.oninitialized;
+  server.oninitialized = () => {
+    previousInitialized?.();
+
+    const transport = server.transport;
+    if (transport) {
+      const onmessage = transport.onmessage;
+      transport.onmessage = (message, extra) => {
+        lastActivity = now();
+        onmessage?.(message, extra);
+      };
+    }
+
+    lastActivity = now();
+    if (options.pingIntervalMs) {
+      schedule(() => {
+        void server.ping().catch(() => undefined);
+      }, options.pingIntervalMs);
+    }
+
+    const idleTimeoutMs = options.idleTimeoutMs;
+    if (idleTimeoutMs) {
+      schedule(
+        () => {
+          if (now() - lastActivity >= idleTimeoutMs) {
+            stop();
+            void mcpServer.close();
+          }
+        },
+        Math.min(idleTimeoutMs, 1000),
+      );
+    }
+  };
+
+  const previousClose = server.onclose;
+  server.onclose = () => {
+    stop();
+    previousClose?.();
+  };
+
+  return mcpServer;
+}
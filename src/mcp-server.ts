@@ -1,28 +1,142 @@
-import { McpServer, ResourceTemplate } from "@modelcontextprotocol/sdk/server/mcp.js";
+import { createHash, randomUUID } from "node:crypto";
+import { fileURLToPath } from "node:url";
+
+import { completable } from "@modelcontextprotocol/sdk/server/completable.js";
+import {
+  McpServer,
+  RegisteredResource,
+  RegisteredTool,
+  ResourceTemplate,
+} from "@modelcontextprotocol/sdk/server/mcp.js";
+import { RequestHandlerExtra } from "@modelcontextprotocol/sdk/shared/protocol.js";
+import {
+  CallToolResult,
+  ErrorCode,
+  LATEST_PROTOCOL_VERSION,
+  LoggingLevel,
+  McpError,
+  SUPPORTED_PROTOCOL_VERSIONS,
+  ServerNotification,
+  ServerRequest,
+  SubscribeRequestSchema,
+  UnsubscribeRequestSchema,
+} from "@modelcontextprotocol/sdk/types.js";
 import { z } from "zod";
 
+import { BalanceWatcher } from "./balance-watcher.js";
+import { BudgetExceededError, BudgetTracker } from "./budget-tracker.js";
+import { clientProtocolVersion, supportsStructuredContent } from "./client-profile.js";
+import {
+  createJsonOutputChecker,
+  jsonCorrectionPrompt,
+  jsonSchemaSystemPrompt,
+  JsonOutputValidationError,
+} from "./deepseek/json-output.js";
+import { buildServerInstructions } from "./instructions.js";
+import { applyToolResults, ToolLoopRun, ToolLoopRuns } from "./tool-loop.js";
+import { mapWithConcurrency } from "./concurrency.js";
+import { ConfirmationDeclinedError, estimateRequestsCost, exceedsConfirmationThreshold } from "./confirmation.js";
 import { ConversationStore } from "./conversation-store.js";
-import { DeepSeekApiClient, DeepSeekApiError } from "./deepseek/client.js";
+import {
+  DEFAULT_RETRY_STATUS_CODES,
+  DeepSeekApiClient,
+  DeepSeekApiError,
+  extractErrorType,
+  StreamProgressCallback,
+} from "./deepseek/client.js";
+import { attachFiles, insertAttachmentMessage } from "./deepseek/file-attachments.js";
+import { encodeImageFile } from "./deepseek/image-encoding.js";
+import { ApiKeyDiagnosis, diagnoseApiKey } from "./deepseek/key-diagnostics.js";
+import {
+  flattenMessagesToPrompt,
+  mergeConsecutiveMessages,
+  messageContentToText,
+  normalizeOutputText,
+  renderToolCalls,
+  renderTranscript,
+  truncateText,
+} from "./deepseek/message-utils.js";
+import { WeightedModelSelector } from "./deepseek/model-selection.js";
+import { ModelCapabilityRegistry, stripUnsupportedParameters } from "./deepseek/model-capabilities.js";
+import { ModelsCache } from "./deepseek/models-cache.js";
+import { estimateCostUsd, getModelPricing } from "./deepseek/pricing.js";
+import { validateChatCompletionRequest } from "./deepseek/request-validation.js";
+import { diffLines } from "./deepseek/text-diff.js";
+import { estimateMessagesTokens, estimateMessageTokens, estimateTextTokens } from "./deepseek/token-estimator.js";
+import { ModerationBlockedError, ModerationResult, PromptModerator } from "./moderation.js";
+import { ClientRateLimitedError, ClientRateLimiter, TokenBucketRateLimiter } from "./rate-limiter.js";
+import { fromSamplingResult, toSamplingRequest } from "./sampling.js";
+import { ShutdownController } from "./shutdown.js";
+import { ToolToggles } from "./tool-toggles.js";
+import { REMOTE_ADDRESS_HEADER } from "./transports/http.js";
+import { UsageTracker } from "./usage-tracker.js";
 import {
   ChatCompletionToolInput,
+  CACHE_NAMES,
+  ChatPrefixCompletionToolInput,
+  JsonChatToolInput,
+  FunctionCallingToolInput,
+  CountTokensToolInput,
+  EstimateCostToolInput,
+  UsageSummaryToolInput,
+  CreateConversationToolInput,
+  AppendMessageToolInput,
+  ContinueConversationToolInput,
+  ClearCachesToolInput,
+  BatchChatCompletionToolInput,
+  CompareModelsToolInput,
   CompletionToolInput,
+  FimCompletionToolInput,
+  EncodeImageToolInput,
   ImageGenerationToolInput,
+  RawRequestToolInput,
+  AuthenticateToolInput,
+  SetToolEnabledToolInput,
+  SummarizeAndContinueToolInput,
   VideoGenerationToolInput,
   VideoUploadToolInput,
   VisionUploadToolInput,
+  batchChatCompletionToolInputSchema,
   chatCompletionToolInputSchema,
+  chatCompletionToolOutputSchema,
+  chatPrefixCompletionToolInputSchema,
+  clearCachesToolInputSchema,
+  compareModelsToolInputSchema,
   completionToolInputSchema,
+  fimCompletionToolInputSchema,
+  jsonChatToolInputSchema,
+  functionCallingToolInputSchema,
+  countTokensToolInputSchema,
+  estimateCostToolInputSchema,
+  usageSummaryToolInputSchema,
+  createConversationToolInputSchema,
+  appendMessageToolInputSchema,
+  continueConversationToolInputSchema,
   emptyToolInputSchema,
+  encodeImageToolInputSchema,
   imageGenerationToolInputSchema,
+  listModelsToolOutputSchema,
+  rawRequestToolInputSchema,
+  authenticateToolInputSchema,
   resetConversationToolInputSchema,
+  setToolEnabledToolInputSchema,
+  summarizeAndContinueToolInputSchema,
+  userBalanceToolOutputSchema,
   videoGenerationToolInputSchema,
   videoUploadToolInputSchema,
   visionUploadToolInputSchema,
 } from "./deepseek/schemas.js";
 import {
+  ChatCompletionExecutionResult,
   DeepSeekChatCompletionRequest,
   DeepSeekChatMessage,
   DeepSeekCompletionRequest,
+  DeepSeekResponseFormat,
+  DeepSeekTokenParam,
+  DeepSeekToolDefinition,
+  DeepSeekUsage,
+  DeepSeekUserBalanceResponse,
+  RequestTiming,
 } from "./deepseek/types.js";
 import {
   buildImageGenerationRequest,
@@ -37,11 +151,60 @@ import {
   V4_ENDPOINTS,
 } from "./deepseek/v4-mapping.js";
 
+export type ToolErrorFormat = "text" | "json";
+
 export interface DeepSeekMcpServerOptions {
   client: DeepSeekApiClient;
   conversations: ConversationStore;
   defaultModel: string;
   experimentalV4Enabled?: boolean;
+  modelsCache?: ModelsCache;
+  modelCapabilities?: ModelCapabilityRegistry;
+  disabledTools?: readonly string[];
+  enabledTools?: readonly string[];
+  toolToggles?: ToolToggles;
+  enableToolAdmin?: boolean;
+  // True for stdio, where whoever can send requests already controls the process.
+  trustedTransport?: boolean;
+  budget?: BudgetTracker;
+  summaryMaxChars?: number;
+  imageDir?: string;
+  attachDirs?: readonly string[];
+  attachMaxFileBytes?: number;
+  attachMaxTotalBytes?: number;
+  maxMessages?: number;
+  modelSelector?: WeightedModelSelector;
+  chatToCompletionFallback?: boolean;
+  errorFormat?: ToolErrorFormat;
+  gitCommit?: string;
+  offline?: boolean;
+  samplingFallback?: boolean;
+  tokenParam?: DeepSeekTokenParam;
+  timing?: boolean;
+  mergeConsecutiveMessages?: boolean;
+  enableRawRequests?: boolean;
+  enableBatch?: boolean;
+  batchConcurrency?: number;
+  allowClientApiKeys?: boolean;
+  // False when one server answers every client (stateless streamable HTTP), so per-session state can't be kept.
+  sessionScoped?: boolean;
+  moderator?: PromptModerator;
+  autoContinueLimit?: number;
+  rateLimiter?: TokenBucketRateLimiter;
+  rateLimitNotifyThresholdMs?: number;
+  progressIntervalMs?: number;
+  lowBalanceThreshold?: number;
+  balanceWatcher?: BalanceWatcher;
+  balancePollIntervalMs?: number;
+  confirmAboveTokens?: number;
+  confirmAboveUsd?: number;
+  trimOutput?: boolean;
+  stripCodeFences?: boolean;
+  sanitizeExtraBody?: boolean;
+  shutdown?: ShutdownController;
+  clientRateLimiter?: ClientRateLimiter;
+  retryStatuses?: readonly number[];
+  instructions?: string;
   version?: string;
 }
 
@@ -52,12 +215,24 @@ const ENDPOINT_MATRIX = [
     tool: "chat_completion",
     description: "Chat Completions API (streaming and non-streaming)",
   },
+  {
+    endpoint: "/beta/chat/completions",
+    method: "POST",
+    tool: "chat_prefix_completion",
+    description: "Beta chat prefix completion (assistant continues a supplied prefix)",
+  },
   {
     endpoint: "/completions",
     method: "POST",
     tool: "completion",
     description: "Text/FIM Completions API (streaming and non-streaming)",
   },
+  {
+    endpoint: "/beta/completions",
+    method: "POST",
+    tool: "fim_completion",
+    description: "Beta fill-in-the-middle completion (prefix + suffix)",
+  },
   {
     endpoint: "/models",
     method: "GET",
@@ -97,22 +272,161 @@ const ENDPOINT_MATRIX = [
 ] as const;
 
 const SERVER_VERSION = "0.4.0";
-const RETRYABLE_DEEPSEEK_STATUS_CODES = new Set([408, 409, 429, 500, 502, 503, 504]);
+const SUMMARY_SYSTEM_PROMPT =
+  "Summarize the following conversation into a compact note that preserves facts, decisions, open questions, and user preferences needed to continue it. Reply with the summary only.";
+// Raw upstream JSON in text content is capped so debugging output can't flood the context window; the full
+// payload stays in structuredContent.
+const DEFAULT_SUMMARY_MAX_CHARS = 4000;
+const BALANCE_RESOURCE_URI = "deepseek://balance";
+// Hints for tools that send a new, non-repeatable request to DeepSeek.
+const UPSTREAM_CALL_ANNOTATIONS = {
+  readOnlyHint: false,
+  destructiveHint: false,
+  idempotentHint: false,
+  openWorldHint: true,
+} as const;
+// OAuth scope that lets a network client call set_tool_enabled.
+const TOOL_ADMIN_SCOPE = "deepseek:admin";
+// Languages whose line comments don't start with `//`; fim_completion uses this to write its language hint.
+const HASH_COMMENT_LANGUAGES = new Set(["python", "ruby", "perl", "r", "shell", "bash", "sh", "zsh", "yaml", "toml"]);
+const DASH_COMMENT_LANGUAGES = new Set(["sql", "lua", "haskell"]);
+
+export function createDeepSeekMcpServer(serverOptions: DeepSeekMcpServerOptions): McpServer {
+  // Per-server copy so the authenticate tool can swap this session's client without touching others.
+  const options: DeepSeekMcpServerOptions = { ...serverOptions };
+  const server = new McpServer(
+    {
+      name: "deepseek-mcp-server",
+      version: options.version ?? SERVER_VERSION,
+    },
+    { capabilities: { logging: {}, resources: { subscribe: true } }, instructions: options.instructions },
+  );
 
-export function createDeepSeekMcpServer(options: DeepSeekMcpServerOptions): McpServer {
-  const server = new McpServer({
-    name: "deepseek-mcp-server",
-    version: options.version ?? SERVER_VERSION,
-  });
+  const toolResources = registerResources(server, options);
+  const stopBalanceSubscription = registerResourceSubscriptions(server, options);
+  const modelCompleter = createModelCompleter(options);
+  registerPrompts(server, options, modelCompleter);
+  const tools = registerTools(server, options, modelCompleter);
+
+  for (const [toolName, tool] of tools) {
+    if (!isToolEnabled(options, toolName)) {
+      tool.disable();
+      toolResources.get(toolName)?.disable();
+    }
+  }
 
-  registerResources(server, options);
-  registerPrompts(server, options);
-  registerTools(server, options);
+  // Runtime toggles never re-enable a tool that DEEPSEEK_DISABLED_TOOLS/DEEPSEEK_ENABLED_TOOLS rule out, and they
+  // hide or restore the resource serving the same data along with the tool.
+  const unsubscribe = options.toolToggles?.subscribe((toolName, enabled) => {
+    const tool = tools.get(toolName);
+    if (!tool || !isToolAllowedByConfig(options, toolName)) {
+      return;
+    }
+
+    for (const entry of [tool, toolResources.get(toolName)]) {
+      if (enabled) {
+        entry?.enable();
+      } else {
+        entry?.disable();
+      }
+    }
+  });
+  const previousClose = server.server.onclose;
+  server.server.onclose = () => {
+    unsubscribe?.();
+    stopBalanceSubscription();
+    previousClose?.();
+  };
 
   return server;
 }
 
-function registerResources(server: McpServer, options: DeepSeekMcpServerOptions): void {
+// Only deepseek://balance changes on its own; subscribing to it polls /user/balance while the subscription lasts.
+// Sessions subscribe to the process-wide watcher when one is passed in, so they share a single poll.
+function registerResourceSubscriptions(server: McpServer, options: DeepSeekMcpServerOptions): () => void {
+  const watcher =
+    options.balanceWatcher ??
+    new BalanceWatcher({
+      fetchBalance: () => options.client.getUserBalance(),
+      intervalMs: options.balancePollIntervalMs ?? 60_000,
+    });
+  let stopWatching: (() => void) | undefined;
+
+  server.server.setRequestHandler(SubscribeRequestSchema, async (request) => {
+    if (request.params.uri !== BALANCE_RESOURCE_URI || !isToolEnabled(options, "get_user_balance")) {
+      throw new McpError(ErrorCode.InvalidParams, `Subscriptions are only supported for ${BALANCE_RESOURCE_URI}`);
+    }
+
+    if (!stopWatching) {
+      stopWatching = watcher.subscribe(() => {
+        void server.server.sendResourceUpdated({ uri: BALANCE_RESOURCE_URI }).catch(() => undefined);
+      });
+    }
+    return {};
+  });
+
+  server.server.setRequestHandler(UnsubscribeRequestSchema, async (request) => {
+    if (request.params.uri === BALANCE_RESOURCE_URI) {
+      stopWatching?.();
+      stopWatching = undefined;
+    }
+    return {};
+  });
+
+  return () => {
+    stopWatching?.();
+    stopWatching = undefined;
+  };
+}
+
+// Built once per process and sent as the SDK's `instructions`, so the text reflects startup configuration; runtime
+// tool toggles show up in tools/list instead.
+export function describeConfiguration(options: DeepSeekMcpServerOptions): string {
+  return buildServerInstructions({
+    defaultModel: options.defaultModel,
+    baseUrls: options.client.getBaseUrlHealth().map((health) => health.base_url),
+    reasonerFallback: options.client.reasonerFallback,
+    chatToCompletionFallback: options.chatToCompletionFallback ?? false,
+    enabledTools: options.enabledTools?.length ? options.enabledTools : undefined,
+    disabledTools: options.disabledTools ?? [],
+    flags: featureFlags(options),
+  });
+}
+
+// The optional features listed in the instructions and reported by get_version.
+function featureFlags(options: DeepSeekMcpServerOptions): Record<string, boolean> {
+  return {
+    offline: options.offline ?? false,
+    sampling_fallback: options.samplingFallback ?? false,
+    batch: options.enableBatch ?? false,
+    raw_requests: options.enableRawRequests ?? false,
+    tool_admin: options.enableToolAdmin ?? false,
+    experimental_v4: options.experimentalV4Enabled ?? false,
+    chat_to_completion_fallback: options.chatToCompletionFallback ?? false,
+    monthly_budget: options.budget !== undefined,
+    weighted_model_selection: options.modelSelector !== undefined,
+    encode_image: options.imageDir !== undefined,
+    models_cache: options.modelsCache !== undefined,
+    rate_limit: options.rateLimiter !== undefined,
+    confirmation: options.confirmAboveTokens !== undefined || options.confirmAboveUsd !== undefined,
+  };
+}
+
+function isToolAllowedByConfig(options: DeepSeekMcpServerOptions, toolName: string): boolean {
+  if (options.disabledTools?.includes(toolName)) {
+    return false;
+  }
+
+  return !options.enabledTools || options.enabledTools.length === 0 || options.enabledTools.includes(toolName);
+}
+
+function isToolEnabled(options: DeepSeekMcpServerOptions, toolName: string): boolean {
+  return isToolAllowedByConfig(options, toolName) && options.toolToggles?.isEnabled(toolName) !== false;
+}
+
+// Returns the resources that mirror a tool, keyed by tool name, so runtime toggles can hide them together.
+function registerResources(server: McpServer, options: DeepSeekMcpServerOptions): Map<string, RegisteredResource> {
+  const toolResources = new Map<string, RegisteredResource>();
   server.registerResource(
     "deepseek-api-endpoints",
     "deepseek://api/endpoints",
@@ -125,7 +439,13 @@ function registerResources(server: McpServer, options: DeepSeekMcpServerOptions)
         {
           uri: uri.href,
           mimeType: "application/json",
-          text: JSON.stringify({ endpoints: ENDPOINT_MATRIX }, null, 2),
+          text: JSON.stringify(
+            {
+              endpoints: ENDPOINT_MATRIX.filter((entry) => isToolEnabled(options, entry.tool)),
+            },
+            null,
+            2,
+          ),
         },
       ],
     }),
@@ -138,7 +458,7 @@ function registerResources(server: McpServer, options: DeepSeekMcpServerOptions)
       description: "Runtime metadata for this MCP process",
       mimeType: "application/json",
     },
-    async (uri) => ({
+    async (uri, extra) => ({
       contents: [
         {
           uri: uri.href,
@@ -148,10 +468,11 @@ function registerResources(server: McpServer, options: DeepSeekMcpServerOptions)
               server_name: "deepseek-mcp-server",
               server_version: options.version ?? SERVER_VERSION,
               default_model: options.defaultModel,
-              conversation_count: options.conversations.listConversationIds().length,
+              conversation_count: conversationsFor(options, extra).listConversationIds().length,
               supports_streaming: true,
               supports_reasoner_fallback: true,
               experimental_v4_enabled: options.experimentalV4Enabled ?? false,
+              offline: options.offline ?? false,
             },
             null,
             2,
@@ -168,8 +489,8 @@ function registerResources(server: McpServer, options: DeepSeekMcpServerOptions)
       description: "Live model list from DeepSeek /models endpoint",
       mimeType: "application/json",
     },
-    async (uri) => {
-      const models = await options.client.listModels();
+    async (uri, extra) => {
+      const models = await requestClient(options, extra).listModels();
       return {
         contents: [
           {
@@ -182,50 +503,115 @@ function registerResources(server: McpServer, options: DeepSeekMcpServerOptions)
     },
   );
 
+  // Same data as list_models / get_user_balance, readable without a tool call; hidden along with those tools.
+  if (isToolAllowedByConfig(options, "list_models")) {
+    const modelsResource = server.registerResource(
+      "deepseek-models",
+      "deepseek://models",
+      {
+        description: "Available DeepSeek models (falls back to the models cache when /models is unreachable)",
+        mimeType: "application/json",
+      },
+      async (uri, extra) => {
+        let models: Record<string, unknown>;
+        try {
+          models = { ...(await requestClient(options, extra).listModels()), stale: false };
+        } catch (error) {
+          const cached = await options.modelsCache?.read();
+          if (!cached) {
+            throw error;
+          }
+
+          models = { ...cached.response, stale: true, cached_at: cached.cachedAt };
+        }
+
+        return {
+          contents: [{ uri: uri.href, mimeType: "application/json", text: JSON.stringify(models, null, 2) }],
+        };
+      },
+    );
+    toolResources.set("list_models", modelsResource);
+  }
+
+  if (isToolAllowedByConfig(options, "get_user_balance")) {
+    const balanceResource = server.registerResource(
+      "deepseek-balance",
+      BALANCE_RESOURCE_URI,
+      {
+        description: "Current DeepSeek account balance and availability",
+        mimeType: "application/json",
+      },
+      async (uri, extra) => ({
+        contents: [
+          {
+            uri: uri.href,
+            mimeType: "application/json",
+            text: JSON.stringify(await requestClient(options, extra).getUserBalance(), null, 2),
+          },
+        ],
+      }),
+    );
+    toolResources.set("get_user_balance", balanceResource);
+  }
+
   const conversationTemplate = new ResourceTemplate("deepseek://conversations/{conversationId}", {
-    list: async () => ({
-      resources: options.conversations.listConversationIds().map((conversationId) => ({
+    list: async (extra) => ({
+      resources: conversationsFor(options, extra).listConversationIds().map((conversationId) => ({
         uri: `deepseek://conversations/${encodeURIComponent(conversationId)}`,
         name: `Conversation ${conversationId}`,
         description: "Persisted messages for chat_completion",
+        mimeType: "application/json",
       })),
     }),
+    // Completion requests carry no caller identity, so the shared stateless server offers no suggestions.
+    complete: {
+      conversationId: (value) =>
+        options.sessionScoped === false
+          ? []
+          : options.conversations.listConversationIds().filter((conversationId) => conversationId.startsWith(value)),
+    },
   });
 
   server.registerResource(
     "deepseek-conversation",
     conversationTemplate,
     {
-      description: "Read stored messages for a specific conversation_id",
+      description:
+        "Full transcript for a conversation_id: every stored message (including reasoning_content and tool_calls) plus turn and token usage totals",
       mimeType: "application/json",
     },
-    async (uri, variables) => {
+    async (uri, variables, extra) => {
       const raw = variables.conversationId;
-      const conversationId = Array.isArray(raw) ? raw[0] : String(raw ?? "");
+      const conversationId = decodeURIComponent(Array.isArray(raw) ? raw[0] : String(raw ?? ""));
+      const conversations = conversationsFor(options, extra);
+
+      const session = conversations.getSession(conversationId);
+      if (!session) {
+        throw new McpError(ErrorCode.InvalidParams, `Unknown conversation: ${conversationId}`);
+      }
 
-      const messages = options.conversations.get(decodeURIComponent(conversationId));
       return {
         contents: [
           {
             uri: uri.href,
             mimeType: "application/json",
-            text: JSON.stringify(
-              {
-                conversation_id: decodeURIComponent(conversationId),
-                message_count: messages.length,
-                messages,
-              },
-              null,
-              2,
-            ),
+            text: JSON.stringify({ ...session, messages: conversations.get(conversationId) }, null, 2),
           },
         ],
       };
     },
   );
+
+  return toolResources;
 }
 
-function registerPrompts(server: McpServer, options: DeepSeekMcpServerOptions): void {
+function registerPrompts(
+  server: McpServer,
+  options: DeepSeekMcpServerOptions,
+  modelCompleter: ModelCompleter,
+): void {
+  const completeModel = modelCompleter.complete;
+
   server.registerPrompt(
     "deepseek_chat_starter",
     {
@@ -233,7 +619,7 @@ function registerPrompts(server: McpServer, options: DeepSeekMcpServerOptions):
       argsSchema: {
         task: z.string().min(1),
         style: z.string().optional(),
-        model: z.string().optional(),
+        model: completable(z.string().optional(), completeModel),
       },
     },
     ({ task, style, model }) => {
@@ -257,253 +643,2483 @@ function registerPrompts(server: McpServer, options: DeepSeekMcpServerOptions):
       };
     },
   );
-}
 
-function registerTools(server: McpServer, options: DeepSeekMcpServerOptions): void {
-  const experimentalV4Enabled = options.experimentalV4Enabled ?? false;
+  server.registerPrompt(
+    "code_review",
+    {
+      description: "Review a code snippet for bugs, security, performance or style issues",
+      argsSchema: {
+        code: z.string().min(1),
+        language: z.string().optional(),
+        focus: completableEnum(["all", "bugs", "security", "performance", "style"]),
+      },
+    },
+    ({ code, language, focus }) =>
+      userPrompt(
+        [
+          `Review the following ${language ?? "code"}.`,
+          focus && focus !== "all"
+            ? `Focus on ${focus} issues.`
+            : "Look for bugs, security problems, performance issues and style problems.",
+          "List each finding with its location, why it matters and a suggested fix; say so if you find nothing.",
+          "",
+          "```" + (language ?? ""),
+          code,
+          "```",
+        ].join("\n"),
+      ),
+  );
 
-  server.registerTool(
-    "chat_completion",
+  server.registerPrompt(
+    "summarize",
     {
-      description:
-        "Primary DeepSeek chat tool for single-turn and multi-turn generation. Provide either `message` (simple single user turn) or `messages` (full chat history); if both are provided, `messages` is used. Use `conversation_id` to persist context across calls and `clear_conversation=true` to reset stored state before sending the next turn. Set `include_raw_response=true` only for debugging, because it returns the full provider payload and increases token usage.",
-      inputSchema: chatCompletionToolInputSchema,
+      description: "Summarize a piece of text at a chosen length and format",
+      argsSchema: {
+        text: z.string().min(1),
+        length: completableEnum(["short", "medium", "long"]),
+        format: completableEnum(["paragraph", "bullets"]),
+      },
     },
-    async (input) => {
-      try {
-        const normalizedInput = input as ChatCompletionToolInput;
+    ({ text, length, format }) => {
+      const words = { short: 50, medium: 150, long: 400 }[length ?? "medium"];
+      return userPrompt(
+        [
+          `Summarize the following text as ${format === "bullets" ? "bullet points" : "prose"} of about ${words} words.`,
+          "Keep facts, figures and names exact; do not add information that is not in the text.",
+          "",
+          text,
+        ].join("\n"),
+      );
+    },
+  );
 
-        const conversationId = normalizedInput.conversation_id;
-        if (conversationId && normalizedInput.clear_conversation) {
-          options.conversations.clear(conversationId);
+  server.registerPrompt(
+    "translate",
+    {
+      description: "Translate text into a target language",
+      argsSchema: {
+        text: z.string().min(1),
+        target_language: z.string().min(1),
+        source_language: z.string().optional(),
+        tone: completableEnum(["neutral", "formal", "casual"]),
+      },
+    },
+    ({ text, target_language: targetLanguage, source_language: sourceLanguage, tone }) =>
+      userPrompt(
+        [
+          `Translate the following text${sourceLanguage ? ` from ${sourceLanguage}` : ""} into ${targetLanguage}.`,
+          tone && tone !== "neutral" ? `Use a ${tone} tone.` : undefined,
+          "Reply with the translation only.",
+          "",
+          text,
+        ]
+          .filter((line) => line !== undefined)
+          .join("\n"),
+      ),
+  );
+}
+
+interface ModelCompleter {
+  complete: (value: string | undefined) => Promise<string[]>;
+  // Forgets the loaded IDs so the next completion reloads them; returns how many were dropped.
+  clear: () => number;
+}
+
+// Model IDs come from the models cache file, else one /models call per server (retried after a failure).
+function createModelCompleter(options: DeepSeekMcpServerOptions): ModelCompleter {
+  let modelIds: Promise<string[]> | undefined;
+  let loadedCount = 0;
+
+  const loadModelIds = async (): Promise<string[]> => {
+    const cached = await options.modelsCache?.read();
+    if (cached) {
+      return cached.response.data.map((model) => model.id);
+    }
+
+    const models = await options.client.listModels();
+    await options.modelsCache?.write(models).catch(() => undefined);
+    return models.data.map((model) => model.id);
+  };
+
+  return {
+    complete: async (value) => {
+      if (!modelIds) {
+        modelIds = loadModelIds().then(
+          (ids) => {
+            loadedCount = ids.length;
+            return ids;
+          },
+          () => {
+            modelIds = undefined;
+            return [options.defaultModel];
+          },
+        );
+      }
+      const prefix = (value ?? "").toLowerCase();
+      return (await modelIds).filter((id) => id.toLowerCase().startsWith(prefix));
+    },
+    clear: () => {
+      const cleared = loadedCount;
+      modelIds = undefined;
+      loadedCount = 0;
+      return cleared;
+    },
+  };
+}
+
+function completableEnum<const T extends readonly [string, ...string[]]>(values: T) {
+  return completable(z.enum(values).optional(), (value) =>
+    values.filter((candidate) => candidate.startsWith(value ?? "")),
+  );
+}
+
+function userPrompt(text: string): { messages: [{ role: "user"; content: { type: "text"; text: string } }] } {
+  return { messages: [{ role: "user", content: { type: "text", text } }] };
+}
+
+function registerTools(
+  server: McpServer,
+  options: DeepSeekMcpServerOptions,
+  modelCompleter: ModelCompleter,
+): Map<string, RegisteredTool> {
+  const experimentalV4Enabled = options.experimentalV4Enabled ?? false;
+  const modelCapabilities = options.modelCapabilities ?? new ModelCapabilityRegistry();
+  const registeredTools = new Map<string, RegisteredTool>();
+  const inputSchemas = new Map<string, z.ZodType>();
+  const toolLoopRuns = new ToolLoopRuns();
+  const usageTracker = new UsageTracker();
+
+  // Clients choose a threshold with logging/setLevel; the SDK drops messages below it per session.
+  const logEvent = (
+    extra: RequestHandlerExtra<ServerRequest, ServerNotification>,
+    level: LoggingLevel,
+    message: string,
+    data: Record<string, unknown> = {},
+  ): void => {
+    void server
+      .sendLoggingMessage({ level, logger: "deepseek-mcp-server", data: { message, ...data } }, extra.sessionId)
+      .catch(() => undefined);
+  };
+
+  const { shutdown, clientRateLimiter } = options;
+  const serverKey = `session:${randomUUID()}`;
+  const registerTool: McpServer["registerTool"] = (name, config, callback) => {
+    const handler = callback as (...args: unknown[]) => CallToolResult | Promise<CallToolResult>;
+    const guarded = (...args: unknown[]) => {
+      const run = async () => handler(...args);
+      const extra = args[args.length - 1] as RequestHandlerExtra<ServerRequest, ServerNotification>;
+      const limited = clientRateLimiter ? () => clientRateLimiter.run(clientKey(extra, serverKey), run) : run;
+      return (shutdown ? shutdown.track(limited) : limited()).catch((error: unknown) => {
+        if (error instanceof ClientRateLimitedError) {
+          logEvent(extra, "warning", "Per-client rate limit hit", { tool: name, reason: error.message });
         }
+        return toolError(error);
+      });
+    };
+    const wrapped = shutdown || clientRateLimiter ? guarded : handler;
+    // Clients validate structuredContent against outputSchema even on errors, so those tools report errors as text.
+    // The SDK requires structuredContent on their successful results; other tools send text alone to clients whose
+    // protocol predates structured results.
+    const adapted = async (...args: unknown[]) => {
+      const result = await wrapped(...args);
+      const extra = args[args.length - 1] as RequestHandlerExtra<ServerRequest, ServerNotification>;
+      const textOnly = config.outputSchema
+        ? result.isError
+        : !supportsStructuredContent(clientProtocolVersion(extra));
+      if (textOnly) {
+        delete result.structuredContent;
+      }
+      return result;
+    };
+    const tool = server.registerTool(name, config, adapted as typeof callback);
+    registeredTools.set(name, tool);
+    if (config.inputSchema instanceof z.ZodType) {
+      inputSchemas.set(name, config.inputSchema);
+    }
+    return tool;
+  };
 
-        const newMessages = normalizeInputMessages(normalizedInput);
-        const existingHistory = conversationId ? options.conversations.get(conversationId) : [];
-        const outboundMessages = conversationId ? [...existingHistory, ...newMessages] : newMessages;
+  const retryStatuses = new Set(
+    options.retryStatuses && options.retryStatuses.length > 0 ? options.retryStatuses : DEFAULT_RETRY_STATUS_CODES,
+  );
+  const toolError = (error: unknown) => makeToolErrorResult(error, options.errorFormat ?? "text", retryStatuses);
 
-        const request = buildChatCompletionRequest(normalizedInput, outboundMessages, options.defaultModel);
-        const result = await options.client.createChatCompletion(request);
+  // Every upstream call is charged here, so the session's usage_summary and the monthly budget stay in step.
+  const chargeBudget = async (model: string, usage: DeepSeekUsage | undefined, fallback = false): Promise<void> => {
+    const costUsd = estimateCostUsd(model, usage);
+    usageTracker.record(model, usage, costUsd, fallback);
+    await options.budget?.record(costUsd);
+  };
 
-        const choice = result.response.choices[0];
-        const assistantMessage = choice?.message;
+  // MCP requires progress to increase with every notification, so each request keeps one counter that the rate-limit
+  // wait and the upstream timer both advance; what is being waited on goes in the message.
+  const progressCounters = new WeakMap<object, number>();
+  const sendProgress = async (
+    extra: RequestHandlerExtra<ServerRequest, ServerNotification>,
+    message: string,
+  ): Promise<void> => {
+    const progressToken = extra._meta?.progressToken;
+    if (progressToken === undefined) {
+      return;
+    }
 
-        if (conversationId && assistantMessage) {
-          options.conversations.set(conversationId, [
-            ...outboundMessages,
-            {
-              role: "assistant",
-              content: assistantMessage.content,
-              reasoning_content: assistantMessage.reasoning_content,
-              tool_calls: assistantMessage.tool_calls,
-            },
-          ]);
+    const progress = (progressCounters.get(extra) ?? 0) + 1;
+    progressCounters.set(extra, progress);
+    await extra.sendNotification({ method: "notifications/progress", params: { progressToken, progress, message } });
+  };
+
+  const waitForRateLimit = async (
+    extra: RequestHandlerExtra<ServerRequest, ServerNotification>,
+    weight = 1,
+  ): Promise<void> => {
+    await options.rateLimiter?.acquire(weight, async (waitMs) => {
+      logEvent(extra, "notice", "Client-side rate limit reached; waiting", { wait_ms: waitMs });
+      if (waitMs < (options.rateLimitNotifyThresholdMs ?? 1000)) {
+        return;
+      }
+
+      await sendProgress(extra, `waiting for rate limit, ~${waitMs} ms`);
+    });
+  };
+
+  // Every tool that forwards caller text upstream screens it here first, so no tool bypasses DEEPSEEK_MODERATION_URL.
+  const moderatePrompt = async (
+    extra: RequestHandlerExtra<ServerRequest, ServerNotification>,
+    texts: readonly (string | undefined)[],
+  ): Promise<ModerationResult | undefined> => {
+    const input = texts.filter((text): text is string => Boolean(text)).join("\n\n");
+    if (!options.moderator || !input) {
+      return undefined;
+    }
+
+    const moderation = await options.moderator.assertAllowed(input);
+    if (moderation.failedOpen) {
+      logEvent(extra, "warning", "Moderation service unavailable; request allowed (fail-open)");
+    }
+    return moderation;
+  };
+
+  // Asks the user via elicitation before sending a request estimated above the configured token/cost thresholds.
+  // Clients that don't advertise elicitation get the request sent as before.
+  const confirmExpensiveRequest = async (
+    extra: RequestHandlerExtra<ServerRequest, ServerNotification>,
+    ...requests: DeepSeekChatCompletionRequest[]
+  ): Promise<void> => {
+    const estimate = estimateRequestsCost(requests);
+    const thresholds = { tokens: options.confirmAboveTokens, usd: options.confirmAboveUsd };
+    if (!exceedsConfirmationThreshold(estimate, thresholds) || !server.server.getClientCapabilities()?.elicitation) {
+      return;
+    }
+
+    const summary = `~${estimate.totalTokens} tokens, ~$${estimate.costUsd.toFixed(4)}`;
+    const subject =
+      requests.length === 1 ? `This ${String(requests[0]?.model)} request is` : `These ${requests.length} requests are`;
+    const answer = await server.server.elicitInput(
+      {
+        message: `${subject} estimated at ${summary} (${estimate.promptTokens} prompt + ${estimate.maxOutputTokens} max output). Send to DeepSeek?`,
+        requestedSchema: {
+          type: "object",
+          properties: {
+            confirm: { type: "boolean", title: "Send request", default: true },
+          },
+          required: ["confirm"],
+        },
+      },
+      { signal: extra.signal },
+    );
+    if (answer.action !== "accept" || answer.content?.confirm === false) {
+      const outcome = answer.action === "cancel" ? "cancelled" : "declined";
+      throw new ConfirmationDeclinedError(`request not sent (${summary}); the user ${outcome} the confirmation`);
+    }
+  };
+
+  // attach_files may read from DEEPSEEK_ATTACH_DIRS, plus over stdio any file:// roots the client advertises. A network
+  // client's roots name paths on its own machine, so they would grant it reads of arbitrary server directories.
+  const attachmentRoots = async (): Promise<string[]> => {
+    const roots = [...(options.attachDirs ?? [])];
+    if (options.trustedTransport && server.server.getClientCapabilities()?.roots) {
+      const listed = await server.server.listRoots();
+      for (const root of listed.roots) {
+        if (root.uri.startsWith("file://")) {
+          roots.push(fileURLToPath(root.uri));
         }
+      }
+    }
+    return roots;
+  };
 
-        const responseText = assistantMessage?.content ?? "";
-        const reasoning = assistantMessage?.reasoning_content;
-        const toolCalls = assistantMessage?.tool_calls ?? [];
-        const includeRawResponse = normalizedInput.include_raw_response;
+  // chat_completion and resolve_request build the outgoing body here, so a resolved request is exactly what would
+  // be sent. History is left out when clear_conversation is set; the caller clears the store itself. A continued
+  // conversation may be sent as stored, with no new message.
+  const resolveChatCompletionRequest = async (
+    input: ChatCompletionToolInput,
+    conversations: ConversationStore,
+    continuing = false,
+  ) => {
+    const conversationId = input.conversation_id;
+    const attachments = input.attach_files?.length
+      ? await attachFiles(input.attach_files, await attachmentRoots(), {
+          maxFileBytes: options.attachMaxFileBytes,
+          maxTotalBytes: options.attachMaxTotalBytes,
+        })
+      : undefined;
+    const inputMessages = continuing && !input.message && !input.messages ? [] : normalizeInputMessages(input);
+    const newMessages = attachments ? insertAttachmentMessage(inputMessages, attachments.text) : inputMessages;
+    enforceMaxMessages(newMessages, options.maxMessages);
+    const existingHistory = conversationId && !input.clear_conversation ? conversations.get(conversationId) : [];
+    const outboundMessages = [...existingHistory, ...newMessages];
+
+    const weightedModel = input.model === undefined ? options.modelSelector?.select() : undefined;
+    const request = buildChatCompletionRequest(
+      input,
+      outboundMessages,
+      weightedModel ?? options.defaultModel,
+      options.sanitizeExtraBody !== false,
+    );
+    if (options.mergeConsecutiveMessages) {
+      request.messages = mergeConsecutiveMessages(request.messages);
+    }
+    const tokenLimitWarning = applyTokenParam(request, options.tokenParam ?? "max_tokens");
+    const strippedParameters = stripUnsupportedParameters(request, modelCapabilities.get(String(request.model)));
 
-        const summary = [
-          result.fallback
-            ? `Fallback used: ${result.fallback.fromModel} -> ${result.fallback.toModel}`
-            : undefined,
-          responseText || "(no assistant content returned)",
-          reasoning ? "\nReasoning:\n" + reasoning : undefined,
-          toolCalls.length > 0 ? "\nTool calls returned by model: " + JSON.stringify(toolCalls, null, 2) : undefined,
-        ]
-          .filter(Boolean)
-          .join("\n");
+    return {
+      request,
+      newMessages,
+      outboundMessages,
+      attachments,
+      weightedModel,
+      tokenLimitWarning,
+      strippedParameters,
+    };
+  };
 
-        const structuredContent: Record<string, unknown> = {
-          model: result.response.model,
-          conversation_id: conversationId ?? null,
-          response_text: responseText,
-          reasoning_content: reasoning ?? null,
-          tool_calls: toolCalls,
-          finish_reason: choice?.finish_reason ?? null,
-          usage: result.response.usage ?? null,
-          fallback: result.fallback ?? null,
-          stream_chunk_count: result.streamChunkCount ?? null,
-        };
+  // Keyless mode (DEEPSEEK_SAMPLING_FALLBACK=1): chat_completion is answered by the client's model via sampling.
+  const sampleChatCompletion = async (
+    extra: RequestHandlerExtra<ServerRequest, ServerNotification>,
+    request: DeepSeekChatCompletionRequest,
+  ): Promise<ChatCompletionExecutionResult> => {
+    if (!server.server.getClientCapabilities()?.sampling) {
+      throw new DeepSeekApiError("No DeepSeek API key configured and the client does not support MCP sampling", {
+        code: "no_api_key",
+        retryable: false,
+      });
+    }
 
-        if (includeRawResponse) {
-          structuredContent.raw_response = result.response;
+    logEvent(extra, "info", "No DeepSeek API key; forwarding chat_completion to the client via sampling", {
+      model: String(request.model),
+    });
+    return fromSamplingResult(await server.server.createMessage(toSamplingRequest(request), { signal: extra.signal }));
+  };
+
+  // Reports elapsed time (and streamed tokens, when streaming) while an upstream call is in flight.
+  const startUpstreamProgress = (
+    extra: RequestHandlerExtra<ServerRequest, ServerNotification>,
+    model: string,
+  ): { onStreamProgress?: StreamProgressCallback; stop: () => void } => {
+    const progressToken = extra._meta?.progressToken;
+    const intervalMs = options.progressIntervalMs ?? 5000;
+    if (progressToken === undefined || intervalMs <= 0) {
+      return { stop: () => undefined };
+    }
+
+    const startedAt = Date.now();
+    let receivedTokens: number | undefined;
+    const timer = setInterval(() => {
+      const elapsedMs = Date.now() - startedAt;
+      const tokens = receivedTokens !== undefined ? `, ~${receivedTokens} tokens received` : "";
+      void sendProgress(extra, `waiting on ${model}: ${Math.round(elapsedMs / 1000)} s elapsed${tokens}`).catch(
+        () => undefined,
+      );
+    }, intervalMs);
+
+    return {
+      onStreamProgress: (tokens) => {
+        receivedTokens = tokens;
+      },
+      stop: () => clearInterval(timer),
+    };
+  };
+
+  // The chat_completion pipeline; continue_conversation sends stored conversations through it too. When continuing,
+  // the turn may add no message and the reply is not stored if the conversation was deleted during the call.
+  const runChatCompletion = async (
+    normalizedInput: ChatCompletionToolInput,
+    extra: RequestHandlerExtra<ServerRequest, ServerNotification>,
+    continuing = false,
+  ): Promise<CallToolResult> => {
+    const client = requestClient(options, extra);
+    const conversations = conversationsFor(options, extra);
+    await options.budget?.assertAvailable();
+
+    const conversationId = normalizedInput.conversation_id;
+    const {
+      request,
+      newMessages,
+      outboundMessages,
+      attachments,
+      weightedModel,
+      tokenLimitWarning,
+      strippedParameters,
+    } = await resolveChatCompletionRequest(normalizedInput, conversations, continuing);
+    if (conversationId && normalizedInput.clear_conversation) {
+      conversations.clear(conversationId);
+    }
+
+    const moderation = await moderatePrompt(extra, [userMessageText(newMessages)]);
+    const useSampling = options.samplingFallback === true && !client.hasApiKey;
+    // The /completions fallback only runs on an empty reply and auto-continue only on a non-empty one, so at most
+    // one of them follows the first request.
+    const followUps = useSampling
+      ? 0
+      : Math.max(options.chatToCompletionFallback ? 1 : 0, options.autoContinueLimit ?? 0);
+    await confirmExpensiveRequest(extra, ...Array.from({ length: followUps + 1 }, () => request));
+
+    if (!useSampling) {
+      await waitForRateLimit(extra);
+    }
+    const progress = startUpstreamProgress(extra, String(request.model));
+    const result = await (
+      useSampling
+        ? sampleChatCompletion(extra, request)
+        : client.createChatCompletion(request, {
+            disableFallback: normalizedInput.disable_fallback,
+            streamSoftMaxTokens: normalizedInput.stream_soft_max_tokens,
+            onStreamProgress: progress.onStreamProgress,
+            signal: extra.signal,
+            onRetry: ({ attempt, delayMs, status }) =>
+              logEvent(extra, "warning", `DeepSeek returned HTTP ${status}; retrying`, {
+                attempt,
+                delay_ms: delayMs,
+              }),
+          })
+    ).finally(progress.stop);
+    if (result.fallback) {
+      logEvent(extra, "warning", "Fallback model used", {
+        from_model: result.fallback.fromModel,
+        to_model: result.fallback.toModel,
+        reason: result.fallback.reason,
+      });
+    }
+    await chargeBudget(result.response.model, result.response.usage, result.fallback !== undefined);
+
+    const choice = result.response.choices[0];
+    const assistantMessage = choice?.message;
+
+    // Both follow-ups below call DeepSeek directly, which sampling mode has no key for.
+    let completionFallbackText: string | undefined;
+    if (
+      !useSampling &&
+      options.chatToCompletionFallback &&
+      !assistantMessage?.content &&
+      (assistantMessage?.tool_calls ?? []).length === 0
+    ) {
+      const completionRequest: DeepSeekCompletionRequest = {
+        model: String(request.model),
+        prompt: flattenMessagesToPrompt(outboundMessages),
+      };
+      const maxTokens = request.max_tokens ?? request.max_completion_tokens;
+      if (maxTokens !== undefined) {
+        completionRequest.max_tokens = maxTokens;
+      }
+
+      const completionResult = await client.createCompletion(completionRequest, { signal: extra.signal });
+      await chargeBudget(completionResult.response.model, completionResult.response.usage);
+      completionFallbackText = completionResult.response.choices[0]?.text ?? "";
+    }
+
+    let finishReason = choice?.finish_reason ?? null;
+    let continuedText = assistantMessage?.content ?? "";
+    const continuationUsages: (DeepSeekUsage | undefined)[] = [];
+    if (
+      !useSampling &&
+      options.autoContinueLimit !== undefined &&
+      (assistantMessage?.tool_calls ?? []).length === 0
+    ) {
+      while (finishReason === "length" && continuedText && continuationUsages.length < options.autoContinueLimit) {
+        if (extra.signal.aborted) {
+          break;
         }
 
-        return {
-          content: [{ type: "text", text: summary }],
-          structuredContent,
-        };
+        await waitForRateLimit(extra);
+        const continuation = await client.createChatPrefixCompletion(
+          {
+            ...request,
+            messages: [...request.messages, { role: "assistant", content: continuedText, prefix: true }],
+          },
+          { signal: extra.signal },
+        );
+        await chargeBudget(continuation.response.model, continuation.response.usage);
+        continuationUsages.push(continuation.response.usage);
+
+        const continuationChoice = continuation.response.choices[0];
+        continuedText += continuationChoice?.message.content ?? "";
+        finishReason = continuationChoice?.finish_reason ?? null;
+      }
+    }
+    const continuations = continuationUsages.length;
+
+    // Only this turn is appended, so messages stored by concurrent calls are kept.
+    const deletedDuringCall = continuing && conversationId !== undefined && !conversations.has(conversationId);
+    if (conversationId && assistantMessage && !deletedDuringCall) {
+      conversations.append(conversationId, [
+        ...newMessages,
+        {
+          role: "assistant",
+          content: completionFallbackText ?? (continuations > 0 ? continuedText : assistantMessage.content),
+          reasoning_content: assistantMessage.reasoning_content,
+          tool_calls: assistantMessage.tool_calls,
+        },
+      ]);
+      conversations.recordUsage(conversationId, result.response.usage);
+      for (const usage of continuationUsages) {
+        conversations.recordUsage(conversationId, usage);
+      }
+    }
+
+    const responseText = normalizeOutputText(
+      completionFallbackText ?? (continuations > 0 ? continuedText : (assistantMessage?.content ?? "")),
+      { trim: options.trimOutput, stripCodeFences: options.stripCodeFences },
+    );
+    const reasoning = assistantMessage?.reasoning_content;
+    const toolCalls = assistantMessage?.tool_calls ?? [];
+    const includeRawResponse = normalizedInput.include_raw_response;
+    const timing = options.timing && result.timing ? formatTiming(result.timing) : undefined;
+
+    const summary = [
+      result.fallback
+        ? `Fallback used: ${result.fallback.fromModel} -> ${result.fallback.toModel}`
+        : undefined,
+      weightedModel ? `Model selected by weight: ${weightedModel}` : undefined,
+      tokenLimitWarning ? `Warning: ${tokenLimitWarning}` : undefined,
+      attachments?.files.some((file) => file.truncated)
+        ? `Warning: truncated attachments: ${attachments.files
+            .filter((file) => file.truncated)
+            .map((file) => `${file.path} (${file.included_bytes}/${file.bytes} bytes)`)
+            .join(", ")}`
+        : undefined,
+      moderation?.failedOpen ? "Warning: moderation service unavailable; request allowed (fail-open)" : undefined,
+      continuations > 0
+        ? `Auto-continued ${continuations} time(s) after finish_reason=length (final finish_reason: ${finishReason ?? "none"})`
+        : undefined,
+      completionFallbackText !== undefined
+        ? "Chat completion returned empty content; used /completions fallback with a flattened prompt"
+        : undefined,
+      deletedDuringCall ? `Warning: conversation "${conversationId}" was deleted during the call; reply not stored` : undefined,
+      result.loopGuard
+        ? `Stream aborted by loop guard: repeated fragment "${result.loopGuard.repeatedFragment}"`
+        : undefined,
+      result.softCapped ? "Stream stopped at the soft output token cap" : undefined,
+      result.streamCompletion && !result.streamCompletion.completed && !result.softCapped && !result.loopGuard
+        ? `Warning: output may be incomplete (${result.streamCompletion.reason})`
+        : undefined,
+      strippedParameters.length > 0
+        ? `Stripped parameters unsupported by ${String(request.model)}: ${strippedParameters.join(", ")}`
+        : undefined,
+      responseText || (toolCalls.length > 0 ? undefined : "(no assistant content returned)"),
+      reasoning ? "\nReasoning:\n" + reasoning : undefined,
+      toolCalls.length > 0 ? (responseText ? "\n" : "") + "Tool calls:\n" + renderToolCalls(toolCalls) : undefined,
+      includeRawResponse
+        ? "\nRaw response:\n" + truncateText(
+            JSON.stringify(result.response, null, 2),
+            options.summaryMaxChars ?? DEFAULT_SUMMARY_MAX_CHARS,
+          )
+        : undefined,
+      timing ? "\nTiming:\n" + JSON.stringify(timing) : undefined,
+    ]
+      .filter(Boolean)
+      .join("\n");
+
+    const structuredContent: Record<string, unknown> = {
+      id: result.response.id,
+      model: result.response.model,
+      conversation_id: conversationId ?? null,
+      response_text: responseText,
+      reasoning_content: reasoning ?? null,
+      tool_calls: toolCalls,
+      finish_reason: finishReason,
+      usage: sumUsage([result.response.usage, ...continuationUsages]) ?? null,
+      fallback: result.fallback ?? null,
+      auto_continuations: continuations,
+      stripped_parameters: strippedParameters,
+      weighted_model_selection: weightedModel ?? null,
+      token_limit_warning: tokenLimitWarning ?? null,
+      attachments: attachments?.files ?? [],
+      moderation: moderation ? { flagged: false, failed_open: moderation.failedOpen } : null,
+      completion_fallback: completionFallbackText !== undefined,
+      loop_guard: result.loopGuard
+        ? { aborted: true, repeated_fragment: result.loopGuard.repeatedFragment }
+        : null,
+      soft_capped: result.softCapped === true,
+      stream_chunk_count: result.streamChunkCount ?? null,
+      stream_completion: result.streamCompletion ?? null,
+      choices: result.response.choices.map((entry) => ({
+        index: entry.index,
+        finish_reason: entry.finish_reason,
+        content: entry.message.content,
+        reasoning_content: entry.message.reasoning_content ?? null,
+        tool_calls: entry.message.tool_calls ?? [],
+        logprobs: entry.logprobs ?? null,
+      })),
+    };
+
+    if (includeRawResponse) {
+      structuredContent.raw_response = result.response;
+    }
+
+    if (timing) {
+      structuredContent.timing = timing;
+    }
+
+    return {
+      content: [{ type: "text", text: summary }],
+      structuredContent,
+    };
+  };
+
+  registerTool(
+    "chat_completion",
+    {
+      description:
+        "Primary DeepSeek chat tool for single-turn and multi-turn generation. Provide either `message` (simple single user turn) or `messages` (full chat history); if both are provided, `messages` is used. Use `conversation_id` to persist context across calls and `clear_conversation=true` to reset stored state before sending the next turn. Set `disable_fallback=true` to skip the reasoner-to-chat fallback for this call only. With `stream=true`, `stream_soft_max_tokens` stops reading once the estimated output exceeds that many tokens. `attach_files` inlines local text files (read from the client's MCP roots or DEEPSEEK_ATTACH_DIRS, size-capped) as fenced blocks before the newest message. Set `include_raw_response=true` only for debugging, because it returns the full provider payload and increases token usage.",
+      inputSchema: chatCompletionToolInputSchema,
+      outputSchema: chatCompletionToolOutputSchema,
+      // clear_conversation drops stored history before the call.
+      annotations: { ...UPSTREAM_CALL_ANNOTATIONS, destructiveHint: true },
+    },
+    async (input, extra) => {
+      try {
+        return await runChatCompletion(input as ChatCompletionToolInput, extra);
       } catch (error) {
-        return makeToolErrorResult(error);
+        return toolError(error);
       }
     },
   );
 
-  server.registerTool(
-    "completion",
+  registerTool(
+    "chat_prefix_completion",
     {
       description:
-        "DeepSeek text/FIM completion tool for prompt-completion workflows. Use this when you need raw completion text instead of chat message formatting. Supports the same generation controls as the provider completion endpoint and can aggregate streamed output. Set `include_raw_response=true` only when you need the full provider payload for debugging.",
-      inputSchema: completionToolInputSchema,
+        "DeepSeek beta chat prefix completion. Provide the conversation (`message` or `messages`) plus a required assistant `prefix`; the model continues from that prefix. Requests are always sent to the beta base URL with the prefix appended as an assistant message (`prefix: true`). The returned text is only the continuation; set `include_prefix=true` to get the prefix merged with it.",
+      inputSchema: chatPrefixCompletionToolInputSchema,
+      annotations: UPSTREAM_CALL_ANNOTATIONS,
     },
-    async (input) => {
+    async (input, extra) => {
       try {
-        const normalizedInput = input as CompletionToolInput;
-        const request = buildCompletionRequest(normalizedInput, options.defaultModel);
-        const result = await options.client.createCompletion(request);
+        const normalizedInput = input as ChatPrefixCompletionToolInput;
+        await options.budget?.assertAvailable();
+
+        const request = buildChatPrefixCompletionRequest(
+          normalizedInput,
+          options.defaultModel,
+          options.sanitizeExtraBody !== false,
+        );
+        await moderatePrompt(extra, [userMessageText(request.messages), normalizedInput.prefix]);
+        await confirmExpensiveRequest(extra, request);
+        await waitForRateLimit(extra);
+        const result = await requestClient(options, extra).createChatPrefixCompletion(request, { signal: extra.signal });
+        await chargeBudget(result.response.model, result.response.usage);
+
         const choice = result.response.choices[0];
-        const includeRawResponse = normalizedInput.include_raw_response;
+        const continuation = choice?.message?.content ?? "";
+        const mergedText = normalizedInput.include_prefix ? `${normalizedInput.prefix}${continuation}` : continuation;
 
         const structuredContent: Record<string, unknown> = {
           model: result.response.model,
-          text: choice?.text ?? "",
+          prefix: normalizedInput.prefix,
+          continuation,
+          text: mergedText,
           finish_reason: choice?.finish_reason ?? null,
           usage: result.response.usage ?? null,
           stream_chunk_count: result.streamChunkCount ?? null,
+          stream_completion: result.streamCompletion ?? null,
         };
 
-        if (includeRawResponse) {
+        if (normalizedInput.include_raw_response) {
           structuredContent.raw_response = result.response;
         }
 
         return {
-          content: [
-            {
-              type: "text",
-              text: choice?.text || "(no completion text returned)",
-            },
-          ],
+          content: [{ type: "text", text: mergedText }],
           structuredContent,
         };
       } catch (error) {
-        return makeToolErrorResult(error);
+        return toolError(error);
       }
     },
   );
 
-  server.registerTool(
+  registerTool(
+    "json_chat",
+    {
+      description:
+        "Chat completion that must answer with JSON matching a caller-supplied JSON Schema (`schema`). The request uses DeepSeek JSON mode (`response_format: json_object`) with the schema in a system message; replies that are not valid JSON or fail schema validation are retried with a corrective message up to `max_retries` times. Returns the parsed object as `data`, or a `schema_validation_failed` error with the last reply.",
+      inputSchema: jsonChatToolInputSchema,
+      annotations: UPSTREAM_CALL_ANNOTATIONS,
+    },
+    async (input, extra) => {
+      try {
+        const normalizedInput = input as JsonChatToolInput;
+        await options.budget?.assertAvailable();
+        const check = createJsonOutputChecker(normalizedInput.schema);
+        await moderatePrompt(extra, [userMessageText(normalizedInput.messages as DeepSeekChatMessage[])]);
+
+        const messages: DeepSeekChatMessage[] = [
+          { role: "system", content: jsonSchemaSystemPrompt(normalizedInput.schema) },
+          ...(normalizedInput.messages as DeepSeekChatMessage[]),
+        ];
+        const baseRequest: DeepSeekChatCompletionRequest = {
+          model: normalizedInput.model ?? options.defaultModel,
+          messages,
+          response_format: { type: "json_object" },
+        };
+        const optionalFields: (keyof JsonChatToolInput)[] = ["max_tokens", "temperature", "top_p"];
+        const requestRecord = baseRequest as Record<string, unknown>;
+        for (const field of optionalFields) {
+          const value = normalizedInput[field];
+          if (value !== undefined) {
+            requestRecord[field] = value;
+          }
+        }
+        stripUnsupportedParameters(baseRequest, modelCapabilities.get(String(baseRequest.model)));
+        const maxAttempts = normalizedInput.max_retries + 1;
+        await confirmExpensiveRequest(extra, ...Array.from({ length: maxAttempts }, () => baseRequest));
+
+        const usages: unknown[] = [];
+        let lastOutput = "";
+        let lastError = "";
+
+        for (let attempt = 1; attempt <= maxAttempts; attempt += 1) {
+          await waitForRateLimit(extra);
+          const request: DeepSeekChatCompletionRequest = { ...baseRequest, messages: [...messages] };
+          const result = await requestClient(options, extra).createChatCompletion(request, { signal: extra.signal });
+          await chargeBudget(result.response.model, result.response.usage, result.fallback !== undefined);
+          usages.push(result.response.usage ?? null);
+
+          const choice = result.response.choices[0];
+          lastOutput = choice?.message?.content ?? "";
+          const outcome = check(lastOutput);
+          if (outcome.valid) {
+            return {
+              content: [{ type: "text", text: JSON.stringify(outcome.data, null, 2) }],
+              structuredContent: {
+                model: result.response.model,
+                data: outcome.data,
+                attempts: attempt,
+                finish_reason: choice?.finish_reason ?? null,
+                usage: usages,
+              },
+            };
+          }
+
+          lastError = outcome.error;
+          logEvent(extra, "warning", "json_chat reply failed validation", { attempt, error: lastError });
+          messages.push(
+            { role: "assistant", content: lastOutput },
+            { role: "user", content: jsonCorrectionPrompt(lastError) },
+          );
+        }
+
+        throw new JsonOutputValidationError(`${lastError} after ${maxAttempts} attempt(s)`, maxAttempts, lastOutput);
+      } catch (error) {
+        return toolError(error);
+      }
+    },
+  );
+
+  registerTool(
+    "function_calling",
+    {
+      description:
+        "Run a DeepSeek function-calling loop with tools the caller executes. Start with `messages` and `tools` (OpenAI-style function definitions): when the model requests tool calls, the result has `status: requires_tool_results`, a `run_id` and the `tool_calls`; run them (e.g. with your own MCP tools) and call again with `run_id` and `tool_results` (`tool_call_id` + `content`). The loop ends with `status: completed` and the final answer, or `max_depth_reached` after `max_depth` model turns; every result includes a `trace` of the intermediate calls.",
+      inputSchema: functionCallingToolInputSchema,
+      annotations: UPSTREAM_CALL_ANNOTATIONS,
+    },
+    async (input, extra) => {
+      try {
+        const normalizedInput = input as FunctionCallingToolInput;
+        await options.budget?.assertAvailable();
+        await moderatePrompt(extra, [
+          userMessageText((normalizedInput.messages ?? []) as DeepSeekChatMessage[]),
+          ...(normalizedInput.tool_results ?? []).map((result) => result.content),
+        ]);
+
+        let runId = normalizedInput.run_id;
+        let run: ToolLoopRun;
+        if (runId) {
+          const existing = toolLoopRuns.get(runId);
+          if (!existing) {
+            throw new Error(`Unknown or finished run_id: ${runId}`);
+          }
+          run = applyToolResults(existing, normalizedInput.tool_results ?? []);
+        } else {
+          const generation: Record<string, unknown> = {};
+          const optionalFields: (keyof FunctionCallingToolInput)[] = [
+            "tool_choice",
+            "max_tokens",
+            "temperature",
+            "top_p",
+          ];
+          for (const field of optionalFields) {
+            if (normalizedInput[field] !== undefined) {
+              generation[field] = normalizedInput[field];
+            }
+          }
+
+          run = {
+            model: normalizedInput.model ?? options.defaultModel,
+            tools: (normalizedInput.tools ?? []) as DeepSeekToolDefinition[],
+            messages: [...((normalizedInput.messages ?? []) as DeepSeekChatMessage[])],
+            maxDepth: normalizedInput.max_depth,
+            generation,
+            trace: [],
+            pendingCallIds: [],
+          };
+        }
+
+        await waitForRateLimit(extra);
+        const request: DeepSeekChatCompletionRequest = {
+          model: run.model,
+          messages: [...run.messages],
+          tools: run.tools,
+        };
+        Object.assign(request, run.generation);
+        stripUnsupportedParameters(request, modelCapabilities.get(run.model));
+        await confirmExpensiveRequest(extra, request);
+        const result = await requestClient(options, extra).createChatCompletion(request, { signal: extra.signal });
+        await chargeBudget(result.response.model, result.response.usage, result.fallback !== undefined);
+
+        const choice = result.response.choices[0];
+        const depth = run.trace.length + 1;
+        const toolCalls = (choice?.message?.tool_calls ?? []).map((call, index) => ({
+          ...call,
+          id: call.id ?? `call_${depth}_${index}`,
+        }));
+        const content = typeof choice?.message?.content === "string" ? choice.message.content : null;
+        run.messages.push({ role: "assistant", content, ...(toolCalls.length > 0 ? { tool_calls: toolCalls } : {}) });
+        run.trace.push({ depth, content, tool_calls: toolCalls, tool_results: [] });
+
+        const status =
+          toolCalls.length === 0 ? "completed" : depth >= run.maxDepth ? "max_depth_reached" : "requires_tool_results";
+        if (status === "requires_tool_results") {
+          run.pendingCallIds = toolCalls.map((call) => call.id);
+          runId = toolLoopRuns.save(run, runId);
+        } else if (runId) {
+          toolLoopRuns.delete(runId);
+        }
+
+        const structuredContent: Record<string, unknown> = {
+          status,
+          run_id: status === "requires_tool_results" ? runId : null,
+          model: result.response.model,
+          answer: status === "completed" ? (content ?? "") : null,
+          tool_calls: status === "completed" ? [] : toolCalls,
+          depth,
+          max_depth: run.maxDepth,
+          finish_reason: choice?.finish_reason ?? null,
+          usage: result.response.usage ?? null,
+          trace: run.trace,
+        };
+
+        const describeCall = (call: (typeof toolCalls)[number]) =>
+          `- ${call.function.name}(${call.function.arguments}) [tool_call_id ${call.id}]`;
+        const text =
+          status === "completed"
+            ? content || "(no assistant content returned)"
+            : [
+                status === "requires_tool_results"
+                  ? `Run these tools, then call function_calling with run_id ${runId} and their tool_results:`
+                  : `Stopped after max_depth (${run.maxDepth}) model turns; the model still requested:`,
+                ...toolCalls.map(describeCall),
+              ].join("\n");
+
+        return {
+          content: [{ type: "text", text }],
+          structuredContent,
+        };
+      } catch (error) {
+        return toolError(error);
+      }
+    },
+  );
+
+  registerTool(
+    "summarize_and_continue",
+    {
+      description:
+        "Compact a long conversation and continue it. All but the last `keep_last` non-system turns are summarized by the model into a single system note (leading system messages are preserved), then the chat completion runs on the compacted history. Returns the assistant reply plus the generated summary so callers can store the compacted history.",
+      inputSchema: summarizeAndContinueToolInputSchema,
+      annotations: UPSTREAM_CALL_ANNOTATIONS,
+    },
+    async (input, extra) => {
+      try {
+        const client = requestClient(options, extra);
+        const normalizedInput = input as SummarizeAndContinueToolInput;
+        await options.budget?.assertAvailable();
+        const messages = normalizedInput.messages as DeepSeekChatMessage[];
+        await moderatePrompt(extra, [userMessageText(messages)]);
+
+        const model = normalizedInput.model ?? options.defaultModel;
+
+        const firstNonSystemIndex = messages.findIndex((message) => message.role !== "system");
+        const leadingSystem = firstNonSystemIndex === -1 ? messages : messages.slice(0, firstNonSystemIndex);
+        const conversational = messages.slice(leadingSystem.length);
+        const keepCount = Math.min(normalizedInput.keep_last, conversational.length);
+        const toSummarize = conversational.slice(0, conversational.length - keepCount);
+        const kept = conversational.slice(conversational.length - keepCount);
+
+        let summary: string | null = null;
+        let summaryUsage: unknown = null;
+        let outboundMessages = messages;
+
+        const summaryRequest: DeepSeekChatCompletionRequest | undefined =
+          toSummarize.length > 0
+            ? {
+                model: normalizedInput.summary_model ?? model,
+                messages: [
+                  { role: "system", content: SUMMARY_SYSTEM_PROMPT },
+                  { role: "user", content: renderTranscript(toSummarize) },
+                ],
+              }
+            : undefined;
+        if (summaryRequest && normalizedInput.summary_max_tokens !== undefined) {
+          summaryRequest.max_tokens = normalizedInput.summary_max_tokens;
+        }
+
+        // Until the summary exists the final request carries the full history, which can only overestimate the cost.
+        const request: DeepSeekChatCompletionRequest = {
+          model,
+          messages: outboundMessages,
+        };
+
+        const optionalFields: (keyof SummarizeAndContinueToolInput)[] = ["max_tokens", "temperature", "top_p"];
+        const requestRecord = request as Record<string, unknown>;
+        for (const field of optionalFields) {
+          const value = normalizedInput[field];
+          if (value !== undefined) {
+            requestRecord[field] = value;
+          }
+        }
+
+        stripUnsupportedParameters(request, modelCapabilities.get(String(request.model)));
+        if (summaryRequest) {
+          stripUnsupportedParameters(summaryRequest, modelCapabilities.get(String(summaryRequest.model)));
+        }
+        await confirmExpensiveRequest(extra, ...(summaryRequest ? [summaryRequest, request] : [request]));
+        await waitForRateLimit(extra, summaryRequest ? 2 : 1);
+
+        if (summaryRequest) {
+          const summaryResult = await client.createChatCompletion(summaryRequest, { signal: extra.signal });
+          await chargeBudget(
+            summaryResult.response.model,
+            summaryResult.response.usage,
+            summaryResult.fallback !== undefined,
+          );
+          summary = summaryResult.response.choices[0]?.message?.content ?? "";
+          summaryUsage = summaryResult.response.usage ?? null;
+          outboundMessages = [
+            ...leadingSystem,
+            { role: "system", content: `Summary of earlier conversation:\n${summary}` },
+            ...kept,
+          ];
+          request.messages = outboundMessages;
+        }
+
+        const result = await client.createChatCompletion(request, { signal: extra.signal });
+        await chargeBudget(result.response.model, result.response.usage, result.fallback !== undefined);
+
+        const choice = result.response.choices[0];
+        const responseText = choice?.message?.content ?? "";
+
+        const structuredContent: Record<string, unknown> = {
+          model: result.response.model,
+          response_text: responseText,
+          summary,
+          summarized_message_count: toSummarize.length,
+          kept_message_count: kept.length,
+          compacted_messages: outboundMessages,
+          finish_reason: choice?.finish_reason ?? null,
+          usage: result.response.usage ?? null,
+          summary_usage: summaryUsage,
+          fallback: result.fallback ?? null,
+        };
+
+        if (normalizedInput.include_raw_response) {
+          structuredContent.raw_response = result.response;
+        }
+
+        return {
+          content: [
+            {
+              type: "text",
+              text: [
+                summary !== null
+                  ? `Summarized ${toSummarize.length} earlier message(s):\n${summary}\n`
+                  : "No summarization needed.\n",
+                responseText || "(no assistant content returned)",
+              ].join("\n"),
+            },
+          ],
+          structuredContent,
+        };
+      } catch (error) {
+        return toolError(error);
+      }
+    },
+  );
+
+  registerTool(
+    "compare_models",
+    {
+      description:
+        "Run the same `messages` against two models (`model_a`, `model_b`) concurrently and return each model's reply, latency, and token usage side by side. Reasoner fallback is disabled so each result reflects the requested model. If one model fails, its error is reported while the other result is still returned. Set `include_diff=true` for a line diff of the two answers (`-` lines only in model_a, `+` only in model_b).",
+      inputSchema: compareModelsToolInputSchema,
+      annotations: UPSTREAM_CALL_ANNOTATIONS,
+    },
+    async (input, extra) => {
+      try {
+        const normalizedInput = input as CompareModelsToolInput;
+        await options.budget?.assertAvailable();
+        await moderatePrompt(extra, [userMessageText(normalizedInput.messages as DeepSeekChatMessage[])]);
+
+        const buildRequest = (model: string): DeepSeekChatCompletionRequest => {
+          const request: DeepSeekChatCompletionRequest = {
+            model,
+            messages: normalizedInput.messages as DeepSeekChatMessage[],
+          };
+
+          const optionalFields: (keyof CompareModelsToolInput)[] = ["max_tokens", "temperature", "top_p"];
+          const requestRecord = request as Record<string, unknown>;
+          for (const field of optionalFields) {
+            const value = normalizedInput[field];
+            if (value !== undefined) {
+              requestRecord[field] = value;
+            }
+          }
+
+          stripUnsupportedParameters(request, modelCapabilities.get(model));
+          return request;
+        };
+        const requestA = buildRequest(normalizedInput.model_a);
+        const requestB = buildRequest(normalizedInput.model_b);
+        await confirmExpensiveRequest(extra, requestA, requestB);
+        await waitForRateLimit(extra, 2);
+
+        const runModel = async (request: DeepSeekChatCompletionRequest): Promise<Record<string, unknown>> => {
+          const model = String(request.model);
+          const started = Date.now();
+          try {
+            const result = await requestClient(options, extra).createChatCompletion(request, {
+              disableFallback: true,
+              signal: extra.signal,
+            });
+            const latencyMs = Date.now() - started;
+            await chargeBudget(result.response.model, result.response.usage);
+
+            const choice = result.response.choices[0];
+            return {
+              model,
+              ok: true,
+              content: choice?.message?.content ?? "",
+              finish_reason: choice?.finish_reason ?? null,
+              latency_ms: latencyMs,
+              usage: result.response.usage ?? null,
+            };
+          } catch (error) {
+            const failure = makeToolErrorResult(error, "text", retryStatuses).structuredContent;
+            return {
+              model,
+              ok: false,
+              latency_ms: Date.now() - started,
+              error: {
+                error_type: failure.error_type,
+                status: failure.status,
+                message: failure.message,
+              },
+            };
+          }
+        };
+
+        const [resultA, resultB] = await Promise.all([runModel(requestA), runModel(requestB)]);
+
+        const describe = (result: Record<string, unknown>) =>
+          result.ok
+            ? `=== ${String(result.model)} (${String(result.latency_ms)} ms) ===\n${String(result.content) || "(no assistant content returned)"}`
+            : `=== ${String(result.model)} (failed) ===\n${String((result.error as { message: string }).message)}`;
+
+        const diff =
+          normalizedInput.include_diff && resultA.ok && resultB.ok
+            ? diffLines(String(resultA.content), String(resultB.content))
+            : null;
+
+        return {
+          content: [
+            {
+              type: "text",
+              text: [
+                describe(resultA),
+                describe(resultB),
+                diff
+                  ? `=== diff (${diff.removed} removed, ${diff.added} added${diff.approximate ? ", compared line by line" : ""}) ===\n${diff.text}`
+                  : undefined,
+              ]
+                .filter(Boolean)
+                .join("\n\n"),
+            },
+          ],
+          structuredContent: {
+            model_a: resultA,
+            model_b: resultB,
+            diff,
+          },
+        };
+      } catch (error) {
+        return toolError(error);
+      }
+    },
+  );
+
+  if (options.enableBatch) {
+    registerTool(
+      "batch_chat_completion",
+      {
+        description:
+          "Experimental fan-out: run up to 50 independent chat requests (`requests[]`, each with its own `messages` and optional `model`) with at most `concurrency` in flight, and return the results in input order. A failing item reports its error without affecting the others. No conversation history is stored. Only registered when DEEPSEEK_ENABLE_BATCH=1.",
+        inputSchema: batchChatCompletionToolInputSchema,
+        annotations: UPSTREAM_CALL_ANNOTATIONS,
+      },
+      async (input, extra) => {
+        try {
+          const normalizedInput = input as BatchChatCompletionToolInput;
+          await options.budget?.assertAvailable();
+
+          const requests = normalizedInput.requests.map((item) => {
+            const request = buildChatCompletionRequest(
+              item as ChatCompletionToolInput,
+              item.messages as DeepSeekChatMessage[],
+              options.defaultModel,
+              options.sanitizeExtraBody !== false,
+            );
+            if (options.mergeConsecutiveMessages) {
+              request.messages = mergeConsecutiveMessages(request.messages);
+            }
+            applyTokenParam(request, options.tokenParam ?? "max_tokens");
+            stripUnsupportedParameters(request, modelCapabilities.get(String(request.model)));
+            return request;
+          });
+          await confirmExpensiveRequest(extra, ...requests);
+
+          const runItem = async (
+            request: DeepSeekChatCompletionRequest,
+            index: number,
+          ): Promise<Record<string, unknown>> => {
+            const started = Date.now();
+            try {
+              // The tool call itself already counted against the per-client limit for the first item.
+              if (index > 0) {
+                clientRateLimiter?.charge(clientKey(extra, serverKey));
+              }
+              await options.budget?.assertAvailable();
+              await moderatePrompt(extra, [userMessageText(request.messages)]);
+              await waitForRateLimit(extra);
+              const result = await requestClient(options, extra).createChatCompletion(request, {
+                signal: extra.signal,
+              });
+              await chargeBudget(result.response.model, result.response.usage, result.fallback !== undefined);
+
+              const choice = result.response.choices[0];
+              return {
+                index,
+                ok: true,
+                model: result.response.model,
+                content: choice?.message?.content ?? "",
+                finish_reason: choice?.finish_reason ?? null,
+                latency_ms: Date.now() - started,
+                usage: result.response.usage ?? null,
+                fallback: result.fallback ?? null,
+              };
+            } catch (error) {
+              const failure = makeToolErrorResult(error, "text", retryStatuses).structuredContent;
+              return {
+                index,
+                ok: false,
+                model: String(request.model),
+                latency_ms: Date.now() - started,
+                error: {
+                  error_type: failure.error_type,
+                  status: failure.status,
+                  message: failure.message,
+                },
+              };
+            }
+          };
+
+          const results = await mapWithConcurrency(
+            requests,
+            normalizedInput.concurrency ?? options.batchConcurrency ?? 4,
+            runItem,
+          );
+          const failed = results.filter((result) => !result.ok).length;
+          const describe = (result: Record<string, unknown>) =>
+            result.ok
+              ? `=== [${String(result.index)}] ${String(result.model)} (${String(result.latency_ms)} ms) ===\n${String(result.content) || "(no assistant content returned)"}`
+              : `=== [${String(result.index)}] ${String(result.model)} (failed) ===\n${String((result.error as { message: string }).message)}`;
+          const summary = `${results.length - failed}/${results.length} request(s) succeeded`;
+
+          return {
+            content: [{ type: "text", text: [summary, ...results.map(describe)].join("\n\n") }],
+            structuredContent: {
+              succeeded: results.length - failed,
+              failed,
+              results,
+            },
+          };
+        } catch (error) {
+          return toolError(error);
+        }
+      },
+    );
+  }
+
+  registerTool(
+    "validate_request",
+    {
+      description:
+        "Pre-flight a chat_completion request without calling DeepSeek. Accepts the same arguments as `chat_completion` (including stored `conversation_id` history) and reports the estimated prompt token count, whether prompt plus requested output fits the model context window, message role/ordering problems, and parameter conflicts.",
+      inputSchema: chatCompletionToolInputSchema,
+      annotations: {
+        readOnlyHint: true,
+      },
+    },
+    async (input, extra) => {
+      try {
+        const normalizedInput = input as ChatCompletionToolInput;
+        const conversationId = normalizedInput.conversation_id;
+        const existingHistory =
+          conversationId && !normalizedInput.clear_conversation
+            ? conversationsFor(options, extra).get(conversationId)
+            : [];
+        const newMessages = normalizeInputMessages(normalizedInput);
+        enforceMaxMessages(newMessages, options.maxMessages);
+        const outboundMessages = [...existingHistory, ...newMessages];
+
+        const request = buildChatCompletionRequest(
+          normalizedInput,
+          outboundMessages,
+          options.defaultModel,
+          options.sanitizeExtraBody !== false,
+        );
+        const report = validateChatCompletionRequest(request);
+
+        return {
+          content: [
+            {
+              type: "text",
+              text: [
+                report.valid ? "Request is valid." : "Request has validation errors.",
+                `Estimated prompt tokens: ${report.estimated_prompt_tokens} (context window ${report.context_window})`,
+                ...report.issues.map((issue) => `- [${issue.severity}] ${issue.field}: ${issue.message}`),
+              ].join("\n"),
+            },
+          ],
+          structuredContent: report as unknown as Record<string, unknown>,
+        };
+      } catch (error) {
+        return toolError(error);
+      }
+    },
+  );
+
+  registerTool(
+    "resolve_request",
+    {
+      description:
+        "Return the exact chat completion JSON body that `chat_completion` would send, without calling DeepSeek. Accepts the same arguments and applies stored `conversation_id` history, `attach_files`, the default or weighted model selection, message merging, token parameter mapping, unsupported-parameter stripping, and `extra_body` sanitization.",
+      inputSchema: chatCompletionToolInputSchema,
+      annotations: {
+        readOnlyHint: true,
+      },
+    },
+    async (input, extra) => {
+      try {
+        const normalizedInput = input as ChatCompletionToolInput;
+        const { request, attachments, weightedModel, tokenLimitWarning, strippedParameters } =
+          await resolveChatCompletionRequest(normalizedInput, conversationsFor(options, extra));
+
+        return {
+          content: [
+            {
+              type: "text",
+              text: [
+                weightedModel ? `Model selected by weight: ${weightedModel}` : undefined,
+                tokenLimitWarning ? `Warning: ${tokenLimitWarning}` : undefined,
+                strippedParameters.length > 0
+                  ? `Stripped parameters unsupported by ${String(request.model)}: ${strippedParameters.join(", ")}`
+                  : undefined,
+                "Resolved request (not sent):",
+                JSON.stringify(request, null, 2),
+              ]
+                .filter(Boolean)
+                .join("\n"),
+            },
+          ],
+          structuredContent: {
+            request,
+            stripped_parameters: strippedParameters,
+            token_limit_warning: tokenLimitWarning ?? null,
+            weighted_model_selection: weightedModel ?? null,
+            attachments: attachments?.files ?? [],
+          },
+        };
+      } catch (error) {
+        return toolError(error);
+      }
+    },
+  );
+
+  registerTool(
+    "count_tokens",
+    {
+      description:
+        "Estimate token counts for raw `text` and/or a `messages` array without calling DeepSeek, using DeepSeek's published per-character ratios (about 0.3 tokens per Latin character, 0.6 per CJK character) plus per-message overhead. Returns per-message and total counts and how much of the model's context window remains. This is not DeepSeek's tokenizer, so results are labelled as estimates; the `usage` DeepSeek returns is the billed count.",
+      inputSchema: countTokensToolInputSchema,
+      annotations: {
+        readOnlyHint: true,
+        idempotentHint: true,
+      },
+    },
+    async (input) => {
+      try {
+        const normalizedInput = input as CountTokensToolInput;
+        const model = normalizedInput.model ?? options.defaultModel;
+        const messages = (normalizedInput.messages ?? []) as DeepSeekChatMessage[];
+        const perMessage = messages.map((message, index) => ({
+          index,
+          role: message.role,
+          tokens: estimateMessageTokens(message),
+        }));
+        const textTokens = normalizedInput.text === undefined ? null : estimateTextTokens(normalizedInput.text);
+        const totalTokens = perMessage.reduce((total, entry) => total + entry.tokens, textTokens ?? 0);
+        const contextWindow = modelCapabilities.get(model).contextWindow;
+
+        const structuredContent = {
+          model,
+          method: "character_estimate",
+          estimate: true,
+          text_tokens: textTokens,
+          messages: perMessage,
+          total_tokens: totalTokens,
+          context_window: contextWindow,
+          remaining_context_tokens: Math.max(0, contextWindow - totalTokens),
+        };
+
+        return {
+          content: [
+            {
+              type: "text",
+              text: [
+                `Estimated ~${totalTokens} tokens (${model} context window ${contextWindow}; character-ratio estimate)`,
+                textTokens !== null ? `text: ~${textTokens}` : undefined,
+                ...perMessage.map((entry) => `[${entry.index}] ${entry.role}: ~${entry.tokens}`),
+              ]
+                .filter(Boolean)
+                .join("\n"),
+            },
+          ],
+          structuredContent,
+        };
+      } catch (error) {
+        return toolError(error);
+      }
+    },
+  );
+
+  registerTool(
+    "estimate_cost",
+    {
+      description:
+        "Project the USD cost of a request without calling DeepSeek, from the built-in per-million-token price table (cache-hit input, cache-miss input, output). Pass `input_tokens` or a `messages` array (token count estimated offline), plus the expected `output_tokens` and the share of input you expect to hit DeepSeek's context cache (`cache_hit_ratio`, 0-1). Also returns the all-cache-miss and all-cache-hit bounds.",
+      inputSchema: estimateCostToolInputSchema,
+      annotations: {
+        readOnlyHint: true,
+        idempotentHint: true,
+      },
+    },
+    async (input) => {
+      try {
+        const normalizedInput = input as EstimateCostToolInput;
+        const model = normalizedInput.model ?? options.defaultModel;
+        const messages = (normalizedInput.messages ?? []) as DeepSeekChatMessage[];
+        const inputTokens = normalizedInput.input_tokens ?? estimateMessagesTokens(messages);
+        const outputTokens = normalizedInput.output_tokens;
+        const cacheHitTokens = Math.round(inputTokens * normalizedInput.cache_hit_ratio);
+        const costFor = (hitTokens: number) =>
+          estimateCostUsd(model, {
+            prompt_tokens: inputTokens,
+            prompt_cache_hit_tokens: hitTokens,
+            prompt_cache_miss_tokens: inputTokens - hitTokens,
+            completion_tokens: outputTokens,
+          });
+        const pricing = getModelPricing(model);
+
+        const structuredContent = {
+          model,
+          input_tokens: inputTokens,
+          input_tokens_estimated: normalizedInput.input_tokens === undefined,
+          output_tokens: outputTokens,
+          cache_hit_tokens: cacheHitTokens,
+          cache_miss_tokens: inputTokens - cacheHitTokens,
+          cost_usd: costFor(cacheHitTokens),
+          all_cache_miss_cost_usd: costFor(0),
+          all_cache_hit_cost_usd: costFor(inputTokens),
+          pricing_per_million_usd: {
+            input_cache_hit: pricing.inputCacheHitPerMillion,
+            input_cache_miss: pricing.inputCacheMissPerMillion,
+            output: pricing.outputPerMillion,
+          },
+        };
+
+        return {
+          content: [
+            {
+              type: "text",
+              text: `${model}: ~$${structuredContent.cost_usd.toFixed(6)} for ${inputTokens} input (${cacheHitTokens} cache hit) + ${outputTokens} output tokens (range $${structuredContent.all_cache_hit_cost_usd.toFixed(6)}-$${structuredContent.all_cache_miss_cost_usd.toFixed(6)})`,
+            },
+          ],
+          structuredContent,
+        };
+      } catch (error) {
+        return toolError(error);
+      }
+    },
+  );
+
+  registerTool(
+    "completion",
+    {
+      description:
+        "DeepSeek text/FIM completion tool for prompt-completion workflows. Use this when you need raw completion text instead of chat message formatting. Supports the same generation controls as the provider completion endpoint and can aggregate streamed output. Set `include_raw_response=true` only when you need the full provider payload for debugging.",
+      inputSchema: completionToolInputSchema,
+      annotations: UPSTREAM_CALL_ANNOTATIONS,
+    },
+    async (input, extra) => {
+      try {
+        const normalizedInput = input as CompletionToolInput;
+        await options.budget?.assertAvailable();
+
+        const request = buildCompletionRequest(
+          normalizedInput,
+          options.defaultModel,
+          options.sanitizeExtraBody !== false,
+        );
+        await moderatePrompt(extra, [normalizedInput.prompt, normalizedInput.suffix]);
+        await waitForRateLimit(extra);
+        const progress = startUpstreamProgress(extra, String(request.model));
+        const result = await requestClient(options, extra)
+          .createCompletion(request, { onStreamProgress: progress.onStreamProgress, signal: extra.signal })
+          .finally(progress.stop);
+        await chargeBudget(result.response.model, result.response.usage);
+        const choice = result.response.choices[0];
+        const includeRawResponse = normalizedInput.include_raw_response;
+
+        const structuredContent: Record<string, unknown> = {
+          id: result.response.id,
+          model: result.response.model,
+          text: choice?.text ?? "",
+          finish_reason: choice?.finish_reason ?? null,
+          usage: result.response.usage ?? null,
+          stream_chunk_count: result.streamChunkCount ?? null,
+          stream_completion: result.streamCompletion ?? null,
+          choices: result.response.choices.map((entry) => ({
+            index: entry.index,
+            text: entry.text,
+            finish_reason: entry.finish_reason,
+            logprobs: entry.logprobs ?? null,
+          })),
+        };
+
+        if (includeRawResponse) {
+          structuredContent.raw_response = result.response;
+        }
+
+        return {
+          content: [
+            {
+              type: "text",
+              text: [
+                choice?.text || "(no completion text returned)",
+                result.streamCompletion && !result.streamCompletion.completed
+                  ? `Warning: output may be incomplete (${result.streamCompletion.reason})`
+                  : undefined,
+              ]
+                .filter(Boolean)
+                .join("\n"),
+            },
+          ],
+          structuredContent,
+        };
+      } catch (error) {
+        return toolError(error);
+      }
+    },
+  );
+
+  registerTool(
+    "fim_completion",
+    {
+      description:
+        "DeepSeek beta fill-in-the-middle completion for code. Provide the text before the gap as `prefix` and the text after it as `suffix`; the model returns only the infill. `language` (e.g. `python`) is written as a comment hint at the top of the prompt. Requests always go to the beta base URL; `max_tokens` is capped at 4096 by the FIM endpoint.",
+      inputSchema: fimCompletionToolInputSchema,
+      annotations: UPSTREAM_CALL_ANNOTATIONS,
+    },
+    async (input, extra) => {
+      try {
+        const normalizedInput = input as FimCompletionToolInput;
+        await options.budget?.assertAvailable();
+
+        const request = buildFimCompletionRequest(
+          normalizedInput,
+          options.defaultModel,
+          options.sanitizeExtraBody !== false,
+        );
+        await moderatePrompt(extra, [normalizedInput.prefix, normalizedInput.suffix]);
+        await waitForRateLimit(extra);
+        const progress = startUpstreamProgress(extra, String(request.model));
+        const result = await requestClient(options, extra)
+          .createFimCompletion(request, { onStreamProgress: progress.onStreamProgress, signal: extra.signal })
+          .finally(progress.stop);
+        await chargeBudget(result.response.model, result.response.usage);
+        const choice = result.response.choices[0];
+
+        const structuredContent: Record<string, unknown> = {
+          id: result.response.id,
+          model: result.response.model,
+          language: normalizedInput.language ?? null,
+          text: choice?.text ?? "",
+          finish_reason: choice?.finish_reason ?? null,
+          usage: result.response.usage ?? null,
+          stream_chunk_count: result.streamChunkCount ?? null,
+          stream_completion: result.streamCompletion ?? null,
+        };
+
+        if (normalizedInput.include_raw_response) {
+          structuredContent.raw_response = result.response;
+        }
+
+        return {
+          content: [
+            {
+              type: "text",
+              text: [
+                choice?.text || "(no infill text returned)",
+                result.streamCompletion && !result.streamCompletion.completed
+                  ? `Warning: output may be incomplete (${result.streamCompletion.reason})`
+                  : undefined,
+              ]
+                .filter(Boolean)
+                .join("\n"),
+            },
+          ],
+          structuredContent,
+        };
+      } catch (error) {
+        return toolError(error);
+      }
+    },
+  );
+
+  registerTool(
     "list_models",
     {
       description:
-        "List available DeepSeek models for model selection and validation. This tool takes no parameters. Use it before passing an explicit model ID to generation tools.",
+        "List available DeepSeek models for model selection and validation. This tool takes no parameters. Use it before passing an explicit model ID to generation tools.",
+      inputSchema: emptyToolInputSchema,
+      outputSchema: listModelsToolOutputSchema,
+      annotations: {
+        readOnlyHint: true,
+        idempotentHint: true,
+        openWorldHint: true,
+      },
+    },
+    async (_input, extra) => {
+      try {
+        const models = await requestClient(options, extra).listModels();
+        modelCapabilities.registerModels(models.data.map((model) => model.id));
+        await options.modelsCache?.write(models).catch(() => undefined);
+
+        return {
+          content: [
+            {
+              type: "text",
+              text: models.data.map((model) => model.id).join("\n") || "(no models returned)",
+            },
+          ],
+          structuredContent: { ...models, stale: false } as unknown as Record<string, unknown>,
+        };
+      } catch (error) {
+        const cached = await options.modelsCache?.read();
+        if (!cached) {
+          return toolError(error);
+        }
+
+        const reason = error instanceof Error ? error.message : String(error);
+        return {
+          content: [
+            {
+              type: "text",
+              text: [
+                `(stale: live /models call failed, serving cache from ${cached.cachedAt})`,
+                cached.response.data.map((model) => model.id).join("\n") || "(no models cached)",
+              ].join("\n"),
+            },
+          ],
+          structuredContent: {
+            ...cached.response,
+            stale: true,
+            cached_at: cached.cachedAt,
+            live_error: reason,
+          } as unknown as Record<string, unknown>,
+        };
+      }
+    },
+  );
+
+  registerTool(
+    "clear_caches",
+    {
+      description:
+        "Flush runtime caches without restarting the server. `models` drops the model IDs used for prompt completions and the model capabilities learned from list_models; `models_file` deletes the on-disk models cache that list_models falls back to during an outage. Omit `name` to clear every in-memory cache (the models file is kept), or pass one cache name. Returns the number of entries removed per cache.",
+      inputSchema: clearCachesToolInputSchema,
+      annotations: {
+        destructiveHint: true,
+        idempotentHint: true,
+      },
+    },
+    async (input) => {
+      try {
+        const normalizedInput = input as ClearCachesToolInput;
+        const clearers: Record<(typeof CACHE_NAMES)[number], () => Promise<number>> = {
+          models: async () => modelCompleter.clear() + modelCapabilities.clearRegisteredModels(),
+          models_file: async () => (await options.modelsCache?.clear()) ?? 0,
+        };
+
+        const names = normalizedInput.name
+          ? [normalizedInput.name]
+          : CACHE_NAMES.filter((name) => name !== "models_file");
+        const removed: Record<string, number> = {};
+        for (const name of names) {
+          removed[name] = await clearers[name]();
+        }
+
+        return {
+          content: [
+            {
+              type: "text",
+              text: names.map((name) => `${name}: removed ${removed[name]} entries`).join("\n"),
+            },
+          ],
+          structuredContent: { removed },
+        };
+      } catch (error) {
+        return toolError(error);
+      }
+    },
+  );
+
+  registerTool(
+    "get_user_balance",
+    {
+      description:
+        "Return the current DeepSeek account balance and availability status. This tool takes no parameters and is read-only. Use it for account health checks when diagnosing provider-side failures.",
+      inputSchema: emptyToolInputSchema,
+      outputSchema: userBalanceToolOutputSchema,
+      annotations: {
+        readOnlyHint: true,
+        idempotentHint: true,
+        openWorldHint: true,
+      },
+    },
+    async (_input, extra) => {
+      try {
+        const balance = await requestClient(options, extra).getUserBalance();
+        const lowBalance = balance.balance_infos.filter(
+          (info) => Number(info.total_balance) < (options.lowBalanceThreshold ?? 1),
+        );
+        if (!balance.is_available || lowBalance.length > 0) {
+          logEvent(extra, "warning", "DeepSeek balance is low", {
+            is_available: balance.is_available,
+            balances: lowBalance.map((info) => `${info.total_balance} ${info.currency}`),
+          });
+        }
+        return {
+          content: [
+            {
+              type: "text",
+              text: JSON.stringify(balance, null, 2),
+            },
+          ],
+          structuredContent: balance as unknown as Record<string, unknown>,
+        };
+      } catch (error) {
+        return toolError(error);
+      }
+    },
+  );
+
+  registerTool(
+    "health_check",
+    {
+      description:
+        "Cheap upstream health probe for gating workflows: lists models with the configured API key (no tokens are billed), reports the round-trip latency and base URL used, and checks whether the beta base URL (FIM, chat prefix completion) is reachable. `healthy` is true when the key works and the beta base answers. This tool takes no parameters.",
+      inputSchema: emptyToolInputSchema,
+      annotations: {
+        readOnlyHint: true,
+        openWorldHint: true,
+      },
+    },
+    async (_input, extra) => {
+      try {
+        const client = requestClient(options, extra);
+        const configuredBaseUrl = client.getBaseUrlHealth()[0]?.base_url ?? null;
+        const report: Record<string, unknown> = {
+          healthy: false,
+          api_key: "missing",
+          latency_ms: null,
+          base_url: configuredBaseUrl,
+          status: null,
+          error: null,
+          beta: null,
+          checked_at: new Date().toISOString(),
+        };
+
+        if (client.hasApiKey) {
+          const started = Date.now();
+          try {
+            await client.listModels();
+            report.api_key = "valid";
+            report.status = 200;
+          } catch (error) {
+            const status = error instanceof DeepSeekApiError ? (error.status ?? null) : null;
+            report.api_key = status === 401 || status === 403 ? "invalid" : "unknown";
+            report.status = status;
+            report.error = error instanceof Error ? error.message : String(error);
+          }
+          report.latency_ms = Date.now() - started;
+          report.base_url = client.activeBaseUrl ?? configuredBaseUrl;
+
+          const beta = await client.probeBetaBaseUrl(extra.signal);
+          report.beta = beta;
+          report.healthy = report.api_key === "valid" && beta.reachable;
+        }
+
+        const betaSummary = report.beta
+          ? `beta ${(report.beta as { reachable: boolean }).reachable ? "reachable" : "unreachable"}`
+          : "beta not checked";
+        return {
+          content: [
+            {
+              type: "text",
+              text: [
+                `${report.healthy ? "healthy" : "unhealthy"}: API key ${String(report.api_key)}, ${betaSummary}`,
+                report.latency_ms !== null ? `latency ${String(report.latency_ms)} ms via ${String(report.base_url)}` : undefined,
+                report.error ? `error: ${String(report.error)}` : undefined,
+              ]
+                .filter(Boolean)
+                .join("\n"),
+            },
+          ],
+          structuredContent: report,
+        };
+      } catch (error) {
+        return toolError(error);
+      }
+    },
+  );
+
+  registerTool(
+    "validate_api_key",
+    {
+      description:
+        "Probe the configured DeepSeek API key against /user/balance and return a structured verdict (`valid`, `invalid`, `expired`, `insufficient_balance`, `missing` or `unknown`) with the upstream status and a remediation hint. This tool takes no parameters and bills no tokens; run it before a long workflow instead of discovering a bad key mid-run.",
+      inputSchema: emptyToolInputSchema,
+      annotations: {
+        readOnlyHint: true,
+        openWorldHint: true,
+      },
+    },
+    async (_input, extra) => {
+      try {
+        let balance: DeepSeekUserBalanceResponse | undefined;
+        let diagnosis: ApiKeyDiagnosis;
+        try {
+          balance = await requestClient(options, extra).getUserBalance();
+          diagnosis = diagnoseApiKey({ balance });
+        } catch (error) {
+          diagnosis = diagnoseApiKey({ error });
+        }
+
+        return {
+          content: [
+            {
+              type: "text",
+              text: `${diagnosis.verdict}: ${diagnosis.message}\n${diagnosis.remediation}`,
+            },
+          ],
+          structuredContent: { ...diagnosis, balance_infos: balance?.balance_infos ?? null },
+        };
+      } catch (error) {
+        return toolError(error);
+      }
+    },
+  );
+
+  registerTool(
+    "get_budget",
+    {
+      description:
+        "Report estimated DeepSeek spend for the current calendar month (UTC) against DEEPSEEK_MONTHLY_BUDGET_USD. Spend is estimated locally from response token usage and list prices. This tool takes no parameters and does not call the DeepSeek API.",
+      inputSchema: emptyToolInputSchema,
+      annotations: {
+        readOnlyHint: true,
+      },
+    },
+    async () => {
+      try {
+        if (!options.budget) {
+          return {
+            content: [{ type: "text", text: "No monthly budget configured (set DEEPSEEK_MONTHLY_BUDGET_USD)." }],
+            structuredContent: { enabled: false },
+          };
+        }
+
+        const status = await options.budget.getStatus();
+        return {
+          content: [
+            {
+              type: "text",
+              text: `${status.month}: spent $${status.spent_usd.toFixed(4)} of $${status.budget_usd.toFixed(2)} (remaining $${status.remaining_usd.toFixed(4)})`,
+            },
+          ],
+          structuredContent: { enabled: true, ...status },
+        };
+      } catch (error) {
+        return toolError(error);
+      }
+    },
+  );
+
+  registerTool(
+    "usage_summary",
+    {
+      description:
+        "Report what this MCP session has used so far: DeepSeek request count, fallbacks, prompt/completion/cache/reasoning tokens and estimated USD spend, in total and per model. Set `reset=true` to start a new tally after reporting. On stateless HTTP there are no sessions: the totals cover every client of the server (`scope: server`) and cannot be reset. Does not call the DeepSeek API; use it to self-report cost at the end of a run.",
+      inputSchema: usageSummaryToolInputSchema,
+      annotations: {
+        readOnlyHint: false,
+        destructiveHint: false,
+        idempotentHint: false,
+      },
+    },
+    async (input) => {
+      try {
+        const normalizedInput = input as UsageSummaryToolInput;
+        const scope = options.sessionScoped === false ? "server" : "session";
+        if (normalizedInput.reset && scope === "server") {
+          throw new Error("usage_summary reset needs a session; on stateless HTTP the totals are shared by every client");
+        }
+
+        const summary = usageTracker.summary();
+        if (normalizedInput.reset) {
+          usageTracker.reset();
+        }
+
+        return {
+          content: [
+            {
+              type: "text",
+              text: [
+                `${summary.requests} request(s) since ${summary.since}, ${summary.fallbacks} fallback(s): ${summary.prompt_tokens} prompt + ${summary.completion_tokens} completion tokens, ~$${summary.estimated_cost_usd.toFixed(6)}`,
+                ...Object.entries(summary.by_model).map(
+                  ([model, totals]) =>
+                    `${model}: ${totals.requests} request(s), ${totals.total_tokens} tokens, ~$${totals.estimated_cost_usd.toFixed(6)}`,
+                ),
+              ].join("\n"),
+            },
+          ],
+          structuredContent: { ...summary, scope, reset: normalizedInput.reset },
+        };
+      } catch (error) {
+        return toolError(error);
+      }
+    },
+  );
+
+  registerTool(
+    "get_version",
+    {
+      description:
+        "Report the running server version, build git commit (when provided at build time), MCP protocol versions, the connected client's name, negotiated protocol version and capabilities, which optional features are enabled, and the server instructions describing the default model, fallback policy, base URL and enabled tools. This tool takes no parameters and does not call the DeepSeek API; include its output in bug reports.",
+      inputSchema: emptyToolInputSchema,
+      annotations: {
+        readOnlyHint: true,
+      },
+    },
+    async (_input, extra) => {
+      const clientInfo = server.server.getClientVersion();
+      const protocolVersion = clientProtocolVersion(extra);
+      const versionInfo = {
+        server_name: "deepseek-mcp-server",
+        server_version: options.version ?? SERVER_VERSION,
+        git_commit: options.gitCommit ?? null,
+        protocol_version: LATEST_PROTOCOL_VERSION,
+        supported_protocol_versions: SUPPORTED_PROTOCOL_VERSIONS,
+        node_version: process.version,
+        client: {
+          name: clientInfo?.name ?? null,
+          version: clientInfo?.version ?? null,
+          protocol_version: protocolVersion ?? null,
+          structured_content: supportsStructuredContent(protocolVersion),
+          capabilities: server.server.getClientCapabilities() ?? {},
+        },
+        features: {
+          ...featureFlags(options),
+          error_format: options.errorFormat ?? "text",
+        },
+        instructions: options.instructions ?? null,
+      };
+
+      return {
+        content: [{ type: "text", text: JSON.stringify(versionInfo, null, 2) }],
+        structuredContent: versionInfo,
+      };
+    },
+  );
+
+  registerTool(
+    "get_tool_schemas",
+    {
+      description:
+        "Return the JSON Schema of every enabled tool's input, keyed by tool name. This tool takes no parameters and does not call the DeepSeek API; use it when the client cannot read schemas from MCP tools/list.",
+      inputSchema: emptyToolInputSchema,
+      annotations: {
+        readOnlyHint: true,
+      },
+    },
+    async () => {
+      try {
+        const schemas: Record<string, unknown> = {};
+        for (const [name, schema] of inputSchemas) {
+          if (registeredTools.get(name)?.enabled) {
+            schemas[name] = z.toJSONSchema(schema, { io: "input", unrepresentable: "any" });
+          }
+        }
+
+        return {
+          content: [{ type: "text", text: JSON.stringify(schemas, null, 2) }],
+          structuredContent: { schemas },
+        };
+      } catch (error) {
+        return toolError(error);
+      }
+    },
+  );
+
+  // reset_conversation, delete_session and delete_conversation all drop a stored conversation; they differ only in
+  // the noun and the result field their callers already read.
+  const clearConversation =
+    (noun: string, field: "removed" | "deleted") =>
+    async (
+      { conversation_id }: { conversation_id: string },
+      extra: RequestHandlerExtra<ServerRequest, ServerNotification>,
+    ): Promise<CallToolResult> => {
+      const cleared = conversationsFor(options, extra).clear(conversation_id);
+      return {
+        content: [
+          {
+            type: "text",
+            text: cleared
+              ? `${noun} "${conversation_id}" was ${field}.`
+              : `${noun} "${conversation_id}" did not exist.`,
+          },
+        ],
+        structuredContent: {
+          conversation_id,
+          [field]: cleared,
+        },
+      };
+    };
+
+  registerTool(
+    "reset_conversation",
+    {
+      description:
+        "Delete stored in-memory chat history for a `conversation_id`. Use this when you want to keep the same ID but start a fresh thread. This only affects server-side memory in the current MCP process.",
+      inputSchema: resetConversationToolInputSchema,
+      annotations: {
+        idempotentHint: true,
+      },
+    },
+    clearConversation("Conversation", "removed"),
+  );
+
+  registerTool(
+    "list_conversations",
+    {
+      description:
+        "List all conversation IDs currently stored in this MCP process memory. This tool takes no parameters and does not call the DeepSeek API. On stateless HTTP only the calling client's conversations are listed. Useful for debugging conversation persistence behavior.",
+      inputSchema: emptyToolInputSchema,
+      annotations: {
+        readOnlyHint: true,
+      },
+    },
+    async (_input, extra) => {
+      const ids = conversationsFor(options, extra).listConversationIds();
+      return {
+        content: [
+          {
+            type: "text",
+            text: ids.length > 0 ? ids.join("\n") : "(no stored conversations)",
+          },
+        ],
+        structuredContent: {
+          conversation_ids: ids,
+          count: ids.length,
+        },
+      };
+    },
+  );
+
+  registerTool(
+    "list_sessions",
+    {
+      description:
+        "List stored conversation sessions with message and user-turn counts, created/last-used timestamps, an approximate token footprint, and cumulative prompt/completion token usage reported by DeepSeek. This tool takes no parameters and does not call the DeepSeek API. Sessions beyond CONVERSATION_MAX_SESSIONS are evicted least-recently-used first.",
       inputSchema: emptyToolInputSchema,
       annotations: {
         readOnlyHint: true,
       },
     },
-    async () => {
+    async (_input, extra) => {
+      const sessions = conversationsFor(options, extra).listSessions();
+      return {
+        content: [
+          {
+            type: "text",
+            text:
+              sessions.length > 0
+                ? sessions
+                    .map(
+                      (session) =>
+                        `${session.conversation_id}: ${session.turn_count} turn(s), ${session.message_count} message(s), ~${session.estimated_tokens} tokens stored, ${session.prompt_tokens_total}/${session.completion_tokens_total} prompt/completion tokens billed, last used ${session.last_used_at}`,
+                    )
+                    .join("\n")
+                : "(no stored sessions)",
+          },
+        ],
+        structuredContent: {
+          sessions,
+          count: sessions.length,
+        },
+      };
+    },
+  );
+
+  registerTool(
+    "delete_session",
+    {
+      description:
+        "Delete a stored conversation session by `conversation_id` to free server memory. Returns whether the session existed.",
+      inputSchema: resetConversationToolInputSchema,
+      annotations: {
+        idempotentHint: true,
+        destructiveHint: true,
+      },
+    },
+    clearConversation("Session", "deleted"),
+  );
+
+  const requireConversation = (conversations: ConversationStore, conversationId: string): void => {
+    if (!conversations.has(conversationId)) {
+      throw new Error(`Unknown conversation: ${conversationId}`);
+    }
+  };
+
+  registerTool(
+    "create_conversation",
+    {
+      description:
+        "Create a server-side conversation so stateless clients can send only new turns. Optionally pass your own `conversation_id` (a random one is generated otherwise), a `system_prompt`, and seed `messages`. Fails if the ID already exists. Follow with append_message / continue_conversation, or pass the ID to chat_completion.",
+      inputSchema: createConversationToolInputSchema,
+      annotations: {
+        readOnlyHint: false,
+        destructiveHint: false,
+        idempotentHint: false,
+      },
+    },
+    async (input, extra) => {
+      try {
+        const normalizedInput = input as CreateConversationToolInput;
+        const conversations = conversationsFor(options, extra);
+        const conversationId = normalizedInput.conversation_id ?? randomUUID();
+        if (conversations.has(conversationId)) {
+          throw new Error(`Conversation already exists: ${conversationId}`);
+        }
+
+        const messages = (normalizedInput.messages ?? []) as DeepSeekChatMessage[];
+        conversations.set(
+          conversationId,
+          normalizedInput.system_prompt
+            ? [{ role: "system", content: normalizedInput.system_prompt }, ...messages]
+            : messages,
+        );
+        const session = conversations.getSession(conversationId);
+
+        return {
+          content: [{ type: "text", text: `Created conversation "${conversationId}".` }],
+          structuredContent: { ...session },
+        };
+      } catch (error) {
+        return toolError(error);
+      }
+    },
+  );
+
+  registerTool(
+    "append_message",
+    {
+      description:
+        "Append one message (`role`, default `user`, plus `content`) to a stored conversation without calling DeepSeek, e.g. to record tool output or context before the next continue_conversation. Fails for unknown `conversation_id`s.",
+      inputSchema: appendMessageToolInputSchema,
+      annotations: {
+        readOnlyHint: false,
+        destructiveHint: false,
+        idempotentHint: false,
+      },
+    },
+    async (input, extra) => {
+      try {
+        const normalizedInput = input as AppendMessageToolInput;
+        const conversations = conversationsFor(options, extra);
+        requireConversation(conversations, normalizedInput.conversation_id);
+
+        const message: DeepSeekChatMessage = { role: normalizedInput.role, content: normalizedInput.content };
+        if (normalizedInput.name) {
+          message.name = normalizedInput.name;
+        }
+        if (normalizedInput.tool_call_id) {
+          message.tool_call_id = normalizedInput.tool_call_id;
+        }
+        conversations.append(normalizedInput.conversation_id, [message]);
+        const session = conversations.getSession(normalizedInput.conversation_id);
+
+        return {
+          content: [
+            {
+              type: "text",
+              text: `Appended ${message.role} message to "${normalizedInput.conversation_id}" (${session?.message_count ?? 0} message(s) stored).`,
+            },
+          ],
+          structuredContent: { ...session },
+        };
+      } catch (error) {
+        return toolError(error);
+      }
+    },
+  );
+
+  registerTool(
+    "continue_conversation",
+    {
+      description:
+        "Run a chat completion on a stored conversation's full history, optionally adding a new user `message` first, and store the new turn together with the assistant reply. Runs through the same request pipeline as chat_completion; a conversation deleted mid-call is not recreated. Only the new turn needs to be sent; use get_conversation to read the transcript back. Fails for unknown `conversation_id`s.",
+      inputSchema: continueConversationToolInputSchema,
+      annotations: UPSTREAM_CALL_ANNOTATIONS,
+    },
+    async (input, extra) => {
+      try {
+        const normalizedInput = input as ContinueConversationToolInput;
+        const conversationId = normalizedInput.conversation_id;
+        const conversations = conversationsFor(options, extra);
+        requireConversation(conversations, conversationId);
+        if (!normalizedInput.message && conversations.get(conversationId).length === 0) {
+          throw new Error(`Conversation ${conversationId} is empty; pass \`message\` or append_message first`);
+        }
+
+        const chatInput: ChatCompletionToolInput = {
+          conversation_id: conversationId,
+          message: normalizedInput.message,
+          model: normalizedInput.model,
+          max_tokens: normalizedInput.max_tokens,
+          temperature: normalizedInput.temperature,
+          top_p: normalizedInput.top_p,
+          clear_conversation: false,
+          stream: false,
+          include_raw_response: false,
+        };
+        const result = await runChatCompletion(chatInput, extra, true);
+
+        return {
+          ...result,
+          structuredContent: {
+            ...result.structuredContent,
+            session: conversations.getSession(conversationId) ?? null,
+          },
+        };
+      } catch (error) {
+        return toolError(error);
+      }
+    },
+  );
+
+  registerTool(
+    "get_conversation",
+    {
+      description:
+        "Return a stored conversation's full transcript (`messages`) plus its session metadata (message and turn counts, timestamps, token totals). Does not call the DeepSeek API. Fails for unknown `conversation_id`s.",
+      inputSchema: resetConversationToolInputSchema,
+      annotations: {
+        readOnlyHint: true,
+      },
+    },
+    async ({ conversation_id }, extra) => {
+      try {
+        const conversations = conversationsFor(options, extra);
+        requireConversation(conversations, conversation_id);
+        const session = conversations.getSession(conversation_id);
+        const messages = conversations.get(conversation_id);
+
+        return {
+          content: [{ type: "text", text: renderTranscript(messages) || "(no messages stored)" }],
+          structuredContent: { ...session, messages },
+        };
+      } catch (error) {
+        return toolError(error);
+      }
+    },
+  );
+
+  registerTool(
+    "delete_conversation",
+    {
+      description:
+        "Delete a stored conversation and its history by `conversation_id`. Returns whether it existed; deleting an unknown ID is not an error.",
+      inputSchema: resetConversationToolInputSchema,
+      annotations: {
+        idempotentHint: true,
+        destructiveHint: true,
+      },
+    },
+    clearConversation("Conversation", "deleted"),
+  );
+
+  registerTool(
+    "encode_image",
+    {
+      description:
+        "Read a local image (png, jpg, gif, webp) from DEEPSEEK_IMAGE_DIR and return it as a base64 data URL wrapped in an `image_url` content part, ready to drop into a chat_completion message. `path` is resolved relative to the allowed directory; paths escaping it are rejected. Does not call the DeepSeek API.",
+      inputSchema: encodeImageToolInputSchema,
+      annotations: {
+        readOnlyHint: true,
+      },
+    },
+    async (input) => {
       try {
-        const models = await options.client.listModels();
+        const normalizedInput = input as EncodeImageToolInput;
+        if (!options.imageDir) {
+          throw new Error("encode_image is disabled; set DEEPSEEK_IMAGE_DIR to the directory images may be read from");
+        }
+
+        const image = await encodeImageFile(normalizedInput.path, options.imageDir);
+        const contentPart = {
+          type: "image_url",
+          image_url: {
+            url: image.data_url,
+            ...(normalizedInput.detail ? { detail: normalizedInput.detail } : {}),
+          },
+        };
+
         return {
           content: [
             {
               type: "text",
-              text: models.data.map((model) => model.id).join("\n") || "(no models returned)",
+              text: `Encoded ${image.path} (${image.mime_type}, ${image.bytes} bytes) as an image_url content part.`,
             },
           ],
-          structuredContent: models as unknown as Record<string, unknown>,
+          structuredContent: {
+            path: image.path,
+            mime_type: image.mime_type,
+            bytes: image.bytes,
+            content_part: contentPart,
+          },
         };
       } catch (error) {
-        return makeToolErrorResult(error);
+        return toolError(error);
       }
     },
   );
 
-  server.registerTool(
-    "get_user_balance",
+  if (options.enableRawRequests) {
+    registerTool(
+      "raw_request",
+      {
+        description:
+          "Power-user passthrough to DeepSeek endpoints this server does not wrap yet. Sends `method` to `path` (relative to the configured base URL, e.g. `/user/balance`) with an optional JSON `body`, using the configured auth, and returns the raw response. Subject to the client-side rate limit and monthly budget; replies that report `usage` are charged. Only registered when DEEPSEEK_ENABLE_RAW=1.",
+        inputSchema: rawRequestToolInputSchema,
+        annotations: { ...UPSTREAM_CALL_ANNOTATIONS, destructiveHint: true },
+      },
+      async (input, extra) => {
+        try {
+          const normalizedInput = input as RawRequestToolInput;
+          await options.budget?.assertAvailable();
+          await moderatePrompt(extra, [
+            normalizedInput.body === undefined ? undefined : JSON.stringify(normalizedInput.body),
+          ]);
+          await waitForRateLimit(extra);
+          const response = await requestClient(options, extra).rawRequest(
+            normalizedInput.method,
+            normalizedInput.path,
+            normalizedInput.body,
+            { signal: extra.signal },
+          );
+          // A passthrough to a paid endpoint (e.g. POST /chat/completions) is charged like the wrapped tools.
+          const reply = response as { model?: unknown; usage?: DeepSeekUsage } | null;
+          if (reply && typeof reply === "object" && reply.usage) {
+            const model = typeof reply.model === "string" ? reply.model : normalizedInput.body?.model;
+            await chargeBudget(String(model ?? options.defaultModel), reply.usage);
+          }
+
+          return {
+            content: [
+              {
+                type: "text",
+                text: truncateText(
+                  JSON.stringify(response, null, 2),
+                  options.summaryMaxChars ?? DEFAULT_SUMMARY_MAX_CHARS,
+                ),
+              },
+            ],
+            structuredContent: {
+              method: normalizedInput.method,
+              path: normalizedInput.path,
+              response,
+            },
+          };
+        } catch (error) {
+          return toolError(error);
+        }
+      },
+    );
+  }
+
+  const serverClient = options.client;
+  registerTool(
+    "authenticate",
     {
       description:
-        "Return the current DeepSeek account balance and availability status. This tool takes no parameters and is read-only. Use it for account health checks when diagnosing provider-side failures.",
-      inputSchema: emptyToolInputSchema,
-      annotations: {
-        readOnlyHint: true,
-      },
+        "Use your own DeepSeek API key for the rest of this MCP session instead of the server's key. Pass `api_key` to set it, or omit it to go back to the server key. Disabled unless DEEPSEEK_ALLOW_CLIENT_KEYS=1. Not available on stateless HTTP; send an X-DeepSeek-Api-Key header with each request instead. The key is kept in memory for this session only.",
+      inputSchema: authenticateToolInputSchema,
     },
-    async () => {
+    async (input) => {
       try {
-        const balance = await options.client.getUserBalance();
+        const normalizedInput = input as AuthenticateToolInput;
+        if (!options.allowClientApiKeys) {
+          throw new Error("authenticate is disabled; set DEEPSEEK_ALLOW_CLIENT_KEYS=1 to allow per-session API keys");
+        }
+        if (options.sessionScoped === false) {
+          throw new Error(
+            "authenticate needs a session; on stateless HTTP send an X-DeepSeek-Api-Key header with each request",
+          );
+        }
+
+        const apiKey = normalizedInput.api_key?.trim();
+        options.client = apiKey ? serverClient.withApiKey(apiKey) : serverClient;
+        const keySource = apiKey ? "session" : "server";
+
         return {
           content: [
             {
               type: "text",
-              text: JSON.stringify(balance, null, 2),
+              text: apiKey
+                ? `Using session API key ${maskApiKey(apiKey)} for this session`
+                : "Using the server API key for this session",
             },
           ],
-          structuredContent: balance as unknown as Record<string, unknown>,
+          structuredContent: {
+            key_source: keySource,
+            key_hint: apiKey ? maskApiKey(apiKey) : null,
+          },
         };
       } catch (error) {
-        return makeToolErrorResult(error);
+        return toolError(error);
       }
     },
   );
 
-  server.registerTool(
-    "reset_conversation",
-    {
-      description:
-        "Delete stored in-memory chat history for a `conversation_id`. Use this when you want to keep the same ID but start a fresh thread. This only affects server-side memory in the current MCP process.",
-      inputSchema: resetConversationToolInputSchema,
-      annotations: {
-        idempotentHint: true,
-      },
-    },
-    async ({ conversation_id }) => {
-      const deleted = options.conversations.clear(conversation_id);
-      return {
-        content: [
-          {
-            type: "text",
-            text: deleted
-              ? `Conversation \"${conversation_id}\" was removed.`
-              : `Conversation \"${conversation_id}\" did not exist.`,
-          },
-        ],
-        structuredContent: {
-          conversation_id,
-          removed: deleted,
+  const toolToggles = options.toolToggles;
+  if (options.enableToolAdmin && toolToggles) {
+    registerTool(
+      "set_tool_enabled",
+      {
+        description:
+          "Admin tool: hide or restore another tool for every connected session at runtime; clients are told to refresh via notifications/tools/list_changed. Tools excluded by DEEPSEEK_DISABLED_TOOLS or DEEPSEEK_ENABLED_TOOLS cannot be enabled. Only registered when DEEPSEEK_ENABLE_TOOL_ADMIN=1, and over a network transport only callable with an OAuth token carrying the deepseek:admin scope.",
+        inputSchema: setToolEnabledToolInputSchema,
+        annotations: {
+          idempotentHint: true,
         },
-      };
-    },
-  );
+      },
+      async (input, extra) => {
+        try {
+          const normalizedInput = input as SetToolEnabledToolInput;
+          if (!options.trustedTransport && !extra.authInfo?.scopes.includes(TOOL_ADMIN_SCOPE)) {
+            throw new Error(`set_tool_enabled needs stdio or an OAuth token with the ${TOOL_ADMIN_SCOPE} scope`);
+          }
 
-  server.registerTool(
-    "list_conversations",
-    {
-      description:
-        "List all conversation IDs currently stored in this MCP process memory. This tool takes no parameters and does not call the DeepSeek API. Useful for debugging conversation persistence behavior.",
-      inputSchema: emptyToolInputSchema,
-      annotations: {
-        readOnlyHint: true,
+          const name = normalizedInput.name;
+          if (!registeredTools.has(name)) {
+            throw new Error(`Unknown tool: ${name}`);
+          }
+
+          if (name === "set_tool_enabled") {
+            throw new Error("set_tool_enabled cannot toggle itself");
+          }
+
+          if (normalizedInput.enabled && !isToolAllowedByConfig(options, name)) {
+            throw new Error(
+              `${name} is disabled by DEEPSEEK_DISABLED_TOOLS/DEEPSEEK_ENABLED_TOOLS and cannot be enabled`,
+            );
+          }
+
+          const changed = toolToggles.set(name, normalizedInput.enabled);
+          const disabledTools = toolToggles.disabledTools();
+
+          return {
+            content: [
+              {
+                type: "text",
+                text: `${name} is now ${normalizedInput.enabled ? "enabled" : "disabled"}${changed ? "" : " (unchanged)"}`,
+              },
+            ],
+            structuredContent: {
+              name,
+              enabled: normalizedInput.enabled,
+              changed,
+              runtime_disabled_tools: disabledTools,
+            },
+          };
+        } catch (error) {
+          return toolError(error);
+        }
       },
-    },
-    async () => {
-      const ids = options.conversations.listConversationIds();
-      return {
-        content: [
-          {
-            type: "text",
-            text: ids.length > 0 ? ids.join("\n") : "(no stored conversations)",
-          },
-        ],
-        structuredContent: {
-          conversation_ids: ids,
-          count: ids.length,
-        },
-      };
-    },
-  );
+    );
+  }
 
-  server.registerTool(
+  registerTool(
     "vision_upload",
     {
       description:
         "Experimental v4 tool for uploading visual input assets for multimodal flows. This tool is feature-gated and fails fast when `DEEPSEEK_EXPERIMENTAL_V4_ENABLED=false`.",
       inputSchema: visionUploadToolInputSchema,
     },
-    async (input) => {
+    async (input, extra) => {
       if (!experimentalV4Enabled) {
         return makeExperimentalFeatureDisabledResult("vision_upload");
       }
@@ -511,7 +3127,7 @@ function registerTools(server: McpServer, options: DeepSeekMcpServerOptions): vo
       try {
         const normalizedInput = input as VisionUploadToolInput;
         const request = buildVisionUploadRequest(normalizedInput);
-        const response = await options.client.uploadVisionAsset(request);
+        const response = await requestClient(options, extra).uploadVisionAsset(request);
         const normalizedResponse = normalizeUploadResponse(response);
 
         const structuredContent: Record<string, unknown> = {
@@ -538,19 +3154,19 @@ function registerTools(server: McpServer, options: DeepSeekMcpServerOptions): vo
           structuredContent,
         };
       } catch (error) {
-        return makeToolErrorResult(error);
+        return toolError(error);
       }
     },
   );
 
-  server.registerTool(
+  registerTool(
     "image_generation",
     {
       description:
         "Experimental v4 tool for image generation. Request/response normalization is intentionally adapter-based for quick endpoint and parameter corrections.",
       inputSchema: imageGenerationToolInputSchema,
     },
-    async (input) => {
+    async (input, extra) => {
       if (!experimentalV4Enabled) {
         return makeExperimentalFeatureDisabledResult("image_generation");
       }
@@ -558,7 +3174,7 @@ function registerTools(server: McpServer, options: DeepSeekMcpServerOptions): vo
       try {
         const normalizedInput = input as ImageGenerationToolInput;
         const request = buildImageGenerationRequest(normalizedInput);
-        const response = await options.client.generateImage(request);
+        const response = await requestClient(options, extra).generateImage(request);
         const normalizedResponse = normalizeImageGenerationResponse(response);
 
         const structuredContent: Record<string, unknown> = {
@@ -588,19 +3204,19 @@ function registerTools(server: McpServer, options: DeepSeekMcpServerOptions): vo
           structuredContent,
         };
       } catch (error) {
-        return makeToolErrorResult(error);
+        return toolError(error);
       }
     },
   );
 
-  server.registerTool(
+  registerTool(
     "video_upload",
     {
       description:
         "Experimental v4 tool for uploading video assets. This tool is feature-gated and designed for fast adapter updates as upstream specs stabilize.",
       inputSchema: videoUploadToolInputSchema,
     },
-    async (input) => {
+    async (input, extra) => {
       if (!experimentalV4Enabled) {
         return makeExperimentalFeatureDisabledResult("video_upload");
       }
@@ -608,7 +3224,7 @@ function registerTools(server: McpServer, options: DeepSeekMcpServerOptions): vo
       try {
         const normalizedInput = input as VideoUploadToolInput;
         const request = buildVideoUploadRequest(normalizedInput);
-        const response = await options.client.uploadVideoAsset(request);
+        const response = await requestClient(options, extra).uploadVideoAsset(request);
         const normalizedResponse = normalizeUploadResponse(response);
 
         const structuredContent: Record<string, unknown> = {
@@ -635,27 +3251,28 @@ function registerTools(server: McpServer, options: DeepSeekMcpServerOptions): vo
           structuredContent,
         };
       } catch (error) {
-        return makeToolErrorResult(error);
+        return toolError(error);
       }
     },
   );
 
-  server.registerTool(
+  registerTool(
     "video_generation",
     {
       description:
         "Experimental v4 tool for video generation. Supports optional polling (`wait_for_completion`) for async task-based providers.",
       inputSchema: videoGenerationToolInputSchema,
     },
-    async (input) => {
+    async (input, extra) => {
       if (!experimentalV4Enabled) {
         return makeExperimentalFeatureDisabledResult("video_generation");
       }
 
       try {
+        const client = requestClient(options, extra);
         const normalizedInput = input as VideoGenerationToolInput;
         const request = buildVideoGenerationRequest(normalizedInput);
-        const response = await options.client.generateVideo(request);
+        const response = await client.generateVideo(request);
         const normalizedResponse = normalizeVideoGenerationResponse(response);
 
         let finalStatus = normalizedResponse.status;
@@ -672,7 +3289,7 @@ function registerTools(server: McpServer, options: DeepSeekMcpServerOptions): vo
             await wait(normalizedInput.poll_interval_ms);
             pollCount += 1;
 
-            const statusResponse = await options.client.getV4TaskStatus(normalizedResponse.task_id);
+            const statusResponse = await client.getV4TaskStatus(normalizedResponse.task_id);
             const normalizedStatus = normalizeTaskStatusResponse(statusResponse);
 
             if (normalizedStatus.status) {
@@ -734,13 +3351,17 @@ function registerTools(server: McpServer, options: DeepSeekMcpServerOptions): vo
           structuredContent,
         };
       } catch (error) {
-        return makeToolErrorResult(error);
+        return toolError(error);
       }
     },
   );
+
+  return registeredTools;
 }
 
-function normalizeInputMessages(input: ChatCompletionToolInput): DeepSeekChatMessage[] {
+function normalizeInputMessages(
+  input: Pick<ChatCompletionToolInput, "message" | "messages">,
+): DeepSeekChatMessage[] {
   if (input.messages && input.messages.length > 0) {
     return input.messages as DeepSeekChatMessage[];
   }
@@ -752,10 +3373,55 @@ function normalizeInputMessages(input: ChatCompletionToolInput): DeepSeekChatMes
   throw new Error("Either `message` or `messages` must be provided");
 }
 
+// Auto-continued replies are billed per call, so the reported usage adds up every call that produced the text.
+function sumUsage(usages: readonly (DeepSeekUsage | undefined)[]): DeepSeekUsage | undefined {
+  const present = usages.filter((usage): usage is DeepSeekUsage => usage !== undefined);
+  if (present.length <= 1) {
+    return present[0];
+  }
+
+  const total: DeepSeekUsage = { prompt_tokens: 0, completion_tokens: 0, total_tokens: 0 };
+  for (const usage of present) {
+    for (const field of ["prompt_tokens", "completion_tokens", "total_tokens"] as const) {
+      total[field] += usage[field] ?? 0;
+    }
+    for (const field of ["prompt_cache_hit_tokens", "prompt_cache_miss_tokens"] as const) {
+      const value = usage[field];
+      if (typeof value === "number") {
+        total[field] = (total[field] ?? 0) + value;
+      }
+    }
+    const reasoningTokens = usage.completion_tokens_details?.reasoning_tokens;
+    if (typeof reasoningTokens === "number") {
+      total.completion_tokens_details = {
+        reasoning_tokens: (total.completion_tokens_details?.reasoning_tokens ?? 0) + reasoningTokens,
+      };
+    }
+  }
+  return total;
+}
+
+function userMessageText(messages: readonly DeepSeekChatMessage[]): string {
+  return messages
+    .filter((message) => message.role === "user")
+    .map((message) => messageContentToText(message.content))
+    .filter((text) => text.length > 0)
+    .join("\n\n");
+}
+
+function enforceMaxMessages(messages: DeepSeekChatMessage[], maxMessages: number | undefined): void {
+  if (maxMessages !== undefined && messages.length > maxMessages) {
+    throw new Error(
+      `Request contains ${messages.length} messages, above the DEEPSEEK_MAX_MESSAGES limit of ${maxMessages}`,
+    );
+  }
+}
+
 function buildChatCompletionRequest(
   input: ChatCompletionToolInput,
   messages: DeepSeekChatMessage[],
   defaultModel: string,
+  sanitizeExtra = true,
 ): DeepSeekChatCompletionRequest {
   const request: DeepSeekChatCompletionRequest = {
     model: input.model ?? defaultModel,
@@ -767,7 +3433,6 @@ function buildChatCompletionRequest(
     "max_tokens",
     "max_completion_tokens",
     "presence_penalty",
-    "response_format",
     "stop",
     "stream",
     "stream_options",
@@ -790,8 +3455,121 @@ function buildChatCompletionRequest(
     }
   }
 
+  if (input.response_format) {
+    request.response_format = buildResponseFormat(input.response_format);
+  }
+
+  if (input.extra_body) {
+    Object.assign(request, sanitizeExtra ? sanitizeExtraBody(input.extra_body) : input.extra_body);
+  }
+
+  return request;
+}
+
+// Stateless HTTP answers every client from one server and store, so there each caller (told apart as for the
+// per-client rate limit) only sees the conversations it created.
+function conversationsFor(
+  options: DeepSeekMcpServerOptions,
+  extra: RequestHandlerExtra<ServerRequest, ServerNotification>,
+): ConversationStore {
+  return options.sessionScoped === false
+    ? options.conversations.scoped(clientKey(extra, "shared"))
+    : options.conversations;
+}
+
+// With DEEPSEEK_ALLOW_CLIENT_KEYS, an X-DeepSeek-Api-Key header on an HTTP request uses that key for the request
+// itself, so stateless HTTP clients sharing one server never see each other's key.
+function requestClient(
+  options: DeepSeekMcpServerOptions,
+  extra: RequestHandlerExtra<ServerRequest, ServerNotification>,
+): DeepSeekApiClient {
+  const header = extra.requestInfo?.headers["x-deepseek-api-key"];
+  const apiKey = options.allowClientApiKeys ? (Array.isArray(header) ? header[0] : header)?.trim() : undefined;
+  return apiKey ? options.client.withApiKey(apiKey) : options.client;
+}
+
+function maskApiKey(apiKey: string): string {
+  return apiKey.length <= 8 ? "****" : `${apiKey.slice(0, 3)}...${apiKey.slice(-4)}`;
+}
+
+function sanitizeExtraBody(extraBody: Record<string, unknown>): Record<string, unknown> {
+  return Object.fromEntries(Object.entries(extraBody).filter(([, value]) => value !== null && value !== ""));
+}
+
+function formatTiming(timing: RequestTiming): Record<string, number> {
+  return {
+    time_to_first_byte_ms: timing.timeToFirstByteMs,
+    total_ms: timing.totalMs,
+    ...(timing.firstToLastChunkMs !== undefined ? { first_to_last_chunk_ms: timing.firstToLastChunkMs } : {}),
+  };
+}
+
+function applyTokenParam(request: DeepSeekChatCompletionRequest, tokenParam: DeepSeekTokenParam): string | undefined {
+  const { max_tokens: maxTokens, max_completion_tokens: maxCompletionTokens } = request;
+  if (maxTokens === undefined && maxCompletionTokens === undefined) {
+    return undefined;
+  }
+
+  const value =
+    tokenParam === "max_completion_tokens" ? (maxCompletionTokens ?? maxTokens) : (maxTokens ?? maxCompletionTokens);
+  delete request.max_tokens;
+  delete request.max_completion_tokens;
+  request[tokenParam] = value;
+
+  if (maxTokens !== undefined && maxCompletionTokens !== undefined && maxTokens !== maxCompletionTokens) {
+    return `max_tokens (${maxTokens}) conflicts with max_completion_tokens (${maxCompletionTokens}); sent ${tokenParam}=${value}`;
+  }
+
+  return undefined;
+}
+
+function buildResponseFormat(
+  format: NonNullable<ChatCompletionToolInput["response_format"]>,
+): DeepSeekResponseFormat {
+  if (format.type !== "json_schema") {
+    return { ...format, type: format.type };
+  }
+
+  const { name, description, schema, strict } = format.json_schema;
+  return {
+    type: "json_schema",
+    json_schema: {
+      name,
+      ...(description !== undefined ? { description } : {}),
+      schema,
+      ...(strict !== undefined ? { strict } : {}),
+    },
+  };
+}
+
+function buildChatPrefixCompletionRequest(
+  input: ChatPrefixCompletionToolInput,
+  defaultModel: string,
+  sanitizeExtra = true,
+): DeepSeekChatCompletionRequest {
+  const request: DeepSeekChatCompletionRequest = {
+    model: input.model ?? defaultModel,
+    messages: [...normalizeInputMessages(input), { role: "assistant", content: input.prefix, prefix: true }],
+  };
+
+  const optionalFields: (keyof ChatPrefixCompletionToolInput)[] = [
+    "max_tokens",
+    "temperature",
+    "top_p",
+    "stop",
+    "stream",
+  ];
+  const requestRecord = request as Record<string, unknown>;
+
+  for (const field of optionalFields) {
+    const value = input[field];
+    if (value !== undefined) {
+      requestRecord[field] = value;
+    }
+  }
+
   if (input.extra_body) {
-    Object.assign(request, input.extra_body);
+    Object.assign(request, sanitizeExtra ? sanitizeExtraBody(input.extra_body) : input.extra_body);
   }
 
   return request;
@@ -800,6 +3578,7 @@ function buildChatCompletionRequest(
 function buildCompletionRequest(
   input: CompletionToolInput,
   defaultModel: string,
+  sanitizeExtra = true,
 ): DeepSeekCompletionRequest {
   const request: DeepSeekCompletionRequest = {
     model: input.model ?? defaultModel,
@@ -830,12 +3609,46 @@ function buildCompletionRequest(
   }
 
   if (input.extra_body) {
-    Object.assign(request, input.extra_body);
+    Object.assign(request, sanitizeExtra ? sanitizeExtraBody(input.extra_body) : input.extra_body);
+  }
+
+  return request;
+}
+
+function buildFimCompletionRequest(
+  input: FimCompletionToolInput,
+  defaultModel: string,
+  sanitizeExtra = true,
+): DeepSeekCompletionRequest {
+  const request: DeepSeekCompletionRequest = {
+    model: input.model ?? defaultModel,
+    prompt: input.language ? `${languageHintComment(input.language)}\n${input.prefix}` : input.prefix,
+    suffix: input.suffix,
+  };
+
+  const optionalFields: (keyof FimCompletionToolInput)[] = ["max_tokens", "temperature", "top_p", "stop", "stream"];
+  const requestRecord = request as Record<string, unknown>;
+
+  for (const field of optionalFields) {
+    const value = input[field];
+    if (value !== undefined) {
+      requestRecord[field] = value;
+    }
+  }
+
+  if (input.extra_body) {
+    Object.assign(request, sanitizeExtra ? sanitizeExtraBody(input.extra_body) : input.extra_body);
   }
 
   return request;
 }
 
+function languageHintComment(language: string): string {
+  const normalized = language.trim().toLowerCase();
+  const marker = HASH_COMMENT_LANGUAGES.has(normalized) ? "#" : DASH_COMMENT_LANGUAGES.has(normalized) ? "--" : "//";
+  return `${marker} language: ${language.trim()}`;
+}
+
 async function wait(ms: number): Promise<void> {
   await new Promise<void>((resolve) => {
     setTimeout(resolve, ms);
@@ -871,20 +3684,167 @@ function makeExperimentalFeatureDisabledResult(toolName: string): {
   };
 }
 
-function makeToolErrorResult(error: unknown): {
+interface ToolErrorResult {
   isError: true;
   content: [{ type: "text"; text: string }];
   structuredContent: {
-    error_type: "deepseek_api_error" | "tool_execution_error";
+    error_type:
+      | "deepseek_api_error"
+      | "budget_exceeded"
+      | "moderation_blocked"
+      | "rate_limited"
+      | "user_declined"
+      | "schema_validation_failed"
+      | "tool_execution_error";
     status: number | null;
     message: string;
+    category?: string;
     retryable: boolean;
     suggestion: string;
+    categories?: string[];
+    retry_after_ms?: number;
+    attempts?: number;
+    last_output?: string;
+    code?: string | null;
+    upstream?: unknown;
   };
-} {
+}
+
+function makeToolErrorResult(
+  error: unknown,
+  format: ToolErrorFormat,
+  retryStatuses: ReadonlySet<number>,
+): ToolErrorResult {
+  const result = buildToolErrorResult(error, retryStatuses);
+  if (format !== "json") {
+    return result;
+  }
+
+  const code = result.structuredContent.code || result.structuredContent.error_type;
+  return {
+    ...result,
+    content: [
+      {
+        type: "text",
+        text: JSON.stringify({
+          error: true,
+          status: result.structuredContent.status,
+          code,
+          category: error instanceof DeepSeekApiError ? error.category() : null,
+          message: result.structuredContent.message,
+          upstream: result.structuredContent.upstream ?? null,
+        }),
+      },
+    ],
+  };
+}
+
+// Clients sharing a bearer token share one limit; otherwise each MCP session gets its own. Stateless HTTP has no
+// session, so its callers are told apart by remote address; other transports fall back to the server instance.
+function clientKey(extra: RequestHandlerExtra<ServerRequest, ServerNotification>, fallback: string): string {
+  const authorization = extra.requestInfo?.headers.authorization;
+  const header = Array.isArray(authorization) ? authorization[0] : authorization;
+  const token = header ? /^Bearer\s+(.+)$/i.exec(header.trim())?.[1] : undefined;
+  if (token) {
+    return `token:${createHash("sha256").update(token).digest("hex")}`;
+  }
+  if (extra.sessionId) {
+    return `session:${extra.sessionId}`;
+  }
+
+  const remoteAddress = extra.requestInfo?.headers[REMOTE_ADDRESS_HEADER];
+  return remoteAddress ? `address:${String(remoteAddress)}` : fallback;
+}
+
+function buildToolErrorResult(error: unknown, retryStatuses: ReadonlySet<number>): ToolErrorResult {
+  if (error instanceof ClientRateLimitedError) {
+    const suggestion =
+      error.retryAfterMs !== undefined
+        ? `Retry in ${Math.ceil(error.retryAfterMs / 1000)} s.`
+        : "Wait for an in-flight tool call to finish before starting another.";
+    return {
+      isError: true,
+      content: [{ type: "text", text: `Rate limited: ${error.message}. ${suggestion}` }],
+      structuredContent: {
+        error_type: "rate_limited",
+        status: null,
+        message: error.message,
+        retryable: true,
+        suggestion,
+        ...(error.retryAfterMs !== undefined ? { retry_after_ms: error.retryAfterMs } : {}),
+      },
+    };
+  }
+
+  if (error instanceof ConfirmationDeclinedError) {
+    const suggestion = "Shorten the conversation or lower max_tokens, or confirm the request when asked.";
+    return {
+      isError: true,
+      content: [{ type: "text", text: `Cancelled by user: ${error.message}. ${suggestion}` }],
+      structuredContent: {
+        error_type: "user_declined",
+        status: null,
+        message: error.message,
+        retryable: false,
+        suggestion,
+      },
+    };
+  }
+
+  if (error instanceof JsonOutputValidationError) {
+    const suggestion = "Simplify the schema, describe the expected fields in the messages, or raise max_retries.";
+    return {
+      isError: true,
+      content: [{ type: "text", text: `Schema validation failed: ${error.message}. ${suggestion}` }],
+      structuredContent: {
+        error_type: "schema_validation_failed",
+        status: null,
+        message: error.message,
+        retryable: true,
+        suggestion,
+        attempts: error.attempts,
+        last_output: error.lastOutput,
+      },
+    };
+  }
+
+  if (error instanceof BudgetExceededError) {
+    const suggestion = "Raise DEEPSEEK_MONTHLY_BUDGET_USD or wait for the next calendar month.";
+    return {
+      isError: true,
+      content: [{ type: "text", text: `Budget exceeded: ${error.message}. ${suggestion}` }],
+      structuredContent: {
+        error_type: "budget_exceeded",
+        status: null,
+        message: error.message,
+        retryable: false,
+        suggestion,
+      },
+    };
+  }
+
+  if (error instanceof ModerationBlockedError) {
+    const suggestion = "Revise the prompt, or check DEEPSEEK_MODERATION_URL if the moderation service is down.";
+    return {
+      isError: true,
+      content: [{ type: "text", text: `Blocked by moderation: ${error.message}. ${suggestion}` }],
+      structuredContent: {
+        error_type: "moderation_blocked",
+        status: null,
+        message: error.message,
+        retryable: false,
+        suggestion,
+        categories: error.categories,
+      },
+    };
+  }
+
   if (error instanceof DeepSeekApiError) {
-    const retryable = isRetryableDeepSeekError(error.status);
-    const suggestion = getDeepSeekErrorSuggestion(error.status);
+    const retryable = error.retryable ?? isRetryableDeepSeekError(error.status, retryStatuses);
+    const suggestion =
+      error.code === "no_api_key"
+        ? "Set DEEPSEEK_API_KEY and restart the server without DEEPSEEK_OFFLINE."
+        : getDeepSeekErrorSuggestion(error.status);
 
     return {
       isError: true,
@@ -899,9 +3859,12 @@ function makeToolErrorResult(error: unknown): {
       structuredContent: {
         error_type: "deepseek_api_error",
         status: error.status ?? null,
+        category: error.category(),
+        code: error.code ?? extractErrorType(error.payload) ?? null,
         message: error.message,
         retryable,
         suggestion,
+        upstream: error.payload ?? null,
       },
     };
   }
@@ -920,12 +3883,12 @@ function makeToolErrorResult(error: unknown): {
   };
 }
 
-function isRetryableDeepSeekError(status: number | undefined): boolean {
+function isRetryableDeepSeekError(status: number | undefined, retryStatuses: ReadonlySet<number>): boolean {
   if (status === undefined) {
     return true;
   }
 
-  return RETRYABLE_DEEPSEEK_STATUS_CODES.has(status);
+  return retryStatuses.has(status);
 }
 
 function getDeepSeekErrorSuggestion(status: number | undefined): string {
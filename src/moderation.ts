@@ -0,0 +1,93 @@
+export type ModerationFailMode = "open" | "closed";
+
+export interface PromptModeratorOptions {
+  url: string;
+  failMode?: ModerationFailMode;
+  timeoutMs?: number;
+  fetchFn?: typeof fetch;
+}
+
+export interface ModerationResult {
+  flagged: boolean;
+  categories: string[];
+  failedOpen: boolean;
+}
+
+export class ModerationBlockedError extends Error {
+  public readonly categories: string[];
+
+  constructor(message: string, categories: string[] = []) {
+    super(message);
+    this.name = "ModerationBlockedError";
+    this.categories = categories;
+  }
+}
+
+export class PromptModerator {
+  private readonly url: string;
+  private readonly failMode: ModerationFailMode;
+  private readonly timeoutMs: number;
+  private readonly fetchFn: typeof fetch;
+
+  constructor(options: PromptModeratorOptions) {
+    this.url = options.url;
+    this.failMode = options.failMode ?? "closed";
+    this.timeoutMs = options.timeoutMs ?? 10000;
+    this.fetchFn = options.fetchFn ?? fetch;
+  }
+
+  // Accepts either `{ flagged, categories }` or the OpenAI moderation shape `{ results: [{ flagged, categories }] }`.
+  async check(input: string): Promise<ModerationResult> {
+    let payload: unknown;
+
+    try {
+      const response = await this.fetchFn(this.url, {
+        method: "POST",
+        headers: { "Content-Type": "application/json", Accept: "application/json" },
+        body: JSON.stringify({ input }),
+        signal: AbortSignal.timeout(this.timeoutMs),
+      });
+
+      if (!response.ok) {
+        throw new Error(`moderation service returned status ${response.status}`);
+      }
+
+      payload = await response.json();
+    } catch (error) {
+      if (this.failMode === "open") {
+        return { flagged: false, categories: [], failedOpen: true };
+      }
+
+      const reason = error instanceof Error ? error.message : String(error);
+      throw new ModerationBlockedError(
+        `Prompt moderation unavailable (${reason}); blocking request because DEEPSEEK_MODERATION_FAIL=closed`,
+      );
+    }
+
+    const record = isObject(payload) ? payload : {};
+    const result = Array.isArray(record.results) && isObject(record.results[0]) ? record.results[0] : record;
+    const categories = isObject(result.categories)
+      ? Object.entries(result.categories)
+          .filter(([, value]) => value === true)
+          .map(([name]) => name)
+      : Array.isArray(result.categories)
+        ? result.categories.filter((name): name is string => typeof name === "string")
+        : [];
+
+    return { flagged: result.flagged === true, categories, failedOpen: false };
+  }
+
+  async assertAllowed(input: string): Promise<ModerationResult> {
+    const result = await this.check(input);
+    if (result.flagged) {
+      const detail = result.categories.length > 0 ? ` (${result.categories.join(", ")})` : "";
+      throw new ModerationBlockedError(`Prompt was flagged by the moderation service${detail}`, result.categories);
+    }
+
+    return result;
+  }
+}
+
+function isObject(value: unknown): value is Record<string, unknown> {
+  return typeof value === "object" && value !== null && !Array.isArray(value);
+}
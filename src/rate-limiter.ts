@@ -0,0 +1,154 @@
+export interface TokenBucketRateLimiterOptions {
+  requestsPerMinute: number;
+  burst?: number;
+  now?: () => number;
+  sleep?: (ms: number) => Promise<void>;
+}
+
+// Callers reserve tokens up front (the bucket may go negative), so concurrent requests queue in arrival order.
+export class TokenBucketRateLimiter {
+  private readonly capacity: number;
+  private readonly tokensPerMs: number;
+  private readonly now: () => number;
+  private readonly sleep: (ms: number) => Promise<void>;
+  private tokens: number;
+  private updatedAt: number;
+
+  constructor(options: TokenBucketRateLimiterOptions) {
+    this.capacity = Math.max(1, options.burst ?? Math.ceil(options.requestsPerMinute / 60));
+    this.tokensPerMs = options.requestsPerMinute / 60000;
+    this.now = options.now ?? Date.now;
+    this.sleep = options.sleep ?? ((ms) => new Promise((resolve) => setTimeout(resolve, ms)));
+    this.tokens = this.capacity;
+    this.updatedAt = this.now();
+  }
+
+  reserve(weight = 1): number {
+    this.refill();
+    this.tokens -= weight;
+
+    return this.tokens >= 0 ? 0 : Math.ceil(-this.tokens / this.tokensPerMs);
+  }
+
+  // Takes the tokens only if they are available now; otherwise returns the wait without reserving anything.
+  tryReserve(weight = 1): number {
+    this.refill();
+    if (this.tokens >= weight) {
+      this.tokens -= weight;
+      return 0;
+    }
+
+    return Math.ceil((weight - this.tokens) / this.tokensPerMs);
+  }
+
+  async acquire(weight = 1, onWait?: (waitMs: number) => Promise<void> | void): Promise<number> {
+    const waitMs = this.reserve(weight);
+    if (waitMs > 0) {
+      await onWait?.(waitMs);
+      await this.sleep(waitMs);
+    }
+
+    return waitMs;
+  }
+
+  private refill(): void {
+    const now = this.now();
+    this.tokens = Math.min(this.capacity, this.tokens + (now - this.updatedAt) * this.tokensPerMs);
+    this.updatedAt = now;
+  }
+}
+
+export interface ClientRateLimiterOptions {
+  requestsPerMinute?: number;
+  maxConcurrent?: number;
+  now?: () => number;
+}
+
+export class ClientRateLimitedError extends Error {
+  public readonly retryAfterMs?: number;
+
+  constructor(message: string, retryAfterMs?: number) {
+    super(message);
+    this.name = "ClientRateLimitedError";
+    this.retryAfterMs = retryAfterMs;
+  }
+}
+
+const CLIENT_IDLE_EVICT_MS = 60000;
+
+// Per-client limits for network transports. Unlike TokenBucketRateLimiter this rejects instead of
+// queueing, so a runaway client gets an error rather than holding upstream capacity.
+export class ClientRateLimiter {
+  private readonly options: ClientRateLimiterOptions;
+  private readonly now: () => number;
+  private readonly clients = new Map<string, { bucket?: TokenBucketRateLimiter; active: number; lastUsed: number }>();
+
+  constructor(options: ClientRateLimiterOptions) {
+    this.options = options;
+    this.now = options.now ?? Date.now;
+  }
+
+  async run<T>(key: string, work: () => Promise<T>): Promise<T> {
+    const client = this.client(key);
+    const maxConcurrent = this.options.maxConcurrent;
+    if (maxConcurrent && client.active >= maxConcurrent) {
+      throw new ClientRateLimitedError(`Too many concurrent tool calls (limit ${maxConcurrent})`);
+    }
+
+    this.reserve(client);
+    client.active += 1;
+    try {
+      return await work();
+    } finally {
+      client.active -= 1;
+      client.lastUsed = this.now();
+    }
+  }
+
+  // Counts one more upstream request against the key's per-minute limit without taking a concurrency slot, for
+  // tools such as batch_chat_completion that fan a single call out into several requests.
+  charge(key: string): void {
+    this.reserve(this.client(key));
+  }
+
+  private reserve(client: { bucket?: TokenBucketRateLimiter }): void {
+    const retryAfterMs = client.bucket?.tryReserve() ?? 0;
+    if (retryAfterMs > 0) {
+      throw new ClientRateLimitedError(
+        `Exceeded ${this.options.requestsPerMinute} tool calls per minute`,
+        retryAfterMs,
+      );
+    }
+  }
+
+  private client(key: string) {
+    const now = this.now();
+    let client = this.clients.get(key);
+    if (!client) {
+      this.evictIdle(now);
+      client = {
+        bucket: this.options.requestsPerMinute
+          ? new TokenBucketRateLimiter({
+              requestsPerMinute: this.options.requestsPerMinute,
+              burst: this.options.requestsPerMinute,
+              now: this.now,
+            })
+          : undefined,
+        active: 0,
+        lastUsed: now,
+      };
+      this.clients.set(key, client);
+    }
+
+    client.lastUsed = now;
+    return client;
+  }
+
+  private evictIdle(now: number): void {
+    for (const [key, client] of this.clients) {
+      if (client.active === 0 && now - client.lastUsed > CLIENT_IDLE_EVICT_MS) {
+        this.clients.delete(key);
+      }
+    }
+  }
+}
@@ -0,0 +1,59 @@
+import { randomUUID } from "node:crypto";
+
+import { CreateMessageRequest, CreateMessageResult } from "@modelcontextprotocol/sdk/types.js";
+
+import { messageContentToText } from "./deepseek/message-utils.js";
+import { ChatCompletionExecutionResult, DeepSeekChatCompletionRequest } from "./deepseek/types.js";
+
+const DEFAULT_SAMPLING_MAX_TOKENS = 4096;
+
+// Maps a DeepSeek chat request onto sampling/createMessage: system turns become the system prompt,
+// tool results are replayed as user text, and the requested model is passed as a preference hint.
+export function toSamplingRequest(request: DeepSeekChatCompletionRequest): CreateMessageRequest["params"] {
+  const systemPrompt = request.messages
+    .filter((message) => message.role === "system")
+    .map((message) => messageContentToText(message.content))
+    .join("\n\n");
+  const stop = request.stop === undefined ? undefined : Array.isArray(request.stop) ? request.stop : [request.stop];
+
+  return {
+    messages: request.messages
+      .filter((message) => message.role !== "system")
+      .map((message) => ({
+        role: message.role === "assistant" ? ("assistant" as const) : ("user" as const),
+        content: {
+          type: "text" as const,
+          text:
+            message.role === "tool"
+              ? `Tool result: ${messageContentToText(message.content)}`
+              : messageContentToText(message.content),
+        },
+      })),
+    ...(systemPrompt ? { systemPrompt } : {}),
+    maxTokens: request.max_tokens ?? request.max_completion_tokens ?? DEFAULT_SAMPLING_MAX_TOKENS,
+    ...(request.temperature !== undefined ? { temperature: request.temperature } : {}),
+    ...(stop ? { stopSequences: stop } : {}),
+    modelPreferences: { hints: [{ name: String(request.model) }] },
+  };
+}
+
+export function fromSamplingResult(result: CreateMessageResult): ChatCompletionExecutionResult {
+  const blocks = Array.isArray(result.content) ? result.content : [result.content];
+  const text = blocks.map((block) => (block.type === "text" ? block.text : "")).join("");
+  const finishReason =
+    result.stopReason === "maxTokens"
+      ? "length"
+      : result.stopReason === "endTurn" || result.stopReason === "stopSequence" || result.stopReason === undefined
+        ? "stop"
+        : result.stopReason;
+
+  return {
+    response: {
+      id: `sampling-${randomUUID()}`,
+      object: "chat.completion",
+      created: Math.floor(Date.now() / 1000),
+      model: result.model,
+      choices: [{ index: 0, message: { role: "assistant", content: text }, finish_reason: finishReason }],
+    },
+  };
+}
@@ -0,0 +1,56 @@
+// Tracks in-flight tool calls so a signal can stop new work and wait (bounded) for the rest to finish.
+export class ShutdownController {
+  private active = 0;
+  private draining = false;
+  private idleWaiters: Array<() => void> = [];
+
+  get isDraining(): boolean {
+    return this.draining;
+  }
+
+  get inFlight(): number {
+    return this.active;
+  }
+
+  async track<T>(work: () => Promise<T>): Promise<T> {
+    if (this.draining) {
+      throw new Error("Server is shutting down and not accepting new tool calls");
+    }
+
+    this.active += 1;
+    try {
+      return await work();
+    } finally {
+      this.active -= 1;
+      if (this.active === 0) {
+        const waiters = this.idleWaiters;
+        this.idleWaiters = [];
+        for (const resolve of waiters) {
+          resolve();
+        }
+      }
+    }
+  }
+
+  // Resolves true once every tracked call has settled, or false if timeoutMs elapses first.
+  async drain(timeoutMs: number): Promise<boolean> {
+    this.draining = true;
+    if (this.active === 0) {
+      return true;
+    }
+
+    let timer: NodeJS.Timeout | undefined;
+    const idle = new Promise<boolean>((resolve) => {
+      this.idleWaiters.push(() => resolve(true));
+    });
+    const timeout = new Promise<boolean>((resolve) => {
+      timer = setTimeout(() => resolve(false), timeoutMs);
+    });
+
+    try {
+      return await Promise.race([idle, timeout]);
+    } finally {
+      clearTimeout(timer);
+    }
+  }
+}
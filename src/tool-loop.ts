@@ -0,0 +1,84 @@
+import { randomUUID } from "node:crypto";
+
+import { DeepSeekChatMessage, DeepSeekToolCall, DeepSeekToolDefinition } from "./deepseek/types.js";
+
+export interface ToolLoopResult {
+  tool_call_id: string;
+  content: string;
+}
+
+export interface ToolLoopStep {
+  depth: number;
+  content: string | null;
+  tool_calls: DeepSeekToolCall[];
+  tool_results: ToolLoopResult[];
+}
+
+export interface ToolLoopRun {
+  model: string;
+  tools: DeepSeekToolDefinition[];
+  messages: DeepSeekChatMessage[];
+  maxDepth: number;
+  generation: Record<string, unknown>;
+  trace: ToolLoopStep[];
+  pendingCallIds: string[];
+}
+
+// Paused function-calling loops waiting for the caller to run the requested tools. Only the most recent
+// maxRuns are kept, so abandoned loops don't accumulate for the lifetime of a session.
+export class ToolLoopRuns {
+  private readonly runs = new Map<string, ToolLoopRun>();
+  private readonly maxRuns: number;
+
+  constructor(maxRuns = 100) {
+    this.maxRuns = maxRuns;
+  }
+
+  save(run: ToolLoopRun, runId: string = randomUUID()): string {
+    this.runs.delete(runId);
+    this.runs.set(runId, run);
+    while (this.runs.size > this.maxRuns) {
+      const oldest = this.runs.keys().next().value as string;
+      this.runs.delete(oldest);
+    }
+
+    return runId;
+  }
+
+  get(runId: string): ToolLoopRun | undefined {
+    return this.runs.get(runId);
+  }
+
+  delete(runId: string): boolean {
+    return this.runs.delete(runId);
+  }
+}
+
+// Returns a copy of the run with the caller's results appended as tool messages, in the order the model requested
+// the calls. The stored run is left as it was, so a resume whose upstream call fails can be retried.
+export function applyToolResults(run: ToolLoopRun, results: readonly ToolLoopResult[]): ToolLoopRun {
+  const byId = new Map(results.map((result) => [result.tool_call_id, result]));
+  const missing = run.pendingCallIds.filter((id) => !byId.has(id));
+  if (missing.length > 0) {
+    throw new Error(`Missing tool_results for tool_call_id(s): ${missing.join(", ")}`);
+  }
+
+  const unknown = results.filter((result) => !run.pendingCallIds.includes(result.tool_call_id));
+  if (unknown.length > 0) {
+    throw new Error(`Unexpected tool_call_id(s): ${unknown.map((result) => result.tool_call_id).join(", ")}`);
+  }
+
+  const ordered = run.pendingCallIds.map((id) => byId.get(id) as ToolLoopResult);
+  const toolMessages = ordered.map((result): DeepSeekChatMessage => ({
+    role: "tool",
+    tool_call_id: result.tool_call_id,
+    content: result.content,
+  }));
+  const step = run.trace[run.trace.length - 1];
+  return {
+    ...run,
+    messages: [...run.messages, ...toolMessages],
+    trace: step ? [...run.trace.slice(0, -1), { ...step, tool_results: ordered }] : [...run.trace],
+    pendingCallIds: [],
+  };
+}
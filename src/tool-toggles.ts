@@ -0,0 +1,40 @@
+export type ToolToggleListener = (name: string, enabled: boolean) => void;
+
+// Process-wide runtime tool switches shared by every MCP server instance, so set_tool_enabled
+// in one session hides or restores the tool (and sends tools/list_changed) in all of them.
+export class ToolToggles {
+  private readonly disabled = new Set<string>();
+  private readonly listeners = new Set<ToolToggleListener>();
+
+  isEnabled(name: string): boolean {
+    return !this.disabled.has(name);
+  }
+
+  disabledTools(): string[] {
+    return [...this.disabled].sort();
+  }
+
+  set(name: string, enabled: boolean): boolean {
+    if (this.isEnabled(name) === enabled) {
+      return false;
+    }
+
+    if (enabled) {
+      this.disabled.delete(name);
+    } else {
+      this.disabled.add(name);
+    }
+
+    for (const listener of this.listeners) {
+      listener(name, enabled);
+    }
+    return true;
+  }
+
+  subscribe(listener: ToolToggleListener): () => void {
+    this.listeners.add(listener);
+    return () => {
+      this.listeners.delete(listener);
+    };
+  }
+}
@@ -0,0 +1,42 @@
+import { timingSafeEqual } from "node:crypto";
+import { IncomingMessage, ServerResponse } from "node:http";
+
+export function extractBearerToken(req: IncomingMessage, allowQueryToken = false): string | undefined {
+  const header = req.headers.authorization;
+  const match = typeof header === "string" ? /^Bearer\s+(.+)$/i.exec(header.trim()) : null;
+  if (match) {
+    return match[1].trim();
+  }
+
+  // Browser WebSocket clients cannot set headers, so they may pass ?access_token= instead.
+  if (allowQueryToken) {
+    const requestUrl = new URL(req.url ?? "/", "http://localhost");
+    return requestUrl.searchParams.get("access_token") ?? undefined;
+  }
+
+  return undefined;
+}
+
+export function isAuthorized(req: IncomingMessage, tokens: string[] | undefined, allowQueryToken = false): boolean {
+  if (!tokens || tokens.length === 0) {
+    return true;
+  }
+
+  const presented = extractBearerToken(req, allowQueryToken);
+  if (!presented) {
+    return false;
+  }
+
+  const presentedBytes = Buffer.from(presented, "utf8");
+  return tokens.some((token) => {
+    const tokenBytes = Buffer.from(token, "utf8");
+    return tokenBytes.length === presentedBytes.length && timingSafeEqual(tokenBytes, presentedBytes);
+  });
+}
+
+export function writeUnauthorized(res: ServerResponse): void {
+  res.statusCode = 401;
+  res.setHeader("Content-Type", "application/json");
+  res.setHeader("WWW-Authenticate", 'Bearer realm="deepseek-mcp-server"');
+  res.end(JSON.stringify({ error: "Missing or invalid bearer token" }));
+}
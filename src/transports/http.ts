@@ -1,12 +1,23 @@
-import { createServer, IncomingMessage, Server, ServerResponse } from "node:http";
+import { IncomingMessage, Server, ServerResponse } from "node:http";
 import { randomUUID } from "node:crypto";
+import { AddressInfo } from "node:net";
 
+import { AuthInfo } from "@modelcontextprotocol/sdk/server/auth/types.js";
 import { McpServer } from "@modelcontextprotocol/sdk/server/mcp.js";
 import { StreamableHTTPServerTransport } from "@modelcontextprotocol/sdk/server/streamableHttp.js";
+import { isInitializeRequest } from "@modelcontextprotocol/sdk/types.js";
+
+import { HealthMonitor } from "../health.js";
+import { extractBearerToken, isAuthorized, writeUnauthorized } from "./auth.js";
+import { createListener, TlsOptions } from "./listener.js";
+import { OAuthResourceServer } from "./oauth.js";
+
+export const REMOTE_ADDRESS_HEADER = "x-mcp-remote-address";
 
 export interface StreamableHttpRuntime {
-  transport: StreamableHTTPServerTransport;
   server: Server;
+  port: number;
+  sessionCount: () => number;
   close: () => Promise<void>;
 }
 
@@ -14,22 +25,69 @@ export interface StreamableHttpOptions {
   host: string;
   port: number;
   path: string;
+  basePath?: string;
   statefulSession: boolean;
+  authTokens?: string[];
+  oauth?: OAuthResourceServer;
+  tls?: TlsOptions;
+  health?: HealthMonitor;
+}
+
+interface HttpSession {
+  transport: StreamableHTTPServerTransport;
+  mcpServer: McpServer;
 }
 
+// Stateless mode serves every request from one MCP server; stateful mode gives each
+// Mcp-Session-Id its own transport and MCP server, closed when the session ends.
 export async function startStreamableHttpServer(
-  mcpServer: McpServer,
+  createMcpServer: (req?: IncomingMessage) => McpServer,
   options: StreamableHttpOptions,
 ): Promise<StreamableHttpRuntime> {
-  const transport = new StreamableHTTPServerTransport({
-    sessionIdGenerator: options.statefulSession ? () => randomUUID() : undefined,
-  });
+  const sessions = new Map<string, HttpSession>();
+  let statelessSession: HttpSession | undefined;
+
+  if (!options.statefulSession) {
+    const transport = new StreamableHTTPServerTransport({ sessionIdGenerator: undefined });
+    const mcpServer = createMcpServer();
+    await mcpServer.connect(transport);
+    statelessSession = { transport, mcpServer };
+  }
+
+  const resolveSession = async (req: IncomingMessage, parsedBody: unknown): Promise<HttpSession | undefined> => {
+    if (statelessSession) {
+      return statelessSession;
+    }
 
-  await mcpServer.connect(transport);
+    const sessionId = req.headers["mcp-session-id"];
+    if (typeof sessionId === "string") {
+      return sessions.get(sessionId);
+    }
+
+    if (!isInitializeRequest(parsedBody)) {
+      return undefined;
+    }
+
+    const mcpServer = createMcpServer(req);
+    const transport: StreamableHTTPServerTransport = new StreamableHTTPServerTransport({
+      sessionIdGenerator: () => randomUUID(),
+      onsessioninitialized: (newSessionId) => {
+        sessions.set(newSessionId, { transport, mcpServer });
+      },
+    });
+    transport.onclose = () => {
+      if (transport.sessionId && sessions.delete(transport.sessionId)) {
+        void mcpServer.close();
+      }
+    };
 
-  const server = createServer(async (req, res) => {
+    await mcpServer.connect(transport);
+    return { transport, mcpServer };
+  };
+
+  const server = createListener(options.tls, async (req, res) => {
     try {
-      await handleIncomingRequest(req, res, options.path, transport);
+      await handleIncomingRequest(req, res, options, resolveSession);
     } catch (error) {
       if (!res.headersSent) {
         res.statusCode = 500;
@@ -49,10 +107,13 @@ export async function startStreamableHttpServer(
   });
 
   return {
-    transport,
     server,
+    port: (server.address() as AddressInfo).port,
+    sessionCount: () => sessions.size,
     close: async () => {
-      await transport.close();
+      const active = statelessSession ? [statelessSession, ...sessions.values()] : [...sessions.values()];
+      sessions.clear();
+      await Promise.all(active.map((session) => session.mcpServer.close()));
       await closeServer(server);
     },
   };
@@ -61,11 +122,35 @@ export async function startStreamableHttpServer(
 async function handleIncomingRequest(
   req: IncomingMessage,
   res: ServerResponse,
-  expectedPath: string,
-  transport: StreamableHTTPServerTransport,
+  options: StreamableHttpOptions,
+  resolveSession: (req: IncomingMessage, parsedBody: unknown) => Promise<HttpSession | undefined>,
 ): Promise<void> {
   const requestUrl = new URL(req.url ?? "/", `http://${req.headers.host ?? "localhost"}`);
-  if (requestUrl.pathname !== expectedPath) {
+  if (options.oauth && req.method === "GET" && options.oauth.metadataPaths.includes(requestUrl.pathname)) {
+    res.statusCode = 200;
+    res.setHeader("Content-Type", "application/json");
+    res.setHeader("Access-Control-Allow-Origin", "*");
+    res.end(JSON.stringify(options.oauth.metadata()));
+    return;
+  }
+
+  // Unauthenticated so orchestrator probes work without credentials.
+  if (options.health && req.method === "GET" && requestUrl.pathname === `${options.basePath ?? ""}/healthz`) {
+    res.statusCode = 200;
+    res.setHeader("Content-Type", "application/json");
+    res.end(JSON.stringify({ status: "ok" }));
+    return;
+  }
+
+  if (options.health && req.method === "GET" && requestUrl.pathname === `${options.basePath ?? ""}/readyz`) {
+    const report = options.health.readiness();
+    res.statusCode = report.ready ? 200 : 503;
+    res.setHeader("Content-Type", "application/json");
+    res.end(JSON.stringify(report));
+    return;
+  }
+
+  if (requestUrl.pathname !== options.path) {
     res.statusCode = 404;
     res.setHeader("Content-Type", "application/json");
     res.end(JSON.stringify({ error: `Not found: ${requestUrl.pathname}` }));
@@ -76,6 +161,7 @@ async function handleIncomingRequest(
   res.setHeader("Access-Control-Allow-Origin", "*");
   res.setHeader("Access-Control-Allow-Headers", "Content-Type, Authorization, Mcp-Session-Id, Last-Event-ID");
   res.setHeader("Access-Control-Allow-Methods", "GET, POST, DELETE, OPTIONS");
+  res.setHeader("Access-Control-Expose-Headers", "Mcp-Session-Id, WWW-Authenticate");
 
   if (req.method === "OPTIONS") {
     res.statusCode = 204;
@@ -83,8 +169,66 @@ async function handleIncomingRequest(
     return;
   }
 
+  if (options.oauth) {
+    if (!(await authorizeOAuth(req, res, options.oauth))) {
+      return;
+    }
+  } else if (!isAuthorized(req, options.authTokens)) {
+    writeUnauthorized(res);
+    return;
+  }
+
   const parsedBody = await parseJsonBody(req);
-  await transport.handleRequest(req, res, parsedBody);
+  const session = await resolveSession(req, parsedBody);
+  if (!session) {
+    res.statusCode = req.headers["mcp-session-id"] ? 404 : 400;
+    res.setHeader("Content-Type", "application/json");
+    res.end(
+      JSON.stringify({
+        error: req.headers["mcp-session-id"]
+          ? `Unknown MCP session: ${String(req.headers["mcp-session-id"])}`
+          : "Missing Mcp-Session-Id header",
+      }),
+    );
+    return;
+  }
+
+  // Tool handlers only see request headers, so the peer address travels as one; any client-sent value is replaced.
+  req.headers[REMOTE_ADDRESS_HEADER] = req.socket.remoteAddress ?? "unknown";
+  await session.transport.handleRequest(req, res, parsedBody);
+}
+
+async function authorizeOAuth(
+  req: IncomingMessage,
+  res: ServerResponse,
+  oauth: OAuthResourceServer,
+): Promise<boolean> {
+  const token = extractBearerToken(req);
+  const verification = token ? await oauth.verify(token) : undefined;
+  if (token && verification?.ok) {
+    // The SDK hands req.auth to tool handlers as extra.authInfo, which is how scoped tools see the token's scopes.
+    (req as IncomingMessage & { auth?: AuthInfo }).auth = {
+      token,
+      clientId: verification.subject ?? "",
+      scopes: verification.scopes,
+    };
+    return true;
+  }
+
+  res.statusCode = verification?.status ?? 401;
+  res.setHeader("Content-Type", "application/json");
+  if (verification?.status === 503) {
+    res.setHeader("Retry-After", "5");
+  } else {
+    res.setHeader("WWW-Authenticate", oauth.challenge(verification));
+  }
+  res.end(
+    JSON.stringify({
+      error: verification?.error ?? "unauthorized",
+      error_description: verification?.description ?? "Missing bearer token",
+    }),
+  );
+  return false;
 }
 
 async function parseJsonBody(req: IncomingMessage): Promise<unknown> {
@@ -0,0 +1,11 @@
+import { createServer as createHttpServer, RequestListener, Server } from "node:http";
+import { createServer as createHttpsServer } from "node:https";
+
+export interface TlsOptions {
+  cert: string | Buffer;
+  key: string | Buffer;
+}
+
+export function createListener(tls: TlsOptions | undefined, handler: RequestListener): Server {
+  return tls ? createHttpsServer({ cert: tls.cert, key: tls.key }, handler) : createHttpServer(handler);
+}
@@ -0,0 +1,191 @@
+export interface OAuthResourceServerOptions {
+  resource: string;
+  authorizationServers: string[];
+  introspectionUrl: string;
+  clientId?: string;
+  clientSecret?: string;
+  requiredScopes?: string[];
+  cacheTtlMs?: number;
+  maxCacheEntries?: number;
+  introspectionTimeoutMs?: number;
+  fetchFn?: typeof fetch;
+  now?: () => number;
+}
+
+export type OAuthVerification =
+  | { ok: true; scopes: string[]; subject?: string }
+  | {
+      ok: false;
+      status: 401 | 403 | 503;
+      error: "invalid_token" | "insufficient_scope" | "temporarily_unavailable";
+      description: string;
+    };
+
+const METADATA_PREFIX = "/.well-known/oauth-protected-resource";
+const DEFAULT_MAX_CACHE_ENTRIES = 1000;
+const DEFAULT_INTROSPECTION_TIMEOUT_MS = 5000;
+
+// Validates bearer tokens by RFC 7662 introspection against the authorization server and
+// publishes RFC 9728 protected resource metadata so clients can discover where to log in.
+export class OAuthResourceServer {
+  private readonly options: OAuthResourceServerOptions;
+  private readonly fetchFn: typeof fetch;
+  private readonly now: () => number;
+  private readonly cache = new Map<string, { expiresAt: number; result: OAuthVerification }>();
+
+  constructor(options: OAuthResourceServerOptions) {
+    this.options = options;
+    this.fetchFn = options.fetchFn ?? fetch;
+    this.now = options.now ?? Date.now;
+  }
+
+  get metadataPaths(): string[] {
+    const resourcePath = new URL(this.options.resource).pathname.replace(/\/+$/, "");
+    return resourcePath ? [METADATA_PREFIX + resourcePath, METADATA_PREFIX] : [METADATA_PREFIX];
+  }
+
+  get metadataUrl(): string {
+    return new URL(this.metadataPaths[0], this.options.resource).toString();
+  }
+
+  metadata(): Record<string, unknown> {
+    return {
+      resource: this.options.resource,
+      authorization_servers: this.options.authorizationServers,
+      bearer_methods_supported: ["header"],
+      ...(this.options.requiredScopes && this.options.requiredScopes.length > 0
+        ? { scopes_supported: this.options.requiredScopes }
+        : {}),
+    };
+  }
+
+  challenge(verification?: OAuthVerification): string {
+    const parts = [`resource_metadata="${this.metadataUrl}"`];
+    if (verification && !verification.ok) {
+      parts.push(`error="${verification.error}"`, `error_description="${verification.description}"`);
+    }
+
+    if (this.options.requiredScopes && this.options.requiredScopes.length > 0) {
+      parts.push(`scope="${this.options.requiredScopes.join(" ")}"`);
+    }
+
+    return `Bearer ${parts.join(", ")}`;
+  }
+
+  async verify(token: string): Promise<OAuthVerification> {
+    const cached = this.cache.get(token);
+    if (cached && cached.expiresAt > this.now()) {
+      return cached.result;
+    }
+
+    let introspection: Record<string, unknown>;
+    try {
+      introspection = await this.introspect(token);
+    } catch (error) {
+      // The token may well be valid; the client should retry rather than re-authenticate.
+      const reason = error instanceof Error ? error.message : String(error);
+      return {
+        ok: false,
+        status: 503,
+        error: "temporarily_unavailable",
+        description: `Token introspection unavailable: ${reason}`,
+      };
+    }
+
+    const result = this.evaluate(introspection);
+    if (result.ok) {
+      const exp = typeof introspection.exp === "number" ? introspection.exp * 1000 : Infinity;
+      this.remember(token, Math.min(exp, this.now() + (this.options.cacheTtlMs ?? 60000)), result);
+    }
+
+    return result;
+  }
+
+  // Expired entries are dropped on every insert and the oldest go first once the cache is full, so a stream of
+  // distinct tokens can't grow it without bound.
+  private remember(token: string, expiresAt: number, result: OAuthVerification): void {
+    const now = this.now();
+    for (const [cachedToken, entry] of this.cache) {
+      if (entry.expiresAt <= now) {
+        this.cache.delete(cachedToken);
+      }
+    }
+
+    const maxEntries = Math.max(1, this.options.maxCacheEntries ?? DEFAULT_MAX_CACHE_ENTRIES);
+    while (this.cache.size >= maxEntries) {
+      const oldest = this.cache.keys().next();
+      if (oldest.done) {
+        break;
+      }
+      this.cache.delete(oldest.value);
+    }
+
+    this.cache.set(token, { expiresAt, result });
+  }
+
+  private async introspect(token: string): Promise<Record<string, unknown>> {
+    const headers: Record<string, string> = {
+      "Content-Type": "application/x-www-form-urlencoded",
+      Accept: "application/json",
+    };
+    if (this.options.clientId) {
+      const clientId = encodeURIComponent(this.options.clientId);
+      const clientSecret = encodeURIComponent(this.options.clientSecret ?? "");
+      headers.Authorization = `Basic ${Buffer.from(`${clientId}:${clientSecret}`).toString("base64")}`;
+    }
+
+    const response = await this.fetchFn(this.options.introspectionUrl, {
+      method: "POST",
+      headers,
+      body: new URLSearchParams({ token, token_type_hint: "access_token" }).toString(),
+      signal: AbortSignal.timeout(this.options.introspectionTimeoutMs ?? DEFAULT_INTROSPECTION_TIMEOUT_MS),
+    });
+    if (!response.ok) {
+      throw new Error(`Token introspection failed with HTTP ${response.status}`);
+    }
+
+    return (await response.json()) as Record<string, unknown>;
+  }
+
+  private evaluate(introspection: Record<string, unknown>): OAuthVerification {
+    if (introspection.active !== true) {
+      return { ok: false, status: 401, error: "invalid_token", description: "Token is inactive or expired" };
+    }
+
+    if (typeof introspection.exp === "number" && introspection.exp * 1000 <= this.now()) {
+      return { ok: false, status: 401, error: "invalid_token", description: "Token is expired" };
+    }
+
+    const audiences = Array.isArray(introspection.aud)
+      ? introspection.aud.map(String)
+      : typeof introspection.aud === "string"
+        ? [introspection.aud]
+        : [];
+    if (!audiences.includes(this.options.resource)) {
+      return {
+        ok: false,
+        status: 401,
+        error: "invalid_token",
+        description:
+          audiences.length > 0 ? "Token audience does not match this resource" : "Token has no audience claim",
+      };
+    }
+
+    const scopes = typeof introspection.scope === "string" ? introspection.scope.split(" ").filter(Boolean) : [];
+    const missing = (this.options.requiredScopes ?? []).filter((scope) => !scopes.includes(scope));
+    if (missing.length > 0) {
+      return {
+        ok: false,
+        status: 403,
+        error: "insufficient_scope",
+        description: `Missing required scope(s): ${missing.join(" ")}`,
+      };
+    }
+
+    return {
+      ok: true,
+      scopes,
+      ...(typeof introspection.sub === "string" ? { subject: introspection.sub } : {}),
+    };
+  }
+}
@@ -0,0 +1,74 @@
+import { lstat, unlink } from "node:fs/promises";
+import { connect, Server } from "node:net";
+
+import { McpServer } from "@modelcontextprotocol/sdk/server/mcp.js";
+
+import { createSocketSessionServer } from "./tcp.js";
+
+export interface PipeRuntime {
+  server: Server;
+  path: string;
+  close: () => Promise<void>;
+}
+
+export interface PipeOptions {
+  path: string;
+}
+
+export const DEFAULT_PIPE_PATH = process.platform === "win32" ? "\\\\.\\pipe\\deepseek-mcp" : "/tmp/deepseek-mcp.sock";
+
+// Windows named pipe (\\.\pipe\name) or Unix domain socket, with the same framing as the TCP transport.
+export async function startPipeServer(createMcpServer: () => McpServer, options: PipeOptions): Promise<PipeRuntime> {
+  if (!isWindowsPipePath(options.path)) {
+    await removeStaleSocket(options.path);
+  }
+
+  const { server, close } = createSocketSessionServer(createMcpServer);
+
+  await new Promise<void>((resolve, reject) => {
+    server.once("error", reject);
+    server.listen(options.path, () => resolve());
+  });
+
+  return { server, path: options.path, close };
+}
+
+function isWindowsPipePath(path: string): boolean {
+  return /^\\\\[.?]\\pipe\\/i.test(path);
+}
+
+// A crashed process leaves its socket file behind, which would make listen() fail with EADDRINUSE. The file is only
+// removed when nothing accepts connections on it, so a second start cannot take the path from a running instance.
+async function removeStaleSocket(path: string): Promise<void> {
+  try {
+    if (!(await lstat(path)).isSocket()) {
+      return;
+    }
+  } catch (error) {
+    if ((error as NodeJS.ErrnoException).code === "ENOENT") {
+      return;
+    }
+    throw error;
+  }
+
+  const probe = await new Promise<NodeJS.ErrnoException | undefined>((resolve) => {
+    const socket = connect(path);
+    socket.once("connect", () => {
+      socket.destroy();
+      resolve(undefined);
+    });
+    socket.once("error", (error) => resolve(error));
+  });
+  if (!probe) {
+    throw new Error(`Another server is already running on ${path}`);
+  }
+  if (probe.code === "ECONNREFUSED") {
+    await unlink(path).catch((error: NodeJS.ErrnoException) => {
+      if (error.code !== "ENOENT") {
+        throw error;
+      }
+    });
+  } else if (probe.code !== "ENOENT") {
+    throw probe;
+  }
+}
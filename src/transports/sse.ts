@@ -0,0 +1,138 @@
+import { IncomingMessage, Server, ServerResponse } from "node:http";
+import { AddressInfo } from "node:net";
+
+import { McpServer } from "@modelcontextprotocol/sdk/server/mcp.js";
+import { SSEServerTransport } from "@modelcontextprotocol/sdk/server/sse.js";
+
+import { isAuthorized, writeUnauthorized } from "./auth.js";
+import { createListener, TlsOptions } from "./listener.js";
+
+export interface SseRuntime {
+  server: Server;
+  port: number;
+  close: () => Promise<void>;
+}
+
+export interface SseOptions {
+  host: string;
+  port: number;
+  ssePath: string;
+  messagesPath: string;
+  authTokens?: string[];
+  tls?: TlsOptions;
+}
+
+interface SseSession {
+  transport: SSEServerTransport;
+  mcpServer: McpServer;
+}
+
+export async function startSseServer(
+  createMcpServer: (req?: IncomingMessage) => McpServer,
+  options: SseOptions,
+): Promise<SseRuntime> {
+  const sessions = new Map<string, SseSession>();
+
+  const server = createListener(options.tls, async (req, res) => {
+    try {
+      await handleIncomingRequest(req, res, options, sessions, createMcpServer);
+    } catch (error) {
+      if (!res.headersSent) {
+        res.statusCode = 500;
+        res.setHeader("Content-Type", "application/json");
+      }
+
+      if (!res.writableEnded) {
+        const message = error instanceof Error ? error.message : String(error);
+        res.end(JSON.stringify({ error: message }));
+      }
+    }
+  });
+
+  await new Promise<void>((resolve, reject) => {
+    server.once("error", reject);
+    server.listen(options.port, options.host, () => resolve());
+  });
+
+  return {
+    server,
+    port: (server.address() as AddressInfo).port,
+    close: async () => {
+      const active = [...sessions.values()];
+      sessions.clear();
+      await Promise.all(active.map((session) => session.mcpServer.close()));
+      await closeServer(server);
+    },
+  };
+}
+
+async function handleIncomingRequest(
+  req: IncomingMessage,
+  res: ServerResponse,
+  options: SseOptions,
+  sessions: Map<string, SseSession>,
+  createMcpServer: (req?: IncomingMessage) => McpServer,
+): Promise<void> {
+  const requestUrl = new URL(req.url ?? "/", `http://${req.headers.host ?? "localhost"}`);
+
+  res.setHeader("Access-Control-Allow-Origin", "*");
+  res.setHeader("Access-Control-Allow-Headers", "Content-Type, Authorization");
+  res.setHeader("Access-Control-Allow-Methods", "GET, POST, OPTIONS");
+
+  if (req.method === "OPTIONS") {
+    res.statusCode = 204;
+    res.end();
+    return;
+  }
+
+  if (!isAuthorized(req, options.authTokens)) {
+    writeUnauthorized(res);
+    return;
+  }
+
+  if (req.method === "GET" && requestUrl.pathname === options.ssePath) {
+    const transport = new SSEServerTransport(options.messagesPath, res);
+    const mcpServer = createMcpServer(req);
+    sessions.set(transport.sessionId, { transport, mcpServer });
+
+    res.on("close", () => {
+      if (sessions.delete(transport.sessionId)) {
+        void mcpServer.close();
+      }
+    });
+
+    await mcpServer.connect(transport);
+    return;
+  }
+
+  if (req.method === "POST" && requestUrl.pathname === options.messagesPath) {
+    const sessionId = requestUrl.searchParams.get("sessionId") ?? "";
+    const session = sessions.get(sessionId);
+    if (!session) {
+      res.statusCode = 404;
+      res.setHeader("Content-Type", "application/json");
+      res.end(JSON.stringify({ error: `Unknown SSE session: ${sessionId}` }));
+      return;
+    }
+
+    await session.transport.handlePostMessage(req, res);
+    return;
+  }
+
+  res.statusCode = 404;
+  res.setHeader("Content-Type", "application/json");
+  res.end(JSON.stringify({ error: `Not found: ${requestUrl.pathname}` }));
+}
+
+function closeServer(server: Server): Promise<void> {
+  return new Promise((resolve, reject) => {
+    server.close((error) => {
+      if (error) {
+        reject(error);
+        return;
+      }
+
+      resolve();
+    });
+  });
+}
@@ -0,0 +1,161 @@
+import { AddressInfo, createServer, Server, Socket } from "node:net";
+
+import { McpServer } from "@modelcontextprotocol/sdk/server/mcp.js";
+import { ReadBuffer, serializeMessage } from "@modelcontextprotocol/sdk/shared/stdio.js";
+import { Transport } from "@modelcontextprotocol/sdk/shared/transport.js";
+import { JSONRPCMessage } from "@modelcontextprotocol/sdk/types.js";
+
+export interface TcpRuntime {
+  server: Server;
+  port: number;
+  close: () => Promise<void>;
+}
+
+export interface TcpOptions {
+  host: string;
+  port: number;
+}
+
+export const DEFAULT_MAX_LINE_BYTES = 4 * 1024 * 1024;
+
+// Same newline-delimited JSON-RPC framing as the stdio transport, over a TCP socket; usable on either end.
+// A peer that sends more than maxLineBytes without a newline is disconnected rather than buffered without limit.
+export class TcpSocketTransport implements Transport {
+  onclose?: () => void;
+  onerror?: (error: Error) => void;
+  onmessage?: (message: JSONRPCMessage) => void;
+
+  private readonly readBuffer = new ReadBuffer();
+  private pendingLineBytes = 0;
+  private closed = false;
+
+  constructor(
+    private readonly socket: Socket,
+    private readonly maxLineBytes = DEFAULT_MAX_LINE_BYTES,
+  ) {}
+
+  async start(): Promise<void> {
+    this.socket.on("data", (chunk: Buffer) => {
+      const lastNewline = chunk.lastIndexOf(0x0a);
+      this.pendingLineBytes =
+        lastNewline === -1 ? this.pendingLineBytes + chunk.length : chunk.length - lastNewline - 1;
+      if (this.pendingLineBytes > this.maxLineBytes) {
+        this.onerror?.(new Error(`TCP message exceeds ${this.maxLineBytes} bytes without a newline`));
+        this.socket.destroy();
+        return;
+      }
+
+      this.readBuffer.append(chunk);
+      this.processReadBuffer();
+    });
+    this.socket.on("error", (error) => this.onerror?.(error));
+    this.socket.on("close", () => this.handleClosed());
+  }
+
+  async send(message: JSONRPCMessage): Promise<void> {
+    if (this.closed) {
+      throw new Error("TCP connection is closed");
+    }
+
+    await new Promise<void>((resolve, reject) => {
+      this.socket.write(serializeMessage(message), (error) => (error ? reject(error) : resolve()));
+    });
+  }
+
+  async close(): Promise<void> {
+    this.socket.end();
+    this.handleClosed();
+  }
+
+  private processReadBuffer(): void {
+    while (true) {
+      try {
+        const message = this.readBuffer.readMessage();
+        if (message === null) {
+          return;
+        }
+
+        this.onmessage?.(message);
+      } catch (error) {
+        this.onerror?.(error instanceof Error ? error : new Error(String(error)));
+      }
+    }
+  }
+
+  private handleClosed(): void {
+    if (this.closed) {
+      return;
+    }
+
+    this.closed = true;
+    this.readBuffer.clear();
+    this.onclose?.();
+  }
+}
+
+export async function startTcpServer(createMcpServer: () => McpServer, options: TcpOptions): Promise<TcpRuntime> {
+  const { server, close } = createSocketSessionServer(createMcpServer);
+
+  await new Promise<void>((resolve, reject) => {
+    server.once("error", reject);
+    server.listen(options.port, options.host, () => resolve());
+  });
+
+  return {
+    server,
+    port: (server.address() as AddressInfo).port,
+    close,
+  };
+}
+
+// One MCP server per accepted connection; shared by the TCP and named-pipe/Unix-socket listeners.
+export function createSocketSessionServer(createMcpServer: () => McpServer): {
+  server: Server;
+  close: () => Promise<void>;
+} {
+  const sessions = new Set<McpServer>();
+  const sockets = new Set<Socket>();
+
+  const server = createServer((socket) => {
+    const transport = new TcpSocketTransport(socket);
+    const mcpServer = createMcpServer();
+    sessions.add(mcpServer);
+    sockets.add(socket);
+    transport.onclose = () => {
+      sockets.delete(socket);
+      if (sessions.delete(mcpServer)) {
+        void mcpServer.close();
+      }
+    };
+
+    void mcpServer.connect(transport).catch(() => {
+      socket.destroy();
+    });
+  });
+
+  return {
+    server,
+    close: async () => {
+      const active = [...sessions];
+      sessions.clear();
+      await Promise.all(active.map((mcpServer) => mcpServer.close()));
+      for (const socket of sockets) {
+        socket.destroy();
+      }
+      await closeServer(server);
+    },
+  };
+}
+
+function closeServer(server: Server): Promise<void> {
+  return new Promise((resolve, reject) => {
+    server.close((error) => {
+      if (error) {
+        reject(error);
+        return;
+      }
+
+      resolve();
+    });
+  });
+}
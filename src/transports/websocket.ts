@@ -0,0 +1,365 @@
+import { createHash } from "node:crypto";
+import { IncomingMessage, Server } from "node:http";
+import { AddressInfo } from "node:net";
+import { Duplex } from "node:stream";
+
+import { McpServer } from "@modelcontextprotocol/sdk/server/mcp.js";
+import { Transport } from "@modelcontextprotocol/sdk/shared/transport.js";
+import { JSONRPCMessage, JSONRPCMessageSchema } from "@modelcontextprotocol/sdk/types.js";
+
+import { isAuthorized } from "./auth.js";
+import { createListener, TlsOptions } from "./listener.js";
+
+export interface WebSocketRuntime {
+  server: Server;
+  port: number;
+  close: () => Promise<void>;
+}
+
+export interface WebSocketOptions {
+  host: string;
+  port: number;
+  path: string;
+  maxMessageBytes?: number;
+  authTokens?: string[];
+  tls?: TlsOptions;
+}
+
+const WEBSOCKET_GUID = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+const DEFAULT_MAX_MESSAGE_BYTES = 4 * 1024 * 1024;
+
+const OPCODE_CONTINUATION = 0x0;
+const OPCODE_TEXT = 0x1;
+const OPCODE_BINARY = 0x2;
+const OPCODE_CLOSE = 0x8;
+const OPCODE_PING = 0x9;
+const OPCODE_PONG = 0xa;
+
+// Minimal RFC 6455 server side: one MCP session per connection, JSON-RPC messages as text frames.
+export class WebSocketServerTransport implements Transport {
+  onclose?: () => void;
+  onerror?: (error: Error) => void;
+  onmessage?: (message: JSONRPCMessage) => void;
+
+  private buffer = Buffer.alloc(0);
+  private fragments: Buffer[] = [];
+  private messageInProgress = false;
+  private closed = false;
+
+  constructor(
+    private readonly socket: Duplex,
+    private readonly maxMessageBytes = DEFAULT_MAX_MESSAGE_BYTES,
+  ) {}
+
+  async start(): Promise<void> {
+    this.socket.on("data", (chunk: Buffer) => {
+      this.buffer = Buffer.concat([this.buffer, chunk]);
+      this.drainFrames();
+    });
+    this.socket.on("error", (error) => this.onerror?.(error));
+    this.socket.on("close", () => this.handleClosed());
+  }
+
+  async send(message: JSONRPCMessage): Promise<void> {
+    if (this.closed) {
+      throw new Error("WebSocket connection is closed");
+    }
+
+    this.socket.write(encodeFrame(OPCODE_TEXT, Buffer.from(JSON.stringify(message), "utf8")));
+  }
+
+  async close(): Promise<void> {
+    this.sendClose(1000);
+    this.handleClosed();
+  }
+
+  private drainFrames(): void {
+    while (!this.closed) {
+      const frame = decodeFrame(this.buffer);
+      if (!frame) {
+        if (this.buffer.length > this.maxMessageBytes + 14) {
+          this.failConnection(1009, "WebSocket message too large");
+        }
+        return;
+      }
+
+      this.buffer = this.buffer.subarray(frame.frameLength);
+      const violation = this.checkFrame(frame);
+      if (violation) {
+        this.failConnection(1002, violation);
+        return;
+      }
+
+      this.handleFrame(frame.fin, frame.opcode, frame.payload);
+    }
+  }
+
+  // RFC 6455 section 5: clients must mask, no extensions are negotiated, control frames are short and unfragmented,
+  // and continuation frames only follow an unfinished text/binary frame.
+  private checkFrame(frame: DecodedFrame): string | undefined {
+    if (!frame.masked) {
+      return "Client WebSocket frames must be masked";
+    }
+
+    if (frame.rsv !== 0) {
+      return "WebSocket extension bits set without a negotiated extension";
+    }
+
+    if (frame.opcode >= OPCODE_CLOSE) {
+      return !frame.fin || frame.payload.length > 125 ? "Invalid WebSocket control frame" : undefined;
+    }
+
+    if (frame.opcode === OPCODE_CONTINUATION && !this.messageInProgress) {
+      return "WebSocket continuation frame without a message in progress";
+    }
+
+    if (frame.opcode !== OPCODE_CONTINUATION && this.messageInProgress) {
+      return "New WebSocket message started before the previous one finished";
+    }
+
+    return undefined;
+  }
+
+  private handleFrame(fin: boolean, opcode: number, payload: Buffer): void {
+    if (opcode === OPCODE_PING) {
+      this.socket.write(encodeFrame(OPCODE_PONG, payload));
+      return;
+    }
+
+    if (opcode === OPCODE_PONG) {
+      return;
+    }
+
+    if (opcode === OPCODE_CLOSE) {
+      this.sendClose(1000);
+      this.handleClosed();
+      return;
+    }
+
+    if (opcode !== OPCODE_TEXT && opcode !== OPCODE_BINARY && opcode !== OPCODE_CONTINUATION) {
+      this.failConnection(1002, `Unsupported WebSocket opcode: ${opcode}`);
+      return;
+    }
+
+    this.fragments.push(payload);
+    const messageBytes = this.fragments.reduce((total, fragment) => total + fragment.length, 0);
+    if (messageBytes > this.maxMessageBytes) {
+      this.failConnection(1009, "WebSocket message too large");
+      return;
+    }
+
+    if (!fin) {
+      this.messageInProgress = true;
+      return;
+    }
+
+    const text = Buffer.concat(this.fragments).toString("utf8");
+    this.fragments = [];
+    this.messageInProgress = false;
+
+    try {
+      this.onmessage?.(JSONRPCMessageSchema.parse(JSON.parse(text)));
+    } catch (error) {
+      this.onerror?.(error instanceof Error ? error : new Error(String(error)));
+    }
+  }
+
+  private failConnection(code: number, reason: string): void {
+    this.onerror?.(new Error(reason));
+    this.sendClose(code);
+    this.handleClosed();
+  }
+
+  private sendClose(code: number): void {
+    if (this.closed || this.socket.destroyed) {
+      return;
+    }
+
+    const payload = Buffer.alloc(2);
+    payload.writeUInt16BE(code, 0);
+    this.socket.end(encodeFrame(OPCODE_CLOSE, payload));
+  }
+
+  private handleClosed(): void {
+    if (this.closed) {
+      return;
+    }
+
+    this.closed = true;
+    this.socket.destroy();
+    this.onclose?.();
+  }
+}
+
+export async function startWebSocketServer(
+  createMcpServer: (req?: IncomingMessage) => McpServer,
+  options: WebSocketOptions,
+): Promise<WebSocketRuntime> {
+  const sessions = new Set<McpServer>();
+
+  const server = createListener(options.tls, (req, res) => {
+    res.statusCode = 426;
+    res.setHeader("Content-Type", "application/json");
+    res.setHeader("Upgrade", "websocket");
+    res.end(JSON.stringify({ error: `Use a WebSocket connection to ${options.path}` }));
+  });
+
+  server.on("upgrade", (req: IncomingMessage, socket: Duplex, head: Buffer) => {
+    const requestUrl = new URL(req.url ?? "/", `http://${req.headers.host ?? "localhost"}`);
+    const key = req.headers["sec-websocket-key"];
+
+    if (requestUrl.pathname !== options.path || typeof key !== "string" || !isWebSocketUpgrade(req)) {
+      socket.end("HTTP/1.1 400 Bad Request\r\nConnection: close\r\n\r\n");
+      return;
+    }
+
+    if (req.headers["sec-websocket-version"] !== "13") {
+      socket.end("HTTP/1.1 426 Upgrade Required\r\nSec-WebSocket-Version: 13\r\nConnection: close\r\n\r\n");
+      return;
+    }
+
+    if (!isAuthorized(req, options.authTokens, true)) {
+      socket.end(
+        'HTTP/1.1 401 Unauthorized\r\nWWW-Authenticate: Bearer realm="deepseek-mcp-server"\r\nConnection: close\r\n\r\n',
+      );
+      return;
+    }
+
+    const requestedProtocols = String(req.headers["sec-websocket-protocol"] ?? "")
+      .split(",")
+      .map((protocol) => protocol.trim());
+    socket.write(
+      [
+        "HTTP/1.1 101 Switching Protocols",
+        "Upgrade: websocket",
+        "Connection: Upgrade",
+        `Sec-WebSocket-Accept: ${createHash("sha1").update(key + WEBSOCKET_GUID).digest("base64")}`,
+        ...(requestedProtocols.includes("mcp") ? ["Sec-WebSocket-Protocol: mcp"] : []),
+        "",
+        "",
+      ].join("\r\n"),
+    );
+
+    if (head.length > 0) {
+      socket.unshift(head);
+    }
+
+    const transport = new WebSocketServerTransport(socket, options.maxMessageBytes);
+    const mcpServer = createMcpServer(req);
+    sessions.add(mcpServer);
+    transport.onclose = () => {
+      if (sessions.delete(mcpServer)) {
+        void mcpServer.close();
+      }
+    };
+
+    void mcpServer.connect(transport).catch(() => {
+      socket.destroy();
+    });
+  });
+
+  await new Promise<void>((resolve, reject) => {
+    server.once("error", reject);
+    server.listen(options.port, options.host, () => resolve());
+  });
+
+  return {
+    server,
+    port: (server.address() as AddressInfo).port,
+    close: async () => {
+      const active = [...sessions];
+      sessions.clear();
+      await Promise.all(active.map((mcpServer) => mcpServer.close()));
+      await closeServer(server);
+    },
+  };
+}
+
+function isWebSocketUpgrade(req: IncomingMessage): boolean {
+  return String(req.headers.upgrade ?? "").toLowerCase() === "websocket";
+}
+
+interface DecodedFrame {
+  fin: boolean;
+  rsv: number;
+  opcode: number;
+  masked: boolean;
+  payload: Buffer;
+  frameLength: number;
+}
+
+function decodeFrame(buffer: Buffer): DecodedFrame | undefined {
+  if (buffer.length < 2) {
+    return undefined;
+  }
+
+  const fin = (buffer[0] & 0x80) !== 0;
+  const rsv = buffer[0] & 0x70;
+  const opcode = buffer[0] & 0x0f;
+  const masked = (buffer[1] & 0x80) !== 0;
+  let payloadLength = buffer[1] & 0x7f;
+  let offset = 2;
+
+  if (payloadLength === 126) {
+    if (buffer.length < offset + 2) {
+      return undefined;
+    }
+    payloadLength = buffer.readUInt16BE(offset);
+    offset += 2;
+  } else if (payloadLength === 127) {
+    if (buffer.length < offset + 8) {
+      return undefined;
+    }
+    payloadLength = Number(buffer.readBigUInt64BE(offset));
+    offset += 8;
+  }
+
+  const maskLength = masked ? 4 : 0;
+  if (buffer.length < offset + maskLength + payloadLength) {
+    return undefined;
+  }
+
+  const mask = masked ? buffer.subarray(offset, offset + 4) : undefined;
+  offset += maskLength;
+  const payload = Buffer.from(buffer.subarray(offset, offset + payloadLength));
+  if (mask) {
+    for (let index = 0; index < payload.length; index += 1) {
+      payload[index] ^= mask[index % 4];
+    }
+  }
+
+  return { fin, rsv, opcode, masked, payload, frameLength: offset + payloadLength };
+}
+
+function encodeFrame(opcode: number, payload: Buffer): Buffer {
+  let header: Buffer;
+
+  if (payload.length < 126) {
+    header = Buffer.from([0x80 | opcode, payload.length]);
+  } else if (payload.length < 0x10000) {
+    header = Buffer.alloc(4);
+    header[0] = 0x80 | opcode;
+    header[1] = 126;
+    header.writeUInt16BE(payload.length, 2);
+  } else {
+    header = Buffer.alloc(10);
+    header[0] = 0x80 | opcode;
+    header[1] = 127;
+    header.writeBigUInt64BE(BigInt(payload.length), 2);
+  }
+
+  return Buffer.concat([header, payload]);
+}
+
+function closeServer(server: Server): Promise<void> {
+  return new Promise((resolve, reject) => {
+    server.close((error) => {
+      if (error) {
+        reject(error);
+        return;
+      }
+
+      resolve();
+    });
+  });
+}
@@ -0,0 +1,79 @@
+import { DeepSeekUsage } from "./deepseek/types.js";
+
+export interface UsageTotals {
+  requests: number;
+  fallbacks: number;
+  prompt_tokens: number;
+  completion_tokens: number;
+  total_tokens: number;
+  prompt_cache_hit_tokens: number;
+  prompt_cache_miss_tokens: number;
+  reasoning_tokens: number;
+  estimated_cost_usd: number;
+}
+
+export interface UsageSummary extends UsageTotals {
+  since: string;
+  by_model: Record<string, UsageTotals>;
+}
+
+const emptyTotals = (): UsageTotals => ({
+  requests: 0,
+  fallbacks: 0,
+  prompt_tokens: 0,
+  completion_tokens: 0,
+  total_tokens: 0,
+  prompt_cache_hit_tokens: 0,
+  prompt_cache_miss_tokens: 0,
+  reasoning_tokens: 0,
+  estimated_cost_usd: 0,
+});
+
+// In-memory tally of the upstream calls made by one MCP session, so agents can report their own spend. Stateless
+// HTTP serves every client from one server, so there the tally is server-wide.
+export class UsageTracker {
+  private readonly now: () => Date;
+  private since: Date;
+  private totals = emptyTotals();
+  private byModel = new Map<string, UsageTotals>();
+
+  constructor(now: () => Date = () => new Date()) {
+    this.now = now;
+    this.since = now();
+  }
+
+  record(model: string, usage: DeepSeekUsage | undefined, costUsd: number, fallback = false): void {
+    let modelTotals = this.byModel.get(model);
+    if (!modelTotals) {
+      modelTotals = emptyTotals();
+      this.byModel.set(model, modelTotals);
+    }
+
+    for (const totals of [this.totals, modelTotals]) {
+      totals.requests += 1;
+      totals.fallbacks += fallback ? 1 : 0;
+      totals.prompt_tokens += usage?.prompt_tokens ?? 0;
+      totals.completion_tokens += usage?.completion_tokens ?? 0;
+      totals.total_tokens += usage?.total_tokens ?? 0;
+      totals.prompt_cache_hit_tokens += usage?.prompt_cache_hit_tokens ?? 0;
+      totals.prompt_cache_miss_tokens += usage?.prompt_cache_miss_tokens ?? 0;
+      totals.reasoning_tokens += usage?.completion_tokens_details?.reasoning_tokens ?? 0;
+      totals.estimated_cost_usd += costUsd;
+    }
+  }
+
+  summary(): UsageSummary {
+    const byModel: Record<string, UsageTotals> = {};
+    for (const model of [...this.byModel.keys()].sort()) {
+      byModel[model] = { ...(this.byModel.get(model) as UsageTotals) };
+    }
+
+    return { ...this.totals, since: this.since.toISOString(), by_model: byModel };
+  }
+
+  reset(): void {
+    this.since = this.now();
+    this.totals = emptyTotals();
+    this.byModel = new Map();
+  }
+}
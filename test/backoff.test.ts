@@ -0,0 +1,21 @@
+import { describe, expect, it } from "vitest";
+
+import { computeBackoffDelay, createSeededRandom } from "../src/deepseek/backoff.js";
+
+describe("computeBackoffDelay", () => {
+  it("doubles the base delay per attempt and adds the jitter fraction", () => {
+    expect([0, 1, 2, 3].map((attempt) => computeBackoffDelay(attempt, 100, 0))).toEqual([100, 200, 400, 800]);
+    expect(computeBackoffDelay(1, 100, 0.25)).toBe(250);
+  });
+
+  it("produces a reproducible delay sequence for a fixed seed and caps at the maximum", () => {
+    const delaysForSeed = (seed: number) => {
+      const random = createSeededRandom(seed);
+      return [0, 1, 2, 3, 4].map((attempt) => computeBackoffDelay(attempt, 100, random() * 0.5, 1000));
+    };
+
+    expect(delaysForSeed(42)).toEqual([130, 245, 570, 1000, 1000]);
+    expect(delaysForSeed(42)).toEqual(delaysForSeed(42));
+    expect(delaysForSeed(7)).not.toEqual(delaysForSeed(42));
+  });
+});
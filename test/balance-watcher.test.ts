@@ -0,0 +1,39 @@
+import { describe, expect, it, vi } from "vitest";
+
+import { BalanceWatcher } from "../src/balance-watcher.js";
+
+describe("BalanceWatcher", () => {
+  it("notifies subscribers only when the polled balance changes", async () => {
+    const balances = [{ total: "10" }, { total: "10" }, { total: "9" }];
+    const fetchBalance = vi.fn(async () => balances.shift());
+    const watcher = new BalanceWatcher({ fetchBalance, intervalMs: 60_000 });
+    const listener = vi.fn();
+
+    const unsubscribe = watcher.subscribe(listener);
+    await vi.waitFor(() => expect(fetchBalance).toHaveBeenCalledTimes(1));
+
+    await expect(watcher.poll()).resolves.toBe(false);
+    await expect(watcher.poll()).resolves.toBe(true);
+    expect(listener).toHaveBeenCalledTimes(1);
+
+    unsubscribe();
+    expect(watcher.subscriberCount).toBe(0);
+  });
+
+  it("treats a failed poll as no change", async () => {
+    const fetchBalance = vi
+      .fn<() => Promise<unknown>>()
+      .mockResolvedValueOnce({ total: "10" })
+      .mockRejectedValueOnce(new Error("upstream down"))
+      .mockResolvedValueOnce({ total: "10" });
+    const watcher = new BalanceWatcher({ fetchBalance, intervalMs: 60_000 });
+    const listener = vi.fn();
+    const unsubscribe = watcher.subscribe(listener);
+    await vi.waitFor(() => expect(fetchBalance).toHaveBeenCalledTimes(1));
+
+    await expect(watcher.poll()).resolves.toBe(false);
+    await expect(watcher.poll()).resolves.toBe(false);
+    expect(listener).not.toHaveBeenCalled();
+    unsubscribe();
+  });
+});
@@ -0,0 +1,35 @@
+import { mkdtemp, readFile, rm } from "node:fs/promises";
+import { tmpdir } from "node:os";
+import { join } from "node:path";
+
+import { describe, expect, it } from "vitest";
+
+import { BudgetExceededError, BudgetTracker } from "../src/budget-tracker.js";
+
+describe("BudgetTracker", () => {
+  it("persists spend and resets it when a new calendar month starts", async () => {
+    const dir = await mkdtemp(join(tmpdir(), "deepseek-budget-"));
+    const filePath = join(dir, "budget.json");
+    let now = new Date("2026-03-31T23:00:00.000Z");
+
+    try {
+      const tracker = new BudgetTracker({ monthlyBudgetUsd: 1, filePath, now: () => now });
+      await tracker.record(0.4);
+      await tracker.record(0.5);
+
+      const reloaded = new BudgetTracker({ monthlyBudgetUsd: 1, filePath, now: () => now });
+      expect(await reloaded.getStatus()).toMatchObject({ month: "2026-03", spent_usd: 0.9, exceeded: false });
+      await expect(reloaded.record(0.2)).rejects.toBeInstanceOf(BudgetExceededError);
+      await expect(reloaded.assertAvailable()).rejects.toBeInstanceOf(BudgetExceededError);
+
+      now = new Date("2026-04-01T00:00:00.000Z");
+      await expect(reloaded.assertAvailable()).resolves.toBeUndefined();
+      expect(await reloaded.getStatus()).toMatchObject({ month: "2026-04", spent_usd: 0, remaining_usd: 1 });
+
+      await reloaded.record(0.1);
+      expect(JSON.parse(await readFile(filePath, "utf8"))).toEqual({ month: "2026-04", spent_usd: 0.1 });
+    } finally {
+      await rm(dir, { recursive: true, force: true });
+    }
+  });
+});
@@ -0,0 +1,20 @@
+import { describe, expect, it } from "vitest";
+
+import { clientProtocolVersion, supportsStructuredContent } from "../src/client-profile.js";
+
+const withHeaders = (headers: Record<string, string | string[]>) =>
+  ({ requestInfo: { headers } }) as unknown as Parameters<typeof clientProtocolVersion>[0];
+
+describe("client protocol version", () => {
+  it("reads the version from the request's MCP-Protocol-Version header", () => {
+    expect(clientProtocolVersion(withHeaders({ "mcp-protocol-version": "2025-03-26" }))).toBe("2025-03-26");
+    expect(clientProtocolVersion(withHeaders({}))).toBeUndefined();
+    expect(clientProtocolVersion({} as Parameters<typeof clientProtocolVersion>[0])).toBeUndefined();
+  });
+
+  it("keeps structured results unless the client's protocol predates them", () => {
+    expect(supportsStructuredContent("2025-06-18")).toBe(true);
+    expect(supportsStructuredContent(undefined)).toBe(true);
+    expect(supportsStructuredContent("2025-03-26")).toBe(false);
+  });
+});
@@ -0,0 +1,24 @@
+import { describe, expect, it } from "vitest";
+
+import { mapWithConcurrency } from "../src/concurrency.js";
+
+describe("mapWithConcurrency", () => {
+  it("keeps input order while capping the number of calls in flight", async () => {
+    let inFlight = 0;
+    let peak = 0;
+    const results = await mapWithConcurrency([30, 10, 20, 5, 15], 2, async (delay, index) => {
+      inFlight += 1;
+      peak = Math.max(peak, inFlight);
+      await new Promise((resolve) => setTimeout(resolve, delay));
+      inFlight -= 1;
+      return `${index}:${delay}`;
+    });
+
+    expect(results).toEqual(["0:30", "1:10", "2:20", "3:5", "4:15"]);
+    expect(peak).toBe(2);
+  });
+
+  it("returns an empty array for no items", async () => {
+    await expect(mapWithConcurrency([], 4, async () => 1)).resolves.toEqual([]);
+  });
+});
@@ -0,0 +1,179 @@
+import { describe, expect, it } from "vitest";
+
+import { loadRuntimeConfig } from "../src/config.js";
+
+describe("loadRuntimeConfig", () => {
+  it("parses connection pool settings and falls back to defaults on invalid values", () => {
+    const configured = loadRuntimeConfig({
+      DEEPSEEK_API_KEY: "test-key",
+      DEEPSEEK_POOL_MAX_CONNECTIONS_PER_HOST: "4",
+      DEEPSEEK_POOL_IDLE_TIMEOUT_MS: "1500",
+      DEEPSEEK_TCP_KEEPALIVE: "false",
+    });
+
+    expect(configured.poolMaxConnectionsPerHost).toBe(4);
+    expect(configured.poolIdleTimeoutMs).toBe(1500);
+    expect(configured.tcpKeepAlive).toBe(false);
+
+    const invalid = loadRuntimeConfig({
+      DEEPSEEK_API_KEY: "test-key",
+      DEEPSEEK_POOL_MAX_CONNECTIONS_PER_HOST: "-3",
+      DEEPSEEK_POOL_IDLE_TIMEOUT_MS: "soon",
+      DEEPSEEK_TCP_KEEPALIVE: "maybe",
+    });
+
+    expect(invalid.poolMaxConnectionsPerHost).toBeUndefined();
+    expect(invalid.poolIdleTimeoutMs).toBe(60000);
+    expect(invalid.tcpKeepAlive).toBe(true);
+  });
+
+  it("selects the transport from --transport, then DEEPSEEK_TRANSPORT, then MCP_TRANSPORT", () => {
+    const env = { DEEPSEEK_API_KEY: "test-key", MCP_TRANSPORT: "streamable-http" };
+
+    expect(loadRuntimeConfig(env).transports).toEqual(["streamable-http"]);
+    expect(loadRuntimeConfig({ ...env, DEEPSEEK_TRANSPORT: "SSE" }).transports).toEqual(["sse"]);
+    expect(loadRuntimeConfig({ ...env, DEEPSEEK_TRANSPORT: "sse" }, ["--transport", "stdio"]).transports).toEqual([
+      "stdio",
+    ]);
+    expect(loadRuntimeConfig(env, ["--transport=sse"]).transports).toEqual(["sse"]);
+    expect(loadRuntimeConfig({ ...env, DEEPSEEK_TRANSPORT: "stdio, streamable-http,stdio" }).transports).toEqual([
+      "stdio",
+      "streamable-http",
+    ]);
+  });
+
+  it("selects the TCP transport from --listen and parses the listen address", () => {
+    const config = loadRuntimeConfig({ DEEPSEEK_API_KEY: "test-key", MCP_TCP_ALLOW_REMOTE: "1" }, [
+      "--listen",
+      "0.0.0.0:7500",
+    ]);
+
+    expect(config.transports).toEqual(["tcp"]);
+    expect(config.tcpHost).toBe("0.0.0.0");
+    expect(config.tcpPort).toBe(7500);
+
+    const defaults = loadRuntimeConfig({ DEEPSEEK_API_KEY: "test-key", MCP_TRANSPORT: "tcp" });
+    expect(defaults.transports).toEqual(["tcp"]);
+    expect(defaults.tcpHost).toBe("127.0.0.1");
+    expect(defaults.tcpPort).toBe(7400);
+  });
+
+  it("refuses a non-loopback TCP listen host unless MCP_TCP_ALLOW_REMOTE is set", () => {
+    expect(() => loadRuntimeConfig({ DEEPSEEK_API_KEY: "test-key" }, ["--listen", "0.0.0.0:7500"])).toThrow(
+      "MCP_TCP_ALLOW_REMOTE",
+    );
+    expect(loadRuntimeConfig({ DEEPSEEK_API_KEY: "test-key" }, ["--listen", "localhost:7500"]).tcpHost).toBe(
+      "localhost",
+    );
+    // The listen address only matters when the TCP transport is selected.
+    expect(() =>
+      loadRuntimeConfig({ DEEPSEEK_API_KEY: "test-key", MCP_TCP_LISTEN: "0.0.0.0:7500", MCP_TRANSPORT: "stdio" }),
+    ).not.toThrow();
+  });
+
+  it("selects the pipe transport from --pipe", () => {
+    const config = loadRuntimeConfig({ DEEPSEEK_API_KEY: "test-key" }, ["--pipe", "\\\\.\\pipe\\deepseek-mcp"]);
+
+    expect(config.transports).toEqual(["pipe"]);
+    expect(config.pipePath).toBe("\\\\.\\pipe\\deepseek-mcp");
+
+    const fromEnv = loadRuntimeConfig({ DEEPSEEK_API_KEY: "test-key", MCP_TRANSPORT: "pipe" });
+    expect(fromEnv.transports).toEqual(["pipe"]);
+    expect(fromEnv.pipePath).toBeUndefined();
+  });
+
+  it("prefixes HTTP, SSE and WebSocket routes with --base-path", () => {
+    const config = loadRuntimeConfig({ DEEPSEEK_API_KEY: "test-key", MCP_BASE_PATH: "ignored" }, [
+      "--base-path=mcp/deepseek/",
+    ]);
+
+    expect(config.basePath).toBe("/mcp/deepseek");
+    expect(config.httpPath).toBe("/mcp/deepseek/mcp");
+    expect(config.ssePath).toBe("/mcp/deepseek/sse");
+    expect(config.sseMessagesPath).toBe("/mcp/deepseek/messages");
+    expect(config.webSocketPath).toBe("/mcp/deepseek/ws");
+
+    const defaults = loadRuntimeConfig({ DEEPSEEK_API_KEY: "test-key" });
+    expect(defaults.basePath).toBe("");
+    expect(defaults.httpPath).toBe("/mcp");
+  });
+
+  it("parses DEEPSEEK_DISABLED_TOOLS as a trimmed comma-separated list", () => {
+    const config = loadRuntimeConfig({
+      DEEPSEEK_API_KEY: "test-key",
+      DEEPSEEK_DISABLED_TOOLS: " get_user_balance, ,completion ",
+    });
+
+    expect(config.disabledTools).toEqual(["get_user_balance", "completion"]);
+  });
+
+  it("interpolates ${VAR} references in the base URL and extra headers", () => {
+    const config = loadRuntimeConfig({
+      DEEPSEEK_API_KEY: "test-key",
+      REGION: "eu",
+      TENANT: "acme",
+      DEEPSEEK_BASE_URL: "https://${REGION}.api.deepseek.com",
+      DEEPSEEK_EXTRA_HEADERS: '{"X-Tenant":"${TENANT}-${REGION}","X-Static":"plain"}',
+    });
+
+    expect(config.deepseekBaseUrl).toBe("https://eu.api.deepseek.com");
+    expect(config.extraHeaders).toEqual({ "X-Tenant": "acme-eu", "X-Static": "plain" });
+  });
+
+  it("rejects references to unset environment variables", () => {
+    expect(() =>
+      loadRuntimeConfig({
+        DEEPSEEK_API_KEY: "test-key",
+        DEEPSEEK_BASE_URL: "https://${REGION}.api.deepseek.com",
+      }),
+    ).toThrow("DEEPSEEK_BASE_URL references unset environment variable REGION");
+
+    expect(() =>
+      loadRuntimeConfig({
+        DEEPSEEK_API_KEY: "test-key",
+        DEEPSEEK_EXTRA_HEADERS: '{"X-Tenant":"${TENANT}"}',
+      }),
+    ).toThrow("DEEPSEEK_EXTRA_HEADERS.X-Tenant references unset environment variable TENANT");
+  });
+
+  it("requires an API key unless DEEPSEEK_OFFLINE or DEEPSEEK_SAMPLING_FALLBACK is set", () => {
+    expect(() => loadRuntimeConfig({})).toThrow("DEEPSEEK_API_KEY environment variable is required");
+
+    const offline = loadRuntimeConfig({ DEEPSEEK_OFFLINE: "1" });
+    expect(offline.offline).toBe(true);
+    expect(offline.deepseekApiKey).toBe("");
+
+    const sampling = loadRuntimeConfig({ DEEPSEEK_SAMPLING_FALLBACK: "1" });
+    expect(sampling.samplingFallback).toBe(true);
+    expect(sampling.offline).toBe(false);
+  });
+
+  it("gives each HTTP-based transport its own port and rejects collisions", () => {
+    expect(() =>
+      loadRuntimeConfig({ DEEPSEEK_API_KEY: "test-key", MCP_TRANSPORT: "streamable-http,websocket" }),
+    ).toThrow("streamable-http and websocket would both listen on port 3001");
+
+    const config = loadRuntimeConfig({
+      DEEPSEEK_API_KEY: "test-key",
+      MCP_TRANSPORT: "streamable-http,sse,websocket",
+      MCP_SSE_PORT: "3002",
+      MCP_WS_PORT: "3003",
+    });
+    expect([config.httpPort, config.ssePort, config.webSocketPort]).toEqual([3001, 3002, 3003]);
+    expect(loadRuntimeConfig({ DEEPSEEK_API_KEY: "test-key", MCP_TRANSPORT: "sse" }).ssePort).toBe(3001);
+  });
+
+  it("requires DEEPSEEK_TLS_CERT and DEEPSEEK_TLS_KEY to be set together", () => {
+    expect(() => loadRuntimeConfig({ DEEPSEEK_API_KEY: "test-key", DEEPSEEK_TLS_CERT: "/tmp/cert.pem" })).toThrow(
+      "DEEPSEEK_TLS_CERT and DEEPSEEK_TLS_KEY must be set together",
+    );
+
+    const config = loadRuntimeConfig({
+      DEEPSEEK_API_KEY: "test-key",
+      DEEPSEEK_TLS_CERT: "/tmp/cert.pem",
+      DEEPSEEK_TLS_KEY: "/tmp/key.pem",
+    });
+    expect(config.tlsCertFile).toBe("/tmp/cert.pem");
+    expect(config.tlsKeyFile).toBe("/tmp/key.pem");
+  });
+});
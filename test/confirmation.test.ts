@@ -0,0 +1,41 @@
+import { describe, expect, it } from "vitest";
+
+import { estimateRequestCost, estimateRequestsCost, exceedsConfirmationThreshold } from "../src/confirmation.js";
+
+describe("estimateRequestCost", () => {
+  it("counts the prompt plus the full max output budget", () => {
+    const estimate = estimateRequestCost({
+      model: "deepseek-chat",
+      messages: [{ role: "user", content: "hello" }],
+      max_tokens: 1000,
+    });
+
+    expect(estimate.maxOutputTokens).toBe(1000);
+    expect(estimate.totalTokens).toBe(estimate.promptTokens + 1000);
+    expect(estimate.costUsd).toBeGreaterThan(0);
+  });
+
+  it("assumes the model's full output length when max_tokens is unset and sums several requests", () => {
+    const messages = [{ role: "user" as const, content: "hello" }];
+    const chat = estimateRequestCost({ model: "deepseek-chat", messages });
+    const reasoner = estimateRequestCost({ model: "deepseek-reasoner", messages });
+
+    expect(chat.maxOutputTokens).toBe(8192);
+    expect(reasoner.maxOutputTokens).toBe(65536);
+    expect(
+      estimateRequestsCost([
+        { model: "deepseek-chat", messages },
+        { model: "deepseek-reasoner", messages },
+      ]).totalTokens,
+    ).toBe(chat.totalTokens + reasoner.totalTokens);
+  });
+
+  it("only exceeds a threshold that is configured", () => {
+    const estimate = { promptTokens: 50, maxOutputTokens: 100, totalTokens: 150, costUsd: 0.01 };
+
+    expect(exceedsConfirmationThreshold(estimate, {})).toBe(false);
+    expect(exceedsConfirmationThreshold(estimate, { tokens: 200 })).toBe(false);
+    expect(exceedsConfirmationThreshold(estimate, { tokens: 100 })).toBe(true);
+    expect(exceedsConfirmationThreshold(estimate, { usd: 0.005 })).toBe(true);
+  });
+});
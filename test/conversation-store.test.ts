@@ -0,0 +1,78 @@
+import { describe, expect, it } from "vitest";
+
+import { ConversationStore } from "../src/conversation-store.js";
+
+describe("ConversationStore", () => {
+  it("evicts the least-recently-used session when the session cap is exceeded", () => {
+    let tick = 0;
+    const store = new ConversationStore(200, 2, () => new Date(Date.UTC(2026, 0, 1, 0, 0, tick++)));
+
+    store.set("a", [{ role: "user", content: "first" }]);
+    store.set("b", [{ role: "user", content: "second" }]);
+    store.append("a", [{ role: "assistant", content: "reply" }]);
+    store.set("c", [{ role: "user", content: "third" }]);
+
+    expect(store.listConversationIds()).toEqual(["a", "c"]);
+
+    const [sessionA] = store.listSessions();
+    expect(sessionA).toMatchObject({
+      conversation_id: "a",
+      message_count: 2,
+      turn_count: 1,
+      created_at: "2026-01-01T00:00:00.000Z",
+      last_used_at: "2026-01-01T00:00:02.000Z",
+    });
+    expect(sessionA?.estimated_tokens).toBeGreaterThan(0);
+  });
+
+  it("treats a read as use when choosing which session to evict", () => {
+    const store = new ConversationStore(200, 2);
+
+    store.set("a", [{ role: "user", content: "first" }]);
+    store.set("b", [{ role: "user", content: "second" }]);
+    expect(store.get("a")).toEqual([{ role: "user", content: "first" }]);
+    store.set("c", [{ role: "user", content: "third" }]);
+
+    expect(store.listConversationIds()).toEqual(["a", "c"]);
+  });
+
+  it("describes a single session and returns undefined for unknown ids", () => {
+    const store = new ConversationStore();
+    store.set("a", [{ role: "user", content: "hi" }]);
+    store.recordUsage("a", { prompt_tokens: 5, completion_tokens: 7, total_tokens: 12 });
+
+    expect(store.getSession("a")).toMatchObject({
+      conversation_id: "a",
+      prompt_tokens_total: 5,
+      completion_tokens_total: 7,
+    });
+    expect(store.getSession("b")).toBeUndefined();
+  });
+
+  it("tracks empty conversations as existing until cleared", () => {
+    const store = new ConversationStore();
+    store.set("empty", []);
+
+    expect(store.has("empty")).toBe(true);
+    expect(store.getSession("empty")).toMatchObject({ message_count: 0 });
+    expect(store.clear("empty")).toBe(true);
+    expect(store.has("empty")).toBe(false);
+  });
+
+  it("keeps scoped views apart while sharing the session limit", () => {
+    const store = new ConversationStore(200, 2);
+    const alice = store.scoped("alice");
+    const bob = store.scoped("bob");
+
+    alice.set("thread", [{ role: "user", content: "from alice" }]);
+    bob.set("thread", [{ role: "user", content: "from bob" }]);
+    expect(alice.get("thread")).toEqual([{ role: "user", content: "from alice" }]);
+    expect(bob.listConversationIds()).toEqual(["thread"]);
+    expect(bob.getSession("thread")).toMatchObject({ conversation_id: "thread", message_count: 1 });
+
+    bob.set("other", []);
+    expect(bob.has("thread")).toBe(false);
+    expect(alice.has("thread")).toBe(true);
+    expect(bob.listConversationIds()).toEqual(["other"]);
+  });
+});
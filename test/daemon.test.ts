@@ -0,0 +1,60 @@
+import { mkdtemp, readFile, rm, writeFile } from "node:fs/promises";
+import { tmpdir } from "node:os";
+import { join } from "node:path";
+
+import { afterEach, describe, expect, it, vi } from "vitest";
+
+import { daemonStatus, parseDaemonOptions, readPidFile, startDaemon, stopDaemon } from "../src/daemon.js";
+
+describe("daemon commands", () => {
+  let dir: string | undefined;
+
+  afterEach(async () => {
+    vi.restoreAllMocks();
+    if (dir) {
+      await rm(dir, { recursive: true, force: true });
+      dir = undefined;
+    }
+  });
+
+  it("parses the subcommand and PID/log file locations", () => {
+    const options = parseDaemonOptions({ DEEPSEEK_LOG_FILE: "/var/log/ds.log" }, ["--stop", "--pid-file=/run/ds.pid"]);
+
+    expect(options).toEqual({ command: "stop", pidFile: "/run/ds.pid", logFile: "/var/log/ds.log" });
+    expect(parseDaemonOptions({}, ["--daemon"]).command).toBe("start");
+    expect(parseDaemonOptions({}, []).command).toBeUndefined();
+    expect(parseDaemonOptions({ XDG_RUNTIME_DIR: "/run/user/1000" }, [])).toMatchObject({
+      pidFile: "/run/user/1000/deepseek-mcp-server.pid",
+      logFile: "/run/user/1000/deepseek-mcp-server.log",
+      runtimeDir: "/run/user/1000",
+    });
+  });
+
+  it("refuses to start over a PID file that a running server holds", async () => {
+    const errors = vi.spyOn(console, "error").mockImplementation(() => undefined);
+    dir = await mkdtemp(join(tmpdir(), "deepseek-daemon-"));
+    const pidFile = join(dir, "server.pid");
+    await writeFile(pidFile, `${process.pid}\n`);
+
+    expect(startDaemon({ pidFile, logFile: join(dir, "server.log") }, ["--daemon"])).toBe(1);
+    expect(errors).toHaveBeenCalledWith(expect.stringContaining(`pid ${process.pid}`));
+    await expect(readFile(join(dir, "server.log"))).rejects.toThrow();
+  });
+
+  it("reports status from the PID file and cleans up a stale one on --stop", async () => {
+    vi.spyOn(console, "log").mockImplementation(() => undefined);
+    dir = await mkdtemp(join(tmpdir(), "deepseek-daemon-"));
+    const pidFile = join(dir, "server.pid");
+    const options = { pidFile, logFile: join(dir, "server.log") };
+
+    expect(daemonStatus(options)).toBe(3);
+
+    await writeFile(pidFile, `${process.pid}\n`);
+    expect(readPidFile(pidFile)).toBe(process.pid);
+    expect(daemonStatus(options)).toBe(0);
+
+    await writeFile(pidFile, "2147483646\n");
+    expect(stopDaemon(options)).toBe(1);
+    await expect(readFile(pidFile, "utf8")).rejects.toThrow();
+  });
+});
@@ -1,6 +1,19 @@
+import { mkdtemp, readdir, rm } from "node:fs/promises";
+import { createServer } from "node:http";
+import { AddressInfo } from "node:net";
+import { tmpdir } from "node:os";
+import { join } from "node:path";
+import { gunzipSync } from "node:zlib";
+
 import { describe, expect, it, vi } from "vitest";
 
-import { DeepSeekApiClient, DeepSeekApiError } from "../src/deepseek/client.js";
+import { DeepSeekApiClient, DeepSeekApiError, signRequestBody } from "../src/deepseek/client.js";
+import {
+  connectionPoolAgentOptions,
+  DEFAULT_POOL_IDLE_TIMEOUT_MS,
+  resolveConnectionPoolOptions,
+  TCP_KEEPALIVE_INITIAL_DELAY_MS,
+} from "../src/deepseek/pooled-fetch.js";
 import { V4_ENDPOINTS } from "../src/deepseek/v4-mapping.js";
 
 function jsonResponse(payload: unknown, status = 200): Response {
@@ -164,6 +177,44 @@ describe("DeepSeekApiClient", () => {
     expect(result.response.choices[0]?.finish_reason).toBe("tool_calls");
   });
 
+  it("reconstructs a standard non-streaming chat completion object from delta chunks", async () => {
+    const chunk = (extra: Record<string, unknown>) => ({
+      id: "chat-stream-2",
+      object: "chat.completion.chunk",
+      created: 20,
+      model: "deepseek-chat",
+      system_fingerprint: "fp_1",
+      ...extra,
+    });
+    const fetchMock = vi.fn<typeof fetch>().mockResolvedValue(
+      sseResponse([
+        chunk({ choices: [{ index: 0, delta: { role: "assistant", content: "" }, finish_reason: null }] }),
+        chunk({ choices: [{ index: 0, delta: { content: "Par" }, finish_reason: null }] }),
+        chunk({ choices: [{ index: 0, delta: { content: "is" }, finish_reason: "stop" }], usage: null }),
+        chunk({ choices: [], usage: { prompt_tokens: 9, prompt_cache_hit_tokens: 0 } }),
+        chunk({ choices: [], usage: { completion_tokens: 2, total_tokens: 11 } }),
+        "[DONE]",
+      ]),
+    );
+
+    const client = new DeepSeekApiClient({ apiKey: "test-key", fetchFn: fetchMock });
+    const result = await client.createChatCompletion({
+      model: "deepseek-chat",
+      stream: true,
+      messages: [{ role: "user", content: "Capital of France?" }],
+    });
+
+    expect(result.response).toEqual({
+      id: "chat-stream-2",
+      object: "chat.completion",
+      created: 20,
+      model: "deepseek-chat",
+      system_fingerprint: "fp_1",
+      choices: [{ index: 0, finish_reason: "stop", message: { role: "assistant", content: "Paris" } }],
+      usage: { prompt_tokens: 9, prompt_cache_hit_tokens: 0, completion_tokens: 2, total_tokens: 11 },
+    });
+  });
+
   it("falls back from deepseek-reasoner to deepseek-chat on retriable failures", async () => {
     const fetchMock = vi
       .fn<typeof fetch>()
@@ -222,6 +273,146 @@ describe("DeepSeekApiClient", () => {
     expect(result.response.choices[0]?.message.content).toBe("fallback answer");
   });
 
+  it("skips reasoner fallback when the request disables it even if the client enables it", async () => {
+    const fetchMock = vi.fn<typeof fetch>().mockResolvedValue(
+      jsonResponse(
+        {
+          error: {
+            message: "temporarily unavailable",
+          },
+        },
+        503,
+      ),
+    );
+
+    const client = new DeepSeekApiClient({
+      apiKey: "test-key",
+      fetchFn: fetchMock,
+      enableReasonerFallback: true,
+      fallbackModel: "deepseek-chat",
+    });
+
+    await expect(
+      client.createChatCompletion(
+        {
+          model: "deepseek-reasoner",
+          messages: [{ role: "user", content: "test" }],
+        },
+        { disableFallback: true },
+      ),
+    ).rejects.toMatchObject({ status: 503 });
+
+    expect(fetchMock).toHaveBeenCalledTimes(1);
+  });
+
+  it("uses a custom retry status set for fallback decisions and the retryable flag", async () => {
+    const chatResponse = () =>
+      jsonResponse({
+        id: "chat-3",
+        object: "chat.completion",
+        created: 3,
+        model: "deepseek-chat",
+        choices: [{ index: 0, finish_reason: "stop", message: { role: "assistant", content: "ok" } }],
+      });
+    const fetchMock = vi
+      .fn<typeof fetch>()
+      .mockResolvedValueOnce(jsonResponse({ error: { message: "teapot" } }, 418))
+      .mockResolvedValueOnce(chatResponse())
+      .mockResolvedValueOnce(jsonResponse({ error: { message: "unavailable" } }, 503));
+
+    const client = new DeepSeekApiClient({
+      apiKey: "test-key",
+      fetchFn: fetchMock,
+      enableReasonerFallback: true,
+      retryStatuses: [418],
+    });
+    const request = { model: "deepseek-reasoner", messages: [{ role: "user" as const, content: "test" }] };
+
+    const recovered = await client.createChatCompletion(request);
+    expect(recovered.fallback?.toModel).toBe("deepseek-chat");
+    expect(fetchMock).toHaveBeenCalledTimes(2);
+
+    await expect(client.createChatCompletion(request)).rejects.toMatchObject({ status: 503, retryable: false });
+    expect(fetchMock).toHaveBeenCalledTimes(3);
+  });
+
+  it("backs off and retries the same model on 429 before falling back", async () => {
+    const chatResponse = (model: string) =>
+      jsonResponse({
+        id: "chat-429",
+        object: "chat.completion",
+        created: 4,
+        model,
+        choices: [{ index: 0, finish_reason: "stop", message: { role: "assistant", content: "ok" } }],
+      });
+    const rateLimited = () => jsonResponse({ error: { message: "slow down" } }, 429);
+    const fetchMock = vi
+      .fn<typeof fetch>()
+      .mockResolvedValueOnce(rateLimited())
+      .mockResolvedValueOnce(chatResponse("deepseek-reasoner"))
+      .mockResolvedValueOnce(rateLimited())
+      .mockResolvedValueOnce(rateLimited())
+      .mockResolvedValueOnce(rateLimited())
+      .mockResolvedValueOnce(chatResponse("deepseek-chat"));
+    const sleepFn = vi.fn(async () => undefined);
+
+    const client = new DeepSeekApiClient({
+      apiKey: "test-key",
+      fetchFn: fetchMock,
+      rateLimitRetries: 2,
+      rateLimitBackoffMs: 100,
+      rateLimitJitter: 0,
+      sleepFn,
+    });
+    const request = { model: "deepseek-reasoner", messages: [{ role: "user" as const, content: "test" }] };
+
+    const retried = await client.createChatCompletion(request);
+    expect(retried.fallback).toBeUndefined();
+    expect(retried.response.model).toBe("deepseek-reasoner");
+    expect(sleepFn.mock.calls).toEqual([[100]]);
+
+    const fellBack = await client.createChatCompletion(request);
+    expect(fellBack.fallback).toMatchObject({ fromModel: "deepseek-reasoner", toModel: "deepseek-chat" });
+    expect(sleepFn.mock.calls).toEqual([[100], [100], [200]]);
+
+    const models = fetchMock.mock.calls.map((call) => JSON.parse(String((call[1] as RequestInit).body)).model);
+    expect(models).toEqual([
+      "deepseek-reasoner",
+      "deepseek-reasoner",
+      "deepseek-reasoner",
+      "deepseek-reasoner",
+      "deepseek-reasoner",
+      "deepseek-chat",
+    ]);
+  });
+
+  it("falls back immediately on 5xx without backing off", async () => {
+    const fetchMock = vi
+      .fn<typeof fetch>()
+      .mockResolvedValueOnce(jsonResponse({ error: { message: "internal error" } }, 500))
+      .mockResolvedValueOnce(
+        jsonResponse({
+          id: "chat-500",
+          object: "chat.completion",
+          created: 5,
+          model: "deepseek-chat",
+          choices: [{ index: 0, finish_reason: "stop", message: { role: "assistant", content: "ok" } }],
+        }),
+      );
+    const sleepFn = vi.fn(async () => undefined);
+
+    const client = new DeepSeekApiClient({ apiKey: "test-key", fetchFn: fetchMock, sleepFn });
+
+    const result = await client.createChatCompletion({
+      model: "deepseek-reasoner",
+      messages: [{ role: "user", content: "test" }],
+    });
+
+    expect(result.fallback).toMatchObject({ toModel: "deepseek-chat", reason: "internal error" });
+    expect(fetchMock).toHaveBeenCalledTimes(2);
+    expect(sleepFn).not.toHaveBeenCalled();
+  });
+
   it("does not fallback on non-retriable API errors", async () => {
     const fetchMock = vi.fn<typeof fetch>().mockResolvedValue(
       jsonResponse(
@@ -407,4 +598,810 @@ describe("DeepSeekApiClient", () => {
       "https://api.deepseek.com/tasks/task-1",
     ]);
   });
+
+  it("signs request bodies with HMAC-SHA256 when a signing secret is configured", async () => {
+    const fetchMock = vi
+      .fn<typeof fetch>()
+      .mockResolvedValueOnce(
+        jsonResponse({
+          id: "chat-signed",
+          object: "chat.completion",
+          created: 1,
+          model: "deepseek-chat",
+          choices: [{ index: 0, finish_reason: "stop", message: { role: "assistant", content: "ok" } }],
+        }),
+      )
+      .mockResolvedValueOnce(jsonResponse({ object: "list", data: [] }));
+
+    const client = new DeepSeekApiClient({
+      apiKey: "test-key",
+      fetchFn: fetchMock,
+      signingSecret: "gateway-secret",
+      now: () => 1_700_000_000_000,
+    });
+
+    await client.createChatCompletion({
+      model: "deepseek-chat",
+      messages: [{ role: "user", content: "hello" }],
+    });
+    await client.listModels();
+
+    const [, postInit] = fetchMock.mock.calls[0] as [string, RequestInit];
+    const postHeaders = postInit.headers as Record<string, string>;
+    expect(postHeaders["X-Timestamp"]).toBe("1700000000");
+    expect(postHeaders["X-Signature"]).toBe(signRequestBody("gateway-secret", "1700000000", String(postInit.body)));
+
+    const [, getInit] = fetchMock.mock.calls[1] as [string, RequestInit];
+    const getHeaders = getInit.headers as Record<string, string>;
+    expect(getHeaders["X-Timestamp"]).toBe("1700000000");
+    expect(getHeaders["X-Signature"]).toBe("4985acf709e0ae2e42c1ae64f48798579f734c3cb5bd7f10803448702a7ec1b2");
+    expect(signRequestBody("gateway-secret", "1700000000", "{}")).toBe(
+      "cfd28f922e82bd83b39b19535b547d5b2108af48d523f83708d18fce4f6f47b6",
+    );
+  });
+
+  it("posts chat prefix completions to the beta base URL", async () => {
+    const fetchMock = vi.fn<typeof fetch>().mockResolvedValue(
+      jsonResponse({
+        id: "chat-prefix-1",
+        object: "chat.completion",
+        created: 1,
+        model: "deepseek-chat",
+        choices: [{ index: 0, finish_reason: "stop", message: { role: "assistant", content: " world" } }],
+      }),
+    );
+
+    const client = new DeepSeekApiClient({
+      apiKey: "test-key",
+      fetchFn: fetchMock,
+    });
+
+    const result = await client.createChatPrefixCompletion({
+      model: "deepseek-chat",
+      messages: [
+        { role: "user", content: "Say hello world" },
+        { role: "assistant", content: "hello", prefix: true },
+      ],
+    });
+
+    expect(result.response.choices[0]?.message.content).toBe(" world");
+
+    const [url, init] = fetchMock.mock.calls[0] as [string, RequestInit];
+    expect(url).toBe("https://api.deepseek.com/beta/chat/completions");
+    const body = JSON.parse(String(init.body));
+    expect(body.messages.at(-1)).toEqual({ role: "assistant", content: "hello", prefix: true });
+  });
+
+  it("posts FIM completions straight to the beta base URL", async () => {
+    const fetchMock = vi.fn<typeof fetch>().mockResolvedValue(
+      jsonResponse({
+        id: "fim-1",
+        object: "text_completion",
+        created: 1,
+        model: "deepseek-chat",
+        choices: [{ index: 0, finish_reason: "stop", text: "return a + b" }],
+      }),
+    );
+
+    const client = new DeepSeekApiClient({
+      apiKey: "test-key",
+      fetchFn: fetchMock,
+    });
+
+    const result = await client.createFimCompletion({
+      model: "deepseek-chat",
+      prompt: "def add(a, b):\n    ",
+      suffix: "\n",
+    });
+
+    expect(result.response.choices[0]?.text).toBe("return a + b");
+    expect(fetchMock).toHaveBeenCalledTimes(1);
+    const [url, init] = fetchMock.mock.calls[0] as [string, RequestInit];
+    expect(url).toBe("https://api.deepseek.com/beta/completions");
+    expect(JSON.parse(String(init.body))).toMatchObject({ prompt: "def add(a, b):\n    ", suffix: "\n" });
+  });
+
+  it("applies connection pool settings and falls back to defaults for invalid values", async () => {
+    const invalid = new DeepSeekApiClient({
+      apiKey: "test-key",
+      connectionPool: { maxConnectionsPerHost: -1, idleTimeoutMs: Number.NaN },
+    });
+
+    expect(invalid.connectionPool).toEqual({
+      maxConnectionsPerHost: undefined,
+      idleTimeoutMs: DEFAULT_POOL_IDLE_TIMEOUT_MS,
+      keepAlive: true,
+    });
+
+    const upstream = createServer((req, res) => {
+      res.setHeader("content-type", "application/json");
+      res.end(JSON.stringify({ object: "list", data: [{ id: "pooled-model", object: "model" }], path: req.url }));
+    });
+    await new Promise<void>((resolve) => upstream.listen(0, "127.0.0.1", () => resolve()));
+
+    try {
+      const { port } = upstream.address() as AddressInfo;
+      const pooled = new DeepSeekApiClient({
+        apiKey: "test-key",
+        baseUrl: `http://127.0.0.1:${port}`,
+        connectionPool: { maxConnectionsPerHost: 2, idleTimeoutMs: 1000, keepAlive: true },
+      });
+
+      expect(pooled.connectionPool).toEqual({ maxConnectionsPerHost: 2, idleTimeoutMs: 1000, keepAlive: true });
+
+      const models = await pooled.listModels();
+      expect(models.data[0]?.id).toBe("pooled-model");
+      expect((models as unknown as Record<string, unknown>).path).toBe("/models");
+    } finally {
+      upstream.closeAllConnections();
+      await new Promise<void>((resolve) => upstream.close(() => resolve()));
+    }
+  });
+
+  it("maps pool settings to undici Agent options without turning off connection reuse", () => {
+    expect(connectionPoolAgentOptions(resolveConnectionPoolOptions({ idleTimeoutMs: 1000 }))).toEqual({
+      connections: null,
+      keepAliveTimeout: 1000,
+      keepAliveMaxTimeout: 1000,
+      connect: { keepAlive: true, keepAliveInitialDelay: TCP_KEEPALIVE_INITIAL_DELAY_MS },
+    });
+
+    const capped = connectionPoolAgentOptions(
+      resolveConnectionPoolOptions({ maxConnectionsPerHost: 8, keepAlive: false }),
+    );
+    expect(capped).toMatchObject({ connections: 8, connect: { keepAlive: false } });
+    expect(capped).not.toHaveProperty("pipelining");
+  });
+
+  it("rejects responses larger than maxResponseBytes for JSON and stream bodies", async () => {
+    const oversizedText = "x".repeat(512);
+    const fetchMock = vi
+      .fn<typeof fetch>()
+      .mockResolvedValueOnce(jsonResponse({ object: "list", data: [], padding: oversizedText }))
+      .mockResolvedValueOnce(
+        sseResponse([
+          {
+            id: "chat-stream-big",
+            object: "chat.completion.chunk",
+            created: 1,
+            model: "deepseek-chat",
+            choices: [{ index: 0, delta: { content: oversizedText }, finish_reason: null }],
+          },
+          "[DONE]",
+        ]),
+      );
+
+    const client = new DeepSeekApiClient({
+      apiKey: "test-key",
+      fetchFn: fetchMock,
+      maxResponseBytes: 256,
+    });
+
+    await expect(client.listModels()).rejects.toThrow(/exceeded the 256 byte limit/);
+    await expect(
+      client.createChatCompletion({
+        model: "deepseek-chat",
+        stream: true,
+        messages: [{ role: "user", content: "hi" }],
+      }),
+    ).rejects.toBeInstanceOf(DeepSeekApiError);
+  });
+
+  it("exposes parsed SSE chunks through createChatCompletionStream", async () => {
+    const fetchMock = vi.fn<typeof fetch>().mockResolvedValue(
+      sseResponse([
+        {
+          id: "chat-stream-lib",
+          object: "chat.completion.chunk",
+          created: 1,
+          model: "deepseek-chat",
+          choices: [{ index: 0, delta: { role: "assistant", content: "Hel" }, finish_reason: null }],
+        },
+        {
+          id: "chat-stream-lib",
+          object: "chat.completion.chunk",
+          created: 1,
+          model: "deepseek-chat",
+          choices: [{ index: 0, delta: { content: "lo" }, finish_reason: "stop" }],
+        },
+        "[DONE]",
+      ]),
+    );
+
+    const client = new DeepSeekApiClient({
+      apiKey: "test-key",
+      fetchFn: fetchMock,
+    });
+
+    const deltas: string[] = [];
+    for await (const chunk of client.createChatCompletionStream({
+      model: "deepseek-chat",
+      messages: [{ role: "user", content: "hi" }],
+    })) {
+      const choice = (chunk.choices as Array<{ delta: { content?: string } }>)[0];
+      deltas.push(choice?.delta.content ?? "");
+    }
+
+    expect(deltas).toEqual(["Hel", "lo"]);
+
+    const [, init] = fetchMock.mock.calls[0] as [string, RequestInit];
+    expect(JSON.parse(String(init.body)).stream).toBe(true);
+    expect((init.headers as Record<string, string>).Accept).toBe("text/event-stream");
+  });
+
+  it("retries once on truncated JSON and does not retry genuine non-JSON payloads", async () => {
+    const truncated = () =>
+      new Response('{"object":"list","da', { status: 200, headers: { "content-type": "application/json" } });
+
+    const fetchMock = vi
+      .fn<typeof fetch>()
+      .mockResolvedValueOnce(truncated())
+      .mockResolvedValueOnce(jsonResponse({ object: "list", data: [{ id: "deepseek-chat", object: "model" }] }))
+      .mockResolvedValueOnce(new Response("<html>gateway</html>", { status: 200, headers: { "content-type": "text/html" } }));
+
+    const client = new DeepSeekApiClient({
+      apiKey: "test-key",
+      fetchFn: fetchMock,
+    });
+
+    const models = await client.listModels();
+    expect(models.data[0]?.id).toBe("deepseek-chat");
+    expect(fetchMock).toHaveBeenCalledTimes(2);
+
+    await expect(client.getUserBalance()).rejects.toMatchObject({ code: "non_json_response" });
+    expect(fetchMock).toHaveBeenCalledTimes(3);
+
+    const noRetryFetch = vi.fn<typeof fetch>().mockResolvedValue(truncated());
+    const noRetryClient = new DeepSeekApiClient({
+      apiKey: "test-key",
+      fetchFn: noRetryFetch,
+      retryOnDecodeError: false,
+    });
+
+    await expect(noRetryClient.listModels()).rejects.toMatchObject({ code: "decode_error" });
+    expect(noRetryFetch).toHaveBeenCalledTimes(1);
+  });
+
+  it("replays a recorded interaction without touching the network", async () => {
+    const fixtureDir = await mkdtemp(join(tmpdir(), "deepseek-fixtures-"));
+    const request = {
+      model: "deepseek-chat",
+      messages: [{ role: "user" as const, content: "recorded?" }],
+    };
+
+    try {
+      const liveFetch = vi.fn<typeof fetch>().mockResolvedValue(
+        jsonResponse({
+          id: "chat-recorded",
+          object: "chat.completion",
+          created: 1,
+          model: "deepseek-chat",
+          choices: [{ index: 0, finish_reason: "stop", message: { role: "assistant", content: "from the tape" } }],
+        }),
+      );
+      const recorder = new DeepSeekApiClient({ apiKey: "test-key", fetchFn: liveFetch, recordDir: fixtureDir });
+      await recorder.createChatCompletion(request);
+      expect(liveFetch).toHaveBeenCalledTimes(1);
+
+      const offlineFetch = vi.fn<typeof fetch>().mockRejectedValue(new Error("network disabled"));
+      const replayer = new DeepSeekApiClient({ apiKey: "test-key", fetchFn: offlineFetch, replayDir: fixtureDir });
+      const replayed = await replayer.createChatCompletion(request);
+
+      expect(offlineFetch).not.toHaveBeenCalled();
+      expect(replayed.response.choices[0]?.message.content).toBe("from the tape");
+
+      await expect(
+        replayer.createChatCompletion({ ...request, messages: [{ role: "user", content: "never recorded" }] }),
+      ).rejects.toThrow("No recorded interaction for POST /chat/completions");
+    } finally {
+      await rm(fixtureDir, { recursive: true, force: true });
+    }
+  });
+
+  it("keys gzip-compressed recordings by the uncompressed request body", async () => {
+    const fixtureDir = await mkdtemp(join(tmpdir(), "deepseek-fixtures-"));
+    const request = { model: "deepseek-chat", messages: [{ role: "user" as const, content: "compressed?" }] };
+    const otherRequest = { ...request, messages: [{ role: "user" as const, content: "also compressed" }] };
+
+    try {
+      const liveFetch = vi.fn<typeof fetch>().mockImplementation(async () =>
+        jsonResponse({
+          id: "chat-recorded",
+          object: "chat.completion",
+          created: 1,
+          model: "deepseek-chat",
+          choices: [{ index: 0, finish_reason: "stop", message: { role: "assistant", content: "inflated" } }],
+        }),
+      );
+      const recorder = new DeepSeekApiClient({
+        apiKey: "test-key",
+        fetchFn: liveFetch,
+        recordDir: fixtureDir,
+        compressRequests: true,
+        compressionThresholdBytes: 0,
+      });
+      await recorder.createChatCompletion(request);
+      await recorder.createChatCompletion(otherRequest);
+      expect((await readdir(fixtureDir)).length).toBe(2);
+
+      const replayer = new DeepSeekApiClient({
+        apiKey: "test-key",
+        fetchFn: vi.fn<typeof fetch>().mockRejectedValue(new Error("network disabled")),
+        replayDir: fixtureDir,
+      });
+      const replayed = await replayer.createChatCompletion(request);
+      expect(replayed.response.choices[0]?.message.content).toBe("inflated");
+    } finally {
+      await rm(fixtureDir, { recursive: true, force: true });
+    }
+  });
+
+  it("aborts a looping stream when the loop guard detects a repeated fragment", async () => {
+    const deltas = Array.from({ length: 40 }, () => ({
+      id: "chat-loop",
+      object: "chat.completion.chunk",
+      created: 1,
+      model: "deepseek-chat",
+      choices: [{ index: 0, delta: { content: "round and round " }, finish_reason: null }],
+    }));
+    const fetchMock = vi.fn<typeof fetch>().mockResolvedValue(sseResponse([...deltas, "[DONE]"]));
+
+    const client = new DeepSeekApiClient({
+      apiKey: "test-key",
+      fetchFn: fetchMock,
+      loopGuard: { ngramSize: 4, maxRepeats: 3 },
+    });
+
+    const result = await client.createChatCompletion({
+      model: "deepseek-chat",
+      messages: [{ role: "user", content: "spin" }],
+      stream: true,
+    });
+
+    expect(result.loopGuard?.repeatedFragment).toBe("round round and round");
+    expect(result.streamChunkCount).toBeLessThan(deltas.length);
+    expect(result.response.choices[0]?.message.content).toMatch(/^(round and round )+$/);
+  });
+
+  it("sends a different bearer token from a client derived with withApiKey", async () => {
+    const fetchMock = vi
+      .fn<typeof fetch>()
+      .mockImplementation(async () => new Response(JSON.stringify({ object: "list", data: [] }), { status: 200 }));
+    const client = new DeepSeekApiClient({ apiKey: "server-key", fetchFn: fetchMock });
+
+    await client.withApiKey("session-key").listModels();
+    await client.listModels();
+
+    const authorization = fetchMock.mock.calls.map(
+      (call) => (call[1]?.headers as Record<string, string> | undefined)?.Authorization,
+    );
+    expect(authorization).toEqual(["Bearer session-key", "Bearer server-key"]);
+  });
+
+  it("stops reading a stream once the estimated output exceeds the soft token cap", async () => {
+    const deltas = Array.from({ length: 20 }, () => ({
+      id: "chat-soft-cap",
+      object: "chat.completion.chunk",
+      created: 1,
+      model: "deepseek-chat",
+      choices: [{ index: 0, delta: { content: "abcdefghij" }, finish_reason: null }],
+    }));
+    const fetchMock = vi.fn<typeof fetch>().mockImplementation(async () => sseResponse([...deltas, "[DONE]"]));
+
+    const client = new DeepSeekApiClient({ apiKey: "test-key", fetchFn: fetchMock, streamSoftMaxTokens: 10 });
+    const request = { model: "deepseek-chat", messages: [{ role: "user" as const, content: "go" }], stream: true };
+
+    const capped = await client.createChatCompletion(request);
+    expect(capped.softCapped).toBe(true);
+    expect(capped.streamChunkCount).toBe(4);
+    expect(capped.streamCompletion).toEqual({ completed: false, reason: "stopped at soft token cap" });
+    expect(capped.response.choices[0]?.message.content).toBe("abcdefghij".repeat(4));
+
+    const overridden = await client.createChatCompletion(request, { streamSoftMaxTokens: 1000 });
+    expect(overridden.softCapped).toBeUndefined();
+    expect(overridden.response.choices[0]?.message.content).toBe("abcdefghij".repeat(20));
+  });
+
+  it("reports the estimated tokens received so far while streaming", async () => {
+    const chatDelta = (delta: Record<string, string>) => ({
+      id: "chat-progress",
+      object: "chat.completion.chunk",
+      created: 1,
+      model: "deepseek-reasoner",
+      choices: [{ index: 0, delta, finish_reason: null }],
+    });
+    const fetchMock = vi
+      .fn<typeof fetch>()
+      .mockResolvedValueOnce(
+        sseResponse([
+          chatDelta({ reasoning_content: "thinking it over" }),
+          chatDelta({ content: "The answer" }),
+          chatDelta({ content: " is 42." }),
+          "[DONE]",
+        ]),
+      )
+      .mockResolvedValueOnce(
+        sseResponse([
+          { id: "cmpl-progress", object: "text_completion", created: 1, choices: [{ index: 0, text: "def foo" }] },
+          "[DONE]",
+        ]),
+      );
+
+    const client = new DeepSeekApiClient({ apiKey: "test-key", fetchFn: fetchMock });
+    const onStreamProgress = vi.fn();
+
+    await client.createChatCompletion(
+      { model: "deepseek-reasoner", messages: [{ role: "user", content: "go" }], stream: true },
+      { onStreamProgress },
+    );
+    const counts = onStreamProgress.mock.calls.map(([tokens]) => tokens as number);
+    expect(counts).toHaveLength(3);
+    expect(counts[0]).toBeGreaterThan(0);
+    expect(counts[2]).toBeGreaterThan(counts[1]);
+    expect(counts[1]).toBeGreaterThan(counts[0]);
+
+    const onCompletionProgress = vi.fn();
+    await client.createCompletion(
+      { model: "deepseek-chat", prompt: "go", stream: true },
+      { onStreamProgress: onCompletionProgress },
+    );
+    expect(onCompletionProgress).toHaveBeenCalledTimes(1);
+  });
+
+  it("aborts the upstream request when the caller's signal fires, without failover or fallback", async () => {
+    const fetchMock = vi.fn<typeof fetch>().mockImplementation(
+      (_url, init) =>
+        new Promise<Response>((_resolve, reject) => {
+          init?.signal?.addEventListener("abort", () => {
+            const error = new Error("The operation was aborted");
+            error.name = "AbortError";
+            reject(error);
+          });
+        }),
+    );
+
+    const client = new DeepSeekApiClient({
+      apiKey: "test-key",
+      fetchFn: fetchMock,
+      baseUrls: ["https://primary.example.com", "https://secondary.example.com"],
+    });
+    const controller = new AbortController();
+    const pending = client.createChatCompletion(
+      { model: "deepseek-reasoner", messages: [{ role: "user", content: "go" }] },
+      { signal: controller.signal },
+    );
+    controller.abort();
+
+    await expect(pending).rejects.toMatchObject({ code: "cancelled", retryable: false });
+    expect(fetchMock).toHaveBeenCalledTimes(1);
+
+    await expect(
+      client.createCompletion({ model: "deepseek-chat", prompt: "go" }, { signal: controller.signal }),
+    ).rejects.toMatchObject({ code: "cancelled" });
+    expect(fetchMock).toHaveBeenCalledTimes(1);
+  });
+
+  it("detaches from the caller's signal after the headers yet still cancels a stream mid-body", async () => {
+    const encoder = new TextEncoder();
+    const delta = { id: "chat-slow", choices: [{ index: 0, delta: { content: "partial" } }] };
+    const fetchMock = vi.fn<typeof fetch>().mockImplementation(
+      async () =>
+        new Response(
+          new ReadableStream<Uint8Array>({
+            start(controller) {
+              controller.enqueue(encoder.encode(`data: ${JSON.stringify(delta)}\n\n`));
+            },
+          }),
+          { status: 200, headers: { "content-type": "text/event-stream" } },
+        ),
+    );
+    const client = new DeepSeekApiClient({ apiKey: "test-key", fetchFn: fetchMock });
+    const controller = new AbortController();
+    const removeListener = vi.spyOn(controller.signal, "removeEventListener");
+    const onProgress = vi.fn();
+
+    const pending = client.createChatCompletion(
+      { model: "deepseek-chat", messages: [{ role: "user", content: "go" }], stream: true },
+      { signal: controller.signal, onStreamProgress: onProgress },
+    );
+    await vi.waitFor(() => expect(onProgress).toHaveBeenCalled());
+    expect(removeListener).toHaveBeenCalledTimes(1);
+    controller.abort();
+
+    await expect(pending).rejects.toMatchObject({ code: "cancelled" });
+    expect(removeListener).toHaveBeenCalledTimes(2);
+  });
+
+  it("reports whether a stream finished with [DONE] or ended abruptly", async () => {
+    const delta = {
+      id: "chat-partial",
+      object: "chat.completion.chunk",
+      created: 1,
+      model: "deepseek-chat",
+      choices: [{ index: 0, delta: { content: "Partial" }, finish_reason: null }],
+    };
+    const fetchMock = vi
+      .fn<typeof fetch>()
+      .mockResolvedValueOnce(sseResponse([delta, "[DONE]"]))
+      .mockResolvedValueOnce(sseResponse([delta]))
+      .mockResolvedValueOnce(
+        sseResponse([{ id: "cmpl-partial", object: "text_completion", created: 1, choices: [{ index: 0, text: "x" }] }]),
+      );
+
+    const client = new DeepSeekApiClient({ apiKey: "test-key", fetchFn: fetchMock });
+    const request = { model: "deepseek-chat", messages: [{ role: "user" as const, content: "go" }], stream: true };
+
+    const clean = await client.createChatCompletion(request);
+    expect(clean.streamCompletion).toEqual({ completed: true });
+
+    const abrupt = await client.createChatCompletion(request);
+    expect(abrupt.streamCompletion).toEqual({ completed: false, reason: "stream ended without [DONE]" });
+    expect(abrupt.response.choices[0]?.message.content).toBe("Partial");
+
+    const completion = await client.createCompletion({ model: "deepseek-chat", prompt: "go", stream: true });
+    expect(completion.streamCompletion).toEqual({ completed: false, reason: "stream ended without [DONE]" });
+  });
+
+  it("measures time to first byte, total time, and stream chunk spread", async () => {
+    const deltas = ["Hel", "lo"].map((content) => ({
+      id: "chat-timing",
+      object: "chat.completion.chunk",
+      created: 1,
+      model: "deepseek-chat",
+      choices: [{ index: 0, delta: { content }, finish_reason: null }],
+    }));
+    const fetchMock = vi.fn<typeof fetch>().mockResolvedValue(sseResponse([...deltas, "[DONE]"]));
+    const client = new DeepSeekApiClient({ apiKey: "test-key", fetchFn: fetchMock });
+
+    const result = await client.createChatCompletion({
+      model: "deepseek-chat",
+      messages: [{ role: "user", content: "hi" }],
+      stream: true,
+    });
+
+    expect(result.timing).toEqual({
+      timeToFirstByteMs: expect.any(Number),
+      totalMs: expect.any(Number),
+      firstToLastChunkMs: expect.any(Number),
+    });
+    expect(result.timing?.totalMs).toBeGreaterThanOrEqual(result.timing?.timeToFirstByteMs ?? 0);
+  });
+
+  it("sends chat and model requests to configured endpoint paths", async () => {
+    const fetchMock = vi
+      .fn<typeof fetch>()
+      .mockResolvedValueOnce(
+        jsonResponse({
+          id: "chat-path",
+          object: "chat.completion",
+          created: 1,
+          model: "deepseek-chat",
+          choices: [{ index: 0, finish_reason: "stop", message: { role: "assistant", content: "ok" } }],
+        }),
+      )
+      .mockResolvedValueOnce(jsonResponse({ object: "list", data: [] }));
+
+    const client = new DeepSeekApiClient({
+      apiKey: "test-key",
+      baseUrl: "https://gateway.example.com",
+      fetchFn: fetchMock,
+      endpointPaths: { chat: "/v1/chat/completions", models: "/v1/models" },
+    });
+
+    await client.createChatCompletion({ model: "deepseek-chat", messages: [{ role: "user", content: "hi" }] });
+    await client.listModels();
+
+    expect(fetchMock.mock.calls.map((call) => String(call[0]))).toEqual([
+      "https://gateway.example.com/v1/chat/completions",
+      "https://gateway.example.com/v1/models",
+    ]);
+  });
+
+  it("fails over to the next base URL when the primary errors and prefers healthy bases afterwards", async () => {
+    const okResponse = () =>
+      jsonResponse({
+        id: "chat-failover",
+        object: "chat.completion",
+        created: 1,
+        model: "deepseek-chat",
+        choices: [{ index: 0, finish_reason: "stop", message: { role: "assistant", content: "ok" } }],
+      });
+    const fetchMock = vi
+      .fn<typeof fetch>()
+      .mockRejectedValueOnce(new TypeError("fetch failed"))
+      .mockResolvedValueOnce(okResponse())
+      .mockResolvedValueOnce(okResponse());
+
+    const client = new DeepSeekApiClient({
+      apiKey: "test-key",
+      baseUrls: ["https://primary.example.com", "https://secondary.example.com/"],
+      fetchFn: fetchMock,
+    });
+    const request = { model: "deepseek-chat", messages: [{ role: "user" as const, content: "hi" }] };
+
+    const result = await client.createChatCompletion(request);
+    expect(result.response.choices[0]?.message.content).toBe("ok");
+    expect(client.activeBaseUrl).toBe("https://secondary.example.com");
+    expect(client.getBaseUrlHealth()).toEqual([
+      { base_url: "https://primary.example.com", healthy: false },
+      { base_url: "https://secondary.example.com", healthy: true },
+    ]);
+
+    await client.createChatCompletion(request);
+    expect(fetchMock.mock.calls.map((call) => String(call[0]))).toEqual([
+      "https://primary.example.com/chat/completions",
+      "https://secondary.example.com/chat/completions",
+      "https://secondary.example.com/chat/completions",
+    ]);
+  });
+
+  it("re-probes a failed base URL once its cooldown has elapsed", async () => {
+    const okResponse = () =>
+      jsonResponse({ object: "list", data: [{ id: "deepseek-chat", object: "model", owned_by: "deepseek" }] });
+    const fetchMock = vi
+      .fn<typeof fetch>()
+      .mockResolvedValueOnce(jsonResponse({ error: { message: "primary down" } }, 503))
+      .mockResolvedValueOnce(okResponse())
+      .mockResolvedValueOnce(okResponse())
+      .mockResolvedValueOnce(okResponse());
+    let now = 0;
+
+    const client = new DeepSeekApiClient({
+      apiKey: "test-key",
+      baseUrls: ["https://primary.example.com", "https://secondary.example.com"],
+      baseUrlCooldownMs: 1000,
+      now: () => now,
+      fetchFn: fetchMock,
+    });
+
+    await client.listModels();
+    now = 999;
+    await client.listModels();
+    now = 1000;
+    expect(client.getBaseUrlHealth()[0]).toEqual({ base_url: "https://primary.example.com", healthy: true });
+    await client.listModels();
+
+    expect(fetchMock.mock.calls.map((call) => String(call[0]))).toEqual([
+      "https://primary.example.com/models",
+      "https://secondary.example.com/models",
+      "https://secondary.example.com/models",
+      "https://primary.example.com/models",
+    ]);
+    expect(client.activeBaseUrl).toBe("https://primary.example.com");
+  });
+
+  it("fails over on 5xx responses and returns the last error when every base fails", async () => {
+    const fetchMock = vi
+      .fn<typeof fetch>()
+      .mockResolvedValueOnce(jsonResponse({ error: { message: "primary down" } }, 502))
+      .mockResolvedValueOnce(jsonResponse({ error: { message: "secondary down" } }, 503));
+
+    const client = new DeepSeekApiClient({
+      apiKey: "test-key",
+      baseUrls: ["https://primary.example.com", "https://secondary.example.com"],
+      fetchFn: fetchMock,
+    });
+
+    await expect(client.listModels()).rejects.toMatchObject({ status: 503, message: "secondary down" });
+    expect(fetchMock).toHaveBeenCalledTimes(2);
+    expect(client.activeBaseUrl).toBeUndefined();
+  });
+
+  it("passes raw GET requests through with configured auth and rejects absolute URLs", async () => {
+    const fetchMock = vi.fn<typeof fetch>().mockResolvedValue(jsonResponse({ is_available: true }));
+    const client = new DeepSeekApiClient({ apiKey: "test-key", baseUrl: "https://api.example.com", fetchFn: fetchMock });
+
+    await expect(client.rawRequest("GET", "/user/balance")).resolves.toEqual({ is_available: true });
+    const [url, init] = fetchMock.mock.calls[0] as [string, RequestInit];
+    expect(url).toBe("https://api.example.com/user/balance");
+    expect(init.method).toBe("GET");
+    expect((init.headers as Record<string, string>).Authorization).toBe("Bearer test-key");
+
+    await expect(client.rawRequest("GET", "https://evil.example.com/steal")).rejects.toMatchObject({
+      code: "invalid_path",
+    });
+    await expect(client.rawRequest("GET", "//evil.example.com/steal")).rejects.toMatchObject({ code: "invalid_path" });
+    expect(fetchMock).toHaveBeenCalledTimes(1);
+  });
+
+  it("wraps non-JSON success bodies for raw requests while typed endpoints still require JSON", async () => {
+    const textResponse = () =>
+      new Response("pong", { status: 200, headers: { "content-type": "text/plain; charset=utf-8" } });
+    const fetchMock = vi
+      .fn<typeof fetch>()
+      .mockResolvedValueOnce(textResponse())
+      .mockResolvedValueOnce(
+        new Response(new Uint8Array([0, 1, 2]), { status: 200, headers: { "content-type": "application/octet-stream" } }),
+      )
+      .mockResolvedValueOnce(textResponse());
+    const client = new DeepSeekApiClient({ apiKey: "test-key", fetchFn: fetchMock });
+
+    await expect(client.rawRequest("GET", "/ping")).resolves.toEqual({
+      content_type: "text/plain; charset=utf-8",
+      body_text: "pong",
+    });
+    await expect(client.rawRequest("GET", "/blob")).resolves.toEqual({
+      content_type: "application/octet-stream",
+      body_base64: "AAEC",
+    });
+    await expect(client.getUserBalance()).rejects.toMatchObject({ code: "non_json_response" });
+  });
+
+  it("gzips request bodies above the threshold when compression is enabled", async () => {
+    const chatResponse = () =>
+      jsonResponse({
+        id: "chat-gzip",
+        object: "chat.completion",
+        created: 1,
+        model: "deepseek-chat",
+        choices: [{ index: 0, finish_reason: "stop", message: { role: "assistant", content: "ok" } }],
+      });
+    const fetchMock = vi.fn<typeof fetch>().mockResolvedValueOnce(chatResponse()).mockResolvedValueOnce(chatResponse());
+    const client = new DeepSeekApiClient({
+      apiKey: "test-key",
+      fetchFn: fetchMock,
+      compressRequests: true,
+      compressionThresholdBytes: 1024,
+    });
+
+    const largeRequest = {
+      model: "deepseek-chat",
+      messages: [{ role: "user" as const, content: "x".repeat(4096) }],
+    };
+    await client.createChatCompletion(largeRequest);
+    await client.createChatCompletion({ model: "deepseek-chat", messages: [{ role: "user", content: "small" }] });
+
+    const [, largeInit] = fetchMock.mock.calls[0] as [string, RequestInit];
+    expect((largeInit.headers as Record<string, string>)["Content-Encoding"]).toBe("gzip");
+    const compressed = largeInit.body as Uint8Array;
+    expect(compressed.byteLength).toBeLessThan(4096);
+    expect(JSON.parse(gunzipSync(compressed).toString("utf8"))).toEqual(largeRequest);
+
+    const [, smallInit] = fetchMock.mock.calls[1] as [string, RequestInit];
+    expect((smallInit.headers as Record<string, string>)["Content-Encoding"]).toBeUndefined();
+    expect(typeof smallInit.body).toBe("string");
+  });
+
+  it("classifies errors into stable categories from status codes and error types", () => {
+    const cases: Array<[DeepSeekApiError, string]> = [
+      [new DeepSeekApiError("bad key", { status: 401 }), "auth"],
+      [new DeepSeekApiError("no balance", { status: 402 }), "auth"],
+      [new DeepSeekApiError("offline", { code: "no_api_key" }), "auth"],
+      [new DeepSeekApiError("slow down", { status: 429 }), "rate_limit"],
+      [
+        new DeepSeekApiError("busy", { status: 400, payload: { error: { type: "rate_limit_error", message: "busy" } } }),
+        "rate_limit",
+      ],
+      [new DeepSeekApiError("bad field", { status: 422 }), "invalid_request"],
+      [new DeepSeekApiError("bad path", { code: "invalid_path" }), "invalid_request"],
+      [new DeepSeekApiError("boom", { status: 500 }), "server"],
+      [new DeepSeekApiError("garbled", { status: 200, code: "decode_error" }), "server"],
+      [new DeepSeekApiError("unreachable", { code: "network_error" }), "network"],
+      [new DeepSeekApiError("too slow", { code: "timeout" }), "timeout"],
+      [new DeepSeekApiError("gateway timeout", { status: 504 }), "timeout"],
+      [new DeepSeekApiError("mystery"), "unknown"],
+    ];
+
+    expect(cases.map(([error]) => error.category())).toEqual(cases.map(([, category]) => category));
+  });
+
+  it("tags transport failures so they classify as network or timeout", async () => {
+    const networkClient = new DeepSeekApiClient({
+      apiKey: "test-key",
+      fetchFn: vi.fn<typeof fetch>().mockRejectedValue(new TypeError("fetch failed")),
+    });
+    await expect(networkClient.listModels()).rejects.toSatisfy(
+      (error: DeepSeekApiError) => error.category() === "network",
+    );
+
+    const abortError = new Error("aborted");
+    abortError.name = "AbortError";
+    const timeoutClient = new DeepSeekApiClient({
+      apiKey: "test-key",
+      fetchFn: vi.fn<typeof fetch>().mockRejectedValue(abortError),
+    });
+    await expect(timeoutClient.listModels()).rejects.toSatisfy(
+      (error: DeepSeekApiError) => error.category() === "timeout",
+    );
+  });
 });
@@ -0,0 +1,83 @@
+import { mkdtemp, rm, writeFile } from "node:fs/promises";
+import { tmpdir } from "node:os";
+import { basename, join } from "node:path";
+
+import { afterEach, describe, expect, it } from "vitest";
+
+import { attachFiles, insertAttachmentMessage } from "../src/deepseek/file-attachments.js";
+
+describe("attachFiles", () => {
+  const dirs: string[] = [];
+
+  afterEach(async () => {
+    await Promise.all(dirs.splice(0).map((dir) => rm(dir, { recursive: true, force: true })));
+  });
+
+  async function makeRoot(): Promise<string> {
+    const dir = await mkdtemp(join(tmpdir(), "deepseek-attach-"));
+    dirs.push(dir);
+    return dir;
+  }
+
+  it("fences files found under any allowed root and reports truncation", async () => {
+    const first = await makeRoot();
+    const second = await makeRoot();
+    await writeFile(join(first, "main.rs"), "fn main() {}\n");
+    await writeFile(join(second, "notes.md"), "use ```code``` here\n" + "x".repeat(100));
+
+    const attached = await attachFiles(["main.rs", "notes.md"], [first, second], { maxFileBytes: 40 });
+
+    expect(attached.text).toContain("Attached file: main.rs\n```rs\nfn main() {}\n\n```");
+    expect(attached.text).toContain("````md\nuse ```code```");
+    expect(attached.text).toContain("[truncated: included 40 of 120 bytes]");
+    expect(attached.files).toEqual([
+      { path: "main.rs", bytes: 13, included_bytes: 13, truncated: false },
+      { path: "notes.md", bytes: 120, included_bytes: 40, truncated: true },
+    ]);
+  });
+
+  it("cuts a truncated file on a UTF-8 character boundary", async () => {
+    const root = await makeRoot();
+    await writeFile(join(root, "accents.txt"), "aé€");
+
+    const attached = await attachFiles(["accents.txt"], [root], { maxFileBytes: 4 });
+
+    expect(attached.files).toEqual([{ path: "accents.txt", bytes: 6, included_bytes: 3, truncated: true }]);
+    expect(attached.text).toContain("```txt\naé\n```");
+    expect(attached.text).not.toContain("\uFFFD");
+  });
+
+  it("rejects paths outside the roots, binary files and missing roots", async () => {
+    const root = await makeRoot();
+    const outside = await makeRoot();
+    await writeFile(join(outside, "secret.txt"), "secret");
+    await writeFile(join(root, "blob.bin"), Buffer.from([1, 0, 2]));
+
+    await expect(attachFiles([join(outside, "secret.txt")], [root])).rejects.toThrow(
+      "not found inside the allowed roots",
+    );
+    await expect(attachFiles([join("..", basename(outside), "secret.txt")], [root])).rejects.toThrow(
+      "not found inside the allowed roots",
+    );
+    await expect(attachFiles(["blob.bin"], [root])).rejects.toThrow("binary file");
+    await expect(attachFiles(["a.txt"], [])).rejects.toThrow("DEEPSEEK_ATTACH_DIRS");
+  });
+});
+
+describe("insertAttachmentMessage", () => {
+  it("places the attachments right before the newest message", () => {
+    expect(
+      insertAttachmentMessage(
+        [
+          { role: "system", content: "Be brief." },
+          { role: "user", content: "Review this" },
+        ],
+        "files",
+      ),
+    ).toEqual([
+      { role: "system", content: "Be brief." },
+      { role: "user", content: "files" },
+      { role: "user", content: "Review this" },
+    ]);
+  });
+});
@@ -0,0 +1,26 @@
+import { describe, expect, it, vi } from "vitest";
+
+import { HealthMonitor } from "../src/health.js";
+
+describe("HealthMonitor", () => {
+  it("is not ready until a probe succeeds and reports the last probe failure", async () => {
+    const probe = vi.fn(async () => undefined);
+    const monitor = new HealthMonitor({ apiKeyPresent: true, probe, intervalMs: 60000, now: () => 0 });
+
+    expect(monitor.readiness()).toEqual({ ready: false, api_key_present: true, upstream: "unknown" });
+
+    await expect(monitor.probe()).resolves.toBe(true);
+    expect(monitor.readiness()).toMatchObject({ ready: true, upstream: "ok", last_probe_at: "1970-01-01T00:00:00.000Z" });
+
+    probe.mockRejectedValueOnce(new Error("HTTP 503"));
+    await expect(monitor.probe()).resolves.toBe(false);
+    expect(monitor.readiness()).toMatchObject({ ready: false, upstream: "failed", last_probe_error: "HTTP 503" });
+  });
+
+  it("is never ready without an API key", async () => {
+    const monitor = new HealthMonitor({ apiKeyPresent: false, probe: async () => undefined, intervalMs: 60000 });
+
+    await monitor.probe();
+    expect(monitor.readiness()).toMatchObject({ ready: false, api_key_present: false, upstream: "ok" });
+  });
+});
@@ -0,0 +1,197 @@
+import { describe, expect, it, vi } from "vitest";
+
+import { Client } from "@modelcontextprotocol/sdk/client/index.js";
+import { StreamableHTTPClientTransport } from "@modelcontextprotocol/sdk/client/streamableHttp.js";
+
+import { ConversationStore } from "../src/conversation-store.js";
+import { DeepSeekApiClient } from "../src/deepseek/client.js";
+import { HealthMonitor } from "../src/health.js";
+import { createDeepSeekMcpServer } from "../src/mcp-server.js";
+import { startStreamableHttpServer } from "../src/transports/http.js";
+
+describe("startStreamableHttpServer", () => {
+  it("isolates conversation state per stateful session and cleans up on disconnect", async () => {
+    const api = {
+      createChatCompletion: vi.fn(async () => ({
+        response: {
+          id: "chat-1",
+          object: "chat.completion",
+          created: 1,
+          model: "deepseek-chat",
+          choices: [{ index: 0, finish_reason: "stop", message: { role: "assistant", content: "hi" } }],
+        },
+      })),
+    };
+    const runtime = await startStreamableHttpServer(
+      () =>
+        createDeepSeekMcpServer({
+          client: api as unknown as DeepSeekApiClient,
+          conversations: new ConversationStore(200),
+          defaultModel: "deepseek-chat",
+          version: "test",
+        }),
+      { host: "127.0.0.1", port: 0, path: "/mcp", statefulSession: true },
+    );
+    const url = new URL(`http://127.0.0.1:${runtime.port}/mcp`);
+    const first = new Client({ name: "http-session-1", version: "1.0.0" });
+    const second = new Client({ name: "http-session-2", version: "1.0.0" });
+    const firstTransport = new StreamableHTTPClientTransport(url);
+
+    try {
+      await first.connect(firstTransport);
+      await second.connect(new StreamableHTTPClientTransport(url));
+      expect(runtime.sessionCount()).toBe(2);
+
+      await first.callTool({ name: "chat_completion", arguments: { message: "hello", conversation_id: "mine" } });
+
+      const firstSessions = await first.callTool({ name: "list_conversations", arguments: {} });
+      const secondSessions = await second.callTool({ name: "list_conversations", arguments: {} });
+      expect(JSON.stringify(firstSessions.structuredContent)).toContain("mine");
+      expect(JSON.stringify(secondSessions.structuredContent)).not.toContain("mine");
+
+      await firstTransport.terminateSession();
+      expect(runtime.sessionCount()).toBe(1);
+    } finally {
+      await first.close();
+      await second.close();
+      await runtime.close();
+    }
+  });
+
+  it("serves /healthz and /readyz without authentication", async () => {
+    const probe = vi.fn(async () => undefined);
+    const health = new HealthMonitor({ apiKeyPresent: true, probe, intervalMs: 60000 });
+    const runtime = await startStreamableHttpServer(
+      () =>
+        createDeepSeekMcpServer({
+          client: {} as DeepSeekApiClient,
+          conversations: new ConversationStore(200),
+          defaultModel: "deepseek-chat",
+          version: "test",
+        }),
+      { host: "127.0.0.1", port: 0, path: "/mcp", statefulSession: false, authTokens: ["secret"], health },
+    );
+
+    try {
+      const healthz = await fetch(`http://127.0.0.1:${runtime.port}/healthz`);
+      expect(healthz.status).toBe(200);
+      expect(await healthz.json()).toEqual({ status: "ok" });
+
+      const notReady = await fetch(`http://127.0.0.1:${runtime.port}/readyz`);
+      expect(notReady.status).toBe(503);
+      expect(await notReady.json()).toMatchObject({ ready: false, upstream: "unknown" });
+
+      await health.probe();
+      const ready = await fetch(`http://127.0.0.1:${runtime.port}/readyz`);
+      expect(ready.status).toBe(200);
+      expect(await ready.json()).toMatchObject({ ready: true, upstream: "ok" });
+    } finally {
+      await runtime.close();
+    }
+  });
+
+  it("uses each request's own API key header on the shared stateless server", async () => {
+    const keyedApi = (content: string) => ({
+      createChatCompletion: vi.fn(async () => ({
+        response: {
+          id: "chat-key",
+          object: "chat.completion",
+          created: 1,
+          model: "deepseek-chat",
+          choices: [{ index: 0, finish_reason: "stop", message: { role: "assistant", content } }],
+        },
+      })),
+    });
+    const userApis = new Map<string, ReturnType<typeof keyedApi>>();
+    const api = {
+      ...keyedApi("server key"),
+      withApiKey: vi.fn((apiKey: string) => userApis.get(apiKey) ?? userApis.set(apiKey, keyedApi(apiKey)).get(apiKey)),
+    };
+    const runtime = await startStreamableHttpServer(
+      () =>
+        createDeepSeekMcpServer({
+          client: api as unknown as DeepSeekApiClient,
+          conversations: new ConversationStore(200),
+          defaultModel: "deepseek-chat",
+          allowClientApiKeys: true,
+          sessionScoped: false,
+          version: "test",
+        }),
+      { host: "127.0.0.1", port: 0, path: "/mcp", statefulSession: false },
+    );
+    const url = new URL(`http://127.0.0.1:${runtime.port}/mcp`);
+    const withKey = (apiKey: string) =>
+      new StreamableHTTPClientTransport(url, { requestInit: { headers: { "X-DeepSeek-Api-Key": apiKey } } });
+    const alice = new Client({ name: "http-key-alice", version: "1.0.0" });
+    const bob = new Client({ name: "http-key-bob", version: "1.0.0" });
+    const anonymous = new Client({ name: "http-key-anonymous", version: "1.0.0" });
+
+    try {
+      await alice.connect(withKey("sk-alice"));
+      await bob.connect(withKey("sk-bob"));
+      await anonymous.connect(new StreamableHTTPClientTransport(url));
+
+      const aliceReply = await alice.callTool({ name: "chat_completion", arguments: { message: "hi" } });
+      const bobReply = await bob.callTool({ name: "chat_completion", arguments: { message: "hi" } });
+      const serverReply = await anonymous.callTool({ name: "chat_completion", arguments: { message: "hi" } });
+      expect(JSON.stringify(aliceReply.content)).toContain("sk-alice");
+      expect(JSON.stringify(bobReply.content)).toContain("sk-bob");
+      expect(JSON.stringify(serverReply.content)).toContain("server key");
+
+      const authenticate = await alice.callTool({ name: "authenticate", arguments: { api_key: "sk-alice" } });
+      expect(authenticate.isError).toBe(true);
+      expect(JSON.stringify(authenticate.content)).toContain("X-DeepSeek-Api-Key");
+    } finally {
+      await alice.close();
+      await bob.close();
+      await anonymous.close();
+      await runtime.close();
+    }
+  });
+
+  it("sends text-only tool results to requests on a protocol that predates structured content", async () => {
+    const runtime = await startStreamableHttpServer(
+      () =>
+        createDeepSeekMcpServer({
+          client: new DeepSeekApiClient({ apiKey: "" }),
+          conversations: new ConversationStore(200),
+          defaultModel: "deepseek-chat",
+          version: "test",
+        }),
+      { host: "127.0.0.1", port: 0, path: "/mcp", statefulSession: false },
+    );
+    const callGetVersion = async (protocolVersion: string) => {
+      const response = await fetch(`http://127.0.0.1:${runtime.port}/mcp`, {
+        method: "POST",
+        headers: {
+          "content-type": "application/json",
+          accept: "application/json, text/event-stream",
+          "mcp-protocol-version": protocolVersion,
+        },
+        body: JSON.stringify({
+          jsonrpc: "2.0",
+          id: 1,
+          method: "tools/call",
+          params: { name: "get_version", arguments: {} },
+        }),
+      });
+      const body = await response.text();
+      const data = body.split("\n").find((line) => line.startsWith("data: "));
+      return JSON.parse(data ? data.slice("data: ".length) : body).result as Record<string, unknown>;
+    };
+
+    try {
+      const legacy = await callGetVersion("2025-03-26");
+      expect(legacy).not.toHaveProperty("structuredContent");
+      const text = (legacy.content as { text: string }[])[0]?.text ?? "";
+      expect(JSON.parse(text).client).toMatchObject({ protocol_version: "2025-03-26", structured_content: false });
+
+      const current = await callGetVersion("2025-06-18");
+      expect(current.structuredContent).toMatchObject({
+        client: { protocol_version: "2025-06-18", structured_content: true },
+      });
+    } finally {
+      await runtime.close();
+    }
+  });
+});
@@ -0,0 +1,41 @@
+import { describe, expect, it } from "vitest";
+
+import { buildServerInstructions } from "../src/instructions.js";
+
+describe("buildServerInstructions", () => {
+  it("describes the default model, fallback policy, base URLs, tools and enabled flags", () => {
+    const instructions = buildServerInstructions({
+      defaultModel: "deepseek-reasoner",
+      baseUrls: ["https://api.deepseek.com", "https://backup.example.com"],
+      reasonerFallback: { enabled: true, model: "deepseek-chat" },
+      chatToCompletionFallback: true,
+      enabledTools: ["chat_completion", "list_models", "completion"],
+      disabledTools: ["completion"],
+      flags: { offline: false, batch: true, raw_requests: true },
+    });
+
+    expect(instructions).toContain("Default model: deepseek-reasoner.");
+    expect(instructions).toContain(
+      "Fallback policy: retryable deepseek-reasoner failures fall back to deepseek-chat; chat failures retry via /completions.",
+    );
+    expect(instructions).toContain("Base URL: https://api.deepseek.com, https://backup.example.com.");
+    expect(instructions).toContain("Enabled tools: chat_completion, list_models.");
+    expect(instructions).toContain("Enabled features: batch, raw_requests.");
+  });
+
+  it("says so when fallback, tools and flags are all off", () => {
+    const instructions = buildServerInstructions({
+      defaultModel: "deepseek-chat",
+      baseUrls: [],
+      reasonerFallback: { enabled: false, model: "deepseek-chat" },
+      chatToCompletionFallback: false,
+      enabledTools: [],
+      disabledTools: [],
+      flags: { offline: false },
+    });
+
+    expect(instructions).toContain("Fallback policy: no model fallback.");
+    expect(instructions).toContain("Enabled tools: none.");
+    expect(instructions).toContain("Enabled features: none.");
+  });
+});
@@ -0,0 +1,34 @@
+import { describe, expect, it } from "vitest";
+
+import { createJsonOutputChecker, jsonCorrectionPrompt } from "../src/deepseek/json-output.js";
+
+const schema = {
+  type: "object",
+  properties: { name: { type: "string" }, age: { type: "integer" } },
+  required: ["name", "age"],
+};
+
+describe("createJsonOutputChecker", () => {
+  it("accepts replies that parse and match the schema, including fenced JSON", () => {
+    const check = createJsonOutputChecker(schema);
+
+    expect(check('{"name":"Ada","age":36}')).toEqual({ valid: true, data: { name: "Ada", age: 36 } });
+    expect(check('```json\n{"name":"Ada","age":36}\n```')).toEqual({ valid: true, data: { name: "Ada", age: 36 } });
+  });
+
+  it("explains why a reply is rejected", () => {
+    const check = createJsonOutputChecker(schema);
+
+    const unparsable = check("Sure! Here is the JSON you asked for.");
+    expect(unparsable.valid).toBe(false);
+    expect(!unparsable.valid && unparsable.error).toMatch(/^reply is not valid JSON/);
+
+    const mismatched = check('{"name":"Ada"}');
+    expect(mismatched.valid).toBe(false);
+    expect(!mismatched.valid && mismatched.error).toMatch(/^reply does not match the schema/);
+  });
+
+  it("builds a corrective prompt carrying the validation error", () => {
+    expect(jsonCorrectionPrompt("reply is not valid JSON")).toContain("rejected: reply is not valid JSON.");
+  });
+});
@@ -0,0 +1,52 @@
+import { describe, expect, it, vi } from "vitest";
+
+import { Client } from "@modelcontextprotocol/sdk/client/index.js";
+import { InMemoryTransport } from "@modelcontextprotocol/sdk/inMemory.js";
+
+import { ConversationStore } from "../src/conversation-store.js";
+import { DeepSeekApiClient } from "../src/deepseek/client.js";
+import { attachKeepAlive } from "../src/keepalive.js";
+import { createDeepSeekMcpServer } from "../src/mcp-server.js";
+
+async function connectPair(options: Parameters<typeof attachKeepAlive>[1]) {
+  const server = attachKeepAlive(
+    createDeepSeekMcpServer({
+      client: {} as DeepSeekApiClient,
+      conversations: new ConversationStore(200),
+      defaultModel: "deepseek-chat",
+      version: "test",
+    }),
+    options,
+  );
+  const client = new Client({ name: "keepalive", version: "1.0.0" });
+  const [clientTransport, serverTransport] = InMemoryTransport.createLinkedPair();
+  await server.connect(serverTransport);
+  await client.connect(clientTransport);
+  return { server, client };
+}
+
+describe("attachKeepAlive", () => {
+  it("keeps a session that answers pings alive", async () => {
+    const { server, client } = await connectPair({ pingIntervalMs: 10, idleTimeoutMs: 40 });
+    const onclose = vi.fn();
+    client.onclose = onclose;
+
+    try {
+      await new Promise((resolve) => setTimeout(resolve, 150));
+      expect(onclose).not.toHaveBeenCalled();
+      expect(server.isConnected()).toBe(true);
+    } finally {
+      await client.close();
+    }
+  });
+
+  it("closes a session that sends nothing within the idle timeout", async () => {
+    const { server, client } = await connectPair({ idleTimeoutMs: 30 });
+    const closed = new Promise<void>((resolve) => {
+      client.onclose = () => resolve();
+    });
+
+    await closed;
+    expect(server.isConnected()).toBe(false);
+  });
+});
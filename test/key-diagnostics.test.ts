@@ -0,0 +1,33 @@
+import { describe, expect, it } from "vitest";
+
+import { DeepSeekApiError } from "../src/deepseek/client.js";
+import { diagnoseApiKey } from "../src/deepseek/key-diagnostics.js";
+
+describe("diagnoseApiKey", () => {
+  it("treats an available balance as valid and an unavailable one as insufficient", () => {
+    expect(diagnoseApiKey({ balance: { is_available: true, balance_infos: [] } })).toMatchObject({
+      verdict: "valid",
+      key_valid: true,
+      status: 200,
+    });
+    expect(diagnoseApiKey({ balance: { is_available: false, balance_infos: [] } })).toMatchObject({
+      verdict: "insufficient_balance",
+      key_valid: true,
+      remediation: expect.stringContaining("top up"),
+    });
+  });
+
+  it("classifies upstream errors by status and message", () => {
+    const verdictFor = (error: unknown) => diagnoseApiKey({ error }).verdict;
+
+    expect(verdictFor(new DeepSeekApiError("Authentication Fails, Your api key is invalid", { status: 401 }))).toBe(
+      "invalid",
+    );
+    expect(verdictFor(new DeepSeekApiError("API key has expired", { status: 401 }))).toBe("expired");
+    expect(verdictFor(new DeepSeekApiError("Insufficient Balance", { status: 402 }))).toBe("insufficient_balance");
+    expect(verdictFor(new DeepSeekApiError("No key", { code: "no_api_key", retryable: false }))).toBe("missing");
+    expect(verdictFor(new DeepSeekApiError("Server busy", { status: 503 }))).toBe("unknown");
+    expect(verdictFor(new Error("socket hang up"))).toBe("unknown");
+    expect(diagnoseApiKey({ error: new DeepSeekApiError("bad", { status: 401 }) }).key_valid).toBe(false);
+  });
+});
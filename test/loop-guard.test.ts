@@ -0,0 +1,17 @@
+import { describe, expect, it } from "vitest";
+
+import { LoopDetector } from "../src/deepseek/loop-guard.js";
+
+describe("LoopDetector", () => {
+  it("detects a loop after a long non-repeating prefix, with words split across deltas", () => {
+    const detector = new LoopDetector({ ngramSize: 3, maxRepeats: 2 });
+    for (let index = 0; index < 5000; index += 1) {
+      expect(detector.push(`word${index} `)).toBeUndefined();
+    }
+
+    const deltas = ["again and ag", "ain ", "again and again ", "again and again ", "again and again "];
+    const results = deltas.map((delta) => detector.push(delta));
+
+    expect(results.at(-1)).toBe("again and again");
+  });
+});
@@ -1,20 +1,44 @@
+import { mkdtemp, readFile, rm, writeFile } from "node:fs/promises";
+import { tmpdir } from "node:os";
+import { join, relative } from "node:path";
+import { pathToFileURL } from "node:url";
+
 import { afterEach, describe, expect, it, vi } from "vitest";
 
-import { Client } from "@modelcontextprotocol/sdk/client/index.js";
+import { Client, ClientOptions } from "@modelcontextprotocol/sdk/client/index.js";
 import { InMemoryTransport } from "@modelcontextprotocol/sdk/inMemory.js";
+import {
+  CreateMessageRequestSchema,
+  ElicitRequestSchema,
+  ListRootsRequestSchema,
+  LoggingMessageNotificationSchema,
+  ResourceUpdatedNotificationSchema,
+  ToolListChangedNotificationSchema,
+} from "@modelcontextprotocol/sdk/types.js";
 
+import { BalanceWatcher } from "../src/balance-watcher.js";
+import { BudgetTracker } from "../src/budget-tracker.js";
 import { ConversationStore } from "../src/conversation-store.js";
-import { DeepSeekApiClient } from "../src/deepseek/client.js";
-import { createDeepSeekMcpServer } from "../src/mcp-server.js";
+import { DeepSeekApiClient, DeepSeekApiError } from "../src/deepseek/client.js";
+import { WeightedModelSelector } from "../src/deepseek/model-selection.js";
+import { ModelsCache } from "../src/deepseek/models-cache.js";
+import { createDeepSeekMcpServer, DeepSeekMcpServerOptions, describeConfiguration } from "../src/mcp-server.js";
+import { PromptModerator } from "../src/moderation.js";
+import { ClientRateLimiter, TokenBucketRateLimiter } from "../src/rate-limiter.js";
+import { ShutdownController } from "../src/shutdown.js";
+import { ToolToggles } from "../src/tool-toggles.js";
 
 interface Harness {
   serverClose: () => Promise<void>;
   client: Client;
   api: {
     createChatCompletion: ReturnType<typeof vi.fn>;
+    createChatPrefixCompletion: ReturnType<typeof vi.fn>;
     createCompletion: ReturnType<typeof vi.fn>;
+    createFimCompletion: ReturnType<typeof vi.fn>;
     listModels: ReturnType<typeof vi.fn>;
     getUserBalance: ReturnType<typeof vi.fn>;
+    rawRequest: ReturnType<typeof vi.fn>;
     uploadVisionAsset: ReturnType<typeof vi.fn>;
     uploadVideoAsset: ReturnType<typeof vi.fn>;
     generateImage: ReturnType<typeof vi.fn>;
@@ -23,7 +47,11 @@ interface Harness {
   };
 }
 
-async function createHarness(experimentalV4Enabled = false): Promise<Harness> {
+async function createHarness(
+  experimentalV4Enabled = false,
+  overrides: Partial<DeepSeekMcpServerOptions> = {},
+  clientOptions: ClientOptions = {},
+): Promise<Harness> {
   const api = {
     createChatCompletion: vi.fn(async (request) => ({
       response: {
@@ -43,6 +71,24 @@ async function createHarness(experimentalV4Enabled = false): Promise<Harness> {
         ],
       },
     })),
+    createChatPrefixCompletion: vi.fn(async (request) => ({
+      response: {
+        id: "chat-prefix-1",
+        object: "chat.completion",
+        created: 1,
+        model: String(request.model),
+        choices: [
+          {
+            index: 0,
+            finish_reason: "stop",
+            message: {
+              role: "assistant",
+              content: "print('hello')\n```",
+            },
+          },
+        ],
+      },
+    })),
     createCompletion: vi.fn(async () => ({
       response: {
         id: "cmpl-1",
@@ -58,6 +104,15 @@ async function createHarness(experimentalV4Enabled = false): Promise<Harness> {
         ],
       },
     })),
+    createFimCompletion: vi.fn(async () => ({
+      response: {
+        id: "fim-1",
+        object: "text_completion",
+        created: 1,
+        model: "deepseek-chat",
+        choices: [{ index: 0, finish_reason: "stop", text: "return a + b" }],
+      },
+    })),
     listModels: vi.fn(async () => ({
       object: "list",
       data: [
@@ -101,6 +156,7 @@ async function createHarness(experimentalV4Enabled = false): Promise<Harness> {
       status: "completed",
       video_url: "https://cdn.example.com/out.mp4",
     })),
+    rawRequest: vi.fn(async () => ({ is_available: true })),
   };
 
   const mcpServer = createDeepSeekMcpServer({
@@ -109,13 +165,17 @@ async function createHarness(experimentalV4Enabled = false): Promise<Harness> {
     defaultModel: "deepseek-chat",
     experimentalV4Enabled,
     version: "test",
+    ...overrides,
   });
 
   const [clientTransport, serverTransport] = InMemoryTransport.createLinkedPair();
-  const client = new Client({
-    name: "test-client",
-    version: "1.0.0",
-  });
+  const client = new Client(
+    {
+      name: "test-client",
+      version: "1.0.0",
+    },
+    clientOptions,
+  );
 
   await Promise.all([mcpServer.connect(serverTransport), client.connect(clientTransport)]);
 
@@ -165,6 +225,358 @@ describe("createDeepSeekMcpServer", () => {
     }
   });
 
+  it("annotates read-only and open-world generation tools", async () => {
+    const harness = await createHarness();
+
+    try {
+      const tools = await harness.client.listTools();
+      const annotations = new Map(tools.tools.map((tool) => [tool.name, tool.annotations]));
+
+      for (const name of ["list_models", "get_user_balance"]) {
+        expect(annotations.get(name)).toMatchObject({ readOnlyHint: true, openWorldHint: true });
+      }
+      for (const name of ["completion", "fim_completion", "json_chat"]) {
+        expect(annotations.get(name)).toMatchObject({
+          readOnlyHint: false,
+          destructiveHint: false,
+          idempotentHint: false,
+          openWorldHint: true,
+        });
+      }
+      expect(annotations.get("chat_completion")).toMatchObject({ destructiveHint: true, openWorldHint: true });
+    } finally {
+      await harness.serverClose();
+    }
+  });
+
+  it("advertises output schemas for chat_completion, list_models and get_user_balance", async () => {
+    const harness = await createHarness();
+
+    try {
+      const tools = await harness.client.listTools();
+      const schemas = new Map(tools.tools.map((tool) => [tool.name, tool.outputSchema]));
+      expect(Object.keys(schemas.get("chat_completion")?.properties ?? {})).toEqual(
+        expect.arrayContaining(["response_text", "choices", "usage"]),
+      );
+      expect(Object.keys(schemas.get("list_models")?.properties ?? {})).toEqual(
+        expect.arrayContaining(["data", "stale"]),
+      );
+      expect(Object.keys(schemas.get("get_user_balance")?.properties ?? {})).toEqual(
+        expect.arrayContaining(["is_available", "balance_infos"]),
+      );
+
+      const chat = await harness.client.callTool({ name: "chat_completion", arguments: { message: "hi" } });
+      expect(chat.isError).toBeFalsy();
+      const models = await harness.client.callTool({ name: "list_models", arguments: {} });
+      expect(models.structuredContent).toMatchObject({ stale: false });
+
+      harness.api.getUserBalance.mockRejectedValueOnce(new DeepSeekApiError("Insufficient Balance", { status: 402 }));
+      const balance = await harness.client.callTool({ name: "get_user_balance", arguments: {} });
+      expect(balance.isError).toBe(true);
+      expect(balance.structuredContent).toBeUndefined();
+      expect(JSON.stringify(balance.content)).toContain("DeepSeek API error (402)");
+    } finally {
+      await harness.serverClose();
+    }
+  });
+
+  it("marks tool errors retryable from the configured retry statuses", async () => {
+    const harness = await createHarness(false, { retryStatuses: [402] });
+    harness.api.createCompletion.mockRejectedValueOnce(new DeepSeekApiError("Insufficient Balance", { status: 402 }));
+    harness.api.createCompletion.mockRejectedValueOnce(new DeepSeekApiError("upstream down", { status: 503 }));
+
+    try {
+      const first = await harness.client.callTool({ name: "completion", arguments: { prompt: "hi" } });
+      expect(first.structuredContent).toMatchObject({ status: 402, retryable: true });
+      const second = await harness.client.callTool({ name: "completion", arguments: { prompt: "hi" } });
+      expect(second.structuredContent).toMatchObject({ status: 503, retryable: false });
+    } finally {
+      await harness.serverClose();
+    }
+  });
+
+  it("hides and blocks tools listed in disabledTools", async () => {
+    const harness = await createHarness(false, { disabledTools: ["get_user_balance"] });
+
+    try {
+      const tools = await harness.client.listTools();
+      const names = tools.tools.map((tool) => tool.name);
+      expect(names).toContain("chat_completion");
+      expect(names).not.toContain("get_user_balance");
+
+      const blocked = await harness.client
+        .callTool({ name: "get_user_balance", arguments: {} })
+        .then((result) => result.isError === true, () => true);
+      expect(blocked).toBe(true);
+      expect(harness.api.getUserBalance).not.toHaveBeenCalled();
+
+      const endpoints = await harness.client.readResource({ uri: "deepseek://api/endpoints" });
+      const content = endpoints.contents[0];
+      if (!("text" in content) || typeof content.text !== "string") {
+        throw new Error("expected text endpoints resource");
+      }
+      expect(content.text).not.toContain("get_user_balance");
+
+      const resources = await harness.client.listResources();
+      expect(resources.resources.map((resource) => resource.uri)).not.toContain("deepseek://balance");
+    } finally {
+      await harness.serverClose();
+    }
+  });
+
+  it("only exposes tools in the enabledTools allowlist", async () => {
+    const harness = await createHarness(false, { enabledTools: ["chat_completion", "list_models"] });
+
+    try {
+      const tools = await harness.client.listTools();
+      expect(tools.tools.map((tool) => tool.name).sort()).toEqual(["chat_completion", "list_models"]);
+    } finally {
+      await harness.serverClose();
+    }
+  });
+
+  it("toggles tools at runtime with set_tool_enabled and notifies list_changed", async () => {
+    const harness = await createHarness(false, {
+      enableToolAdmin: true,
+      trustedTransport: true,
+      toolToggles: new ToolToggles(),
+      disabledTools: ["completion"],
+    });
+    const listChanged = vi.fn();
+    harness.client.setNotificationHandler(ToolListChangedNotificationSchema, listChanged);
+
+    try {
+      const disabled = await harness.client.callTool({
+        name: "set_tool_enabled",
+        arguments: { name: "get_user_balance", enabled: false },
+      });
+      expect(disabled.isError).not.toBe(true);
+      expect(disabled.structuredContent).toMatchObject({
+        name: "get_user_balance",
+        enabled: false,
+        changed: true,
+        runtime_disabled_tools: ["get_user_balance"],
+      });
+
+      await vi.waitFor(() => expect(listChanged).toHaveBeenCalled());
+      let names = (await harness.client.listTools()).tools.map((tool) => tool.name);
+      expect(names).not.toContain("get_user_balance");
+      let uris = (await harness.client.listResources()).resources.map((resource) => resource.uri);
+      expect(uris).not.toContain("deepseek://balance");
+
+      const blocked = await harness.client.callTool({
+        name: "set_tool_enabled",
+        arguments: { name: "completion", enabled: true },
+      });
+      expect(blocked.isError).toBe(true);
+
+      await harness.client.callTool({
+        name: "set_tool_enabled",
+        arguments: { name: "get_user_balance", enabled: true },
+      });
+      names = (await harness.client.listTools()).tools.map((tool) => tool.name);
+      expect(names).toContain("get_user_balance");
+      expect(names).not.toContain("completion");
+      uris = (await harness.client.listResources()).resources.map((resource) => resource.uri);
+      expect(uris).toContain("deepseek://balance");
+    } finally {
+      await harness.serverClose();
+    }
+  });
+
+  it("only offers set_tool_enabled with tool admin on, and only to stdio or admin-scoped callers", async () => {
+    const disabled = await createHarness(false, { toolToggles: new ToolToggles() });
+    try {
+      const names = (await disabled.client.listTools()).tools.map((tool) => tool.name);
+      expect(names).not.toContain("set_tool_enabled");
+    } finally {
+      await disabled.serverClose();
+    }
+
+    const network = await createHarness(false, { enableToolAdmin: true, toolToggles: new ToolToggles() });
+    try {
+      const result = await network.client.callTool({
+        name: "set_tool_enabled",
+        arguments: { name: "get_user_balance", enabled: false },
+      });
+      expect(result.isError).toBe(true);
+      expect(JSON.stringify(result.content)).toContain("deepseek:admin");
+    } finally {
+      await network.serverClose();
+    }
+  });
+
+  it("exposes models and balance as readable resources", async () => {
+    const harness = await createHarness();
+
+    try {
+      const resources = await harness.client.listResources();
+      const uris = resources.resources.map((resource) => resource.uri);
+      expect(uris).toContain("deepseek://models");
+      expect(uris).toContain("deepseek://balance");
+
+      const models = await harness.client.readResource({ uri: "deepseek://models" });
+      const modelsContent = models.contents[0];
+      if (!("text" in modelsContent) || typeof modelsContent.text !== "string") {
+        throw new Error("expected text models resource");
+      }
+      expect(JSON.parse(modelsContent.text)).toMatchObject({
+        stale: false,
+        data: [{ id: "deepseek-chat" }, { id: "deepseek-reasoner" }],
+      });
+
+      const balance = await harness.client.readResource({ uri: "deepseek://balance" });
+      const balanceContent = balance.contents[0];
+      if (!("text" in balanceContent) || typeof balanceContent.text !== "string") {
+        throw new Error("expected text balance resource");
+      }
+      expect(JSON.parse(balanceContent.text)).toMatchObject({ is_available: true });
+      expect(harness.api.getUserBalance).toHaveBeenCalledTimes(1);
+    } finally {
+      await harness.serverClose();
+    }
+  });
+
+  it("pushes resources/updated to balance subscribers when the polled balance changes", async () => {
+    const harness = await createHarness(false, { balancePollIntervalMs: 10 });
+    harness.api.getUserBalance
+      .mockResolvedValueOnce({ is_available: true, balance_infos: [] })
+      .mockResolvedValue({ is_available: false, balance_infos: [] });
+    const updated = vi.fn();
+    harness.client.setNotificationHandler(ResourceUpdatedNotificationSchema, updated);
+
+    try {
+      await expect(harness.client.subscribeResource({ uri: "deepseek://models" })).rejects.toThrow(
+        "only supported for deepseek://balance",
+      );
+
+      await harness.client.subscribeResource({ uri: "deepseek://balance" });
+      await vi.waitFor(() =>
+        expect(updated).toHaveBeenCalledWith(expect.objectContaining({ params: { uri: "deepseek://balance" } })),
+      );
+
+      await harness.client.unsubscribeResource({ uri: "deepseek://balance" });
+      const calls = harness.api.getUserBalance.mock.calls.length;
+      await new Promise((resolve) => setTimeout(resolve, 40));
+      expect(harness.api.getUserBalance.mock.calls.length).toBe(calls);
+    } finally {
+      await harness.serverClose();
+    }
+  });
+
+  it("shares one balance poll across sessions subscribed to the process-wide watcher", async () => {
+    const fetchBalance = vi.fn(async () => ({ is_available: true, balance_infos: [] }));
+    const balanceWatcher = new BalanceWatcher({ fetchBalance, intervalMs: 60_000 });
+    const first = await createHarness(false, { balanceWatcher });
+    const second = await createHarness(false, { balanceWatcher });
+
+    try {
+      await first.client.subscribeResource({ uri: "deepseek://balance" });
+      await second.client.subscribeResource({ uri: "deepseek://balance" });
+      expect(balanceWatcher.subscriberCount).toBe(2);
+      expect(fetchBalance).toHaveBeenCalledTimes(1);
+      expect(first.api.getUserBalance).not.toHaveBeenCalled();
+
+      await first.client.unsubscribeResource({ uri: "deepseek://balance" });
+      expect(balanceWatcher.subscriberCount).toBe(1);
+    } finally {
+      await first.serverClose();
+      await second.serverClose();
+    }
+  });
+
+  it("lists and renders the prompt library with typed arguments", async () => {
+    const harness = await createHarness();
+
+    try {
+      const prompts = await harness.client.listPrompts();
+      expect(prompts.prompts.map((prompt) => prompt.name)).toEqual(
+        expect.arrayContaining(["deepseek_chat_starter", "code_review", "summarize", "translate"]),
+      );
+      const translate = prompts.prompts.find((prompt) => prompt.name === "translate");
+      expect(translate?.arguments).toEqual(
+        expect.arrayContaining([
+          expect.objectContaining({ name: "text", required: true }),
+          expect.objectContaining({ name: "target_language", required: true }),
+          expect.objectContaining({ name: "tone", required: false }),
+        ]),
+      );
+
+      const review = await harness.client.getPrompt({
+        name: "code_review",
+        arguments: { code: "eval(input)", language: "python", focus: "security" },
+      });
+      expect(review.messages[0]?.content).toMatchObject({
+        type: "text",
+        text: expect.stringContaining("Focus on security issues."),
+      });
+
+      const translated = await harness.client.getPrompt({
+        name: "translate",
+        arguments: { text: "Bonjour", target_language: "English", source_language: "French" },
+      });
+      expect(translated.messages[0]?.content).toMatchObject({
+        text: "Translate the following text from French into English.\nReply with the translation only.\n\nBonjour",
+      });
+
+      await expect(
+        harness.client.getPrompt({ name: "summarize", arguments: { text: "x", length: "huge" } }),
+      ).rejects.toThrow();
+    } finally {
+      await harness.serverClose();
+    }
+  });
+
+  it("completes prompt model arguments from /models and enum arguments from their allowed values", async () => {
+    const harness = await createHarness();
+
+    try {
+      const models = await harness.client.complete({
+        ref: { type: "ref/prompt", name: "deepseek_chat_starter" },
+        argument: { name: "model", value: "deepseek-r" },
+      });
+      expect(models.completion.values).toEqual(["deepseek-reasoner"]);
+
+      await harness.client.complete({
+        ref: { type: "ref/prompt", name: "deepseek_chat_starter" },
+        argument: { name: "model", value: "" },
+      });
+      expect(harness.api.listModels).toHaveBeenCalledTimes(1);
+
+      const lengths = await harness.client.complete({
+        ref: { type: "ref/prompt", name: "summarize" },
+        argument: { name: "length", value: "s" },
+      });
+      expect(lengths.completion.values).toEqual(["short"]);
+    } finally {
+      await harness.serverClose();
+    }
+  });
+
+  it("emits MCP log messages at or above the level the client selected", async () => {
+    const harness = await createHarness(false, { lowBalanceThreshold: 10 });
+    const messages: Array<{ level: string; data: unknown }> = [];
+    harness.client.setNotificationHandler(LoggingMessageNotificationSchema, (notification) => {
+      messages.push(notification.params);
+    });
+
+    try {
+      await harness.client.setLoggingLevel("error");
+      await harness.client.callTool({ name: "get_user_balance", arguments: {} });
+
+      await harness.client.setLoggingLevel("warning");
+      await harness.client.callTool({ name: "get_user_balance", arguments: {} });
+
+      await vi.waitFor(() => expect(messages).toHaveLength(1));
+      expect(messages[0]).toMatchObject({
+        level: "warning",
+        data: { message: "DeepSeek balance is low", is_available: true, balances: ["9.99 USD"] },
+      });
+    } finally {
+      await harness.serverClose();
+    }
+  });
+
   it("persists conversation history by conversation_id for chat_completion", async () => {
     const harness = await createHarness();
 
@@ -178,6 +590,11 @@ describe("createDeepSeekMcpServer", () => {
       });
 
       expect(first.isError).toBeFalsy();
+      expect(first.structuredContent).toMatchObject({
+        id: "chat-1",
+        response_text: "assistant:hello",
+        choices: [{ index: 0, finish_reason: "stop", content: "assistant:hello", tool_calls: [] }],
+      });
 
       const second = await harness.client.callTool({
         name: "chat_completion",
@@ -214,6 +631,12 @@ describe("createDeepSeekMcpServer", () => {
 
       const parsed = JSON.parse(content.text);
       expect(parsed.message_count).toBe(4);
+      expect(parsed).toMatchObject({ conversation_id: "thread-1", turn_count: 2 });
+      expect(parsed.messages.at(-1)).toMatchObject({ role: "assistant", content: "assistant:follow up" });
+
+      await expect(harness.client.readResource({ uri: "deepseek://conversations/missing" })).rejects.toThrow(
+        "Unknown conversation: missing",
+      );
 
       await harness.client.callTool({
         name: "reset_conversation",
@@ -235,88 +658,2362 @@ describe("createDeepSeekMcpServer", () => {
     }
   });
 
-  it("forwards parameters for completion/list_models/get_user_balance", async () => {
+  it("routes chat_prefix_completion with an assistant prefix message and returns the continuation", async () => {
     const harness = await createHarness();
 
     try {
-      const completion = await harness.client.callTool({
-        name: "completion",
+      const result = await harness.client.callTool({
+        name: "chat_prefix_completion",
         arguments: {
-          prompt: "def foo():",
-          max_tokens: 64,
-          top_p: 0.7,
-          stream: false,
+          message: "Write hello world in python",
+          prefix: "```python\n",
+          stop: ["```"],
         },
       });
 
-      expect(completion.isError).toBeFalsy();
-      expect(harness.api.createCompletion).toHaveBeenCalledTimes(1);
-      expect(harness.api.createCompletion.mock.calls[0]?.[0]).toMatchObject({
-        prompt: "def foo():",
-        max_tokens: 64,
-        top_p: 0.7,
-      });
+      expect(result.isError).toBeFalsy();
+      expect(harness.api.createChatCompletion).not.toHaveBeenCalled();
 
-      const models = await harness.client.callTool({ name: "list_models", arguments: {} });
-      expect(models.isError).toBeFalsy();
-      expect(harness.api.listModels).toHaveBeenCalledTimes(1);
+      const request = harness.api.createChatPrefixCompletion.mock.calls[0]?.[0];
+      expect(request.messages.at(-1)).toEqual({ role: "assistant", content: "```python\n", prefix: true });
+      expect(request.stop).toEqual(["```"]);
 
-      const balance = await harness.client.callTool({ name: "get_user_balance", arguments: {} });
-      expect(balance.isError).toBeFalsy();
-      expect(harness.api.getUserBalance).toHaveBeenCalledTimes(1);
+      const structured = result.structuredContent as Record<string, unknown>;
+      expect(structured.continuation).toBe("print('hello')\n```");
+      expect(structured.text).toBe("print('hello')\n```");
+      expect(result.content).toEqual([{ type: "text", text: "print('hello')\n```" }]);
+
+      const merged = await harness.client.callTool({
+        name: "chat_prefix_completion",
+        arguments: { message: "Write hello world in python", prefix: "```python\n", include_prefix: true },
+      });
+      expect(merged.content).toEqual([{ type: "text", text: "```python\nprint('hello')\n```" }]);
+      expect(merged.structuredContent).toMatchObject({ prefix: "```python\n", text: "```python\nprint('hello')\n```" });
     } finally {
       await harness.serverClose();
     }
   });
 
-  it("fails fast for v4 tools when feature flag is disabled", async () => {
-    const harness = await createHarness(false);
+  it("sends fim_completion prefix and suffix with a language hint to the FIM endpoint", async () => {
+    const harness = await createHarness();
 
     try {
       const result = await harness.client.callTool({
-        name: "vision_upload",
-        arguments: {
-          file_url: "https://example.com/a.jpg",
-        },
+        name: "fim_completion",
+        arguments: { prefix: "def add(a, b):\n    ", suffix: "\n\nprint(add(1, 2))\n", language: "python", max_tokens: 64 },
       });
 
-      expect(result.isError).toBe(true);
-      expect((result.structuredContent as Record<string, unknown>)?.error_type).toBe("experimental_feature_disabled");
-      expect(harness.api.uploadVisionAsset).not.toHaveBeenCalled();
+      expect(result.isError).toBeFalsy();
+      expect(harness.api.createCompletion).not.toHaveBeenCalled();
+      expect(harness.api.createFimCompletion.mock.calls[0]?.[0]).toEqual({
+        model: "deepseek-chat",
+        prompt: "# language: python\ndef add(a, b):\n    ",
+        suffix: "\n\nprint(add(1, 2))\n",
+        max_tokens: 64,
+        stream: false,
+      });
+      expect(result.structuredContent).toMatchObject({ text: "return a + b", language: "python", finish_reason: "stop" });
+      expect(result.content).toEqual([{ type: "text", text: "return a + b" }]);
     } finally {
       await harness.serverClose();
     }
   });
 
-  it("calls v4 provider methods when feature flag is enabled", async () => {
-    const harness = await createHarness(true);
+  it("retries json_chat with a corrective message until the reply matches the schema", async () => {
+    const harness = await createHarness();
+    const reply = (content: string) => ({
+      response: {
+        id: "json-1",
+        object: "chat.completion",
+        created: 1,
+        model: "deepseek-chat",
+        choices: [{ index: 0, finish_reason: "stop", message: { role: "assistant", content } }],
+      },
+    });
+    harness.api.createChatCompletion
+      .mockImplementationOnce(async () => reply('{"city":"Paris"}'))
+      .mockImplementationOnce(async () => reply('{"city":"Paris","population":2102650}'));
 
     try {
-      const vision = await harness.client.callTool({
-        name: "vision_upload",
-        arguments: { file_url: "https://example.com/a.jpg" },
+      const result = await harness.client.callTool({
+        name: "json_chat",
+        arguments: {
+          messages: [{ role: "user", content: "Describe the capital of France" }],
+          schema: {
+            type: "object",
+            properties: { city: { type: "string" }, population: { type: "integer" } },
+            required: ["city", "population"],
+          },
+        },
       });
-      expect(vision.isError).toBeFalsy();
 
-      const image = await harness.client.callTool({
-        name: "image_generation",
-        arguments: { prompt: "A mountain lake at sunset" },
+      expect(result.isError).toBeFalsy();
+      expect(result.structuredContent).toMatchObject({
+        data: { city: "Paris", population: 2102650 },
+        attempts: 2,
       });
-      expect(image.isError).toBeFalsy();
 
-      const video = await harness.client.callTool({
-        name: "video_generation",
+      const [first, second] = harness.api.createChatCompletion.mock.calls.map((call) => call[0]);
+      expect(first.response_format).toEqual({ type: "json_object" });
+      expect(first.messages[0]).toMatchObject({ role: "system" });
+      expect(first.messages[0].content).toContain('"required":["city","population"]');
+      expect(second.messages.slice(-2)).toEqual([
+        { role: "assistant", content: '{"city":"Paris"}' },
+        { role: "user", content: expect.stringContaining("does not match the schema") },
+      ]);
+
+      harness.api.createChatCompletion.mockImplementation(async () => reply("not json"));
+      const failed = await harness.client.callTool({
+        name: "json_chat",
         arguments: {
-          prompt: "A short drone shot over mountains",
-          wait_for_completion: true,
-          poll_interval_ms: 1,
-          max_wait_ms: 10,
+          messages: [{ role: "user", content: "Describe the capital of France" }],
+          schema: { type: "object" },
+          max_retries: 1,
         },
       });
-      expect(video.isError).toBeFalsy();
+      expect(failed.isError).toBe(true);
+      expect(failed.structuredContent).toMatchObject({
+        error_type: "schema_validation_failed",
+        attempts: 2,
+        last_output: "not json",
+      });
+    } finally {
+      await harness.serverClose();
+    }
+  });
 
-      expect(harness.api.uploadVisionAsset).toHaveBeenCalledTimes(1);
-      expect(harness.api.generateImage).toHaveBeenCalledTimes(1);
+  it("pauses function_calling for caller-run tool results and resumes until the final answer", async () => {
+    const harness = await createHarness();
+    const reply = (message: Record<string, unknown>, finishReason: string) => ({
+      response: {
+        id: "fc-1",
+        object: "chat.completion",
+        created: 1,
+        model: "deepseek-chat",
+        choices: [{ index: 0, finish_reason: finishReason, message: { role: "assistant", ...message } }],
+      },
+    });
+    const weatherCall = {
+      id: "call_1",
+      type: "function",
+      function: { name: "get_weather", arguments: '{"city":"Paris"}' },
+    };
+    harness.api.createChatCompletion
+      .mockImplementationOnce(async () => reply({ content: null, tool_calls: [weatherCall] }, "tool_calls"))
+      .mockImplementationOnce(async () => reply({ content: "It is 18C in Paris." }, "stop"));
+    const tools = [
+      {
+        type: "function",
+        function: { name: "get_weather", parameters: { type: "object", properties: { city: { type: "string" } } } },
+      },
+    ];
+
+    try {
+      const paused = await harness.client.callTool({
+        name: "function_calling",
+        arguments: { messages: [{ role: "user", content: "Weather in Paris?" }], tools },
+      });
+      const pausedContent = paused.structuredContent as Record<string, unknown>;
+      expect(pausedContent).toMatchObject({ status: "requires_tool_results", depth: 1, tool_calls: [weatherCall] });
+      expect(harness.api.createChatCompletion.mock.calls[0]?.[0].tools).toEqual(tools);
+
+      const completed = await harness.client.callTool({
+        name: "function_calling",
+        arguments: {
+          run_id: pausedContent.run_id,
+          tool_results: [{ tool_call_id: "call_1", content: '{"temperature_c":18}' }],
+        },
+      });
+      expect(completed.structuredContent).toMatchObject({
+        status: "completed",
+        run_id: null,
+        answer: "It is 18C in Paris.",
+        depth: 2,
+        trace: [
+          { depth: 1, tool_calls: [weatherCall], tool_results: [{ tool_call_id: "call_1" }] },
+          { depth: 2, content: "It is 18C in Paris.", tool_calls: [] },
+        ],
+      });
+      expect(harness.api.createChatCompletion.mock.calls[1]?.[0].messages.slice(-2)).toEqual([
+        { role: "assistant", content: null, tool_calls: [weatherCall] },
+        { role: "tool", tool_call_id: "call_1", content: '{"temperature_c":18}' },
+      ]);
+
+      const finished = await harness.client.callTool({
+        name: "function_calling",
+        arguments: { run_id: pausedContent.run_id, tool_results: [{ tool_call_id: "call_1", content: "again" }] },
+      });
+      expect(finished.isError).toBe(true);
+      expect(finished.structuredContent).toMatchObject({ message: expect.stringContaining("Unknown or finished run_id") });
+    } finally {
+      await harness.serverClose();
+    }
+  });
+
+  it("keeps a paused function_calling run resumable when the resumed upstream call fails", async () => {
+    const harness = await createHarness();
+    const weatherCall = {
+      id: "call_1",
+      type: "function",
+      function: { name: "get_weather", arguments: '{"city":"Paris"}' },
+    };
+    const reply = (message: Record<string, unknown>, finishReason: string) => ({
+      response: {
+        id: "fc-retry",
+        object: "chat.completion",
+        created: 1,
+        model: "deepseek-chat",
+        choices: [{ index: 0, finish_reason: finishReason, message: { role: "assistant", ...message } }],
+      },
+    });
+    harness.api.createChatCompletion
+      .mockImplementationOnce(async () => reply({ content: null, tool_calls: [weatherCall] }, "tool_calls"))
+      .mockImplementationOnce(async () => {
+        throw new DeepSeekApiError("rate limited", { status: 429 });
+      })
+      .mockImplementationOnce(async () => reply({ content: "It is 18C in Paris." }, "stop"));
+    const resume = (runId: unknown) =>
+      harness.client.callTool({
+        name: "function_calling",
+        arguments: { run_id: runId, tool_results: [{ tool_call_id: "call_1", content: '{"temperature_c":18}' }] },
+      });
+
+    try {
+      const paused = await harness.client.callTool({
+        name: "function_calling",
+        arguments: {
+          messages: [{ role: "user", content: "Weather in Paris?" }],
+          tools: [{ type: "function", function: { name: "get_weather", parameters: { type: "object" } } }],
+        },
+      });
+      const runId = (paused.structuredContent as Record<string, unknown>).run_id;
+
+      const failed = await resume(runId);
+      expect(failed.isError).toBe(true);
+
+      const retried = await resume(runId);
+      expect(retried.structuredContent).toMatchObject({ status: "completed", answer: "It is 18C in Paris.", depth: 2 });
+      expect(harness.api.createChatCompletion.mock.calls[2]?.[0].messages.slice(-2)).toEqual([
+        { role: "assistant", content: null, tool_calls: [weatherCall] },
+        { role: "tool", tool_call_id: "call_1", content: '{"temperature_c":18}' },
+      ]);
+    } finally {
+      await harness.serverClose();
+    }
+  });
+
+  it("estimates per-message and total tokens offline via count_tokens", async () => {
+    const harness = await createHarness();
+
+    try {
+      const result = await harness.client.callTool({
+        name: "count_tokens",
+        arguments: {
+          text: "0123456789",
+          messages: [
+            { role: "system", content: "0123456789" },
+            { role: "user", content: "你好" },
+          ],
+          model: "deepseek-reasoner",
+        },
+      });
+
+      expect(result.isError).toBeFalsy();
+      expect(result.structuredContent).toEqual({
+        model: "deepseek-reasoner",
+        method: "character_estimate",
+        estimate: true,
+        text_tokens: 3,
+        messages: [
+          { index: 0, role: "system", tokens: 7 },
+          { index: 1, role: "user", tokens: 6 },
+        ],
+        total_tokens: 16,
+        context_window: 128000,
+        remaining_context_tokens: 127984,
+      });
+      expect(harness.api.createChatCompletion).not.toHaveBeenCalled();
+    } finally {
+      await harness.serverClose();
+    }
+  });
+
+  it("projects request cost with cache-hit and cache-miss pricing via estimate_cost", async () => {
+    const harness = await createHarness();
+
+    try {
+      const result = await harness.client.callTool({
+        name: "estimate_cost",
+        arguments: { model: "deepseek-chat", input_tokens: 1_000_000, output_tokens: 500_000, cache_hit_ratio: 0.5 },
+      });
+
+      expect(result.isError).toBeFalsy();
+      const structured = result.structuredContent as Record<string, number>;
+      expect(structured).toMatchObject({ cache_hit_tokens: 500_000, cache_miss_tokens: 500_000, input_tokens_estimated: false });
+      expect(structured.cost_usd).toBeCloseTo(0.014 + 0.14 + 0.21, 9);
+      expect(structured.all_cache_miss_cost_usd).toBeCloseTo(0.28 + 0.21, 9);
+      expect(structured.all_cache_hit_cost_usd).toBeCloseTo(0.028 + 0.21, 9);
+
+      const fromMessages = await harness.client.callTool({
+        name: "estimate_cost",
+        arguments: { messages: [{ role: "user", content: "0123456789" }] },
+      });
+      expect(fromMessages.structuredContent).toMatchObject({
+        model: "deepseek-chat",
+        input_tokens: 7,
+        input_tokens_estimated: true,
+        output_tokens: 0,
+      });
+      expect(harness.api.createChatCompletion).not.toHaveBeenCalled();
+    } finally {
+      await harness.serverClose();
+    }
+  });
+
+  it("tallies this session's upstream usage in usage_summary", async () => {
+    const harness = await createHarness();
+    harness.api.createChatCompletion.mockImplementation(async (request) => ({
+      response: {
+        id: "chat-usage",
+        object: "chat.completion",
+        created: 1,
+        model: String(request.model),
+        choices: [{ index: 0, finish_reason: "stop", message: { role: "assistant", content: "ok" } }],
+        usage: { prompt_tokens: 1000, completion_tokens: 500, total_tokens: 1500 },
+      },
+      fallback: { fromModel: "deepseek-reasoner", toModel: "deepseek-chat", reason: "HTTP 503" },
+    }));
+
+    try {
+      await harness.client.callTool({ name: "chat_completion", arguments: { message: "one" } });
+      await harness.client.callTool({ name: "chat_completion", arguments: { message: "two" } });
+
+      const summary = await harness.client.callTool({ name: "usage_summary", arguments: { reset: true } });
+      const structured = summary.structuredContent as Record<string, unknown>;
+      expect(structured).toMatchObject({
+        requests: 2,
+        fallbacks: 2,
+        prompt_tokens: 2000,
+        completion_tokens: 1000,
+        scope: "session",
+        reset: true,
+        by_model: { "deepseek-chat": { requests: 2 } },
+      });
+      expect(structured.estimated_cost_usd).toBeCloseTo((2000 * 0.28 + 1000 * 0.42) / 1_000_000, 12);
+
+      const afterReset = await harness.client.callTool({ name: "usage_summary", arguments: {} });
+      expect(afterReset.structuredContent).toMatchObject({ requests: 0, by_model: {} });
+    } finally {
+      await harness.serverClose();
+    }
+  });
+
+  it("reports server-wide usage without reset on the shared stateless server", async () => {
+    const harness = await createHarness(false, { sessionScoped: false });
+
+    try {
+      await harness.client.callTool({ name: "chat_completion", arguments: { message: "one" } });
+
+      const reset = await harness.client.callTool({ name: "usage_summary", arguments: { reset: true } });
+      expect(reset.isError).toBe(true);
+
+      const summary = await harness.client.callTool({ name: "usage_summary", arguments: {} });
+      expect(summary.structuredContent).toMatchObject({ scope: "server", requests: 1 });
+    } finally {
+      await harness.serverClose();
+    }
+  });
+
+  it("summarizes older turns before continuing a long conversation", async () => {
+    const harness = await createHarness();
+    harness.api.createChatCompletion
+      .mockImplementationOnce(async () => ({
+        response: {
+          id: "summary-1",
+          object: "chat.completion",
+          created: 1,
+          model: "deepseek-chat",
+          choices: [{ index: 0, finish_reason: "stop", message: { role: "assistant", content: "User likes tea." } }],
+        },
+      }))
+      .mockImplementationOnce(async () => ({
+        response: {
+          id: "final-1",
+          object: "chat.completion",
+          created: 2,
+          model: "deepseek-chat",
+          choices: [{ index: 0, finish_reason: "stop", message: { role: "assistant", content: "Try oolong." } }],
+        },
+      }));
+
+    try {
+      const result = await harness.client.callTool({
+        name: "summarize_and_continue",
+        arguments: {
+          keep_last: 2,
+          messages: [
+            { role: "system", content: "Be brief." },
+            { role: "user", content: "I like tea." },
+            { role: "assistant", content: "Noted." },
+            { role: "user", content: "Also green tea." },
+            { role: "assistant", content: "Great." },
+            { role: "user", content: "What should I try next?" },
+          ],
+        },
+      });
+
+      expect(result.isError).toBeFalsy();
+      expect(harness.api.createChatCompletion).toHaveBeenCalledTimes(2);
+
+      const summaryRequest = harness.api.createChatCompletion.mock.calls[0]?.[0];
+      expect(summaryRequest.messages[1].content).toContain("user: I like tea.");
+      expect(summaryRequest.messages[1].content).toContain("user: Also green tea.");
+      expect(summaryRequest.messages[1].content).not.toContain("What should I try next?");
+
+      const finalRequest = harness.api.createChatCompletion.mock.calls[1]?.[0];
+      expect(finalRequest.messages).toEqual([
+        { role: "system", content: "Be brief." },
+        { role: "system", content: "Summary of earlier conversation:\nUser likes tea." },
+        { role: "assistant", content: "Great." },
+        { role: "user", content: "What should I try next?" },
+      ]);
+
+      const structured = result.structuredContent as Record<string, unknown>;
+      expect(structured.summary).toBe("User likes tea.");
+      expect(structured.summarized_message_count).toBe(3);
+      expect(structured.response_text).toBe("Try oolong.");
+    } finally {
+      await harness.serverClose();
+    }
+  });
+
+  it("resolves the effective chat request with defaults and transformations applied without sending it", async () => {
+    const harness = await createHarness(false, {
+      defaultModel: "deepseek-reasoner",
+      tokenParam: "max_completion_tokens",
+      mergeConsecutiveMessages: true,
+    });
+
+    try {
+      await harness.client.callTool({ name: "chat_completion", arguments: { message: "first", conversation_id: "resolve" } });
+      harness.api.createChatCompletion.mockClear();
+
+      const result = await harness.client.callTool({
+        name: "resolve_request",
+        arguments: {
+          message: "second",
+          conversation_id: "resolve",
+          max_tokens: 256,
+          temperature: 0.4,
+          logprobs: true,
+          extra_body: { user: "alice", metadata: null },
+        },
+      });
+
+      expect(result.isError).toBeFalsy();
+      expect(harness.api.createChatCompletion).not.toHaveBeenCalled();
+
+      const resolved = result.structuredContent as {
+        request: Record<string, unknown>;
+        stripped_parameters: string[];
+      };
+      expect(resolved.request).toMatchObject({
+        model: "deepseek-reasoner",
+        max_completion_tokens: 256,
+        stream: false,
+        user: "alice",
+        messages: [
+          { role: "user", content: "first" },
+          { role: "assistant", content: "assistant:first" },
+          { role: "user", content: "second" },
+        ],
+      });
+      expect(resolved.request).not.toHaveProperty("max_tokens");
+      expect(resolved.request).not.toHaveProperty("metadata");
+      expect(resolved.request).not.toHaveProperty("logprobs");
+      expect(resolved.stripped_parameters).toEqual(expect.arrayContaining(["logprobs", "temperature"]));
+    } finally {
+      await harness.serverClose();
+    }
+  });
+
+  it("resolves the weighted model choice the same way chat_completion picks it", async () => {
+    const harness = await createHarness(false, {
+      modelSelector: new WeightedModelSelector({ "deepseek-reasoner": 1 }),
+    });
+
+    try {
+      const result = await harness.client.callTool({ name: "resolve_request", arguments: { message: "hi" } });
+
+      expect(result.structuredContent).toMatchObject({
+        request: { model: "deepseek-reasoner" },
+        weighted_model_selection: "deepseek-reasoner",
+        attachments: [],
+      });
+    } finally {
+      await harness.serverClose();
+    }
+  });
+
+  it("reports every violated constraint from validate_request without calling DeepSeek", async () => {
+    const harness = await createHarness();
+
+    try {
+      const result = await harness.client.callTool({
+        name: "validate_request",
+        arguments: {
+          model: "deepseek-chat",
+          max_tokens: 100000,
+          max_completion_tokens: 2048,
+          tool_choice: "required",
+          messages: [
+            { role: "user", content: "What is the weather?" },
+            { role: "assistant", content: "Checking", prefix: true },
+            { role: "tool", content: "{\"temp\": 20}" },
+          ],
+        },
+      });
+
+      expect(result.isError).toBeFalsy();
+      expect(harness.api.createChatCompletion).not.toHaveBeenCalled();
+
+      const report = result.structuredContent as {
+        valid: boolean;
+        estimated_prompt_tokens: number;
+        fits_context_window: boolean;
+        issues: Array<{ severity: string; field: string; message: string }>;
+      };
+
+      expect(report.valid).toBe(false);
+      expect(report.estimated_prompt_tokens).toBeGreaterThan(0);
+      expect(report.fits_context_window).toBe(true);
+
+      const errorFields = report.issues.filter((issue) => issue.severity === "error").map((issue) => issue.field);
+      expect(errorFields).toEqual(
+        expect.arrayContaining(["messages[1]", "messages[2]", "max_tokens", "tool_choice"]),
+      );
+    } finally {
+      await harness.serverClose();
+    }
+  });
+
+  it("forwards parameters for completion/list_models/get_user_balance", async () => {
+    const harness = await createHarness();
+
+    try {
+      const completion = await harness.client.callTool({
+        name: "completion",
+        arguments: {
+          prompt: "def foo():",
+          max_tokens: 64,
+          top_p: 0.7,
+          stream: false,
+        },
+      });
+
+      expect(completion.isError).toBeFalsy();
+      expect((completion.structuredContent as { choices: unknown[] }).choices).toHaveLength(1);
+      expect(harness.api.createCompletion).toHaveBeenCalledTimes(1);
+      expect(harness.api.createCompletion.mock.calls[0]?.[0]).toMatchObject({
+        prompt: "def foo():",
+        max_tokens: 64,
+        top_p: 0.7,
+      });
+
+      const models = await harness.client.callTool({ name: "list_models", arguments: {} });
+      expect(models.isError).toBeFalsy();
+      expect(harness.api.listModels).toHaveBeenCalledTimes(1);
+
+      const balance = await harness.client.callTool({ name: "get_user_balance", arguments: {} });
+      expect(balance.isError).toBeFalsy();
+      expect(harness.api.getUserBalance).toHaveBeenCalledTimes(1);
+    } finally {
+      await harness.serverClose();
+    }
+  });
+
+  it("refreshes the models cache file after a successful list_models call", async () => {
+    const cacheDir = await mkdtemp(join(tmpdir(), "deepseek-models-cache-"));
+    const cacheFile = join(cacheDir, "models.json");
+    const harness = await createHarness(false, { modelsCache: new ModelsCache(cacheFile) });
+
+    try {
+      const result = await harness.client.callTool({ name: "list_models", arguments: {} });
+      expect(result.isError).toBeFalsy();
+      expect((result.structuredContent as Record<string, unknown>)?.stale).toBe(false);
+
+      const cached = JSON.parse(await readFile(cacheFile, "utf8"));
+      expect(typeof cached.cached_at).toBe("string");
+      expect(cached.response.data.map((model: { id: string }) => model.id)).toEqual([
+        "deepseek-chat",
+        "deepseek-reasoner",
+      ]);
+    } finally {
+      await harness.serverClose();
+      await rm(cacheDir, { recursive: true, force: true });
+    }
+  });
+
+  it("serves stale cached models when the live list_models call fails", async () => {
+    const cacheDir = await mkdtemp(join(tmpdir(), "deepseek-models-cache-"));
+    const cacheFile = join(cacheDir, "models.json");
+    await writeFile(
+      cacheFile,
+      JSON.stringify({
+        cached_at: "2026-01-01T00:00:00.000Z",
+        response: { object: "list", data: [{ id: "deepseek-cached", object: "model" }] },
+      }),
+      "utf8",
+    );
+
+    const harness = await createHarness(false, { modelsCache: new ModelsCache(cacheFile) });
+    harness.api.listModels.mockRejectedValueOnce(new DeepSeekApiError("upstream down", { status: 503 }));
+
+    try {
+      const result = await harness.client.callTool({ name: "list_models", arguments: {} });
+      expect(result.isError).toBeFalsy();
+
+      const structured = result.structuredContent as Record<string, unknown>;
+      expect(structured.stale).toBe(true);
+      expect(structured.cached_at).toBe("2026-01-01T00:00:00.000Z");
+      expect(structured.live_error).toBe("upstream down");
+
+      const textBlock = result.content?.[0];
+      if (!textBlock || textBlock.type !== "text") {
+        throw new Error("expected text tool output");
+      }
+      expect(textBlock.text).toContain("deepseek-cached");
+      expect(textBlock.text).toContain("stale");
+    } finally {
+      await harness.serverClose();
+      await rm(cacheDir, { recursive: true, force: true });
+    }
+  });
+
+  it("renders tool_calls in the summary when the assistant returns no content", async () => {
+    const harness = await createHarness();
+    harness.api.createChatCompletion.mockResolvedValueOnce({
+      response: {
+        id: "chat-tools",
+        object: "chat.completion",
+        created: 1,
+        model: "deepseek-chat",
+        choices: [
+          {
+            index: 0,
+            finish_reason: "tool_calls",
+            message: {
+              role: "assistant",
+              content: null,
+              tool_calls: [
+                { id: "call-1", type: "function", function: { name: "get_weather", arguments: '{"city":"Paris"}' } },
+              ],
+            },
+          },
+        ],
+      },
+    });
+
+    try {
+      const result = await harness.client.callTool({
+        name: "chat_completion",
+        arguments: { message: "weather in Paris?" },
+      });
+
+      const textBlock = result.content?.[0];
+      if (!textBlock || textBlock.type !== "text") {
+        throw new Error("expected text tool output");
+      }
+
+      expect(textBlock.text).not.toContain("(no assistant content returned)");
+      expect(textBlock.text).toContain('Tool calls:\n- get_weather({"city":"Paris"})');
+    } finally {
+      await harness.serverClose();
+    }
+  });
+
+  it("truncates the raw response section of the summary but keeps the full assistant content", async () => {
+    const harness = await createHarness(false, { summaryMaxChars: 40 });
+    const longContent = "x".repeat(200);
+    harness.api.createChatCompletion.mockResolvedValueOnce({
+      response: {
+        id: "chat-long",
+        object: "chat.completion",
+        created: 1,
+        model: "deepseek-chat",
+        choices: [{ index: 0, finish_reason: "stop", message: { role: "assistant", content: longContent } }],
+      },
+    });
+
+    try {
+      const result = await harness.client.callTool({
+        name: "chat_completion",
+        arguments: { message: "long please", include_raw_response: true },
+      });
+
+      const textBlock = result.content?.[0];
+      if (!textBlock || textBlock.type !== "text") {
+        throw new Error("expected text tool output");
+      }
+
+      const [content, raw] = textBlock.text.split("\nRaw response:\n");
+      expect(content).toContain(longContent);
+
+      const rawJson = JSON.stringify((result.structuredContent as Record<string, unknown>).raw_response, null, 2);
+      expect(raw).toBe(`${rawJson.slice(0, 40)}... (truncated, ${rawJson.length - 40} chars omitted)`);
+    } finally {
+      await harness.serverClose();
+    }
+  });
+
+  it("encodes images from the allowed directory and rejects paths outside it", async () => {
+    const imageDir = await mkdtemp(join(tmpdir(), "deepseek-images-"));
+    const outsideDir = await mkdtemp(join(tmpdir(), "deepseek-outside-"));
+    const pngBytes = Buffer.from("89504e470d0a1a0a0000000d49484452", "hex");
+    await writeFile(join(imageDir, "pixel.png"), pngBytes);
+    await writeFile(join(outsideDir, "secret.png"), pngBytes);
+
+    const harness = await createHarness(false, { imageDir });
+
+    try {
+      const encoded = await harness.client.callTool({
+        name: "encode_image",
+        arguments: { path: "pixel.png", detail: "low" },
+      });
+      expect(encoded.isError).toBeFalsy();
+      expect(encoded.structuredContent).toMatchObject({
+        mime_type: "image/png",
+        bytes: pngBytes.length,
+        content_part: {
+          type: "image_url",
+          image_url: { url: `data:image/png;base64,${pngBytes.toString("base64")}`, detail: "low" },
+        },
+      });
+
+      for (const path of [join(outsideDir, "secret.png"), relative(imageDir, join(outsideDir, "secret.png"))]) {
+        const rejected = await harness.client.callTool({ name: "encode_image", arguments: { path } });
+        expect(rejected.isError).toBe(true);
+      }
+    } finally {
+      await harness.serverClose();
+      await rm(imageDir, { recursive: true, force: true });
+      await rm(outsideDir, { recursive: true, force: true });
+    }
+  });
+
+  it("enforces DEEPSEEK_MAX_MESSAGES at the boundary and still rejects empty input", async () => {
+    const harness = await createHarness(false, { maxMessages: 3 });
+    const turns = (count: number) =>
+      Array.from({ length: count }, (_, index) => ({ role: "user", content: `turn ${index}` }));
+
+    try {
+      const atLimit = await harness.client.callTool({ name: "chat_completion", arguments: { messages: turns(3) } });
+      expect(atLimit.isError).toBeFalsy();
+
+      const overLimit = await harness.client.callTool({ name: "chat_completion", arguments: { messages: turns(4) } });
+      expect(overLimit.isError).toBe(true);
+      expect(JSON.stringify(overLimit.content)).toContain(
+        "Request contains 4 messages, above the DEEPSEEK_MAX_MESSAGES limit of 3",
+      );
+      expect(harness.api.createChatCompletion).toHaveBeenCalledTimes(1);
+
+      const empty = await harness.client
+        .callTool({ name: "chat_completion", arguments: {} })
+        .then((result) => result.isError === true, () => true);
+      expect(empty).toBe(true);
+      expect(harness.api.createChatCompletion).toHaveBeenCalledTimes(1);
+    } finally {
+      await harness.serverClose();
+    }
+  });
+
+  it("keeps stored history when a clear_conversation request fails validation", async () => {
+    const harness = await createHarness(false, { maxMessages: 1 });
+
+    try {
+      await harness.client.callTool({
+        name: "chat_completion",
+        arguments: { conversation_id: "kept", message: "first" },
+      });
+      const rejected = await harness.client.callTool({
+        name: "chat_completion",
+        arguments: {
+          conversation_id: "kept",
+          clear_conversation: true,
+          messages: [
+            { role: "user", content: "a" },
+            { role: "user", content: "b" },
+          ],
+        },
+      });
+
+      expect(rejected.isError).toBe(true);
+      const sessions = await harness.client.callTool({ name: "list_sessions", arguments: {} });
+      expect((sessions.structuredContent as { sessions: { message_count: number }[] }).sessions[0]?.message_count).toBe(2);
+    } finally {
+      await harness.serverClose();
+    }
+  });
+
+  it("uses weighted model selection only when the request omits model", async () => {
+    const harness = await createHarness(false, {
+      modelSelector: new WeightedModelSelector({ "deepseek-reasoner": 1 }),
+    });
+
+    try {
+      const selected = await harness.client.callTool({ name: "chat_completion", arguments: { message: "hi" } });
+      expect(harness.api.createChatCompletion.mock.calls[0]?.[0].model).toBe("deepseek-reasoner");
+      expect((selected.structuredContent as Record<string, unknown>).weighted_model_selection).toBe(
+        "deepseek-reasoner",
+      );
+
+      const textBlock = selected.content?.[0];
+      if (!textBlock || textBlock.type !== "text") {
+        throw new Error("expected text tool output");
+      }
+      expect(textBlock.text).toContain("Model selected by weight: deepseek-reasoner");
+
+      const explicit = await harness.client.callTool({
+        name: "chat_completion",
+        arguments: { message: "hi", model: "deepseek-chat" },
+      });
+      expect(harness.api.createChatCompletion.mock.calls[1]?.[0].model).toBe("deepseek-chat");
+      expect((explicit.structuredContent as Record<string, unknown>).weighted_model_selection).toBeNull();
+    } finally {
+      await harness.serverClose();
+    }
+  });
+
+  it("falls back to /completions with a flattened prompt when chat returns empty content", async () => {
+    const harness = await createHarness(false, { chatToCompletionFallback: true });
+    harness.api.createChatCompletion.mockResolvedValueOnce({
+      response: {
+        id: "chat-empty",
+        object: "chat.completion",
+        created: 1,
+        model: "deepseek-chat",
+        choices: [{ index: 0, finish_reason: "stop", message: { role: "assistant", content: "" } }],
+      },
+    });
+
+    try {
+      const result = await harness.client.callTool({
+        name: "chat_completion",
+        arguments: {
+          messages: [
+            { role: "system", content: "Be terse." },
+            { role: "user", content: "Say hi" },
+          ],
+        },
+      });
+
+      expect(harness.api.createCompletion).toHaveBeenCalledTimes(1);
+      expect(harness.api.createCompletion.mock.calls[0]?.[0]).toMatchObject({
+        model: "deepseek-chat",
+        prompt: "system: Be terse.\n\nuser: Say hi\n\nassistant:",
+      });
+
+      const structured = result.structuredContent as Record<string, unknown>;
+      expect(structured.completion_fallback).toBe(true);
+      expect(structured.response_text).toBe("completion-text");
+
+      const textBlock = result.content?.[0];
+      if (!textBlock || textBlock.type !== "text") {
+        throw new Error("expected text tool output");
+      }
+      expect(textBlock.text).toContain("used /completions fallback");
+    } finally {
+      await harness.serverClose();
+    }
+  });
+
+  it("manages server-side conversations with create, append, continue, get and delete", async () => {
+    const harness = await createHarness();
+
+    try {
+      const created = await harness.client.callTool({
+        name: "create_conversation",
+        arguments: { conversation_id: "support", system_prompt: "Be terse." },
+      });
+      expect(created.structuredContent).toMatchObject({ conversation_id: "support", message_count: 1 });
+
+      const duplicate = await harness.client.callTool({
+        name: "create_conversation",
+        arguments: { conversation_id: "support" },
+      });
+      expect(duplicate.isError).toBe(true);
+
+      await harness.client.callTool({
+        name: "append_message",
+        arguments: { conversation_id: "support", content: "My order is late." },
+      });
+      const continued = await harness.client.callTool({
+        name: "continue_conversation",
+        arguments: { conversation_id: "support", message: "Where is it?" },
+      });
+      expect(continued.structuredContent).toMatchObject({
+        conversation_id: "support",
+        response_text: "assistant:Where is it?",
+        session: { message_count: 4, turn_count: 2 },
+      });
+      expect(harness.api.createChatCompletion.mock.calls[0]?.[0].messages).toEqual([
+        { role: "system", content: "Be terse." },
+        { role: "user", content: "My order is late." },
+        { role: "user", content: "Where is it?" },
+      ]);
+
+      const transcript = await harness.client.callTool({
+        name: "get_conversation",
+        arguments: { conversation_id: "support" },
+      });
+      expect((transcript.structuredContent as { messages: unknown[] }).messages).toHaveLength(4);
+      expect(transcript.structuredContent).toMatchObject({
+        messages: [{ role: "system" }, { role: "user" }, { role: "user" }, { role: "assistant" }],
+      });
+
+      const deleted = await harness.client.callTool({
+        name: "delete_conversation",
+        arguments: { conversation_id: "support" },
+      });
+      expect(deleted.structuredContent).toEqual({ conversation_id: "support", deleted: true });
+
+      const missing = await harness.client.callTool({
+        name: "continue_conversation",
+        arguments: { conversation_id: "support", message: "Hello?" },
+      });
+      expect(missing.isError).toBe(true);
+      expect(missing.structuredContent).toMatchObject({ message: "Unknown conversation: support" });
+
+      const generated = await harness.client.callTool({ name: "create_conversation", arguments: {} });
+      expect((generated.structuredContent as { conversation_id: string }).conversation_id).toMatch(/^[0-9a-f-]{36}$/);
+    } finally {
+      await harness.serverClose();
+    }
+  });
+
+  it("does not resurrect a conversation deleted while continue_conversation is in flight", async () => {
+    const conversations = new ConversationStore(200);
+    const harness = await createHarness(false, { conversations });
+
+    try {
+      await harness.client.callTool({ name: "create_conversation", arguments: { conversation_id: "racy" } });
+      const original = harness.api.createChatCompletion.getMockImplementation()!;
+      harness.api.createChatCompletion.mockImplementationOnce(async (request) => {
+        conversations.clear("racy");
+        return original(request);
+      });
+
+      const continued = await harness.client.callTool({
+        name: "continue_conversation",
+        arguments: { conversation_id: "racy", message: "Still there?" },
+      });
+      expect(continued.isError).toBeFalsy();
+      expect(continued.structuredContent).toMatchObject({ response_text: "assistant:Still there?", session: null });
+      const textBlock = continued.content?.[0];
+      if (!textBlock || textBlock.type !== "text") {
+        throw new Error("expected text tool output");
+      }
+      expect(textBlock.text).toContain('conversation "racy" was deleted during the call');
+      expect(conversations.has("racy")).toBe(false);
+    } finally {
+      await harness.serverClose();
+    }
+  });
+
+  it("lists and deletes stored sessions", async () => {
+    const harness = await createHarness();
+
+    try {
+      await harness.client.callTool({
+        name: "chat_completion",
+        arguments: { message: "hello", conversation_id: "session-1" },
+      });
+
+      const listed = await harness.client.callTool({ name: "list_sessions", arguments: {} });
+      const structured = listed.structuredContent as { count: number; sessions: Array<Record<string, unknown>> };
+      expect(structured.count).toBe(1);
+      expect(structured.sessions[0]).toMatchObject({ conversation_id: "session-1", message_count: 2, turn_count: 1 });
+      expect(typeof structured.sessions[0]?.created_at).toBe("string");
+      expect(structured.sessions[0]?.estimated_tokens).toEqual(expect.any(Number));
+
+      const deleted = await harness.client.callTool({
+        name: "delete_session",
+        arguments: { conversation_id: "session-1" },
+      });
+      expect(deleted.structuredContent).toMatchObject({ conversation_id: "session-1", deleted: true });
+
+      const afterDelete = await harness.client.callTool({ name: "list_sessions", arguments: {} });
+      expect((afterDelete.structuredContent as { count: number }).count).toBe(0);
+    } finally {
+      await harness.serverClose();
+    }
+  });
+
+  it("renders the same tool error as plain text by default and as a JSON envelope when configured", async () => {
+    const textHarness = await createHarness();
+    const jsonHarness = await createHarness(false, { errorFormat: "json" });
+    const failure = new DeepSeekApiError("Invalid model", { status: 400, code: "invalid_request_error" });
+    textHarness.api.createChatCompletion.mockRejectedValueOnce(failure);
+    jsonHarness.api.createChatCompletion.mockRejectedValueOnce(failure);
+
+    try {
+      const textResult = await textHarness.client.callTool({ name: "chat_completion", arguments: { message: "hi" } });
+      const jsonResult = await jsonHarness.client.callTool({ name: "chat_completion", arguments: { message: "hi" } });
+
+      expect(textResult.isError).toBe(true);
+      expect(jsonResult.isError).toBe(true);
+      expect(jsonResult.structuredContent).toEqual(textResult.structuredContent);
+
+      const textBlock = textResult.content?.[0];
+      const jsonBlock = jsonResult.content?.[0];
+      if (!textBlock || textBlock.type !== "text" || !jsonBlock || jsonBlock.type !== "text") {
+        throw new Error("expected text tool output");
+      }
+
+      expect(textBlock.text).toContain("DeepSeek API error (400): Invalid model.");
+      expect(JSON.parse(jsonBlock.text)).toEqual({
+        error: true,
+        status: 400,
+        code: "invalid_request_error",
+        category: "invalid_request",
+        message: "Invalid model",
+        upstream: null,
+      });
+    } finally {
+      await textHarness.serverClose();
+      await jsonHarness.serverClose();
+    }
+  });
+
+  it("flags upstream failures with isError and a structured error carrying the upstream payload", async () => {
+    const harness = await createHarness();
+    const payload = { error: { message: "Insufficient Balance", type: "insufficient_balance" } };
+    harness.api.createCompletion.mockRejectedValueOnce(
+      new DeepSeekApiError("Insufficient Balance", { status: 402, payload }),
+    );
+
+    try {
+      const result = await harness.client.callTool({ name: "completion", arguments: { prompt: "hi" } });
+      expect(result.isError).toBe(true);
+      expect(result.structuredContent).toMatchObject({
+        error_type: "deepseek_api_error",
+        status: 402,
+        code: "insufficient_balance",
+        message: "Insufficient Balance",
+        upstream: payload,
+      });
+
+      const block = result.content?.[0];
+      if (!block || block.type !== "text") {
+        throw new Error("expected text tool output");
+      }
+      expect(block.text.startsWith("ERROR:")).toBe(false);
+    } finally {
+      await harness.serverClose();
+    }
+  });
+
+  it("reports the package version, git commit and protocol info via get_version", async () => {
+    const packageJson = JSON.parse(await readFile(new URL("../package.json", import.meta.url), "utf8"));
+    const harness = await createHarness(false, { version: undefined, gitCommit: "abc1234" });
+
+    try {
+      const result = await harness.client.callTool({ name: "get_version", arguments: {} });
+      expect(result.structuredContent).toMatchObject({
+        server_name: "deepseek-mcp-server",
+        server_version: packageJson.version,
+        git_commit: "abc1234",
+        features: { experimental_v4: false, error_format: "text" },
+        client: { name: "test-client", version: "1.0.0", protocol_version: null, structured_content: true },
+      });
+      expect(typeof (result.structuredContent as Record<string, unknown>).protocol_version).toBe("string");
+    } finally {
+      await harness.serverClose();
+    }
+  });
+
+  it("sends instructions describing this instance's configuration on initialize", async () => {
+    const configured = { defaultModel: "deepseek-reasoner", disabledTools: ["completion"], enableBatch: true };
+    const harness = await createHarness(false, {
+      ...configured,
+      instructions: describeConfiguration({
+        ...configured,
+        client: new DeepSeekApiClient({ apiKey: "" }),
+        conversations: new ConversationStore(200),
+      }),
+    });
+
+    try {
+      const instructions = harness.client.getInstructions();
+      expect(instructions).toContain("Default model: deepseek-reasoner.");
+      expect(instructions).toContain("Fallback policy: retryable deepseek-reasoner failures fall back to deepseek-chat.");
+      expect(instructions).toContain("Base URL: https://api.deepseek.com.");
+      expect(instructions).toContain("Enabled tools: all except completion.");
+      expect(instructions).toContain("Enabled features: batch.");
+
+      const version = await harness.client.callTool({ name: "get_version", arguments: {} });
+      expect((version.structuredContent as Record<string, unknown>).instructions).toBe(instructions);
+      expect(version.structuredContent).toMatchObject({ features: { batch: true, raw_requests: false } });
+    } finally {
+      await harness.serverClose();
+    }
+  });
+
+  it("compares two models side by side and reports a failing model without hiding the other", async () => {
+    const harness = await createHarness();
+    harness.api.createChatCompletion.mockImplementation(async (request: { model: string }) => {
+      if (request.model === "deepseek-reasoner") {
+        throw new DeepSeekApiError("reasoner overloaded", { status: 503 });
+      }
+
+      return {
+        response: {
+          id: "chat-compare",
+          object: "chat.completion",
+          created: 1,
+          model: request.model,
+          choices: [{ index: 0, finish_reason: "stop", message: { role: "assistant", content: `from ${request.model}` } }],
+          usage: { prompt_tokens: 5, completion_tokens: 3, total_tokens: 8 },
+        },
+      };
+    });
+
+    try {
+      const result = await harness.client.callTool({
+        name: "compare_models",
+        arguments: {
+          messages: [{ role: "user", content: "Which is better?" }],
+          model_a: "deepseek-chat",
+          model_b: "deepseek-reasoner",
+        },
+      });
+
+      expect(result.isError).toBeFalsy();
+      const structured = result.structuredContent as Record<string, Record<string, unknown>>;
+      expect(structured.model_a).toMatchObject({
+        model: "deepseek-chat",
+        ok: true,
+        content: "from deepseek-chat",
+        usage: { total_tokens: 8 },
+      });
+      expect(structured.model_a?.latency_ms).toEqual(expect.any(Number));
+      expect(structured.model_b).toMatchObject({
+        model: "deepseek-reasoner",
+        ok: false,
+        error: { status: 503, message: "reasoner overloaded" },
+      });
+      expect(harness.api.createChatCompletion).toHaveBeenCalledTimes(2);
+      expect(structured.diff).toBeNull();
+    } finally {
+      await harness.serverClose();
+    }
+  });
+
+  it("adds a line diff of both answers to compare_models when asked", async () => {
+    const harness = await createHarness();
+    harness.api.createChatCompletion.mockImplementation(async (request: { model: string }) => ({
+      response: {
+        id: "chat-compare",
+        object: "chat.completion",
+        created: 1,
+        model: request.model,
+        choices: [
+          {
+            index: 0,
+            finish_reason: "stop",
+            message: { role: "assistant", content: `Answer:\nfrom ${request.model}\nDone.` },
+          },
+        ],
+      },
+    }));
+
+    try {
+      const result = await harness.client.callTool({
+        name: "compare_models",
+        arguments: {
+          messages: [{ role: "user", content: "Which is better?" }],
+          model_a: "deepseek-chat",
+          model_b: "deepseek-reasoner",
+          include_diff: true,
+        },
+      });
+
+      expect(result.structuredContent).toMatchObject({
+        diff: {
+          text: "  Answer:\n- from deepseek-chat\n+ from deepseek-reasoner\n  Done.",
+          added: 1,
+          removed: 1,
+          unchanged: 2,
+        },
+      });
+      expect((result.content as { text: string }[])[0]?.text).toContain("=== diff (1 removed, 1 added) ===");
+    } finally {
+      await harness.serverClose();
+    }
+  });
+
+  it("accumulates per-session token usage across turns and tolerates missing usage", async () => {
+    const harness = await createHarness();
+    const reply = (usage?: Record<string, number>) => ({
+      response: {
+        id: "chat-usage",
+        object: "chat.completion",
+        created: 1,
+        model: "deepseek-chat",
+        choices: [{ index: 0, finish_reason: "stop", message: { role: "assistant", content: "ok" } }],
+        ...(usage ? { usage } : {}),
+      },
+    });
+    harness.api.createChatCompletion
+      .mockResolvedValueOnce(reply({ prompt_tokens: 10, completion_tokens: 4, total_tokens: 14 }))
+      .mockResolvedValueOnce(reply({ prompt_tokens: 20, completion_tokens: 6, total_tokens: 26 }))
+      .mockResolvedValueOnce(reply());
+
+    try {
+      for (const message of ["one", "two", "three"]) {
+        await harness.client.callTool({
+          name: "chat_completion",
+          arguments: { message, conversation_id: "metered" },
+        });
+      }
+
+      const listed = await harness.client.callTool({ name: "list_sessions", arguments: {} });
+      const [session] = (listed.structuredContent as { sessions: Array<Record<string, unknown>> }).sessions;
+      expect(session).toMatchObject({
+        conversation_id: "metered",
+        turn_count: 3,
+        prompt_tokens_total: 30,
+        completion_tokens_total: 10,
+      });
+    } finally {
+      await harness.serverClose();
+    }
+  });
+
+  it("starts in offline mode where network tools fail cleanly and get_version still works", async () => {
+    const fetchFn = vi.fn<typeof fetch>();
+    const mcpServer = createDeepSeekMcpServer({
+      client: new DeepSeekApiClient({ apiKey: "", fetchFn }),
+      conversations: new ConversationStore(200),
+      defaultModel: "deepseek-chat",
+      offline: true,
+      version: "test",
+    });
+    const [clientTransport, serverTransport] = InMemoryTransport.createLinkedPair();
+    const client = new Client({ name: "offline-client", version: "1.0.0" });
+    await Promise.all([mcpServer.connect(serverTransport), client.connect(clientTransport)]);
+
+    try {
+      const balance = await client.callTool({ name: "get_user_balance", arguments: {} });
+      expect(balance.isError).toBe(true);
+      expect(JSON.stringify(balance.content)).toContain("No DeepSeek API key configured");
+
+      const chat = await client.callTool({ name: "chat_completion", arguments: { message: "hi" } });
+      expect(chat.isError).toBe(true);
+      expect(fetchFn).not.toHaveBeenCalled();
+
+      const version = await client.callTool({ name: "get_version", arguments: {} });
+      expect(version.isError).toBeFalsy();
+      expect(version.structuredContent).toMatchObject({ features: { offline: true } });
+    } finally {
+      await client.close();
+      await mcpServer.close();
+    }
+  });
+
+  it("reports key validity, latency and beta reachability via health_check", async () => {
+    const fetchFn = vi.fn<typeof fetch>(async (url) =>
+      String(url).includes("/beta/")
+        ? new Response("not found", { status: 404 })
+        : new Response(JSON.stringify({ object: "list", data: [] }), { status: 200 }),
+    );
+    const harness = await createHarness(false, { client: new DeepSeekApiClient({ apiKey: "sk-test", fetchFn }) });
+    const rejecting = await createHarness(false, {
+      client: new DeepSeekApiClient({
+        apiKey: "sk-bad",
+        fetchFn: vi.fn<typeof fetch>(async () => new Response(JSON.stringify({ error: { message: "bad key" } }), { status: 401 })),
+      }),
+    });
+
+    try {
+      const healthy = await harness.client.callTool({ name: "health_check", arguments: {} });
+      expect(healthy.structuredContent).toMatchObject({
+        healthy: true,
+        api_key: "valid",
+        base_url: "https://api.deepseek.com",
+        beta: { base_url: "https://api.deepseek.com/beta", reachable: true, status: 404 },
+      });
+      expect(typeof (healthy.structuredContent as Record<string, unknown>).latency_ms).toBe("number");
+      expect(fetchFn.mock.calls.map(([url]) => String(url))).toEqual([
+        "https://api.deepseek.com/models",
+        "https://api.deepseek.com/beta/models",
+      ]);
+
+      const unhealthy = await rejecting.client.callTool({ name: "health_check", arguments: {} });
+      expect(unhealthy.isError).toBeFalsy();
+      expect(unhealthy.structuredContent).toMatchObject({ healthy: false, api_key: "invalid", status: 401 });
+    } finally {
+      await harness.serverClose();
+      await rejecting.serverClose();
+    }
+  });
+
+  it("returns a structured verdict with remediation from validate_api_key", async () => {
+    const harness = await createHarness();
+    harness.api.getUserBalance.mockRejectedValueOnce(new DeepSeekApiError("Insufficient Balance", { status: 402 }));
+
+    try {
+      const result = await harness.client.callTool({ name: "validate_api_key", arguments: {} });
+      expect(result.isError).toBeFalsy();
+      expect(result.structuredContent).toMatchObject({
+        verdict: "insufficient_balance",
+        key_valid: true,
+        status: 402,
+        balance_infos: null,
+      });
+
+      const valid = await harness.client.callTool({ name: "validate_api_key", arguments: {} });
+      expect(valid.structuredContent).toMatchObject({ verdict: "valid", remediation: "No action needed." });
+    } finally {
+      await harness.serverClose();
+    }
+  });
+
+  it("maps max_tokens and max_completion_tokens onto the configured token parameter", async () => {
+    const defaultHarness = await createHarness();
+    const completionTokensHarness = await createHarness(false, { tokenParam: "max_completion_tokens" });
+
+    try {
+      await defaultHarness.client.callTool({
+        name: "chat_completion",
+        arguments: { message: "hi", max_completion_tokens: 256 },
+      });
+      const defaultRequest = defaultHarness.api.createChatCompletion.mock.calls[0]?.[0];
+      expect(defaultRequest?.max_tokens).toBe(256);
+      expect(defaultRequest).not.toHaveProperty("max_completion_tokens");
+
+      await completionTokensHarness.client.callTool({
+        name: "chat_completion",
+        arguments: { message: "hi", max_tokens: 128 },
+      });
+      const aliasedRequest = completionTokensHarness.api.createChatCompletion.mock.calls[0]?.[0];
+      expect(aliasedRequest?.max_completion_tokens).toBe(128);
+      expect(aliasedRequest).not.toHaveProperty("max_tokens");
+
+      const conflicting = await completionTokensHarness.client.callTool({
+        name: "chat_completion",
+        arguments: { message: "hi", max_tokens: 128, max_completion_tokens: 512 },
+      });
+      const conflictingRequest = completionTokensHarness.api.createChatCompletion.mock.calls[1]?.[0];
+      expect(conflictingRequest?.max_completion_tokens).toBe(512);
+      expect(conflictingRequest).not.toHaveProperty("max_tokens");
+      expect(conflicting.structuredContent).toMatchObject({
+        token_limit_warning: expect.stringContaining("conflicts with max_completion_tokens"),
+      });
+      const text = (conflicting.content as Array<{ type: string; text: string }>)[0].text;
+      expect(text).toContain("Warning: max_tokens (128) conflicts with max_completion_tokens (512)");
+    } finally {
+      await defaultHarness.serverClose();
+      await completionTokensHarness.serverClose();
+    }
+  });
+
+  it("appends a timing breakdown to chat_completion when DEEPSEEK_TIMING is enabled", async () => {
+    const harness = await createHarness(false, { timing: true });
+    harness.api.createChatCompletion.mockResolvedValueOnce({
+      response: {
+        id: "chat-timed",
+        object: "chat.completion",
+        created: 1,
+        model: "deepseek-chat",
+        choices: [{ index: 0, finish_reason: "stop", message: { role: "assistant", content: "done" } }],
+      },
+      timing: { timeToFirstByteMs: 120.5, totalMs: 480, firstToLastChunkMs: 350 },
+    });
+
+    try {
+      const result = await harness.client.callTool({
+        name: "chat_completion",
+        arguments: { message: "hi", stream: true },
+      });
+
+      expect(result.structuredContent).toMatchObject({
+        timing: { time_to_first_byte_ms: 120.5, total_ms: 480, first_to_last_chunk_ms: 350 },
+      });
+      const text = (result.content as Array<{ type: string; text: string }>)[0].text;
+      expect(text).toContain('Timing:\n{"time_to_first_byte_ms":120.5,"total_ms":480,"first_to_last_chunk_ms":350}');
+
+      const untimed = await harness.client.callTool({ name: "chat_completion", arguments: { message: "again" } });
+      expect(untimed.structuredContent).not.toHaveProperty("timing");
+    } finally {
+      await harness.serverClose();
+    }
+  });
+
+  it("clears the in-memory model caches via clear_caches and removes the models file only when named", async () => {
+    const cacheDir = await mkdtemp(join(tmpdir(), "deepseek-models-cache-"));
+    const cacheFile = join(cacheDir, "models.json");
+    const harness = await createHarness(false, { modelsCache: new ModelsCache(cacheFile) });
+    const modelList = (extra: string) => ({
+      object: "list",
+      data: ["deepseek-chat", "deepseek-reasoner", extra].map((id) => ({ id, object: "model" })),
+    });
+    const completeModel = async () =>
+      (
+        await harness.client.complete({
+          ref: { type: "ref/prompt", name: "deepseek_chat_starter" },
+          argument: { name: "model", value: "deepseek-v" },
+        })
+      ).completion.values;
+
+    try {
+      harness.api.listModels.mockResolvedValue(modelList("deepseek-v4"));
+      await harness.client.callTool({ name: "list_models", arguments: {} });
+      expect(await completeModel()).toEqual(["deepseek-v4"]);
+
+      harness.api.listModels.mockResolvedValue(modelList("deepseek-v5"));
+      await harness.client.callTool({ name: "list_models", arguments: {} });
+      expect(await completeModel()).toEqual(["deepseek-v4"]);
+
+      const cleared = await harness.client.callTool({ name: "clear_caches", arguments: {} });
+      expect(cleared.isError).toBeFalsy();
+      // Three completion IDs plus the two learned capability entries; the file list_models falls back to stays.
+      expect(cleared.structuredContent).toEqual({ removed: { models: 5 } });
+      expect(JSON.parse(await readFile(cacheFile, "utf8")).response.data).toHaveLength(3);
+      expect(await completeModel()).toEqual(["deepseek-v5"]);
+
+      const clearedFile = await harness.client.callTool({ name: "clear_caches", arguments: { name: "models_file" } });
+      expect(clearedFile.structuredContent).toEqual({ removed: { models_file: 3 } });
+      await expect(readFile(cacheFile, "utf8")).rejects.toThrow();
+
+      const unknown = await harness.client
+        .callTool({ name: "clear_caches", arguments: { name: "responses" } })
+        .then(
+          (result) => result.isError === true,
+          () => true,
+        );
+      expect(unknown).toBe(true);
+    } finally {
+      await harness.serverClose();
+      await rm(cacheDir, { recursive: true, force: true });
+    }
+  });
+
+  it("returns input JSON schemas keyed by tool name via get_tool_schemas", async () => {
+    const harness = await createHarness(false, { disabledTools: ["completion"] });
+
+    try {
+      const result = await harness.client.callTool({ name: "get_tool_schemas", arguments: {} });
+      expect(result.isError).toBeFalsy();
+
+      const { schemas } = result.structuredContent as {
+        schemas: Record<string, { type?: string; properties?: Record<string, unknown> }>;
+      };
+      expect(schemas.chat_completion?.type).toBe("object");
+      expect(schemas.chat_completion?.properties).toHaveProperty("messages");
+      expect(schemas.chat_completion?.properties).toHaveProperty("temperature");
+      expect(schemas.list_models?.type).toBe("object");
+      expect(schemas).not.toHaveProperty("completion");
+    } finally {
+      await harness.serverClose();
+    }
+  });
+
+  it("merges consecutive same-role messages when mergeConsecutiveMessages is enabled", async () => {
+    const harness = await createHarness(false, { mergeConsecutiveMessages: true });
+
+    try {
+      await harness.client.callTool({
+        name: "chat_completion",
+        arguments: {
+          messages: [
+            { role: "user", content: "first" },
+            { role: "user", content: "second" },
+            { role: "assistant", content: "reply" },
+            { role: "user", content: [{ type: "text", text: "look" }] },
+            { role: "user", content: "at this" },
+          ],
+        },
+      });
+
+      const mergedRequest = harness.api.createChatCompletion.mock.calls[0]?.[0];
+      expect(mergedRequest?.messages).toEqual([
+        { role: "user", content: "first\n\nsecond" },
+        { role: "assistant", content: "reply" },
+        {
+          role: "user",
+          content: [
+            { type: "text", text: "look" },
+            { type: "text", text: "at this" },
+          ],
+        },
+      ]);
+
+      const alternating = [
+        { role: "system", content: "be brief" },
+        { role: "user", content: "hi" },
+        { role: "assistant", content: "hello" },
+        { role: "user", content: "bye" },
+      ];
+      await harness.client.callTool({ name: "chat_completion", arguments: { messages: alternating } });
+      expect(harness.api.createChatCompletion.mock.calls[1]?.[0]?.messages).toEqual(alternating);
+    } finally {
+      await harness.serverClose();
+    }
+  });
+
+  it("registers raw_request only when enableRawRequests is set and rejects absolute URLs", async () => {
+    const disabledHarness = await createHarness();
+    const harness = await createHarness(false, { enableRawRequests: true });
+
+    try {
+      const disabledTools = await disabledHarness.client.listTools();
+      expect(disabledTools.tools.map((tool) => tool.name)).not.toContain("raw_request");
+      const disabled = await disabledHarness.client
+        .callTool({ name: "raw_request", arguments: { path: "/user/balance" } })
+        .then(
+          (result) => result.isError === true,
+          () => true,
+        );
+      expect(disabled).toBe(true);
+      expect(disabledHarness.api.rawRequest).not.toHaveBeenCalled();
+
+      const passthrough = await harness.client.callTool({
+        name: "raw_request",
+        arguments: { method: "GET", path: "/user/balance" },
+      });
+      expect(passthrough.isError).toBeFalsy();
+      expect(harness.api.rawRequest).toHaveBeenCalledWith("GET", "/user/balance", undefined, {
+        signal: expect.any(AbortSignal),
+      });
+      expect(passthrough.structuredContent).toEqual({
+        method: "GET",
+        path: "/user/balance",
+        response: { is_available: true },
+      });
+
+      const absolute = await harness.client
+        .callTool({ name: "raw_request", arguments: { path: "https://evil.example.com/steal" } })
+        .then(
+          (result) => result.isError === true,
+          () => true,
+        );
+      expect(absolute).toBe(true);
+      expect(harness.api.rawRequest).toHaveBeenCalledTimes(1);
+    } finally {
+      await disabledHarness.serverClose();
+      await harness.serverClose();
+    }
+  });
+
+  it("charges raw_request replies that carry usage against the monthly budget", async () => {
+    const budget = new BudgetTracker({ monthlyBudgetUsd: 0.001, now: () => new Date("2026-05-10T00:00:00.000Z") });
+    const harness = await createHarness(false, { enableRawRequests: true, budget });
+    harness.api.rawRequest.mockResolvedValue({
+      model: "deepseek-chat",
+      usage: { prompt_tokens: 1000, completion_tokens: 1000, total_tokens: 2000, prompt_cache_miss_tokens: 1000 },
+    });
+    const args = { method: "POST", path: "/chat/completions", body: { model: "deepseek-chat", messages: [] } };
+
+    try {
+      const first = await harness.client.callTool({ name: "raw_request", arguments: args });
+      expect(first.isError).toBeFalsy();
+
+      const second = await harness.client.callTool({ name: "raw_request", arguments: args });
+      expect(second.isError).toBe(true);
+      expect(JSON.stringify(second.content)).toContain("Budget exceeded");
+      expect(harness.api.rawRequest).toHaveBeenCalledTimes(1);
+    } finally {
+      await harness.serverClose();
+    }
+  });
+
+  it("truncates raw_request text output but returns the full response as structured content", async () => {
+    const harness = await createHarness(false, { enableRawRequests: true, summaryMaxChars: 20 });
+    const payload = { data: "y".repeat(100) };
+    harness.api.rawRequest.mockResolvedValueOnce(payload);
+
+    try {
+      const result = await harness.client.callTool({ name: "raw_request", arguments: { path: "/models" } });
+      const rawJson = JSON.stringify(payload, null, 2);
+
+      expect(result.content).toEqual([
+        { type: "text", text: `${rawJson.slice(0, 20)}... (truncated, ${rawJson.length - 20} chars omitted)` },
+      ]);
+      expect((result.structuredContent as Record<string, unknown>).response).toEqual(payload);
+    } finally {
+      await harness.serverClose();
+    }
+  });
+
+  it("blocks flagged prompts before calling DeepSeek when a moderator is configured", async () => {
+    const moderationFetch = vi
+      .fn<typeof fetch>()
+      .mockResolvedValueOnce(new Response(JSON.stringify({ flagged: false }), { status: 200 }))
+      .mockResolvedValueOnce(new Response(JSON.stringify({ flagged: true, categories: ["harassment"] }), { status: 200 }));
+    const harness = await createHarness(false, {
+      moderator: new PromptModerator({ url: "https://moderation.example.com/check", fetchFn: moderationFetch }),
+    });
+
+    try {
+      const allowed = await harness.client.callTool({ name: "chat_completion", arguments: { message: "hello" } });
+      expect(allowed.isError).toBeFalsy();
+      expect(harness.api.createChatCompletion).toHaveBeenCalledTimes(1);
+
+      const blocked = await harness.client.callTool({ name: "chat_completion", arguments: { message: "insult" } });
+      expect(blocked.isError).toBe(true);
+      expect(JSON.stringify(blocked.content)).toContain("Blocked by moderation");
+      expect(harness.api.createChatCompletion).toHaveBeenCalledTimes(1);
+    } finally {
+      await harness.serverClose();
+    }
+  });
+
+  it("screens prompts of every upstream tool, not just chat_completion", async () => {
+    const moderationFetch = vi
+      .fn<typeof fetch>()
+      .mockImplementation(async () => new Response(JSON.stringify({ flagged: true }), { status: 200 }));
+    const harness = await createHarness(false, {
+      moderator: new PromptModerator({ url: "https://moderation.example.com/check", fetchFn: moderationFetch }),
+    });
+    const messages = [{ role: "user", content: "insult" }];
+    const calls = [
+      { name: "completion", arguments: { prompt: "insult" } },
+      { name: "fim_completion", arguments: { prefix: "insult", suffix: "" } },
+      { name: "chat_prefix_completion", arguments: { message: "insult", prefix: "Sure" } },
+      { name: "json_chat", arguments: { messages, schema: { type: "object" } } },
+      { name: "compare_models", arguments: { messages, model_a: "a", model_b: "b" } },
+    ];
+
+    try {
+      for (const call of calls) {
+        const result = await harness.client.callTool(call);
+        expect(result.structuredContent).toMatchObject({ error_type: "moderation_blocked" });
+      }
+      expect(moderationFetch).toHaveBeenCalledTimes(calls.length);
+      expect(harness.api.createCompletion).not.toHaveBeenCalled();
+      expect(harness.api.createFimCompletion).not.toHaveBeenCalled();
+      expect(harness.api.createChatPrefixCompletion).not.toHaveBeenCalled();
+      expect(harness.api.createChatCompletion).not.toHaveBeenCalled();
+    } finally {
+      await harness.serverClose();
+    }
+  });
+
+  it("drops null and empty-string extra_body keys before sending", async () => {
+    const harness = await createHarness();
+    const extraBody = { user: "alice", seed: 0, metadata: null, tag: "", flag: false };
+
+    try {
+      await harness.client.callTool({ name: "chat_completion", arguments: { message: "hi", extra_body: extraBody } });
+      await harness.client.callTool({ name: "completion", arguments: { prompt: "def foo():", extra_body: extraBody } });
+
+      for (const request of [
+        harness.api.createChatCompletion.mock.calls[0]?.[0],
+        harness.api.createCompletion.mock.calls[0]?.[0],
+      ]) {
+        expect(request).toMatchObject({ user: "alice", seed: 0, flag: false });
+        expect(request).not.toHaveProperty("metadata");
+        expect(request).not.toHaveProperty("tag");
+      }
+    } finally {
+      await harness.serverClose();
+    }
+
+    const unsanitized = await createHarness(false, { sanitizeExtraBody: false });
+    try {
+      await unsanitized.client.callTool({ name: "chat_completion", arguments: { message: "hi", extra_body: extraBody } });
+      expect(unsanitized.api.createChatCompletion.mock.calls[0]?.[0]).toMatchObject({ metadata: null, tag: "" });
+    } finally {
+      await unsanitized.serverClose();
+    }
+  });
+
+  it("switches a session to a client-supplied API key via authenticate when allowed", async () => {
+    const chatResponse = (content: string) => ({
+      response: {
+        id: "chat-key",
+        object: "chat.completion",
+        created: 1,
+        model: "deepseek-chat",
+        choices: [{ index: 0, finish_reason: "stop", message: { role: "assistant", content } }],
+      },
+    });
+    const sessionApi = { createChatCompletion: vi.fn(async () => chatResponse("session key")) };
+    const serverApi = {
+      createChatCompletion: vi.fn(async () => chatResponse("server key")),
+      withApiKey: vi.fn(() => sessionApi),
+      getBaseUrlHealth: vi.fn(() => []),
+      reasonerFallback: { enabled: true, model: "deepseek-chat" },
+    };
+
+    const disabled = await createHarness(false, { client: serverApi as unknown as DeepSeekApiClient });
+    try {
+      const rejected = await disabled.client.callTool({ name: "authenticate", arguments: { api_key: "sk-user" } });
+      expect(rejected.isError).toBe(true);
+    } finally {
+      await disabled.serverClose();
+    }
+
+    const harness = await createHarness(false, {
+      client: serverApi as unknown as DeepSeekApiClient,
+      allowClientApiKeys: true,
+    });
+
+    try {
+      const authenticated = await harness.client.callTool({
+        name: "authenticate",
+        arguments: { api_key: "sk-user-1234567890" },
+      });
+      expect(authenticated.structuredContent).toEqual({ key_source: "session", key_hint: "sk-...7890" });
+      expect(serverApi.withApiKey).toHaveBeenCalledWith("sk-user-1234567890");
+
+      await harness.client.callTool({ name: "chat_completion", arguments: { message: "hi" } });
+      expect(sessionApi.createChatCompletion).toHaveBeenCalledTimes(1);
+      expect(serverApi.createChatCompletion).not.toHaveBeenCalled();
+
+      const reset = await harness.client.callTool({ name: "authenticate", arguments: {} });
+      expect(reset.structuredContent).toMatchObject({ key_source: "server" });
+      await harness.client.callTool({ name: "chat_completion", arguments: { message: "hi" } });
+      expect(serverApi.createChatCompletion).toHaveBeenCalledTimes(1);
+    } finally {
+      await harness.serverClose();
+    }
+  });
+
+  it("rejects new tool calls once shutdown draining has started", async () => {
+    const shutdown = new ShutdownController();
+    const harness = await createHarness(false, { shutdown });
+
+    try {
+      const before = await harness.client.callTool({ name: "chat_completion", arguments: { message: "hi" } });
+      expect(before.isError).toBeFalsy();
+
+      await expect(shutdown.drain(0)).resolves.toBe(true);
+      const after = await harness.client.callTool({ name: "chat_completion", arguments: { message: "late" } });
+      expect(after.isError).toBe(true);
+      expect(JSON.stringify(after.content)).toContain("Server is shutting down");
+      expect(harness.api.createChatCompletion).toHaveBeenCalledTimes(1);
+    } finally {
+      await harness.serverClose();
+    }
+  });
+
+  it("returns a structured rate_limited error once a client exceeds its per-client limit", async () => {
+    const harness = await createHarness(false, {
+      clientRateLimiter: new ClientRateLimiter({ requestsPerMinute: 1, now: () => 0 }),
+    });
+
+    try {
+      const first = await harness.client.callTool({ name: "completion", arguments: { prompt: "one" } });
+      expect(first.isError).toBeFalsy();
+
+      const second = await harness.client.callTool({ name: "completion", arguments: { prompt: "two" } });
+      expect(second.isError).toBe(true);
+      expect(second.structuredContent).toMatchObject({
+        error_type: "rate_limited",
+        retryable: true,
+        retry_after_ms: 60000,
+      });
+      expect(harness.api.createCompletion).toHaveBeenCalledTimes(1);
+    } finally {
+      await harness.serverClose();
+    }
+  });
+
+  it("sends periodic progress notifications while a chat call is in flight", async () => {
+    const harness = await createHarness(false, { progressIntervalMs: 10 });
+    harness.api.createChatCompletion.mockImplementationOnce(async (request, callOptions) => {
+      callOptions?.onStreamProgress?.(42);
+      await new Promise((resolve) => setTimeout(resolve, 60));
+      return {
+        response: {
+          id: "chat-slow",
+          object: "chat.completion",
+          created: 1,
+          model: String(request.model),
+          choices: [{ index: 0, finish_reason: "stop", message: { role: "assistant", content: "done" } }],
+        },
+      };
+    });
+    const onprogress = vi.fn();
+
+    try {
+      const result = await harness.client.callTool(
+        { name: "chat_completion", arguments: { message: "think hard", model: "deepseek-reasoner" } },
+        undefined,
+        { onprogress },
+      );
+      expect(result.isError).toBeFalsy();
+      expect(onprogress).toHaveBeenCalledWith(
+        expect.objectContaining({
+          message: expect.stringMatching(/^waiting on deepseek-reasoner: \d+ s elapsed, ~42 tokens received$/),
+        }),
+      );
+
+      const progressValues = onprogress.mock.calls.map(([notification]) => notification.progress as number);
+      expect(progressValues).toEqual(progressValues.map((_, index) => index + 1));
+
+      const calls = onprogress.mock.calls.length;
+      await new Promise((resolve) => setTimeout(resolve, 30));
+      expect(onprogress).toHaveBeenCalledTimes(calls);
+    } finally {
+      await harness.serverClose();
+    }
+  });
+
+  it("aborts the upstream call when the client cancels a tool call", async () => {
+    const harness = await createHarness();
+    let upstreamSignal: AbortSignal | undefined;
+    harness.api.createChatCompletion.mockImplementationOnce(async (_request, callOptions) => {
+      upstreamSignal = callOptions?.signal;
+      return new Promise((_resolve, reject) => {
+        upstreamSignal?.addEventListener("abort", () => reject(new Error("aborted")));
+      });
+    });
+    const controller = new AbortController();
+
+    try {
+      const pending = harness.client.callTool(
+        { name: "chat_completion", arguments: { message: "never mind" } },
+        undefined,
+        { signal: controller.signal },
+      );
+      await vi.waitFor(() => expect(upstreamSignal).toBeDefined());
+      controller.abort("user cancelled");
+
+      await expect(pending).rejects.toThrow();
+      await vi.waitFor(() => expect(upstreamSignal?.aborted).toBe(true));
+    } finally {
+      await harness.serverClose();
+    }
+  });
+
+  it("asks for confirmation via elicitation before sending a chat request above the token threshold", async () => {
+    const harness = await createHarness(false, { confirmAboveTokens: 100 }, { capabilities: { elicitation: {} } });
+    const elicit = vi
+      .fn()
+      .mockResolvedValueOnce({ action: "decline" })
+      .mockResolvedValueOnce({ action: "accept", content: { confirm: true } })
+      .mockResolvedValueOnce({ action: "decline" });
+    harness.client.setRequestHandler(ElicitRequestSchema, elicit);
+
+    try {
+      const small = await harness.client.callTool({
+        name: "chat_completion",
+        arguments: { message: "hi", max_tokens: 50 },
+      });
+      expect(small.isError).toBeFalsy();
+      expect(elicit).not.toHaveBeenCalled();
+
+      const args = { message: "hi", max_tokens: 4096 };
+      const declined = await harness.client.callTool({ name: "chat_completion", arguments: args });
+      expect(declined.isError).toBe(true);
+      expect(JSON.stringify(declined.content)).toContain("Cancelled by user");
+      expect(elicit.mock.calls[0]?.[0].params.message).toContain("max output");
+      expect(harness.api.createChatCompletion).toHaveBeenCalledTimes(1);
+
+      const accepted = await harness.client.callTool({ name: "chat_completion", arguments: args });
+      expect(accepted.isError).toBeFalsy();
+      expect(harness.api.createChatCompletion).toHaveBeenCalledTimes(2);
+
+      // No max_tokens: both models are estimated at their full output length and confirmed as one total.
+      const compared = await harness.client.callTool({
+        name: "compare_models",
+        arguments: { messages: [{ role: "user", content: "hi" }], model_a: "deepseek-chat", model_b: "deepseek-reasoner" },
+      });
+      expect(compared.isError).toBe(true);
+      expect(elicit.mock.calls[2]?.[0].params.message).toContain("These 2 requests");
+      expect(harness.api.createChatCompletion).toHaveBeenCalledTimes(2);
+    } finally {
+      await harness.serverClose();
+    }
+  });
+
+  it("confirms the worst case of retries, continuations and multi-request tools", async () => {
+    const harness = await createHarness(
+      false,
+      { confirmAboveTokens: 100, autoContinueLimit: 2 },
+      { capabilities: { elicitation: {} } },
+    );
+    const elicit = vi.fn().mockResolvedValue({ action: "decline" });
+    harness.client.setRequestHandler(ElicitRequestSchema, elicit);
+
+    try {
+      // One ~40-token request is under the threshold, but it may be continued twice.
+      const continued = await harness.client.callTool({
+        name: "chat_completion",
+        arguments: { message: "hi", max_tokens: 40 },
+      });
+      expect(continued.isError).toBe(true);
+      expect(elicit.mock.calls[0]?.[0].params.message).toContain("These 3 requests");
+
+      const summarized = await harness.client.callTool({
+        name: "summarize_and_continue",
+        arguments: {
+          keep_last: 1,
+          max_tokens: 40,
+          messages: [
+            { role: "user", content: "I like tea." },
+            { role: "assistant", content: "Noted." },
+            { role: "user", content: "What next?" },
+          ],
+        },
+      });
+      expect(summarized.isError).toBe(true);
+      expect(elicit.mock.calls[1]?.[0].params.message).toContain("These 2 requests");
+
+      const prefixed = await harness.client.callTool({
+        name: "chat_prefix_completion",
+        arguments: { message: "Write code", prefix: "```python\n" },
+      });
+      expect(prefixed.isError).toBe(true);
+      expect(elicit).toHaveBeenCalledTimes(3);
+
+      const json = await harness.client.callTool({
+        name: "json_chat",
+        arguments: {
+          messages: [{ role: "user", content: "hi" }],
+          schema: { type: "object" },
+          max_tokens: 40,
+          max_retries: 2,
+        },
+      });
+      expect(json.isError).toBe(true);
+      expect(elicit.mock.calls[3]?.[0].params.message).toContain("These 3 requests");
+
+      expect(harness.api.createChatCompletion).not.toHaveBeenCalled();
+      expect(harness.api.createChatPrefixCompletion).not.toHaveBeenCalled();
+    } finally {
+      await harness.serverClose();
+    }
+  });
+
+  it("sends expensive chat requests without confirmation when the client lacks elicitation", async () => {
+    const harness = await createHarness(false, { confirmAboveTokens: 100 });
+
+    try {
+      const result = await harness.client.callTool({
+        name: "chat_completion",
+        arguments: { message: "hi", max_tokens: 4096 },
+      });
+      expect(result.isError).toBeFalsy();
+      expect(harness.api.createChatCompletion).toHaveBeenCalledTimes(1);
+    } finally {
+      await harness.serverClose();
+    }
+  });
+
+  it("answers chat_completion through client sampling when no API key is configured", async () => {
+    const fetchFn = vi.fn<typeof fetch>();
+    const mcpServer = createDeepSeekMcpServer({
+      client: new DeepSeekApiClient({ apiKey: "", fetchFn }),
+      conversations: new ConversationStore(200),
+      defaultModel: "deepseek-chat",
+      samplingFallback: true,
+      autoContinueLimit: 2,
+      chatToCompletionFallback: true,
+      version: "test",
+    });
+    const [clientTransport, serverTransport] = InMemoryTransport.createLinkedPair();
+    const client = new Client({ name: "sampling-client", version: "1.0.0" }, { capabilities: { sampling: {} } });
+    const createMessage = vi.fn(async () => ({
+      role: "assistant" as const,
+      model: "client-model",
+      stopReason: "endTurn",
+      content: { type: "text" as const, text: "sampled reply" },
+    }));
+    client.setRequestHandler(CreateMessageRequestSchema, createMessage);
+    await Promise.all([mcpServer.connect(serverTransport), client.connect(clientTransport)]);
+
+    try {
+      const result = await client.callTool({
+        name: "chat_completion",
+        arguments: { messages: [{ role: "system", content: "Be brief." }, { role: "user", content: "hi" }] },
+      });
+      expect(result.isError).toBeFalsy();
+      expect(result.structuredContent).toMatchObject({
+        model: "client-model",
+        response_text: "sampled reply",
+        finish_reason: "stop",
+      });
+      expect(createMessage.mock.calls[0]?.[0]).toMatchObject({
+        params: {
+          systemPrompt: "Be brief.",
+          messages: [{ role: "user", content: { type: "text", text: "hi" } }],
+          modelPreferences: { hints: [{ name: "deepseek-chat" }] },
+        },
+      });
+      expect(fetchFn).not.toHaveBeenCalled();
+
+      // Auto-continue and the completion fallback need DeepSeek itself, so sampling mode returns the reply as is.
+      const sampled = (stopReason: string, text: string) => ({
+        role: "assistant" as const,
+        model: "client-model",
+        stopReason,
+        content: { type: "text" as const, text },
+      });
+      createMessage.mockResolvedValueOnce(sampled("maxTokens", "partial")).mockResolvedValueOnce(sampled("endTurn", ""));
+      const truncated = await client.callTool({ name: "chat_completion", arguments: { message: "long" } });
+      expect(truncated.structuredContent).toMatchObject({ finish_reason: "length", auto_continuations: 0 });
+      const empty = await client.callTool({ name: "chat_completion", arguments: { message: "empty" } });
+      expect(empty.structuredContent).toMatchObject({ completion_fallback: false });
+      expect(createMessage).toHaveBeenCalledTimes(3);
+      expect(fetchFn).not.toHaveBeenCalled();
+    } finally {
+      await client.close();
+      await mcpServer.close();
+    }
+  });
+
+  it("attaches files from DEEPSEEK_ATTACH_DIRS and client roots into chat_completion", async () => {
+    const attachDir = await mkdtemp(join(tmpdir(), "deepseek-attach-dir-"));
+    const rootDir = await mkdtemp(join(tmpdir(), "deepseek-attach-root-"));
+    await writeFile(join(attachDir, "config.toml"), "debug = true\n");
+    await writeFile(join(rootDir, "main.rs"), "fn main() {}\n");
+    const listRoots = async () => ({ roots: [{ uri: pathToFileURL(rootDir).href, name: "workspace" }] });
+    const harness = await createHarness(
+      false,
+      { attachDirs: [attachDir], trustedTransport: true },
+      { capabilities: { roots: {} } },
+    );
+    harness.client.setRequestHandler(ListRootsRequestSchema, listRoots);
+    const network = await createHarness(false, { attachDirs: [attachDir] }, { capabilities: { roots: {} } });
+    network.client.setRequestHandler(ListRootsRequestSchema, listRoots);
+
+    try {
+      const result = await harness.client.callTool({
+        name: "chat_completion",
+        arguments: { message: "Review these", attach_files: ["config.toml", "main.rs"] },
+      });
+      expect(result.isError).toBeFalsy();
+      expect(result.structuredContent).toMatchObject({
+        attachments: [
+          { path: "config.toml", truncated: false },
+          { path: "main.rs", truncated: false },
+        ],
+      });
+
+      const request = harness.api.createChatCompletion.mock.calls[0]?.[0];
+      expect(request.messages).toHaveLength(2);
+      expect(request.messages[0].content).toContain("Attached file: main.rs\n```rs\nfn main() {}");
+      expect(request.messages[1]).toEqual({ role: "user", content: "Review these" });
+      expect(request).not.toHaveProperty("attach_files");
+
+      const rejected = await network.client.callTool({
+        name: "chat_completion",
+        arguments: { message: "Review this", attach_files: ["main.rs"] },
+      });
+      expect(rejected.isError).toBe(true);
+      expect(JSON.stringify(rejected.content)).toContain("not found inside the allowed roots");
+    } finally {
+      await harness.serverClose();
+      await network.serverClose();
+      await rm(attachDir, { recursive: true, force: true });
+      await rm(rootDir, { recursive: true, force: true });
+    }
+  });
+
+  it("fans out batch_chat_completion with bounded concurrency and per-item error isolation", async () => {
+    const harness = await createHarness(false, { enableBatch: true });
+    let inFlight = 0;
+    let peak = 0;
+    harness.api.createChatCompletion.mockImplementation(async (request) => {
+      inFlight += 1;
+      peak = Math.max(peak, inFlight);
+      await new Promise((resolve) => setTimeout(resolve, 10));
+      inFlight -= 1;
+      const content = request.messages[0]?.content;
+      if (content === "fail") {
+        throw new DeepSeekApiError("bad request", { status: 400 });
+      }
+
+      return {
+        response: {
+          id: "chat-batch",
+          object: "chat.completion",
+          created: 1,
+          model: request.model,
+          choices: [{ index: 0, finish_reason: "stop", message: { role: "assistant", content: `echo:${content}` } }],
+        },
+      };
+    });
+
+    try {
+      const result = await harness.client.callTool({
+        name: "batch_chat_completion",
+        arguments: {
+          concurrency: 2,
+          requests: ["a", "fail", "c", "d"].map((content) => ({ messages: [{ role: "user", content }] })),
+        },
+      });
+
+      expect(result.isError).toBeFalsy();
+      expect(peak).toBe(2);
+      expect(result.structuredContent).toMatchObject({
+        succeeded: 3,
+        failed: 1,
+        results: [
+          { index: 0, ok: true, content: "echo:a" },
+          { index: 1, ok: false, error: { status: 400, message: "bad request" } },
+          { index: 2, ok: true, content: "echo:c" },
+          { index: 3, ok: true, content: "echo:d" },
+        ],
+      });
+    } finally {
+      await harness.serverClose();
+    }
+  });
+
+  it("registers batch_chat_completion only when DEEPSEEK_ENABLE_BATCH is set", async () => {
+    const harness = await createHarness();
+
+    try {
+      const { tools } = await harness.client.listTools();
+      expect(tools.map((tool) => tool.name)).not.toContain("batch_chat_completion");
+    } finally {
+      await harness.serverClose();
+    }
+  });
+
+  it("counts each batch item against the per-client rate limit", async () => {
+    const harness = await createHarness(false, {
+      enableBatch: true,
+      clientRateLimiter: new ClientRateLimiter({ requestsPerMinute: 2, now: () => 0 }),
+    });
+    harness.api.createChatCompletion.mockImplementation(async (request) => ({
+      response: {
+        id: "chat-batch",
+        object: "chat.completion",
+        created: 1,
+        model: request.model,
+        choices: [{ index: 0, finish_reason: "stop", message: { role: "assistant", content: "ok" } }],
+      },
+    }));
+
+    try {
+      const result = await harness.client.callTool({
+        name: "batch_chat_completion",
+        arguments: {
+          concurrency: 1,
+          requests: ["a", "b", "c"].map((content) => ({ messages: [{ role: "user", content }] })),
+        },
+      });
+
+      expect(result.structuredContent).toMatchObject({
+        succeeded: 2,
+        failed: 1,
+        results: [{ ok: true }, { ok: true }, { ok: false, error: { error_type: "rate_limited" } }],
+      });
+      expect(harness.api.createChatCompletion).toHaveBeenCalledTimes(2);
+    } finally {
+      await harness.serverClose();
+    }
+  });
+
+  it("sends a progress notification while waiting on the client-side rate limiter", async () => {
+    const sleep = vi.fn(async () => undefined);
+    const harness = await createHarness(false, {
+      rateLimiter: new TokenBucketRateLimiter({ requestsPerMinute: 60, burst: 1, now: () => 0, sleep }),
+      rateLimitNotifyThresholdMs: 500,
+    });
+    const onprogress = vi.fn();
+
+    try {
+      await harness.client.callTool({ name: "chat_completion", arguments: { message: "first" } }, undefined, {
+        onprogress,
+      });
+      expect(sleep).not.toHaveBeenCalled();
+      expect(onprogress).not.toHaveBeenCalled();
+
+      const second = await harness.client.callTool(
+        { name: "chat_completion", arguments: { message: "second" } },
+        undefined,
+        { onprogress },
+      );
+      expect(second.isError).toBeFalsy();
+      expect(sleep).toHaveBeenCalledWith(1000);
+      expect(onprogress).toHaveBeenCalledWith(
+        expect.objectContaining({ progress: 1, message: "waiting for rate limit, ~1000 ms" }),
+      );
+      expect(harness.api.createChatCompletion).toHaveBeenCalledTimes(2);
+    } finally {
+      await harness.serverClose();
+    }
+  });
+
+  it("auto-continues truncated responses until finish_reason is stop when enabled", async () => {
+    const rateLimiter = new TokenBucketRateLimiter({ requestsPerMinute: 600, burst: 10 });
+    const acquire = vi.spyOn(rateLimiter, "acquire");
+    const harness = await createHarness(false, { autoContinueLimit: 3, rateLimiter });
+    const chatResponse = (content: string, finishReason: string) => ({
+      response: {
+        id: "chat-length",
+        object: "chat.completion",
+        created: 1,
+        model: "deepseek-chat",
+        choices: [{ index: 0, finish_reason: finishReason, message: { role: "assistant", content } }],
+        usage: { prompt_tokens: 10, completion_tokens: 5, total_tokens: 15 },
+      },
+    });
+    harness.api.createChatCompletion.mockResolvedValueOnce(chatResponse("The quick brown", "length"));
+    harness.api.createChatPrefixCompletion
+      .mockResolvedValueOnce(chatResponse(" fox jumps", "length"))
+      .mockResolvedValueOnce(chatResponse(" over the dog.", "stop"));
+
+    try {
+      const result = await harness.client.callTool({
+        name: "chat_completion",
+        arguments: { message: "Tell me the pangram", conversation_id: "pangram" },
+      });
+
+      expect(result.structuredContent).toMatchObject({
+        response_text: "The quick brown fox jumps over the dog.",
+        finish_reason: "stop",
+        auto_continuations: 2,
+        usage: { prompt_tokens: 30, completion_tokens: 15, total_tokens: 45 },
+      });
+      expect(acquire).toHaveBeenCalledTimes(3);
+
+      const secondContinuation = harness.api.createChatPrefixCompletion.mock.calls[1]?.[0];
+      expect(secondContinuation?.messages.at(-1)).toEqual({
+        role: "assistant",
+        content: "The quick brown fox jumps",
+        prefix: true,
+      });
+
+      const stored = await harness.client.readResource({ uri: "deepseek://conversations/pangram" });
+      const storedText = stored.contents[0] && "text" in stored.contents[0] ? stored.contents[0].text : "";
+      expect(JSON.parse(String(storedText)).messages.at(-1).content).toBe("The quick brown fox jumps over the dog.");
+    } finally {
+      await harness.serverClose();
+    }
+  });
+
+  it("trims whitespace and strips code fences from chat output without touching the raw response", async () => {
+    const harness = await createHarness(false, { trimOutput: true, stripCodeFences: true });
+    const chatResponse = (content: string) => ({
+      response: {
+        id: "chat-fenced",
+        object: "chat.completion",
+        created: 1,
+        model: "deepseek-chat",
+        choices: [{ index: 0, finish_reason: "stop", message: { role: "assistant", content } }],
+      },
+    });
+    harness.api.createChatCompletion
+      .mockResolvedValueOnce(chatResponse("\n\n  Hello there.  \n"))
+      .mockResolvedValueOnce(chatResponse('```json\n{"ok": true}\n```\n'));
+
+    try {
+      const trimmed = await harness.client.callTool({ name: "chat_completion", arguments: { message: "hi" } });
+      expect(trimmed.structuredContent).toMatchObject({ response_text: "Hello there." });
+
+      const fenced = await harness.client.callTool({
+        name: "chat_completion",
+        arguments: { message: "json please", include_raw_response: true },
+      });
+      expect(fenced.structuredContent).toMatchObject({ response_text: '{"ok": true}' });
+      expect(
+        (fenced.structuredContent as { raw_response: { choices: Array<{ message: { content: string } }> } }).raw_response
+          .choices[0]?.message.content,
+      ).toBe('```json\n{"ok": true}\n```\n');
+      const text = (fenced.content as Array<{ type: string; text: string }>)[0].text;
+      expect(text.startsWith('{"ok": true}')).toBe(true);
+    } finally {
+      await harness.serverClose();
+    }
+  });
+
+  it("enforces the monthly budget and reports spend via get_budget", async () => {
+    const budget = new BudgetTracker({ monthlyBudgetUsd: 0.001, now: () => new Date("2026-05-10T00:00:00.000Z") });
+    const harness = await createHarness(false, { budget });
+    const usageResponse = {
+      response: {
+        id: "chat-usage",
+        object: "chat.completion",
+        created: 1,
+        model: "deepseek-chat",
+        choices: [{ index: 0, finish_reason: "stop", message: { role: "assistant", content: "ok" } }],
+        usage: { prompt_tokens: 1000, completion_tokens: 1000, total_tokens: 2000, prompt_cache_miss_tokens: 1000 },
+      },
+    };
+    harness.api.createChatCompletion.mockResolvedValue(usageResponse);
+
+    try {
+      const first = await harness.client.callTool({ name: "chat_completion", arguments: { message: "one" } });
+      expect(first.isError).toBeFalsy();
+
+      const second = await harness.client.callTool({ name: "chat_completion", arguments: { message: "two" } });
+      expect(second.isError).toBe(true);
+      expect(JSON.stringify(second.content)).toContain("Budget exceeded");
+
+      const third = await harness.client.callTool({ name: "chat_completion", arguments: { message: "three" } });
+      expect(third.isError).toBe(true);
+      expect(harness.api.createChatCompletion).toHaveBeenCalledTimes(2);
+
+      const status = await harness.client.callTool({ name: "get_budget", arguments: {} });
+      expect(status.structuredContent).toMatchObject({
+        enabled: true,
+        month: "2026-05",
+        budget_usd: 0.001,
+        exceeded: true,
+        remaining_usd: 0,
+      });
+      expect((status.structuredContent as Record<string, number>).spent_usd).toBeCloseTo(0.0014, 10);
+    } finally {
+      await harness.serverClose();
+    }
+  });
+
+  it("fails fast for v4 tools when feature flag is disabled", async () => {
+    const harness = await createHarness(false);
+
+    try {
+      const result = await harness.client.callTool({
+        name: "vision_upload",
+        arguments: {
+          file_url: "https://example.com/a.jpg",
+        },
+      });
+
+      expect(result.isError).toBe(true);
+      expect((result.structuredContent as Record<string, unknown>)?.error_type).toBe("experimental_feature_disabled");
+      expect(harness.api.uploadVisionAsset).not.toHaveBeenCalled();
+    } finally {
+      await harness.serverClose();
+    }
+  });
+
+  it("calls v4 provider methods when feature flag is enabled", async () => {
+    const harness = await createHarness(true);
+
+    try {
+      const vision = await harness.client.callTool({
+        name: "vision_upload",
+        arguments: { file_url: "https://example.com/a.jpg" },
+      });
+      expect(vision.isError).toBeFalsy();
+
+      const image = await harness.client.callTool({
+        name: "image_generation",
+        arguments: { prompt: "A mountain lake at sunset" },
+      });
+      expect(image.isError).toBeFalsy();
+
+      const video = await harness.client.callTool({
+        name: "video_generation",
+        arguments: {
+          prompt: "A short drone shot over mountains",
+          wait_for_completion: true,
+          poll_interval_ms: 1,
+          max_wait_ms: 10,
+        },
+      });
+      expect(video.isError).toBeFalsy();
+
+      expect(harness.api.uploadVisionAsset).toHaveBeenCalledTimes(1);
+      expect(harness.api.generateImage).toHaveBeenCalledTimes(1);
       expect(harness.api.generateVideo).toHaveBeenCalledTimes(1);
       expect(harness.api.getV4TaskStatus).toHaveBeenCalledTimes(1);
     } finally {
@@ -324,6 +3021,112 @@ describe("createDeepSeekMcpServer", () => {
     }
   });
 
+  it("serializes json_schema response_format into the provider shape", async () => {
+    const harness = await createHarness();
+
+    try {
+      const result = await harness.client.callTool({
+        name: "chat_completion",
+        arguments: {
+          message: "weather in NYC",
+          response_format: {
+            type: "json_schema",
+            json_schema: {
+              name: "weather_report",
+              schema: { type: "object", properties: { city: { type: "string" } }, required: ["city"] },
+              strict: true,
+            },
+          },
+        },
+      });
+
+      expect(result.isError).toBeFalsy();
+      const request = harness.api.createChatCompletion.mock.calls[0]?.[0];
+      expect(request.response_format).toEqual({
+        type: "json_schema",
+        json_schema: {
+          name: "weather_report",
+          schema: { type: "object", properties: { city: { type: "string" } }, required: ["city"] },
+          strict: true,
+        },
+      });
+    } finally {
+      await harness.serverClose();
+    }
+  });
+
+  it("strips parameters the resolved model does not support and notes it in the summary", async () => {
+    const harness = await createHarness();
+
+    try {
+      const reasoner = await harness.client.callTool({
+        name: "chat_completion",
+        arguments: {
+          message: "hello",
+          model: "deepseek-reasoner",
+          temperature: 0.3,
+          logprobs: true,
+          top_logprobs: 2,
+        },
+      });
+
+      expect(reasoner.isError).toBeFalsy();
+      const reasonerRequest = harness.api.createChatCompletion.mock.calls[0]?.[0];
+      expect(reasonerRequest.logprobs).toBeUndefined();
+      expect(reasonerRequest.top_logprobs).toBeUndefined();
+      expect(reasonerRequest.temperature).toBeUndefined();
+      expect((reasoner.structuredContent as Record<string, unknown>).stripped_parameters).toEqual([
+        "logprobs",
+        "top_logprobs",
+        "temperature",
+      ]);
+
+      const textBlock = reasoner.content?.[0];
+      if (!textBlock || textBlock.type !== "text") {
+        throw new Error("expected text tool output");
+      }
+      expect(textBlock.text).toContain("Stripped parameters unsupported by deepseek-reasoner");
+
+      const chat = await harness.client.callTool({
+        name: "chat_completion",
+        arguments: { message: "hello", model: "deepseek-chat", thinking: { type: "enabled" }, temperature: 0.3 },
+      });
+
+      expect(chat.isError).toBeFalsy();
+      const chatRequest = harness.api.createChatCompletion.mock.calls[1]?.[0];
+      expect(chatRequest.thinking).toEqual({ type: "enabled" });
+      expect(chatRequest.temperature).toBe(0.3);
+
+      const custom = await harness.client.callTool({
+        name: "chat_completion",
+        arguments: { message: "hello", model: "gateway-model", thinking: { type: "enabled" } },
+      });
+
+      expect(custom.isError).toBeFalsy();
+      expect(harness.api.createChatCompletion.mock.calls[2]?.[0].thinking).toBeUndefined();
+    } finally {
+      await harness.serverClose();
+    }
+  });
+
+  it("returns a tool error for invalid thinking configs without calling DeepSeek", async () => {
+    const harness = await createHarness();
+
+    try {
+      const rejected = await harness.client
+        .callTool({
+          name: "chat_completion",
+          arguments: { message: "hello", thinking: { type: "disabled", budget_tokens: 512 } },
+        })
+        .then((result) => result.isError === true, () => true);
+
+      expect(rejected).toBe(true);
+      expect(harness.api.createChatCompletion).not.toHaveBeenCalled();
+    } finally {
+      await harness.serverClose();
+    }
+  });
+
   it("keeps raw provider payload opt-in to reduce token bloat", async () => {
     const harness = await createHarness();
 
@@ -0,0 +1,29 @@
+import { describe, expect, it } from "vitest";
+
+import { WeightedModelSelector } from "../src/deepseek/model-selection.js";
+
+describe("WeightedModelSelector", () => {
+  it("selects models in proportion to their weights", () => {
+    let seed = 42;
+    const random = () => {
+      seed = (seed * 1103515245 + 12345) % 2147483648;
+      return seed / 2147483648;
+    };
+
+    const selector = new WeightedModelSelector({ "deepseek-chat": 3, "deepseek-reasoner": 1, unused: 0 }, random);
+    const counts: Record<string, number> = {};
+    const total = 20000;
+    for (let index = 0; index < total; index += 1) {
+      const model = selector.select();
+      counts[model] = (counts[model] ?? 0) + 1;
+    }
+
+    expect(Object.keys(counts).sort()).toEqual(["deepseek-chat", "deepseek-reasoner"]);
+    expect(counts["deepseek-chat"]! / total).toBeCloseTo(0.75, 1);
+    expect(counts["deepseek-reasoner"]! / total).toBeCloseTo(0.25, 1);
+  });
+
+  it("rejects weight maps without a positive weight", () => {
+    expect(() => new WeightedModelSelector({ "deepseek-chat": 0 })).toThrow();
+  });
+});
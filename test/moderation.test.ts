@@ -0,0 +1,46 @@
+import { describe, expect, it, vi } from "vitest";
+
+import { ModerationBlockedError, PromptModerator } from "../src/moderation.js";
+
+function jsonResponse(body: unknown, status = 200): Response {
+  return new Response(JSON.stringify(body), { status, headers: { "content-type": "application/json" } });
+}
+
+describe("PromptModerator", () => {
+  it("allows prompts the moderation service does not flag", async () => {
+    const fetchFn = vi.fn<typeof fetch>().mockResolvedValue(jsonResponse({ results: [{ flagged: false }] }));
+    const moderator = new PromptModerator({ url: "https://moderation.example.com/check", fetchFn });
+
+    await expect(moderator.assertAllowed("hello")).resolves.toEqual({
+      flagged: false,
+      categories: [],
+      failedOpen: false,
+    });
+
+    const [url, init] = fetchFn.mock.calls[0] as [string, RequestInit];
+    expect(url).toBe("https://moderation.example.com/check");
+    expect(JSON.parse(String(init.body))).toEqual({ input: "hello" });
+  });
+
+  it("blocks flagged prompts and reports the flagged categories", async () => {
+    const fetchFn = vi
+      .fn<typeof fetch>()
+      .mockResolvedValue(jsonResponse({ flagged: true, categories: { violence: true, spam: false } }));
+    const moderator = new PromptModerator({ url: "https://moderation.example.com/check", fetchFn });
+
+    await expect(moderator.assertAllowed("bad")).rejects.toMatchObject({
+      name: "ModerationBlockedError",
+      categories: ["violence"],
+    });
+  });
+
+  it("fails closed by default and fails open when configured", async () => {
+    const fetchFn = vi.fn<typeof fetch>().mockResolvedValue(jsonResponse({ error: "down" }, 503));
+
+    const closed = new PromptModerator({ url: "https://moderation.example.com/check", fetchFn });
+    await expect(closed.assertAllowed("hello")).rejects.toBeInstanceOf(ModerationBlockedError);
+
+    const open = new PromptModerator({ url: "https://moderation.example.com/check", failMode: "open", fetchFn });
+    await expect(open.assertAllowed("hello")).resolves.toMatchObject({ flagged: false, failedOpen: true });
+  });
+});
@@ -0,0 +1,157 @@
+import { describe, expect, it, vi } from "vitest";
+
+import { ConversationStore } from "../src/conversation-store.js";
+import { DeepSeekApiClient } from "../src/deepseek/client.js";
+import { createDeepSeekMcpServer } from "../src/mcp-server.js";
+import { startStreamableHttpServer } from "../src/transports/http.js";
+import { OAuthResourceServer } from "../src/transports/oauth.js";
+
+function introspectionFetch(responses: Record<string, Record<string, unknown>>) {
+  return vi.fn<typeof fetch>(async (_url, init) => {
+    const token = new URLSearchParams(String(init?.body)).get("token") ?? "";
+    return new Response(JSON.stringify(responses[token] ?? { active: false }), { status: 200 });
+  });
+}
+
+describe("OAuthResourceServer", () => {
+  it("validates introspected tokens for activity, audience and scope, caching successes", async () => {
+    const fetchFn = introspectionFetch({
+      good: { active: true, aud: "https://mcp.example.com/mcp", scope: "mcp:tools profile", sub: "user-1" },
+      "wrong-aud": { active: true, aud: "https://other.example.com", scope: "mcp:tools" },
+      "no-scope": { active: true, aud: "https://mcp.example.com/mcp", scope: "profile" },
+      "no-aud": { active: true, scope: "mcp:tools" },
+    });
+    const oauth = new OAuthResourceServer({
+      resource: "https://mcp.example.com/mcp",
+      authorizationServers: ["https://auth.example.com"],
+      introspectionUrl: "https://auth.example.com/introspect",
+      clientId: "mcp",
+      clientSecret: "secret",
+      requiredScopes: ["mcp:tools"],
+      fetchFn,
+    });
+
+    await expect(oauth.verify("good")).resolves.toEqual({
+      ok: true,
+      scopes: ["mcp:tools", "profile"],
+      subject: "user-1",
+    });
+    await oauth.verify("good");
+    expect(fetchFn).toHaveBeenCalledTimes(1);
+    expect(new Headers(fetchFn.mock.calls[0]?.[1]?.headers).get("authorization")).toBe(
+      `Basic ${Buffer.from("mcp:secret").toString("base64")}`,
+    );
+
+    await expect(oauth.verify("revoked")).resolves.toMatchObject({ ok: false, status: 401, error: "invalid_token" });
+    await expect(oauth.verify("wrong-aud")).resolves.toMatchObject({ ok: false, status: 401 });
+    await expect(oauth.verify("no-aud")).resolves.toMatchObject({
+      ok: false,
+      status: 401,
+      description: "Token has no audience claim",
+    });
+    await expect(oauth.verify("no-scope")).resolves.toMatchObject({
+      ok: false,
+      status: 403,
+      error: "insufficient_scope",
+    });
+
+    expect(oauth.metadataPaths).toEqual([
+      "/.well-known/oauth-protected-resource/mcp",
+      "/.well-known/oauth-protected-resource",
+    ]);
+    expect(oauth.challenge()).toBe(
+      'Bearer resource_metadata="https://mcp.example.com/.well-known/oauth-protected-resource/mcp", scope="mcp:tools"',
+    );
+  });
+
+  it("reports an unreachable introspection endpoint as 503 and bounds the token cache", async () => {
+    const failing = new OAuthResourceServer({
+      resource: "https://mcp.example.com/mcp",
+      authorizationServers: ["https://auth.example.com"],
+      introspectionUrl: "https://auth.example.com/introspect",
+      fetchFn: vi.fn<typeof fetch>().mockRejectedValue(new Error("connect ECONNREFUSED")),
+    });
+    await expect(failing.verify("any")).resolves.toMatchObject({
+      ok: false,
+      status: 503,
+      error: "temporarily_unavailable",
+    });
+
+    const fetchFn = vi.fn<typeof fetch>(
+      async () => new Response(JSON.stringify({ active: true, aud: "https://mcp.example.com/mcp" }), { status: 200 }),
+    );
+    const bounded = new OAuthResourceServer({
+      resource: "https://mcp.example.com/mcp",
+      authorizationServers: ["https://auth.example.com"],
+      introspectionUrl: "https://auth.example.com/introspect",
+      maxCacheEntries: 2,
+      fetchFn,
+    });
+    await bounded.verify("a");
+    await bounded.verify("b");
+    await bounded.verify("c");
+    await bounded.verify("c");
+    await bounded.verify("a");
+    expect(fetchFn).toHaveBeenCalledTimes(4);
+  });
+
+  it("challenges unauthenticated HTTP requests and serves protected resource metadata", async () => {
+    const oauth = new OAuthResourceServer({
+      resource: "http://127.0.0.1/mcp",
+      authorizationServers: ["https://auth.example.com"],
+      introspectionUrl: "https://auth.example.com/introspect",
+      fetchFn: introspectionFetch({ good: { active: true, aud: "http://127.0.0.1/mcp" } }),
+    });
+    const runtime = await startStreamableHttpServer(
+      () =>
+        createDeepSeekMcpServer({
+          client: {} as DeepSeekApiClient,
+          conversations: new ConversationStore(200),
+          defaultModel: "deepseek-chat",
+          version: "test",
+        }),
+      {
+        host: "127.0.0.1",
+        port: 0,
+        path: "/mcp",
+        statefulSession: false,
+        oauth,
+      },
+    );
+    const baseUrl = `http://127.0.0.1:${runtime.port}`;
+    const initialize = {
+      method: "POST",
+      headers: { "Content-Type": "application/json", Accept: "application/json, text/event-stream" },
+      body: JSON.stringify({
+        jsonrpc: "2.0",
+        id: 1,
+        method: "initialize",
+        params: { protocolVersion: "2025-03-26", capabilities: {}, clientInfo: { name: "oauth-test", version: "1" } },
+      }),
+    };
+
+    try {
+      const metadata = await fetch(`${baseUrl}/.well-known/oauth-protected-resource/mcp`);
+      expect(await metadata.json()).toMatchObject({ authorization_servers: ["https://auth.example.com"] });
+
+      const anonymous = await fetch(`${baseUrl}/mcp`, initialize);
+      expect(anonymous.status).toBe(401);
+      expect(anonymous.headers.get("www-authenticate")).toContain("resource_metadata=");
+
+      const invalid = await fetch(`${baseUrl}/mcp`, {
+        ...initialize,
+        headers: { ...initialize.headers, Authorization: "Bearer bad" },
+      });
+      expect(invalid.status).toBe(401);
+      expect(invalid.headers.get("www-authenticate")).toContain('error="invalid_token"');
+
+      const authorized = await fetch(`${baseUrl}/mcp`, {
+        ...initialize,
+        headers: { ...initialize.headers, Authorization: "Bearer good" },
+      });
+      expect(authorized.status).toBe(200);
+    } finally {
+      await runtime.close();
+    }
+  });
+});
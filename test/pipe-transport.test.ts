@@ -0,0 +1,69 @@
+import { connect } from "node:net";
+import { tmpdir } from "node:os";
+import { join } from "node:path";
+
+import { describe, expect, it } from "vitest";
+
+import { Client } from "@modelcontextprotocol/sdk/client/index.js";
+
+import { ConversationStore } from "../src/conversation-store.js";
+import { DeepSeekApiClient } from "../src/deepseek/client.js";
+import { createDeepSeekMcpServer } from "../src/mcp-server.js";
+import { startPipeServer } from "../src/transports/pipe.js";
+import { TcpSocketTransport } from "../src/transports/tcp.js";
+
+describe("startPipeServer", () => {
+  it("completes an MCP initialize over a named pipe or Unix socket", async () => {
+    const path =
+      process.platform === "win32"
+        ? `\\\\.\\pipe\\deepseek-mcp-test-${process.pid}`
+        : join(tmpdir(), `deepseek-mcp-test-${process.pid}.sock`);
+    const runtime = await startPipeServer(
+      () =>
+        createDeepSeekMcpServer({
+          client: {} as DeepSeekApiClient,
+          conversations: new ConversationStore(200),
+          defaultModel: "deepseek-chat",
+          version: "test",
+        }),
+      { path },
+    );
+
+    const client = new Client({ name: "pipe-smoke", version: "1.0.0" });
+
+    try {
+      await client.connect(new TcpSocketTransport(connect(path)));
+
+      expect(client.getServerVersion()).toMatchObject({ name: "deepseek-mcp-server", version: "test" });
+
+      const tools = await client.listTools();
+      expect(tools.tools.map((tool) => tool.name)).toContain("chat_completion");
+    } finally {
+      await client.close();
+      await runtime.close();
+    }
+  });
+
+  it.skipIf(process.platform === "win32")("refuses to take over a socket another server is listening on", async () => {
+    const path = join(tmpdir(), `deepseek-mcp-test-busy-${process.pid}.sock`);
+    const createMcpServer = () =>
+      createDeepSeekMcpServer({
+        client: {} as DeepSeekApiClient,
+        conversations: new ConversationStore(200),
+        defaultModel: "deepseek-chat",
+        version: "test",
+      });
+    const runtime = await startPipeServer(createMcpServer, { path });
+    const client = new Client({ name: "pipe-busy", version: "1.0.0" });
+
+    try {
+      await expect(startPipeServer(createMcpServer, { path })).rejects.toThrow("already running");
+
+      await client.connect(new TcpSocketTransport(connect(path)));
+      expect(client.getServerVersion()).toMatchObject({ name: "deepseek-mcp-server" });
+    } finally {
+      await client.close();
+      await runtime.close();
+    }
+  });
+});
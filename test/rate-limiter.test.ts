@@ -0,0 +1,47 @@
+import { describe, expect, it } from "vitest";
+
+import { ClientRateLimitedError, ClientRateLimiter, TokenBucketRateLimiter } from "../src/rate-limiter.js";
+
+describe("TokenBucketRateLimiter", () => {
+  it("allows the burst immediately and queues later reservations in arrival order", () => {
+    let now = 0;
+    const limiter = new TokenBucketRateLimiter({ requestsPerMinute: 60, burst: 2, now: () => now });
+
+    expect(limiter.reserve()).toBe(0);
+    expect(limiter.reserve()).toBe(0);
+    expect(limiter.reserve()).toBe(1000);
+    expect(limiter.reserve()).toBe(2000);
+
+    now = 5000;
+    expect(limiter.reserve(2)).toBe(0);
+  });
+
+describe("ClientRateLimiter", () => {
+  it("rejects calls over the per-minute budget per key without consuming other keys' budget", async () => {
+    let now = 0;
+    const limiter = new ClientRateLimiter({ requestsPerMinute: 2, now: () => now });
+    const work = async () => "ok";
+
+    await expect(limiter.run("a", work)).resolves.toBe("ok");
+    await expect(limiter.run("a", work)).resolves.toBe("ok");
+    await expect(limiter.run("a", work)).rejects.toMatchObject({
+      name: "ClientRateLimitedError",
+      retryAfterMs: 30000,
+    });
+    await expect(limiter.run("b", work)).resolves.toBe("ok");
+
+    now = 30000;
+    await expect(limiter.run("a", work)).resolves.toBe("ok");
+  });
+
+  it("rejects calls beyond the concurrency limit", async () => {
+    const limiter = new ClientRateLimiter({ maxConcurrent: 1 });
+    let finish: () => void = () => undefined;
+    const pending = limiter.run("a", () => new Promise<void>((resolve) => (finish = resolve)));
+
+    await expect(limiter.run("a", async () => undefined)).rejects.toBeInstanceOf(ClientRateLimitedError);
+    finish();
+    await pending;
+    await expect(limiter.run("a", async () => "ok")).resolves.toBe("ok");
+  });
+});
@@ -0,0 +1,49 @@
+import { describe, expect, it } from "vitest";
+
+import { fromSamplingResult, toSamplingRequest } from "../src/sampling.js";
+
+describe("toSamplingRequest", () => {
+  it("lifts system turns into the system prompt and carries generation limits", () => {
+    const params = toSamplingRequest({
+      model: "deepseek-reasoner",
+      messages: [
+        { role: "system", content: "Be brief." },
+        { role: "user", content: "What is 2+2?" },
+        { role: "assistant", content: "4" },
+        { role: "tool", content: "{\"ok\":true}", tool_call_id: "call-1" },
+      ],
+      max_tokens: 256,
+      temperature: 0.2,
+      stop: "END",
+    });
+
+    expect(params).toEqual({
+      systemPrompt: "Be brief.",
+      messages: [
+        { role: "user", content: { type: "text", text: "What is 2+2?" } },
+        { role: "assistant", content: { type: "text", text: "4" } },
+        { role: "user", content: { type: "text", text: "Tool result: {\"ok\":true}" } },
+      ],
+      maxTokens: 256,
+      temperature: 0.2,
+      stopSequences: ["END"],
+      modelPreferences: { hints: [{ name: "deepseek-reasoner" }] },
+    });
+  });
+});
+
+describe("fromSamplingResult", () => {
+  it("maps the sampled message onto a chat completion response", () => {
+    const result = fromSamplingResult({
+      role: "assistant",
+      model: "client-model",
+      stopReason: "maxTokens",
+      content: { type: "text", text: "partial" },
+    });
+
+    expect(result.response).toMatchObject({
+      model: "client-model",
+      choices: [{ index: 0, finish_reason: "length", message: { role: "assistant", content: "partial" } }],
+    });
+  });
+});
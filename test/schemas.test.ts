@@ -27,6 +27,51 @@ describe("tool input schemas", () => {
     expect(parsed.extra_body?.future_parameter).toBe("supported");
   });
 
+  it("accepts a named json_schema response_format and rejects non-object schemas", () => {
+    const parsed = chatCompletionToolInputSchema.parse({
+      message: "hello",
+      response_format: {
+        type: "json_schema",
+        json_schema: {
+          name: "weather_report",
+          schema: { type: "object", properties: { city: { type: "string" } } },
+          strict: true,
+        },
+      },
+    });
+
+    expect(parsed.response_format?.type).toBe("json_schema");
+
+    const invalid = chatCompletionToolInputSchema.safeParse({
+      message: "hello",
+      response_format: {
+        type: "json_schema",
+        json_schema: {
+          name: "weather_report",
+          schema: ["not", "an", "object"],
+        },
+      },
+    });
+
+    expect(invalid.success).toBe(false);
+  });
+
+  it("accepts typed thinking configs and rejects invalid ones", () => {
+    expect(
+      chatCompletionToolInputSchema.parse({ message: "hi", thinking: { type: "enabled", budget_tokens: 2048 } }).thinking,
+    ).toEqual({ type: "enabled", budget_tokens: 2048 });
+    expect(chatCompletionToolInputSchema.parse({ message: "hi", thinking: { type: "disabled" } }).thinking).toEqual({
+      type: "disabled",
+    });
+
+    expect(chatCompletionToolInputSchema.safeParse({ message: "hi", thinking: { type: "auto" } }).success).toBe(false);
+    expect(chatCompletionToolInputSchema.safeParse({ message: "hi", thinking: {} }).success).toBe(false);
+    expect(
+      chatCompletionToolInputSchema.safeParse({ message: "hi", thinking: { type: "disabled", budget_tokens: 10 } })
+        .success,
+    ).toBe(false);
+  });
+
   it("requires logprobs=true when top_logprobs is set", () => {
     const result = chatCompletionToolInputSchema.safeParse({
       message: "hello",
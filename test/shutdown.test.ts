@@ -0,0 +1,34 @@
+import { describe, expect, it, vi } from "vitest";
+
+import { ShutdownController } from "../src/shutdown.js";
+
+describe("ShutdownController", () => {
+  it("rejects new work while draining and resolves once in-flight work settles", async () => {
+    const controller = new ShutdownController();
+    let finish: (value: string) => void = () => undefined;
+    const pending = controller.track(() => new Promise<string>((resolve) => (finish = resolve)));
+    expect(controller.inFlight).toBe(1);
+
+    const drained = controller.drain(1000);
+    await expect(controller.track(async () => "late")).rejects.toThrow("Server is shutting down");
+
+    finish("done");
+    await expect(pending).resolves.toBe("done");
+    await expect(drained).resolves.toBe(true);
+    expect(controller.inFlight).toBe(0);
+  });
+
+  it("gives up after the drain timeout", async () => {
+    vi.useFakeTimers();
+    try {
+      const controller = new ShutdownController();
+      void controller.track(() => new Promise<void>(() => undefined));
+
+      const drained = controller.drain(500);
+      await vi.advanceTimersByTimeAsync(500);
+      await expect(drained).resolves.toBe(false);
+    } finally {
+      vi.useRealTimers();
+    }
+  });
+});
@@ -0,0 +1,113 @@
+import { readFile } from "node:fs/promises";
+import { request as httpsRequest } from "node:https";
+
+import { describe, expect, it } from "vitest";
+
+import { Client } from "@modelcontextprotocol/sdk/client/index.js";
+import { SSEClientTransport } from "@modelcontextprotocol/sdk/client/sse.js";
+
+import { ConversationStore } from "../src/conversation-store.js";
+import { DeepSeekApiClient } from "../src/deepseek/client.js";
+import { createDeepSeekMcpServer } from "../src/mcp-server.js";
+import { startSseServer } from "../src/transports/sse.js";
+
+describe("startSseServer", () => {
+  it("binds and completes an MCP initialize over SSE", async () => {
+    const conversations = new ConversationStore(200);
+    const runtime = await startSseServer(
+      () =>
+        createDeepSeekMcpServer({
+          client: {} as DeepSeekApiClient,
+          conversations,
+          defaultModel: "deepseek-chat",
+          version: "test",
+        }),
+      { host: "127.0.0.1", port: 0, ssePath: "/sse", messagesPath: "/messages" },
+    );
+
+    const client = new Client({ name: "sse-smoke", version: "1.0.0" });
+
+    try {
+      await client.connect(new SSEClientTransport(new URL(`http://127.0.0.1:${runtime.port}/sse`)));
+
+      expect(client.getServerVersion()).toMatchObject({ name: "deepseek-mcp-server", version: "test" });
+
+      const tools = await client.listTools();
+      expect(tools.tools.map((tool) => tool.name)).toContain("chat_completion");
+    } finally {
+      await client.close();
+      await runtime.close();
+    }
+  });
+
+  it("rejects requests without a configured bearer token", async () => {
+    const conversations = new ConversationStore(200);
+    const runtime = await startSseServer(
+      () =>
+        createDeepSeekMcpServer({
+          client: {} as DeepSeekApiClient,
+          conversations,
+          defaultModel: "deepseek-chat",
+          version: "test",
+        }),
+      { host: "127.0.0.1", port: 0, ssePath: "/sse", messagesPath: "/messages", authTokens: ["secret-token"] },
+    );
+
+    const client = new Client({ name: "sse-auth", version: "1.0.0" });
+
+    try {
+      const anonymous = await fetch(`http://127.0.0.1:${runtime.port}/sse`);
+      expect(anonymous.status).toBe(401);
+      expect(anonymous.headers.get("www-authenticate")).toContain("Bearer");
+
+      const wrongToken = await fetch(`http://127.0.0.1:${runtime.port}/sse`, {
+        headers: { Authorization: "Bearer wrong-token" },
+      });
+      expect(wrongToken.status).toBe(401);
+
+      await client.connect(
+        new SSEClientTransport(new URL(`http://127.0.0.1:${runtime.port}/sse`), {
+          requestInit: { headers: { Authorization: "Bearer secret-token" } },
+        }),
+      );
+      const tools = await client.listTools();
+      expect(tools.tools.map((tool) => tool.name)).toContain("chat_completion");
+    } finally {
+      await client.close();
+      await runtime.close();
+    }
+  });
+
+  it("terminates TLS itself when a certificate and key are configured", async () => {
+    const cert = await readFile(new URL("./fixtures/tls/localhost.crt", import.meta.url));
+    const key = await readFile(new URL("./fixtures/tls/localhost.key", import.meta.url));
+    const runtime = await startSseServer(
+      () =>
+        createDeepSeekMcpServer({
+          client: {} as DeepSeekApiClient,
+          conversations: new ConversationStore(200),
+          defaultModel: "deepseek-chat",
+          version: "test",
+        }),
+      { host: "127.0.0.1", port: 0, ssePath: "/sse", messagesPath: "/messages", tls: { cert, key } },
+    );
+
+    try {
+      const status = await new Promise<number | undefined>((resolve, reject) => {
+        const req = httpsRequest(
+          { host: "127.0.0.1", port: runtime.port, path: "/missing", ca: cert, servername: "localhost" },
+          (res) => {
+            res.resume();
+            resolve(res.statusCode);
+          },
+        );
+        req.on("error", reject);
+        req.end();
+      });
+
+      expect(status).toBe(404);
+    } finally {
+      await runtime.close();
+    }
+  });
+});
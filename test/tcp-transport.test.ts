@@ -0,0 +1,64 @@
+import { AddressInfo, connect, createServer, Socket } from "node:net";
+
+import { describe, expect, it } from "vitest";
+
+import { Client } from "@modelcontextprotocol/sdk/client/index.js";
+
+import { ConversationStore } from "../src/conversation-store.js";
+import { DeepSeekApiClient } from "../src/deepseek/client.js";
+import { createDeepSeekMcpServer } from "../src/mcp-server.js";
+import { startTcpServer, TcpSocketTransport } from "../src/transports/tcp.js";
+
+describe("startTcpServer", () => {
+  it("completes an MCP initialize over newline-delimited JSON-RPC on a TCP socket", async () => {
+    const conversations = new ConversationStore(200);
+    const runtime = await startTcpServer(
+      () =>
+        createDeepSeekMcpServer({
+          client: {} as DeepSeekApiClient,
+          conversations,
+          defaultModel: "deepseek-chat",
+          version: "test",
+        }),
+      { host: "127.0.0.1", port: 0 },
+    );
+
+    const client = new Client({ name: "tcp-smoke", version: "1.0.0" });
+
+    try {
+      await client.connect(new TcpSocketTransport(connect(runtime.port, "127.0.0.1")));
+
+      expect(client.getServerVersion()).toMatchObject({ name: "deepseek-mcp-server", version: "test" });
+
+      const tools = await client.listTools();
+      expect(tools.tools.map((tool) => tool.name)).toContain("chat_completion");
+    } finally {
+      await client.close();
+      await runtime.close();
+    }
+  });
+});
+
+describe("TcpSocketTransport", () => {
+  it("disconnects a peer that sends a line longer than maxLineBytes", async () => {
+    const errors: Error[] = [];
+    const accepted = new Promise<Socket>((resolve) => {
+      const listener = createServer((socket) => {
+        const transport = new TcpSocketTransport(socket, 64);
+        transport.onerror = (error) => errors.push(error);
+        void transport.start();
+        resolve(socket);
+        listener.close();
+      });
+      listener.listen(0, "127.0.0.1", () => {
+        const peer = connect((listener.address() as AddressInfo).port, "127.0.0.1");
+        peer.on("error", () => undefined);
+        peer.write(`{"jsonrpc":"2.0","method":"ping"}\n${"x".repeat(100)}`);
+      });
+    });
+
+    const socket = await accepted;
+    await new Promise((resolve) => socket.once("close", resolve));
+    expect(errors.map((error) => error.message)).toEqual(["TCP message exceeds 64 bytes without a newline"]);
+  });
+});
@@ -0,0 +1,31 @@
+import { describe, expect, it } from "vitest";
+
+import { diffLines } from "../src/deepseek/text-diff.js";
+
+describe("diffLines", () => {
+  it("marks removed and added lines around the common subsequence", () => {
+    expect(diffLines("a\nb\nc", "a\nB\nc\nd")).toEqual({
+      text: "  a\n- b\n+ B\n  c\n+ d",
+      added: 2,
+      removed: 1,
+      unchanged: 2,
+    });
+  });
+
+  it("reports identical text as fully unchanged", () => {
+    expect(diffLines("same\ntext", "same\ntext")).toEqual({ text: "  same\n  text", added: 0, removed: 0, unchanged: 2 });
+  });
+
+  it("compares lines by position when the LCS table would exceed the cell limit", () => {
+    expect(diffLines("a\nb\nc", "a\nB\nc\nd", 10)).toEqual({
+      text: "  a\n- b\n+ B\n  c\n+ d",
+      added: 2,
+      removed: 1,
+      unchanged: 2,
+      approximate: true,
+    });
+
+    const long = Array.from({ length: 5000 }, (_, index) => `line ${index}`).join("\n");
+    expect(diffLines(long, `inserted\n${long}`)).toMatchObject({ approximate: true, added: 5001, removed: 5000 });
+  });
+});
@@ -0,0 +1,59 @@
+import { describe, expect, it } from "vitest";
+
+import { applyToolResults, ToolLoopRun, ToolLoopRuns } from "../src/tool-loop.js";
+
+const pausedRun = (): ToolLoopRun => ({
+  model: "deepseek-chat",
+  tools: [],
+  messages: [{ role: "user", content: "Weather in Paris and Rome?" }],
+  maxDepth: 5,
+  generation: {},
+  trace: [{ depth: 1, content: null, tool_calls: [], tool_results: [] }],
+  pendingCallIds: ["call_a", "call_b"],
+});
+
+describe("applyToolResults", () => {
+  it("appends tool messages in the order the model requested them and records them in the trace", () => {
+    const paused = pausedRun();
+    const run = applyToolResults(paused, [
+      { tool_call_id: "call_b", content: "Rome: 24C" },
+      { tool_call_id: "call_a", content: "Paris: 18C" },
+    ]);
+
+    expect(run.messages.slice(1)).toEqual([
+      { role: "tool", tool_call_id: "call_a", content: "Paris: 18C" },
+      { role: "tool", tool_call_id: "call_b", content: "Rome: 24C" },
+    ]);
+    expect(run.trace[0]?.tool_results.map((result) => result.tool_call_id)).toEqual(["call_a", "call_b"]);
+    expect(run.pendingCallIds).toEqual([]);
+    expect(paused).toEqual(pausedRun());
+  });
+
+  it("rejects missing or unexpected tool_call_ids", () => {
+    expect(() => applyToolResults(pausedRun(), [{ tool_call_id: "call_a", content: "x" }])).toThrow(
+      "Missing tool_results for tool_call_id(s): call_b",
+    );
+    expect(() =>
+      applyToolResults(pausedRun(), [
+        { tool_call_id: "call_a", content: "x" },
+        { tool_call_id: "call_b", content: "y" },
+        { tool_call_id: "call_c", content: "z" },
+      ]),
+    ).toThrow("Unexpected tool_call_id(s): call_c");
+  });
+});
+
+describe("ToolLoopRuns", () => {
+  it("keeps only the most recent runs", () => {
+    const runs = new ToolLoopRuns(2);
+    const first = runs.save(pausedRun());
+    const second = runs.save(pausedRun());
+    const third = runs.save(pausedRun());
+
+    expect(runs.get(first)).toBeUndefined();
+    expect(runs.get(second)).toBeDefined();
+    expect(runs.get(third)).toBeDefined();
+    expect(runs.delete(third)).toBe(true);
+    expect(runs.get(third)).toBeUndefined();
+  });
+});
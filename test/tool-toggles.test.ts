@@ -0,0 +1,23 @@
+import { describe, expect, it, vi } from "vitest";
+
+import { ToolToggles } from "../src/tool-toggles.js";
+
+describe("ToolToggles", () => {
+  it("notifies subscribers only when a tool's state changes", () => {
+    const toggles = new ToolToggles();
+    const listener = vi.fn();
+    const unsubscribe = toggles.subscribe(listener);
+
+    expect(toggles.set("get_user_balance", false)).toBe(true);
+    expect(toggles.set("get_user_balance", false)).toBe(false);
+    expect(toggles.isEnabled("get_user_balance")).toBe(false);
+    expect(toggles.disabledTools()).toEqual(["get_user_balance"]);
+    expect(listener).toHaveBeenCalledTimes(1);
+    expect(listener).toHaveBeenCalledWith("get_user_balance", false);
+
+    unsubscribe();
+    expect(toggles.set("get_user_balance", true)).toBe(true);
+    expect(toggles.isEnabled("get_user_balance")).toBe(true);
+    expect(listener).toHaveBeenCalledTimes(1);
+  });
+});
@@ -0,0 +1,45 @@
+import { describe, expect, it } from "vitest";
+
+import { UsageTracker } from "../src/usage-tracker.js";
+
+describe("UsageTracker", () => {
+  it("accumulates totals and per-model breakdowns, then resets", () => {
+    let now = new Date("2026-01-01T00:00:00Z");
+    const tracker = new UsageTracker(() => now);
+
+    tracker.record(
+      "deepseek-reasoner",
+      {
+        prompt_tokens: 100,
+        completion_tokens: 50,
+        total_tokens: 150,
+        prompt_cache_hit_tokens: 40,
+        prompt_cache_miss_tokens: 60,
+        completion_tokens_details: { reasoning_tokens: 30 },
+      },
+      0.01,
+    );
+    tracker.record("deepseek-chat", { prompt_tokens: 10, completion_tokens: 5, total_tokens: 15 }, 0.002, true);
+    tracker.record("deepseek-chat", undefined, 0);
+
+    const summary = tracker.summary();
+    expect(summary).toMatchObject({
+      since: "2026-01-01T00:00:00.000Z",
+      requests: 3,
+      fallbacks: 1,
+      prompt_tokens: 110,
+      completion_tokens: 55,
+      total_tokens: 165,
+      prompt_cache_hit_tokens: 40,
+      prompt_cache_miss_tokens: 60,
+      reasoning_tokens: 30,
+    });
+    expect(summary.estimated_cost_usd).toBeCloseTo(0.012, 9);
+    expect(Object.keys(summary.by_model)).toEqual(["deepseek-chat", "deepseek-reasoner"]);
+    expect(summary.by_model["deepseek-chat"]).toMatchObject({ requests: 2, fallbacks: 1, total_tokens: 15 });
+
+    now = new Date("2026-01-02T00:00:00Z");
+    tracker.reset();
+    expect(tracker.summary()).toMatchObject({ since: "2026-01-02T00:00:00.000Z", requests: 0, by_model: {} });
+  });
+});
@@ -0,0 +1,121 @@
+import { connect, Socket } from "node:net";
+
+import { describe, expect, it } from "vitest";
+
+import { Client } from "@modelcontextprotocol/sdk/client/index.js";
+import { WebSocketClientTransport } from "@modelcontextprotocol/sdk/client/websocket.js";
+
+import { ConversationStore } from "../src/conversation-store.js";
+import { DeepSeekApiClient } from "../src/deepseek/client.js";
+import { createDeepSeekMcpServer } from "../src/mcp-server.js";
+import { startWebSocketServer } from "../src/transports/websocket.js";
+
+async function startRuntime() {
+  const conversations = new ConversationStore(200);
+  return startWebSocketServer(
+    () =>
+      createDeepSeekMcpServer({
+        client: {} as DeepSeekApiClient,
+        conversations,
+        defaultModel: "deepseek-chat",
+        version: "test",
+      }),
+    { host: "127.0.0.1", port: 0, path: "/ws" },
+  );
+}
+
+// Sends a raw upgrade request and resolves with everything the server writes before closing the socket.
+function rawExchange(port: number, version: string, frame?: Buffer): Promise<Buffer> {
+  return new Promise((resolve, reject) => {
+    const chunks: Buffer[] = [];
+    const socket: Socket = connect(port, "127.0.0.1", () => {
+      socket.write(
+        [
+          "GET /ws HTTP/1.1",
+          `Host: 127.0.0.1:${port}`,
+          "Upgrade: websocket",
+          "Connection: Upgrade",
+          "Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==",
+          `Sec-WebSocket-Version: ${version}`,
+          "",
+          "",
+        ].join("\r\n"),
+      );
+      if (frame) {
+        socket.write(frame);
+      }
+    });
+    socket.on("data", (chunk) => chunks.push(chunk));
+    socket.on("error", reject);
+    socket.on("close", () => resolve(Buffer.concat(chunks)));
+  });
+}
+
+describe("startWebSocketServer", () => {
+  it.skipIf(typeof globalThis.WebSocket === "undefined")(
+    "serves concurrent MCP sessions over WebSocket connections",
+    async () => {
+      const runtime = await startRuntime();
+      const clients = [
+        new Client({ name: "ws-smoke-1", version: "1.0.0" }),
+        new Client({ name: "ws-smoke-2", version: "1.0.0" }),
+      ];
+
+      try {
+        await Promise.all(
+          clients.map((client) =>
+            client.connect(new WebSocketClientTransport(new URL(`ws://127.0.0.1:${runtime.port}/ws`))),
+          ),
+        );
+
+        for (const client of clients) {
+          expect(client.getServerVersion()).toMatchObject({ name: "deepseek-mcp-server", version: "test" });
+          const tools = await client.listTools();
+          expect(tools.tools.map((tool) => tool.name)).toContain("chat_completion");
+        }
+      } finally {
+        await Promise.all(clients.map((client) => client.close()));
+        await runtime.close();
+      }
+    },
+  );
+
+  it("requires Sec-WebSocket-Version 13 on the upgrade request", async () => {
+    const runtime = await startRuntime();
+
+    try {
+      const response = (await rawExchange(runtime.port, "8")).toString("latin1");
+      expect(response).toMatch(/^HTTP\/1\.1 426 /);
+      expect(response).toContain("Sec-WebSocket-Version: 13");
+    } finally {
+      await runtime.close();
+    }
+  });
+
+  it("closes the connection with 1002 when a client frame is not masked", async () => {
+    const runtime = await startRuntime();
+    const payload = Buffer.from('{"jsonrpc":"2.0","method":"ping","id":1}', "utf8");
+    const unmasked = Buffer.concat([Buffer.from([0x81, payload.length]), payload]);
+
+    try {
+      const response = await rawExchange(runtime.port, "13", unmasked);
+      const closeFrame = response.subarray(response.indexOf("\r\n\r\n") + 4);
+      expect(closeFrame[0]).toBe(0x88);
+      expect(closeFrame.readUInt16BE(2)).toBe(1002);
+    } finally {
+      await runtime.close();
+    }
+  });
+
+  it("rejects plain HTTP requests with 426 Upgrade Required", async () => {
+    const runtime = await startRuntime();
+
+    try {
+      const response = await fetch(`http://127.0.0.1:${runtime.port}/ws`);
+      expect(response.status).toBe(426);
+      expect(response.headers.get("upgrade")).toBe("websocket");
+    } finally {
+      await runtime.close();
+    }
+  });
+});